@@ -2,9 +2,10 @@
 
 use serde::{Deserialize, de::DeserializeOwned};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use thiserror::Error;
 use tracing::warn;
@@ -23,14 +24,6 @@ pub enum ConfigError {
     #[error("TOML parse error: {0}")]
     Toml(#[from] toml::de::Error),
 
-    /// A field present in Track-B (Go tunasynctl) but not yet supported.
-    /// `tracked_in` names the issue / spec section where support is planned.
-    #[error("unsupported field `{field}` (tracked in: {tracked_in})")]
-    UnsupportedField {
-        field: String,
-        tracked_in: &'static str,
-    },
-
     /// A field was present but its value is semantically invalid.
     #[error("invalid value for `{field}`: {reason}")]
     InvalidValue { field: String, reason: String },
@@ -120,7 +113,18 @@ pub struct WorkerConfig {
     pub global: Option<WorkerGlobalConfig>,
     pub manager: Option<WorkerManagerConfig>,
     pub cgroup: Option<WorkerCgroupConfig>,
+    pub docker: Option<WorkerDockerConfig>,
+    pub zfs: Option<WorkerZfsConfig>,
+    pub btrfs_snapshot: Option<WorkerBtrfsSnapshotConfig>,
+    pub bandwidth: Option<WorkerBandwidthConfig>,
     pub server: Option<WorkerServerConfig>,
+    /// Named stage-1 filter profiles for `two-stage-rsync` mirrors, keyed by
+    /// the name a mirror's `stage1_profile` refers to.
+    pub stage1_profiles: Option<HashMap<String, Stage1ProfileConfig>>,
+    /// Run limits shared by the mirrors of each group, keyed by group name.
+    /// A key naming an upstream host (`rsync.archlinux.org`) applies to
+    /// every mirror syncing from that host.
+    pub concurrency_groups: Option<HashMap<String, u32>>,
     pub mirrors: Option<Vec<MirrorConfig>>,
     pub include: Option<IncludeConfig>,
 }
@@ -131,7 +135,13 @@ impl Default for WorkerConfig {
             global: Some(WorkerGlobalConfig::default()),
             manager: Some(WorkerManagerConfig::default()),
             cgroup: Some(WorkerCgroupConfig::default()),
+            docker: Some(WorkerDockerConfig::default()),
+            zfs: Some(WorkerZfsConfig::default()),
+            btrfs_snapshot: Some(WorkerBtrfsSnapshotConfig::default()),
+            bandwidth: None,
             server: Some(WorkerServerConfig::default()),
+            stage1_profiles: None,
+            concurrency_groups: None,
             mirrors: Some(vec![MirrorConfig::default()]),
             include: None,
         }
@@ -149,9 +159,19 @@ pub struct WorkerGlobalConfig {
     pub log_dir: Option<String>,
     pub mirror_dir: Option<String>,
     pub concurrent: Option<u32>,
+    /// Run limit for each upstream host without its own entry in
+    /// `[concurrency_groups]`; unset leaves hosts unlimited.
+    pub concurrent_per_host: Option<u32>,
     pub rsync_options: Option<Vec<String>>,
     pub dangerous_global_success_exit_codes: Option<Vec<i32>>,
     pub dangerous_global_rsync_success_exit_codes: Option<Vec<i32>>,
+    /// `[[global.blackout]]`: windows in which no scheduled run starts,
+    /// unless a mirror sets its own `blackout`.
+    pub blackout: Option<Vec<BlackoutWindowConfig>>,
+    /// JSON file the worker keeps each job's next run and paused/disabled
+    /// state in, so a restart without the manager picks up where it left
+    /// off. Unset keeps nothing locally.
+    pub state_file: Option<String>,
 }
 
 impl Default for WorkerGlobalConfig {
@@ -170,9 +190,12 @@ impl Default for WorkerGlobalConfig {
             log_dir: Some("/var/log/hustsync/{{.Name}}".into()),
             mirror_dir: Some("/srv/mirror".into()),
             concurrent: Some(10),
+            concurrent_per_host: None,
             rsync_options: None,
             dangerous_global_success_exit_codes: None,
             dangerous_global_rsync_success_exit_codes: None,
+            blackout: None,
+            state_file: None,
         }
     }
 }
@@ -219,6 +242,129 @@ impl Default for WorkerCgroupConfig {
     }
 }
 
+/// Corresponds to the `[docker]` section of a worker config file.
+///
+/// Mirrors only run inside a container when `enable` is set here *and* the
+/// mirror declares a `docker_image`, matching Go's `docker.enable` gate.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WorkerDockerConfig {
+    pub enable: Option<bool>,
+    /// Container runtime binary. Any CLI that accepts `docker run`-style
+    /// arguments works (e.g. `podman`); defaults to `docker`.
+    pub runtime: Option<String>,
+    /// Volumes mounted into every container, ahead of per-mirror
+    /// `docker_volumes`.
+    pub volumes: Option<Vec<String>>,
+    /// Extra `run` options passed to every container, ahead of per-mirror
+    /// `docker_options`.
+    pub options: Option<Vec<String>>,
+}
+
+impl Default for WorkerDockerConfig {
+    fn default() -> Self {
+        WorkerDockerConfig {
+            enable: Some(false),
+            runtime: Some("docker".into()),
+            volumes: None,
+            options: None,
+        }
+    }
+}
+
+/// Corresponds to the `[zfs]` section of a worker config file.
+///
+/// When enabled, every mirror syncs into its own dataset
+/// `<zpool>/<mirror>`, mounted at the mirror's working directory.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WorkerZfsConfig {
+    pub enable: Option<bool>,
+    /// Parent dataset (a pool or `pool/path`) under which per-mirror
+    /// datasets live. Required when `enable = true`.
+    pub zpool: Option<String>,
+    /// `zfs` binary; defaults to `zfs` on `PATH`.
+    pub command: Option<String>,
+}
+
+impl Default for WorkerZfsConfig {
+    fn default() -> Self {
+        WorkerZfsConfig {
+            enable: Some(false),
+            zpool: None,
+            command: Some("zfs".into()),
+        }
+    }
+}
+
+/// Corresponds to the `[btrfs_snapshot]` section of a worker config file.
+///
+/// When enabled, each mirror's working dir is a btrfs subvolume and every
+/// successful sync publishes a read-only snapshot of it at
+/// `<snapshot_path>/<mirror>`. Replaced snapshots pass through
+/// `<gc_path>/<mirror>` on their way to deletion.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WorkerBtrfsSnapshotConfig {
+    pub enable: Option<bool>,
+    /// `btrfs` binary; defaults to `btrfs` on `PATH`.
+    pub command: Option<String>,
+    pub snapshot_path: Option<String>,
+    /// Must be on the same btrfs filesystem as `snapshot_path` so retired
+    /// snapshots can be moved there by rename.
+    pub gc_path: Option<String>,
+}
+
+impl Default for WorkerBtrfsSnapshotConfig {
+    fn default() -> Self {
+        WorkerBtrfsSnapshotConfig {
+            enable: Some(false),
+            command: Some("btrfs".into()),
+            snapshot_path: None,
+            gc_path: None,
+        }
+    }
+}
+
+/// Corresponds to the `[bandwidth]` section of a worker config file.
+///
+/// A budget shared by every running `rsync` / `two-stage-rsync` job: each
+/// run starts with `--bwlimit` set to an equal share of the budget in
+/// force at that moment. Rates are bytes per second with an optional
+/// binary `K`/`M`/`G` suffix, e.g. `"100M"`.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WorkerBandwidthConfig {
+    /// Budget outside every tier; unlimited when unset.
+    pub limit: Option<String>,
+    /// Time-of-day overrides; the first tier covering the current local
+    /// time wins.
+    pub tiers: Option<Vec<BandwidthTierConfig>>,
+}
+
+/// One `[[bandwidth.tiers]]` entry. `start` and `end` are local `HH:MM`
+/// times; a tier whose `end` is earlier than its `start` runs past
+/// midnight.
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BandwidthTierConfig {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub limit: Option<String>,
+}
+
+/// One blackout window. `start` and `end` are local `HH:MM` times; a
+/// window whose `end` is earlier than its `start` runs past midnight.
+/// `days` (`mon` … `sun`) names the days the window opens on; every day
+/// when unset.
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BlackoutWindowConfig {
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub days: Option<Vec<String>>,
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct WorkerServerConfig {
@@ -241,6 +387,23 @@ impl Default for WorkerServerConfig {
     }
 }
 
+/// One entry of the `[stage1_profiles]` table of a worker config file.
+///
+/// Each pattern becomes an rsync `--include=`/`--exclude=` rule, includes
+/// first, so an include can carve an exception out of a broader exclude.
+/// An entry named `debian` or `debian-oldstyle` replaces the built-in
+/// profile of that name.
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Stage1ProfileConfig {
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    /// Filter rules for stage 2, placed ahead of the mirror's
+    /// `rsync_options`. Without them stage 2 syncs the whole tree.
+    pub stage2_include: Option<Vec<String>>,
+    pub stage2_exclude: Option<Vec<String>>,
+}
+
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct MirrorConfig {
@@ -253,27 +416,129 @@ pub struct MirrorConfig {
     pub name: Option<String>,
     pub provider: Option<String>,
     pub upstream: Option<String>,
+    /// Upstreams tried in order after `upstream` when a sync fails with a
+    /// connection-class error (rsync exit 5, 10, 12, 30 or 35). Honoured by
+    /// the `rsync`, `two-stage-rsync` and `command` providers; a command
+    /// script asks for the next upstream by exiting with one of those codes.
+    pub fallback_upstreams: Option<Vec<String>>,
     pub use_ipv6: Option<bool>,
     pub use_ipv4: Option<bool>,
     pub mirror_dir: Option<String>,
     pub mirror_subdir: Option<String>,
     pub mirror_type: Option<String>,
     pub log_dir: Option<String>,
+    /// Extra environment for the provider, passed as written.
     pub env: Option<HashMap<String, String>>,
+    /// Extra environment whose values are secret references (see
+    /// [`parse_secret_ref`]), resolved when the provider is built. Keys
+    /// must not repeat those in `env`.
+    pub secret_env: Option<HashMap<String, String>>,
     pub role: Option<String>,
     pub command: Option<String>,
     pub fail_on_match: Option<String>,
     pub size_pattern: Option<String>,
+    /// Regular expressions matched against paths relative to the upstream
+    /// root. Matching files are neither fetched nor deleted. Used by the
+    /// `http` provider.
+    pub exclude_patterns: Option<Vec<String>>,
+    /// Repositories mirrored by the `git` provider: URLs, or paths relative to
+    /// `upstream`. Each is kept as a bare mirror under the working dir.
+    pub git_repos: Option<Vec<String>>,
+    /// Delete local files that are no longer in the upstream listing. Used
+    /// by the `s3` provider; defaults to false.
+    pub delete_removed: Option<bool>,
+    /// Suites (`dists/<suite>`) mirrored by the `apt` provider, e.g.
+    /// `["bookworm", "bookworm-updates"]`.
+    pub apt_suites: Option<Vec<String>>,
+    /// Components mirrored by the `apt` provider; defaults to `["main"]`.
+    pub apt_components: Option<Vec<String>>,
+    /// Architectures mirrored by the `apt` provider; `source` selects source
+    /// packages. Defaults to `["amd64"]`.
+    pub apt_architectures: Option<Vec<String>>,
     pub exclude_file: Option<String>,
     pub username: Option<String>,
+    /// rsync password, or a secret reference (see [`parse_secret_ref`]).
     pub password: Option<String>,
+    /// File holding the rsync password (trailing newline dropped), read when the
+    /// provider is built. Mutually exclusive with `password`.
+    pub password_file: Option<String>,
     pub rsync_no_timeout: Option<bool>,
     pub rsync_timeout: Option<u32>,
     pub rsync_options: Option<Vec<String>>,
     pub rsync_override: Option<Vec<String>>,
     pub rsync_override_only: Option<bool>,
+    /// Run rsync with `--info=progress2` and relay live transfer progress
+    /// to the manager. Applies to `rsync` and `two-stage-rsync`; defaults to false.
+    pub rsync_progress: Option<bool>,
+    /// Publish every successful sync atomically. Each run syncs into a staging
+    /// directory under the working dir, seeded with `--link-dest` from the live
+    /// tree; on success this path, a symlink, is swapped to the new tree in one
+    /// step. Expands `{{.Name}}` like `mirror_dir`. Applies to `rsync` and
+    /// `two-stage-rsync`.
+    pub publish_dir: Option<String>,
+    /// Previous trees kept next to the live one when `publish_dir` is set, for
+    /// rollback; older ones are pruned after each publish. Defaults to 1.
+    pub publish_keep: Option<u32>,
+    /// Abort a sync that would delete more than this many files. Checked with
+    /// an rsync dry run before anything is transferred; `hustsynctl start
+    /// --allow-mass-delete` lets the next run through once. Applies to `rsync`
+    /// and `two-stage-rsync`.
+    pub max_delete: Option<u64>,
+    /// Like `max_delete`, as a percentage (0-100) of the files present locally.
+    pub max_delete_percent: Option<u8>,
+    /// File upstream that changes whenever the mirror does (Arch `lastupdate`,
+    /// Debian `project/trace/...`), as an `http(s)://` or `rsync://` URL. A run
+    /// whose probe matches the one taken before the last successful sync is
+    /// skipped and reported as a success.
+    pub freshness_probe: Option<String>,
+    /// How probes are compared: `content` (default) compares the file itself,
+    /// `mtime` only its modification time (HTTP `Last-Modified`, or the
+    /// listing line `rsync --list-only` prints).
+    pub freshness_mode: Option<String>,
+    /// Wait for another process (a second worker, a manual sync) to release
+    /// the mirror's run lock, `<log_dir>/<name>.lock`, instead of failing the
+    /// run straight away. Defaults to false.
+    pub lock_wait: Option<bool>,
+    /// Cron expression (`min hour dom month dow`, optional leading seconds)
+    /// for run times; replaces `interval` between runs when set.
+    pub schedule: Option<String>,
+    /// IANA time zone `schedule` is read in, e.g. `Asia/Shanghai`; defaults
+    /// to UTC.
+    pub schedule_timezone: Option<String>,
+    /// Local time windows in which scheduled runs do not start; replaces
+    /// `[[global.blackout]]` when set, so `blackout = []` exempts the mirror.
+    pub blackout: Option<Vec<BlackoutWindowConfig>>,
+    /// Seconds to wait after a failed attempt before the next one; unset or 0
+    /// retries at once. Each later wait grows by `retry_backoff_multiplier`.
+    pub retry_backoff: Option<u32>,
+    /// Growth factor between retry waits, at least 1. Defaults to 2.
+    pub retry_backoff_multiplier: Option<f64>,
+    /// Cap on a single retry wait, in seconds. Defaults to 3600.
+    pub retry_backoff_max: Option<u32>,
+    /// Fraction (0-1) by which each retry wait is randomly lengthened or
+    /// shortened, so mirrors failing together do not retry together. Defaults
+    /// to 0.
+    pub retry_backoff_jitter: Option<f64>,
+    /// Mirrors (by `name`, on this worker) this one is derived from. It runs
+    /// after each successful sync of any of them instead of on its own
+    /// `interval` or `schedule`; `hustsynctl start` still runs it at once.
+    pub after: Option<Vec<String>>,
+    /// A `[concurrency_groups]` entry whose limit this mirror also counts
+    /// against, on top of `global.concurrent` and any limit on its upstream host.
+    pub concurrency_group: Option<String>,
     pub stage1_profile: Option<String>,
+    /// Memory cap such as `512M` or `4G` (binary units). Written to the
+    /// mirror's `memory.max` when `[cgroup] enable = true`, and passed to
+    /// `--memory` for containerised mirrors.
     pub memory_limit: Option<String>,
+    /// cgroup v2 `cpu.weight` (1–10000, kernel default 100) for the mirror's
+    /// cgroup. Only honoured when `[cgroup] enable = true`.
+    pub cpu_weight: Option<u32>,
+    /// Container image to run the provider command in. Only honoured when
+    /// `[docker] enable = true`.
+    pub docker_image: Option<String>,
+    pub docker_volumes: Option<Vec<String>>,
+    pub docker_options: Option<Vec<String>>,
     pub success_exit_codes: Option<Vec<i32>>,
     pub rsync_success_exit_codes: Option<Vec<i32>>,
     /// Nested child mirrors. Children inherit unset fields from this parent.
@@ -302,6 +567,7 @@ impl Default for MirrorConfig {
             name: Some("".into()),
             provider: Some("rsync".into()),
             upstream: Some("".into()),
+            fallback_upstreams: None,
             use_ipv6: Some(false),
             use_ipv4: None,
             mirror_dir: None,
@@ -309,20 +575,49 @@ impl Default for MirrorConfig {
             mirror_type: None,
             log_dir: None,
             env: None,
+            secret_env: None,
             role: None,
             command: None,
             fail_on_match: None,
             size_pattern: None,
+            exclude_patterns: None,
+            git_repos: None,
+            delete_removed: None,
+            apt_suites: None,
+            apt_components: None,
+            apt_architectures: None,
             exclude_file: None,
             username: None,
             password: None,
+            password_file: None,
             rsync_no_timeout: None,
             rsync_timeout: None,
             rsync_options: None,
             rsync_override: None,
             rsync_override_only: None,
+            rsync_progress: None,
+            publish_dir: None,
+            publish_keep: None,
+            max_delete: None,
+            max_delete_percent: None,
+            freshness_probe: None,
+            freshness_mode: None,
+            lock_wait: None,
+            schedule: None,
+            schedule_timezone: None,
+            blackout: None,
+            retry_backoff: None,
+            retry_backoff_multiplier: None,
+            retry_backoff_max: None,
+            retry_backoff_jitter: None,
+            after: None,
+            concurrency_group: None,
             stage1_profile: None,
             memory_limit: None,
+            cpu_weight: None,
+            docker_image: None,
+            docker_volumes: None,
+            docker_options: None,
             success_exit_codes: None,
             rsync_success_exit_codes: None,
             mirrors: None,
@@ -371,7 +666,13 @@ struct LenientWorkerConfig {
     global: Option<WorkerGlobalConfig>,
     manager: Option<WorkerManagerConfig>,
     cgroup: Option<WorkerCgroupConfig>,
+    docker: Option<WorkerDockerConfig>,
+    zfs: Option<WorkerZfsConfig>,
+    btrfs_snapshot: Option<WorkerBtrfsSnapshotConfig>,
+    bandwidth: Option<WorkerBandwidthConfig>,
     server: Option<WorkerServerConfig>,
+    stage1_profiles: Option<HashMap<String, Stage1ProfileConfig>>,
+    concurrency_groups: Option<HashMap<String, u32>>,
     mirrors: Option<Vec<LenientMirrorConfig>>,
     include: Option<IncludeConfig>,
 }
@@ -389,6 +690,7 @@ struct LenientMirrorConfig {
     name: Option<String>,
     provider: Option<String>,
     upstream: Option<String>,
+    fallback_upstreams: Option<Vec<String>>,
     use_ipv6: Option<bool>,
     use_ipv4: Option<bool>,
     mirror_dir: Option<String>,
@@ -396,20 +698,49 @@ struct LenientMirrorConfig {
     mirror_type: Option<String>,
     log_dir: Option<String>,
     env: Option<HashMap<String, String>>,
+    secret_env: Option<HashMap<String, String>>,
     role: Option<String>,
     command: Option<String>,
     fail_on_match: Option<String>,
     size_pattern: Option<String>,
+    exclude_patterns: Option<Vec<String>>,
+    git_repos: Option<Vec<String>>,
+    delete_removed: Option<bool>,
+    apt_suites: Option<Vec<String>>,
+    apt_components: Option<Vec<String>>,
+    apt_architectures: Option<Vec<String>>,
     exclude_file: Option<String>,
     username: Option<String>,
     password: Option<String>,
+    password_file: Option<String>,
     rsync_no_timeout: Option<bool>,
     rsync_timeout: Option<u32>,
     rsync_options: Option<Vec<String>>,
     rsync_override: Option<Vec<String>>,
     rsync_override_only: Option<bool>,
+    rsync_progress: Option<bool>,
+    publish_dir: Option<String>,
+    publish_keep: Option<u32>,
+    max_delete: Option<u64>,
+    max_delete_percent: Option<u8>,
+    freshness_probe: Option<String>,
+    freshness_mode: Option<String>,
+    lock_wait: Option<bool>,
+    schedule: Option<String>,
+    schedule_timezone: Option<String>,
+    blackout: Option<Vec<BlackoutWindowConfig>>,
+    retry_backoff: Option<u32>,
+    retry_backoff_multiplier: Option<f64>,
+    retry_backoff_max: Option<u32>,
+    retry_backoff_jitter: Option<f64>,
+    after: Option<Vec<String>>,
+    concurrency_group: Option<String>,
     stage1_profile: Option<String>,
     memory_limit: Option<String>,
+    cpu_weight: Option<u32>,
+    docker_image: Option<String>,
+    docker_volumes: Option<Vec<String>>,
+    docker_options: Option<Vec<String>>,
     success_exit_codes: Option<Vec<i32>>,
     rsync_success_exit_codes: Option<Vec<i32>>,
     #[serde(default)]
@@ -425,6 +756,7 @@ impl From<LenientMirrorConfig> for MirrorConfig {
             name: l.name,
             provider: l.provider,
             upstream: l.upstream,
+            fallback_upstreams: l.fallback_upstreams,
             use_ipv6: l.use_ipv6,
             use_ipv4: l.use_ipv4,
             mirror_dir: l.mirror_dir,
@@ -432,20 +764,49 @@ impl From<LenientMirrorConfig> for MirrorConfig {
             mirror_type: l.mirror_type,
             log_dir: l.log_dir,
             env: l.env,
+            secret_env: l.secret_env,
             role: l.role,
             command: l.command,
             fail_on_match: l.fail_on_match,
             size_pattern: l.size_pattern,
+            exclude_patterns: l.exclude_patterns,
+            git_repos: l.git_repos,
+            delete_removed: l.delete_removed,
+            apt_suites: l.apt_suites,
+            apt_components: l.apt_components,
+            apt_architectures: l.apt_architectures,
             exclude_file: l.exclude_file,
             username: l.username,
             password: l.password,
+            password_file: l.password_file,
             rsync_no_timeout: l.rsync_no_timeout,
             rsync_timeout: l.rsync_timeout,
             rsync_options: l.rsync_options,
             rsync_override: l.rsync_override,
             rsync_override_only: l.rsync_override_only,
+            rsync_progress: l.rsync_progress,
+            publish_dir: l.publish_dir,
+            publish_keep: l.publish_keep,
+            max_delete: l.max_delete,
+            max_delete_percent: l.max_delete_percent,
+            freshness_probe: l.freshness_probe,
+            freshness_mode: l.freshness_mode,
+            lock_wait: l.lock_wait,
+            schedule: l.schedule,
+            schedule_timezone: l.schedule_timezone,
+            blackout: l.blackout,
+            retry_backoff: l.retry_backoff,
+            retry_backoff_multiplier: l.retry_backoff_multiplier,
+            retry_backoff_max: l.retry_backoff_max,
+            retry_backoff_jitter: l.retry_backoff_jitter,
+            after: l.after,
+            concurrency_group: l.concurrency_group,
             stage1_profile: l.stage1_profile,
             memory_limit: l.memory_limit,
+            cpu_weight: l.cpu_weight,
+            docker_image: l.docker_image,
+            docker_volumes: l.docker_volumes,
+            docker_options: l.docker_options,
             success_exit_codes: l.success_exit_codes,
             rsync_success_exit_codes: l.rsync_success_exit_codes,
             mirrors: l
@@ -461,7 +822,13 @@ impl From<LenientWorkerConfig> for WorkerConfig {
             global: l.global,
             manager: l.manager,
             cgroup: l.cgroup,
+            docker: l.docker,
+            zfs: l.zfs,
+            btrfs_snapshot: l.btrfs_snapshot,
+            bandwidth: l.bandwidth,
             server: l.server,
+            stage1_profiles: l.stage1_profiles,
+            concurrency_groups: l.concurrency_groups,
             mirrors: l
                 .mirrors
                 .map(|mv| mv.into_iter().map(MirrorConfig::from).collect()),
@@ -695,12 +1062,19 @@ fn merge_from_parent(child: &mut MirrorConfig, parent: &MirrorConfig) {
     merge_retry(child, parent);
     merge_exec_on_status(child, parent);
     merge_exec_on_status_extra(child, parent);
+    // The two password sources replace each other, so a child that sets
+    // either one inherits neither.
+    if child.password.is_none() && child.password_file.is_none() {
+        child.password.clone_from(&parent.password);
+        child.password_file.clone_from(&parent.password_file);
+    }
 
     inherit!(
         child,
         parent,
         provider,
         upstream,
+        fallback_upstreams,
         use_ipv6,
         use_ipv4,
         mirror_dir,
@@ -708,20 +1082,47 @@ fn merge_from_parent(child: &mut MirrorConfig, parent: &MirrorConfig) {
         mirror_type,
         log_dir,
         env,
+        secret_env,
         role,
         command,
         fail_on_match,
         size_pattern,
+        exclude_patterns,
+        git_repos,
+        delete_removed,
+        apt_suites,
+        apt_components,
+        apt_architectures,
         exclude_file,
         username,
-        password,
         rsync_no_timeout,
         rsync_timeout,
         rsync_options,
         rsync_override,
         rsync_override_only,
+        rsync_progress,
+        publish_dir,
+        publish_keep,
+        max_delete,
+        max_delete_percent,
+        freshness_probe,
+        freshness_mode,
+        lock_wait,
+        schedule,
+        schedule_timezone,
+        blackout,
+        retry_backoff,
+        retry_backoff_multiplier,
+        retry_backoff_max,
+        retry_backoff_jitter,
+        after,
+        concurrency_group,
         stage1_profile,
         memory_limit,
+        cpu_weight,
+        docker_image,
+        docker_volumes,
+        docker_options,
         success_exit_codes,
         rsync_success_exit_codes,
     );
@@ -762,7 +1163,10 @@ fn load_included_mirrors(
 // Provider semantic validation
 // ---------------------------------------------------------------------------
 
-const VALID_STAGE1_PROFILES: &[&str] = &["debian", "debian-oldstyle"];
+const BUILTIN_STAGE1_PROFILES: &[&str] = &["debian", "debian-oldstyle"];
+
+/// Accepted `freshness_mode` values.
+const FRESHNESS_MODES: &[&str] = &["content", "mtime"];
 
 /// True when `upstream` begins `scheme://<host>...` and `<host>` contains a
 /// colon without enclosing brackets (bare IPv6 literal).
@@ -786,20 +1190,96 @@ fn contains_unbracketed_ipv6(upstream: &str) -> bool {
 /// 2. `rsync_override_only = true` requires a non-empty `rsync_override`.
 /// 3. Bare IPv6 literals in upstream must be bracketed (`[…]`).
 /// 4. `size_pattern`, when set, must compile and have exactly one capture group.
-/// 5. `stage1_profile`, when set, must be one of the known profiles.
+/// 5. `stage1_profile`, when set, must name a built-in profile or an entry
+///    of `[stage1_profiles]`.
+/// 6. `memory_limit` must parse as a byte size and `cpu_weight` must lie in
+///    the cgroup v2 range.
+/// 7. `http` upstream must be an `http(s)://` directory URL ending with `/`,
+///    and every `exclude_patterns` entry must compile.
+/// 8. `git` needs `upstream` or `git_repos`; `git_repos` entries given as
+///    relative paths need an `upstream` ending with `/`.
+/// 9. `s3` upstream must be `s3://<bucket>/[<prefix>/]`; the access key
+///    pair in `env` or `secret_env` must be set together, and `AWS_ENDPOINT_URL` must be
+///    an http(s) URL.
+/// 10. `apt` upstream must be an `http(s)://` URL ending with `/` and
+///     `apt_suites` must be non-empty; suite, component and architecture
+///     names must be relative paths without `..`.
+/// 11. `yum` upstream must be an `http(s)://` URL of the repository root
+///     (the directory holding `repodata/`), ending with `/`.
+/// 12. `fallback_upstreams` is only accepted by `rsync`, `two-stage-rsync`
+///     and `command` mirrors and needs a primary `upstream`; rsync-family
+///     entries follow rules 1 and 3.
+/// 13. `publish_dir` is only accepted by `rsync` and `two-stage-rsync`
+///     mirrors, must not be empty, and rules out `--inplace` (which would
+///     rewrite files hard-linked into the live tree); `publish_keep` needs
+///     `publish_dir`.
+/// 14. `max_delete` and `max_delete_percent` are only accepted by `rsync`
+///     and `two-stage-rsync` mirrors; the percentage must be at most 100.
+/// 15. `freshness_probe` must be an `http(s)://` or `rsync://` URL of a file;
+///     `freshness_mode` must be `content` or `mtime` and needs a probe.
+/// 16. `password` and `password_file` are mutually exclusive; secret
+///     references in `password` and `secret_env` values must be
+///     well-formed, and `secret_env` keys must not repeat `env` keys.
+/// 17. `schedule` must be a cron expression; `schedule_timezone` must be an
+///     IANA zone name and needs a `schedule`.
+/// 18. `blackout` windows follow the worker-level rule below.
+/// 19. `retry_backoff_multiplier` must be at least 1, `retry_backoff_jitter`
+///     must lie in 0-1, and `retry_backoff_max` must not be below
+///     `retry_backoff`.
+/// 20. `after` entries must name other mirrors of this worker, and the
+///     dependencies they form must not loop.
+/// 21. `concurrency_group` must name a `[concurrency_groups]` entry.
+///
+/// Worker-level: an enabled `[zfs]` section must name a `zpool`, an
+/// enabled `[btrfs_snapshot]` section must set both of its paths, every
+/// `[stage1_profiles]` entry must have at least one stage-1 rule and no
+/// empty patterns, `[bandwidth]` rates and tier times must parse, and
+/// `[[global.blackout]]` windows need distinct `HH:MM` bounds and known
+/// weekday names, and `[concurrency_groups]` limits and
+/// `global.concurrent_per_host` must be at least 1.
 pub fn validate_worker_config(cfg: &WorkerConfig) -> Result<(), ConfigError> {
-    if cfg
-        .cgroup
-        .as_ref()
-        .and_then(|cgroup| cgroup.enable)
-        .unwrap_or(false)
+    if let Some(zfs) = cfg.zfs.as_ref()
+        && zfs.enable.unwrap_or(false)
+        && zfs.zpool.as_deref().unwrap_or("").is_empty()
     {
-        return Err(ConfigError::UnsupportedField {
-            field: "cgroup.enable".into(),
-            tracked_in: "parity-checklist: Cgroup enforcement parity",
+        return Err(ConfigError::InvalidValue {
+            field: "zfs.zpool".into(),
+            reason: "`zfs.enable = true` requires `zpool`, e.g. `zpool = \"tank/mirrors\"`".into(),
         });
     }
 
+    if let Some(btrfs) = cfg.btrfs_snapshot.as_ref()
+        && btrfs.enable.unwrap_or(false)
+    {
+        for (field, value) in [
+            ("snapshot_path", &btrfs.snapshot_path),
+            ("gc_path", &btrfs.gc_path),
+        ] {
+            if value.as_deref().unwrap_or("").is_empty() {
+                return Err(ConfigError::InvalidValue {
+                    field: format!("btrfs_snapshot.{field}"),
+                    reason: format!("`btrfs_snapshot.enable = true` requires `{field}`"),
+                });
+            }
+        }
+    }
+
+    if let Some(bandwidth) = cfg.bandwidth.as_ref() {
+        validate_bandwidth(bandwidth)?;
+    }
+
+    if let Some(windows) = cfg.global.as_ref().and_then(|g| g.blackout.as_deref()) {
+        validate_blackout(windows, "global.blackout")?;
+    }
+
+    if let Some(profiles) = cfg.stage1_profiles.as_ref() {
+        for (name, profile) in profiles {
+            validate_stage1_profile(name, profile)?;
+        }
+    }
+
+    validate_concurrency_limits(cfg)?;
+
     let Some(mirrors) = cfg.mirrors.as_deref() else {
         return Ok(());
     };
@@ -808,33 +1288,213 @@ pub fn validate_worker_config(cfg: &WorkerConfig) -> Result<(), ConfigError> {
             .name
             .clone()
             .unwrap_or_else(|| format!("mirrors[{idx}]"));
-        validate_mirror(mirror, &label)?;
+        validate_mirror(mirror, &label, cfg.stage1_profiles.as_ref())?;
+        if let Some(group) = mirror.concurrency_group.as_deref()
+            && !cfg
+                .concurrency_groups
+                .as_ref()
+                .is_some_and(|groups| groups.contains_key(group))
+        {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.concurrency_group"),
+                reason: format!("no `{group}` entry in `[concurrency_groups]`"),
+            });
+        }
+    }
+    validate_after(mirrors)
+}
+
+fn validate_concurrency_limits(cfg: &WorkerConfig) -> Result<(), ConfigError> {
+    let per_host = cfg.global.as_ref().and_then(|g| g.concurrent_per_host);
+    let limits = cfg
+        .concurrency_groups
+        .iter()
+        .flatten()
+        .map(|(name, &limit)| (format!("concurrency_groups.{name}"), limit))
+        .chain(per_host.map(|limit| ("global.concurrent_per_host".to_string(), limit)));
+    for (field, limit) in limits {
+        if limit == 0 {
+            return Err(ConfigError::InvalidValue {
+                field,
+                reason: "a limit of 0 would never let a sync start; use at least 1".into(),
+            });
+        }
     }
     Ok(())
 }
 
-fn validate_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
-    let provider = mirror.provider.as_deref().unwrap_or("rsync");
-    let is_rsync_family = matches!(provider, "rsync" | "two-stage-rsync");
+/// Rule 20: every `after` entry names another configured mirror, and
+/// following `after` from any mirror never leads back to it.
+fn validate_after(mirrors: &[MirrorConfig]) -> Result<(), ConfigError> {
+    let deps: HashMap<&str, &[String]> = mirrors
+        .iter()
+        .filter_map(|m| Some((m.name.as_deref()?, m.after.as_deref().unwrap_or(&[]))))
+        .collect();
+    for (&name, &sources) in &deps {
+        if let Some(source) = sources
+            .iter()
+            .find(|s| *s == name || !deps.contains_key(s.as_str()))
+        {
+            let reason = if source == name {
+                format!("`{name}` cannot run after itself")
+            } else {
+                format!("no mirror named `{source}` on this worker")
+            };
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{name}.after"),
+                reason,
+            });
+        }
+    }
+    if let Some(cycle) = find_cycle(&deps) {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{}.after", cycle[0]),
+            reason: format!("dependency cycle: {}", cycle.join(" -> ")),
+        });
+    }
+    Ok(())
+}
 
-    if is_rsync_family {
-        if let Some(upstream) = mirror.upstream.as_deref() {
-            if !upstream.ends_with('/') {
-                return Err(ConfigError::InvalidValue {
-                    field: format!("mirrors.{label}.upstream"),
-                    reason: format!("rsync upstream must end with `/`, e.g. `{upstream}/`"),
-                });
+/// A loop in `deps` as the names along it, first name repeated at the
+/// end. Depth-first; a mirror met again while still on the path closes
+/// the loop.
+fn find_cycle<'a>(deps: &HashMap<&'a str, &'a [String]>) -> Option<Vec<&'a str>> {
+    let mut done: HashSet<&str> = HashSet::new();
+    let mut starts: Vec<&str> = deps.keys().copied().collect();
+    starts.sort_unstable();
+    for start in starts {
+        let mut path: Vec<&str> = Vec::new();
+        // (mirror, index of the next source to visit)
+        let mut stack: Vec<(&str, usize)> = vec![(start, 0)];
+        while let Some((name, next)) = stack.pop() {
+            if next == 0 {
+                if done.contains(name) {
+                    continue;
+                }
+                if let Some(pos) = path.iter().position(|&p| p == name) {
+                    let mut cycle = path.split_off(pos);
+                    cycle.push(name);
+                    return Some(cycle);
+                }
+                path.push(name);
+            }
+            match deps.get(name).and_then(|sources| sources.get(next)) {
+                Some(source) => {
+                    stack.push((name, next + 1));
+                    stack.push((source.as_str(), 0));
+                }
+                None => {
+                    path.pop();
+                    done.insert(name);
+                }
             }
-            if contains_unbracketed_ipv6(upstream) {
+        }
+    }
+    None
+}
+
+fn validate_bandwidth(cfg: &WorkerBandwidthConfig) -> Result<(), ConfigError> {
+    let check_rate = |field: String, raw: &str| {
+        if parse_byte_rate(raw).is_none() {
+            return Err(ConfigError::InvalidValue {
+                field,
+                reason: format!(
+                    "`{raw}` is not a rate; use bytes per second with an optional \
+                     K/M/G suffix, e.g. `100M`"
+                ),
+            });
+        }
+        Ok(())
+    };
+    if let Some(limit) = cfg.limit.as_deref() {
+        check_rate("bandwidth.limit".into(), limit)?;
+    }
+    for (idx, tier) in cfg.tiers.iter().flatten().enumerate() {
+        let field = |name: &str| format!("bandwidth.tiers[{idx}].{name}");
+        validate_time_window(&tier.start, &tier.end, "tier", field)?;
+        check_rate(field("limit"), tier.limit.as_deref().unwrap_or(""))?;
+    }
+    Ok(())
+}
+
+/// `start` and `end` must be distinct `HH:MM` times; `field` names a key
+/// of the `what` being checked.
+fn validate_time_window(
+    start: &Option<String>,
+    end: &Option<String>,
+    what: &str,
+    field: impl Fn(&str) -> String,
+) -> Result<(), ConfigError> {
+    let mut bounds = [0; 2];
+    for (slot, (name, value)) in bounds.iter_mut().zip([("start", start), ("end", end)]) {
+        let raw = value.as_deref().unwrap_or("");
+        *slot = parse_time_of_day(raw).ok_or_else(|| ConfigError::InvalidValue {
+            field: field(name),
+            reason: format!("`{raw}` is not a time of day; use `HH:MM`, e.g. `08:30`"),
+        })?;
+    }
+    if bounds[0] == bounds[1] {
+        return Err(ConfigError::InvalidValue {
+            field: field("end"),
+            reason: format!("a {what} must not start and end at the same time"),
+        });
+    }
+    Ok(())
+}
+
+fn validate_blackout(windows: &[BlackoutWindowConfig], prefix: &str) -> Result<(), ConfigError> {
+    for (idx, window) in windows.iter().enumerate() {
+        let field = |name: &str| format!("{prefix}[{idx}].{name}");
+        validate_time_window(&window.start, &window.end, "window", field)?;
+        for day in window.days.iter().flatten() {
+            if parse_weekday(day).is_none() {
                 return Err(ConfigError::InvalidValue {
-                    field: format!("mirrors.{label}.upstream"),
-                    reason: format!(
-                        "IPv6 literal in upstream must be bracketed, \
-                         e.g. `rsync://[fe80::1]/path/` (got `{upstream}`)"
-                    ),
+                    field: field("days"),
+                    reason: format!("unknown day `{day}`; use mon, tue, wed, thu, fri, sat or sun"),
                 });
             }
         }
+    }
+    Ok(())
+}
+
+fn validate_stage1_profile(name: &str, profile: &Stage1ProfileConfig) -> Result<(), ConfigError> {
+    let stage1_empty = profile.include.as_ref().is_none_or(Vec::is_empty)
+        && profile.exclude.as_ref().is_none_or(Vec::is_empty);
+    if stage1_empty {
+        return Err(ConfigError::InvalidValue {
+            field: format!("stage1_profiles.{name}"),
+            reason: "a stage-1 profile needs at least one `include` or `exclude` pattern".into(),
+        });
+    }
+    for (field, patterns) in [
+        ("include", &profile.include),
+        ("exclude", &profile.exclude),
+        ("stage2_include", &profile.stage2_include),
+        ("stage2_exclude", &profile.stage2_exclude),
+    ] {
+        if patterns.iter().flatten().any(|p| p.trim().is_empty()) {
+            return Err(ConfigError::InvalidValue {
+                field: format!("stage1_profiles.{name}.{field}"),
+                reason: "filter patterns must not be empty".into(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_mirror(
+    mirror: &MirrorConfig,
+    label: &str,
+    stage1_profiles: Option<&HashMap<String, Stage1ProfileConfig>>,
+) -> Result<(), ConfigError> {
+    let provider = mirror.provider.as_deref().unwrap_or("rsync");
+    let is_rsync_family = matches!(provider, "rsync" | "two-stage-rsync");
+
+    if is_rsync_family {
+        if let Some(upstream) = mirror.upstream.as_deref() {
+            validate_rsync_upstream(upstream, &format!("mirrors.{label}.upstream"))?;
+        }
 
         if mirror.rsync_override_only == Some(true) {
             let override_empty = mirror
@@ -853,18 +1513,49 @@ fn validate_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError
         }
 
         if let Some(profile) = mirror.stage1_profile.as_deref()
-            && !VALID_STAGE1_PROFILES.contains(&profile)
+            && !BUILTIN_STAGE1_PROFILES.contains(&profile)
+            && !stage1_profiles.is_some_and(|p| p.contains_key(profile))
         {
+            let mut known: Vec<&str> = BUILTIN_STAGE1_PROFILES.to_vec();
+            known.extend(
+                stage1_profiles
+                    .into_iter()
+                    .flat_map(|p| p.keys().map(String::as_str)),
+            );
+            known.sort_unstable();
+            known.dedup();
             return Err(ConfigError::InvalidValue {
                 field: format!("mirrors.{label}.stage1_profile"),
                 reason: format!(
                     "unknown stage1_profile `{profile}`; valid values are: {}",
-                    VALID_STAGE1_PROFILES.join(", ")
+                    known.join(", ")
                 ),
             });
         }
     }
 
+    validate_fallback_upstreams(mirror, label, provider)?;
+    validate_publish(mirror, label, provider)?;
+    validate_delete_guard(mirror, label, provider)?;
+    validate_freshness_probe(mirror, label)?;
+    validate_secrets(mirror, label)?;
+    validate_schedule(mirror, label)?;
+    validate_retry_backoff(mirror, label)?;
+    if let Some(windows) = mirror.blackout.as_deref() {
+        validate_blackout(windows, &format!("mirrors.{label}.blackout"))?;
+    }
+
+    match provider {
+        "http" => validate_http_mirror(mirror, label)?,
+        "git" => validate_git_mirror(mirror, label)?,
+        "s3" => validate_s3_mirror(mirror, label)?,
+        "apt" => validate_apt_mirror(mirror, label)?,
+        "yum" => validate_yum_mirror(mirror, label)?,
+        _ => {}
+    }
+
+    validate_resource_limits(mirror, label)?;
+
     if let Some(pattern) = mirror.size_pattern.as_deref() {
         match regex::Regex::new(pattern) {
             Err(e) => {
@@ -893,3 +1584,590 @@ fn validate_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError
 
     Ok(())
 }
+
+fn validate_rsync_upstream(upstream: &str, field: &str) -> Result<(), ConfigError> {
+    if !upstream.ends_with('/') {
+        return Err(ConfigError::InvalidValue {
+            field: field.to_string(),
+            reason: format!("rsync upstream must end with `/`, e.g. `{upstream}/`"),
+        });
+    }
+    if contains_unbracketed_ipv6(upstream) {
+        return Err(ConfigError::InvalidValue {
+            field: field.to_string(),
+            reason: format!(
+                "IPv6 literal in upstream must be bracketed, \
+                 e.g. `rsync://[fe80::1]/path/` (got `{upstream}`)"
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn validate_fallback_upstreams(
+    mirror: &MirrorConfig,
+    label: &str,
+    provider: &str,
+) -> Result<(), ConfigError> {
+    let Some(fallbacks) = mirror.fallback_upstreams.as_deref() else {
+        return Ok(());
+    };
+    let field = format!("mirrors.{label}.fallback_upstreams");
+    if !matches!(provider, "rsync" | "two-stage-rsync" | "command") {
+        return Err(ConfigError::InvalidValue {
+            field,
+            reason: format!(
+                "provider `{provider}` does not support fallback upstreams; \
+                 only rsync, two-stage-rsync and command do"
+            ),
+        });
+    }
+    if mirror.upstream.as_deref().unwrap_or("").is_empty() {
+        return Err(ConfigError::InvalidValue {
+            field,
+            reason: "`fallback_upstreams` requires a primary `upstream`".into(),
+        });
+    }
+    for (idx, fallback) in fallbacks.iter().enumerate() {
+        let entry = format!("{field}[{idx}]");
+        if fallback.is_empty() {
+            return Err(ConfigError::InvalidValue {
+                field: entry,
+                reason: "fallback upstream must not be empty".into(),
+            });
+        }
+        if provider != "command" {
+            validate_rsync_upstream(fallback, &entry)?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_publish(mirror: &MirrorConfig, label: &str, provider: &str) -> Result<(), ConfigError> {
+    let Some(publish_dir) = mirror.publish_dir.as_deref() else {
+        if mirror.publish_keep.is_some() {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.publish_keep"),
+                reason: "`publish_keep` requires `publish_dir`".into(),
+            });
+        }
+        return Ok(());
+    };
+    let field = format!("mirrors.{label}.publish_dir");
+    if !matches!(provider, "rsync" | "two-stage-rsync") {
+        return Err(ConfigError::InvalidValue {
+            field,
+            reason: format!(
+                "provider `{provider}` does not support atomic publish; \
+                 only rsync and two-stage-rsync do"
+            ),
+        });
+    }
+    if publish_dir.is_empty() {
+        return Err(ConfigError::InvalidValue {
+            field,
+            reason: "`publish_dir` must not be empty".into(),
+        });
+    }
+    let inplace = [&mirror.rsync_options, &mirror.rsync_override]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|opt| opt == "--inplace");
+    if inplace {
+        return Err(ConfigError::InvalidValue {
+            field,
+            reason: "`publish_dir` cannot be combined with `--inplace`: rsync would \
+                     rewrite files shared with the published tree"
+                .into(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_delete_guard(
+    mirror: &MirrorConfig,
+    label: &str,
+    provider: &str,
+) -> Result<(), ConfigError> {
+    for (name, set) in [
+        ("max_delete", mirror.max_delete.is_some()),
+        ("max_delete_percent", mirror.max_delete_percent.is_some()),
+    ] {
+        if set && !matches!(provider, "rsync" | "two-stage-rsync") {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.{name}"),
+                reason: format!(
+                    "provider `{provider}` does not support the mass-deletion guard; \
+                     only rsync and two-stage-rsync do"
+                ),
+            });
+        }
+    }
+    if let Some(percent) = mirror.max_delete_percent
+        && percent > 100
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.max_delete_percent"),
+            reason: format!("`{percent}` is not a percentage; use 0-100"),
+        });
+    }
+    Ok(())
+}
+
+fn validate_freshness_probe(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    let Some(probe) = mirror.freshness_probe.as_deref() else {
+        if mirror.freshness_mode.is_some() {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.freshness_mode"),
+                reason: "`freshness_mode` requires `freshness_probe`".into(),
+            });
+        }
+        return Ok(());
+    };
+    let is_url = ["http://", "https://", "rsync://"]
+        .iter()
+        .any(|scheme| probe.starts_with(scheme));
+    if !is_url || probe.ends_with('/') {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.freshness_probe"),
+            reason: format!(
+                "`{probe}` is not a file URL; use http://, https:// or rsync:// \
+                 and point at a file such as `lastupdate`"
+            ),
+        });
+    }
+    if let Some(mode) = mirror.freshness_mode.as_deref()
+        && !FRESHNESS_MODES.contains(&mode)
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.freshness_mode"),
+            reason: format!(
+                "unknown mode `{mode}`; expected one of: {}",
+                FRESHNESS_MODES.join(", ")
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn validate_secrets(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    if mirror.password.is_some() && mirror.password_file.is_some() {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.password_file"),
+            reason: "set either `password` or `password_file`, not both".into(),
+        });
+    }
+    if mirror.password_file.as_deref().is_some_and(str::is_empty) {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.password_file"),
+            reason: "path must not be empty".into(),
+        });
+    }
+    let secret_env = mirror.secret_env.iter().flatten();
+    if let Some(key) = secret_env
+        .clone()
+        .map(|(k, _)| k)
+        .find(|k| mirror.env.as_ref().is_some_and(|env| env.contains_key(*k)))
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.secret_env.{key}"),
+            reason: "also set in `env`".into(),
+        });
+    }
+    let password = mirror
+        .password
+        .as_ref()
+        .map(|p| ("password".to_string(), p));
+    for (field, value) in password
+        .into_iter()
+        .chain(secret_env.map(|(k, v)| (format!("secret_env.{k}"), v)))
+    {
+        // The reason names the reference kind only; the value may be a
+        // literal secret.
+        if let Err(reason) = parse_secret_ref(value) {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.{field}"),
+                reason,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_schedule(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    let Some(expr) = mirror.schedule.as_deref() else {
+        if mirror.schedule_timezone.is_some() {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.schedule_timezone"),
+                reason: "`schedule_timezone` requires `schedule`".into(),
+            });
+        }
+        return Ok(());
+    };
+    if let Err(e) = croner::Cron::from_str(expr) {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.schedule"),
+            reason: format!(
+                "`{expr}` is not a cron expression ({e}); use `min hour dom month dow`, \
+                 e.g. `0 2,14 * * *`"
+            ),
+        });
+    }
+    if let Some(tz) = mirror.schedule_timezone.as_deref()
+        && tz.parse::<chrono_tz::Tz>().is_err()
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.schedule_timezone"),
+            reason: format!("unknown time zone `{tz}`; use an IANA name such as `Asia/Shanghai`"),
+        });
+    }
+    Ok(())
+}
+
+fn validate_retry_backoff(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    if let Some(multiplier) = mirror.retry_backoff_multiplier
+        && !(multiplier >= 1.0 && multiplier.is_finite())
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.retry_backoff_multiplier"),
+            reason: format!("must be a number of at least 1 (got {multiplier})"),
+        });
+    }
+    if let Some(jitter) = mirror.retry_backoff_jitter
+        && !(0.0..=1.0).contains(&jitter)
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.retry_backoff_jitter"),
+            reason: format!("must lie between 0 and 1 (got {jitter})"),
+        });
+    }
+    if let (Some(initial), Some(max)) = (mirror.retry_backoff, mirror.retry_backoff_max)
+        && max < initial
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.retry_backoff_max"),
+            reason: format!("{max}s is shorter than `retry_backoff` ({initial}s)"),
+        });
+    }
+    Ok(())
+}
+
+fn validate_http_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    if let Some(upstream) = mirror.upstream.as_deref() {
+        let is_http = upstream.starts_with("http://") || upstream.starts_with("https://");
+        if !is_http || !upstream.ends_with('/') {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.upstream"),
+                reason: format!(
+                    "http upstream must be an http(s) directory URL ending with `/` \
+                     (got `{upstream}`)"
+                ),
+            });
+        }
+    }
+
+    for pattern in mirror.exclude_patterns.iter().flatten() {
+        if let Err(e) = regex::Regex::new(pattern) {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.exclude_patterns"),
+                reason: format!("`{pattern}` does not compile: {e}"),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_git_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    let upstream = mirror.upstream.as_deref().unwrap_or("");
+    let Some(repos) = mirror.git_repos.as_deref() else {
+        if upstream.is_empty() {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.upstream"),
+                reason: "the `git` provider needs `upstream` (one repository) or `git_repos`"
+                    .into(),
+            });
+        }
+        return Ok(());
+    };
+
+    for repo in repos {
+        if repo.trim().is_empty() {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.git_repos"),
+                reason: "repository entries must not be empty".into(),
+            });
+        }
+        if !is_absolute_git_url(repo) && !upstream.ends_with('/') {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.git_repos"),
+                reason: format!(
+                    "`{repo}` is relative, so `upstream` must be a base URL ending with `/`"
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_s3_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    if let Some(upstream) = mirror.upstream.as_deref() {
+        let bucket = upstream
+            .strip_prefix("s3://")
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or("");
+        if bucket.is_empty() || !upstream.ends_with('/') {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.upstream"),
+                reason: format!(
+                    "s3 upstream must look like `s3://<bucket>/` or \
+                     `s3://<bucket>/<prefix>/` (got `{upstream}`)"
+                ),
+            });
+        }
+    }
+
+    let env = mirror.env.as_ref();
+    let has = |key: &str| {
+        [env, mirror.secret_env.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|e| e.get(key).is_some_and(|v| !v.is_empty()))
+    };
+    if has("AWS_ACCESS_KEY_ID") != has("AWS_SECRET_ACCESS_KEY") {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.env"),
+            reason: "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set together".into(),
+        });
+    }
+    if let Some(endpoint) = env.and_then(|e| e.get("AWS_ENDPOINT_URL"))
+        && !endpoint.starts_with("http://")
+        && !endpoint.starts_with("https://")
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.env"),
+            reason: format!("AWS_ENDPOINT_URL must be an http(s) URL (got `{endpoint}`)"),
+        });
+    }
+    Ok(())
+}
+
+fn validate_apt_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    if let Some(upstream) = mirror.upstream.as_deref() {
+        let is_http = upstream.starts_with("http://") || upstream.starts_with("https://");
+        if !is_http || !upstream.ends_with('/') {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.upstream"),
+                reason: format!(
+                    "apt upstream must be the archive root as an http(s) URL ending with `/`, \
+                     e.g. `https://deb.debian.org/debian/` (got `{upstream}`)"
+                ),
+            });
+        }
+    }
+
+    if mirror.apt_suites.as_ref().is_none_or(Vec::is_empty) {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.apt_suites"),
+            reason:
+                "the `apt` provider needs at least one suite, e.g. `apt_suites = [\"bookworm\"]`"
+                    .into(),
+        });
+    }
+
+    for (field, names) in [
+        ("apt_suites", &mirror.apt_suites),
+        ("apt_components", &mirror.apt_components),
+        ("apt_architectures", &mirror.apt_architectures),
+    ] {
+        for name in names.iter().flatten() {
+            let bad = name.is_empty()
+                || name.starts_with('/')
+                || name.split('/').any(|seg| seg.is_empty() || seg == "..");
+            if bad {
+                return Err(ConfigError::InvalidValue {
+                    field: format!("mirrors.{label}.{field}"),
+                    reason: format!("`{name}` is not a valid name"),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_yum_mirror(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    if let Some(upstream) = mirror.upstream.as_deref() {
+        let is_http = upstream.starts_with("http://") || upstream.starts_with("https://");
+        if !is_http || !upstream.ends_with('/') {
+            return Err(ConfigError::InvalidValue {
+                field: format!("mirrors.{label}.upstream"),
+                reason: format!(
+                    "yum upstream must be the repository root (holding `repodata/`) as an \
+                     http(s) URL ending with `/` (got `{upstream}`)"
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether a `git_repos` entry names a repository on its own (URL, local
+/// path, or scp-style `host:path`) rather than a path under `upstream`.
+pub fn is_absolute_git_url(repo: &str) -> bool {
+    if repo.contains("://") || repo.starts_with('/') {
+        return true;
+    }
+    // scp-like syntax: a colon before the first slash.
+    match (repo.find(':'), repo.find('/')) {
+        (Some(colon), Some(slash)) => colon < slash,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+fn validate_resource_limits(mirror: &MirrorConfig, label: &str) -> Result<(), ConfigError> {
+    if let Some(limit) = mirror.memory_limit.as_deref()
+        && parse_memory_limit(limit).is_none()
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.memory_limit"),
+            reason: format!(
+                "`{limit}` is not a byte size; use a number with an optional \
+                 K/M/G/T suffix, e.g. `512M`"
+            ),
+        });
+    }
+
+    if let Some(weight) = mirror.cpu_weight
+        && !CPU_WEIGHT_RANGE.contains(&weight)
+    {
+        return Err(ConfigError::InvalidValue {
+            field: format!("mirrors.{label}.cpu_weight"),
+            reason: format!(
+                "cpu_weight must be between {} and {} (got {weight})",
+                CPU_WEIGHT_RANGE.start(),
+                CPU_WEIGHT_RANGE.end()
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Where a secret value comes from; see [`parse_secret_ref`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretRef<'a> {
+    /// The value itself.
+    Literal(&'a str),
+    /// An environment variable of the worker process.
+    Env(&'a str),
+    /// A file, read whole with the trailing newline dropped.
+    File(&'a str),
+    /// A systemd credential: `$CREDENTIALS_DIRECTORY/<name>`.
+    Credential(&'a str),
+}
+
+/// Parse a config value that may point at a secret instead of holding it:
+///
+/// - `env:VAR` reads the worker's environment variable `VAR`
+/// - `file:/path` reads an absolute file path
+/// - `credential:NAME` reads systemd credential `NAME`
+///   (`LoadCredential=` / `SetCredentialEncrypted=`)
+/// - `literal:VALUE` is `VALUE` as written, for values that happen to
+///   start with one of the prefixes above
+///
+/// Anything else is a literal. Errors never quote the value.
+pub fn parse_secret_ref(raw: &str) -> Result<SecretRef<'_>, String> {
+    if let Some(var) = raw.strip_prefix("env:") {
+        let valid = var.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+        if var.is_empty() || !valid {
+            return Err("`env:` must be followed by a variable name".into());
+        }
+        Ok(SecretRef::Env(var))
+    } else if let Some(path) = raw.strip_prefix("file:") {
+        if !path.starts_with('/') {
+            return Err("`file:` must be followed by an absolute path".into());
+        }
+        Ok(SecretRef::File(path))
+    } else if let Some(name) = raw.strip_prefix("credential:") {
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err("`credential:` must be followed by a credential name".into());
+        }
+        Ok(SecretRef::Credential(name))
+    } else {
+        Ok(SecretRef::Literal(
+            raw.strip_prefix("literal:").unwrap_or(raw),
+        ))
+    }
+}
+
+/// Valid values for cgroup v2 `cpu.weight`.
+const CPU_WEIGHT_RANGE: std::ops::RangeInclusive<u32> = 1..=10000;
+
+/// Parse a bandwidth such as `100M` or `512K/s` into bytes per second.
+///
+/// Same syntax as [`parse_memory_limit`] with an optional `/s` suffix.
+pub fn parse_byte_rate(raw: &str) -> Option<u64> {
+    let s = raw.trim();
+    parse_memory_limit(s.strip_suffix("/s").unwrap_or(s))
+}
+
+/// Parse a local `HH:MM` time of day into minutes since midnight.
+pub fn parse_time_of_day(raw: &str) -> Option<u32> {
+    let (hours, minutes) = raw.trim().split_once(':')?;
+    let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if hours.is_empty()
+        || hours.len() > 2
+        || minutes.len() != 2
+        || !digits(hours)
+        || !digits(minutes)
+    {
+        return None;
+    }
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Parse a weekday name (`mon`, `Monday`, …) into days from Monday.
+pub fn parse_weekday(raw: &str) -> Option<u32> {
+    raw.trim()
+        .parse::<chrono::Weekday>()
+        .ok()
+        .map(|day| day.num_days_from_monday())
+}
+
+/// Parse a human-readable memory size into bytes.
+///
+/// Accepts a plain byte count or a number with a `K`/`M`/`G`/`T` suffix
+/// (case-insensitive, optionally followed by `B` or `iB`). Suffixes are
+/// binary, matching Go's `units.RAMInBytes` and docker's `--memory`.
+/// Returns `None` for anything else, including zero.
+pub fn parse_memory_limit(raw: &str) -> Option<u64> {
+    let s = raw.trim();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, suffix) = s.split_at(digits_end);
+    let value: u64 = num.parse().ok()?;
+
+    let suffix = suffix.trim().to_ascii_lowercase();
+    let unit = suffix
+        .strip_suffix("ib")
+        .or_else(|| suffix.strip_suffix('b'))
+        .unwrap_or(&suffix);
+    let shift = match unit {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => return None,
+    };
+    // Bare "b"/"ib" without a magnitude letter is only valid as "b".
+    if unit.is_empty() && suffix == "ib" {
+        return None;
+    }
+
+    value.checked_mul(1u64 << shift).filter(|&bytes| bytes > 0)
+}
@@ -1,12 +1,17 @@
 // Semantic-validation tests for provider config shapes.
 //
-// Each of the five validation rules has at
+// Each of the twelve validation rules has at
 // least two cases: one legal input that must be accepted and at least one
 // illegal input that must produce `ConfigError::InvalidValue`.
 #![allow(clippy::unwrap_used)]
 #![allow(clippy::panic)]
+use std::collections::HashMap;
+
 use hustsync_config_parser::{
-    ConfigError, MirrorConfig, WorkerCgroupConfig, WorkerConfig, validate_worker_config,
+    BandwidthTierConfig, BlackoutWindowConfig, ConfigError, MirrorConfig, SecretRef,
+    Stage1ProfileConfig, WorkerBandwidthConfig, WorkerBtrfsSnapshotConfig, WorkerCgroupConfig,
+    WorkerConfig, WorkerGlobalConfig, WorkerZfsConfig, parse_byte_rate, parse_memory_limit,
+    parse_secret_ref, parse_time_of_day, validate_worker_config,
 };
 
 // ---------------------------------------------------------------------------
@@ -18,7 +23,13 @@ fn worker_with_single_mirror(mirror: MirrorConfig) -> WorkerConfig {
         global: None,
         manager: None,
         cgroup: None,
+        docker: None,
+        zfs: None,
+        btrfs_snapshot: None,
+        bandwidth: None,
         server: None,
+        stage1_profiles: None,
+        concurrency_groups: None,
         mirrors: Some(vec![mirror]),
         include: None,
     }
@@ -34,7 +45,7 @@ fn named_rsync_mirror(name: &str, upstream: &str) -> MirrorConfig {
 }
 
 #[test]
-fn reject_enabled_cgroup_until_runtime_support_exists() {
+fn accept_enabled_cgroup() {
     let mut cfg = worker_with_single_mirror(named_rsync_mirror(
         "arch",
         "rsync://mirror.example.org/archlinux/",
@@ -45,19 +56,150 @@ fn reject_enabled_cgroup_until_runtime_support_exists() {
         group: Some("hustsync".into()),
     });
 
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn accept_enabled_zfs_with_zpool() {
+    let mut cfg = worker_with_single_mirror(named_rsync_mirror(
+        "arch",
+        "rsync://mirror.example.org/archlinux/",
+    ));
+    cfg.zfs = Some(WorkerZfsConfig {
+        enable: Some(true),
+        zpool: Some("tank/mirrors".into()),
+        command: None,
+    });
+
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_enabled_zfs_without_zpool() {
+    let mut cfg = worker_with_single_mirror(named_rsync_mirror(
+        "arch",
+        "rsync://mirror.example.org/archlinux/",
+    ));
+    cfg.zfs = Some(WorkerZfsConfig {
+        enable: Some(true),
+        zpool: None,
+        command: None,
+    });
+
     let err = validate_worker_config(&cfg).unwrap_err();
     assert!(
-        matches!(
-            err,
-            ConfigError::UnsupportedField {
-                ref field,
-                ..
-            } if field == "cgroup.enable"
-        ),
-        "enabled cgroup must be rejected instead of silently ignored, got {err:?}"
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field == "zfs.zpool"),
+        "got {err:?}"
     );
 }
 
+#[test]
+fn reject_enabled_btrfs_snapshot_without_gc_path() {
+    let mut cfg = worker_with_single_mirror(named_rsync_mirror(
+        "arch",
+        "rsync://mirror.example.org/archlinux/",
+    ));
+    cfg.btrfs_snapshot = Some(WorkerBtrfsSnapshotConfig {
+        enable: Some(true),
+        command: None,
+        snapshot_path: Some("/srv/snapshots".into()),
+        gc_path: None,
+    });
+
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field == "btrfs_snapshot.gc_path"),
+        "got {err:?}"
+    );
+
+    cfg.btrfs_snapshot.as_mut().unwrap().gc_path = Some("/srv/snapshots/.gc".into());
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+fn tier(start: &str, end: &str, limit: &str) -> BandwidthTierConfig {
+    BandwidthTierConfig {
+        start: Some(start.into()),
+        end: Some(end.into()),
+        limit: Some(limit.into()),
+    }
+}
+
+#[test]
+fn accept_bandwidth_limit_with_tiers() {
+    let mut cfg = worker_with_single_mirror(named_rsync_mirror(
+        "arch",
+        "rsync://mirror.example.org/archlinux/",
+    ));
+    cfg.bandwidth = Some(WorkerBandwidthConfig {
+        limit: Some("100M/s".into()),
+        tiers: Some(vec![
+            tier("22:00", "06:00", "1G"),
+            tier("08:00", "18:00", "20M"),
+        ]),
+    });
+
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_malformed_bandwidth_values() {
+    let cases = [
+        (
+            Some("fast"),
+            tier("08:00", "18:00", "20M"),
+            "bandwidth.limit",
+        ),
+        (
+            None,
+            tier("8am", "18:00", "20M"),
+            "bandwidth.tiers[0].start",
+        ),
+        (
+            None,
+            tier("08:00", "24:00", "20M"),
+            "bandwidth.tiers[0].end",
+        ),
+        (
+            None,
+            tier("08:00", "08:00", "20M"),
+            "bandwidth.tiers[0].end",
+        ),
+        (
+            None,
+            tier("08:00", "18:00", "lots"),
+            "bandwidth.tiers[0].limit",
+        ),
+    ];
+    for (limit, bad_tier, expected) in cases {
+        let mut cfg = worker_with_single_mirror(named_rsync_mirror(
+            "arch",
+            "rsync://mirror.example.org/archlinux/",
+        ));
+        cfg.bandwidth = Some(WorkerBandwidthConfig {
+            limit: limit.map(String::from),
+            tiers: Some(vec![bad_tier]),
+        });
+
+        let err = validate_worker_config(&cfg).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::InvalidValue { ref field, .. } if field == expected),
+            "expected {expected}, got {err:?}"
+        );
+    }
+}
+
+#[test]
+fn parse_bandwidth_helpers() {
+    assert_eq!(parse_byte_rate("512K"), Some(512 * 1024));
+    assert_eq!(parse_byte_rate("2M/s"), Some(2 * 1024 * 1024));
+    assert_eq!(parse_byte_rate("/s"), None);
+    assert_eq!(parse_time_of_day("00:00"), Some(0));
+    assert_eq!(parse_time_of_day("23:59"), Some(23 * 60 + 59));
+    assert_eq!(parse_time_of_day("7:30"), Some(7 * 60 + 30));
+    assert_eq!(parse_time_of_day("07:5"), None);
+    assert_eq!(parse_time_of_day("12:60"), None);
+}
+
 // ---------------------------------------------------------------------------
 // Rule 1 — rsync upstream must end with `/`
 // ---------------------------------------------------------------------------
@@ -271,6 +413,74 @@ fn reject_stage1_profile_debian_oldstable() {
     assert!(matches!(err, ConfigError::InvalidValue { .. }));
 }
 
+fn kali_mirror() -> MirrorConfig {
+    MirrorConfig {
+        name: Some("kali".into()),
+        provider: Some("two-stage-rsync".into()),
+        upstream: Some("rsync://archive.kali.org/kali/".into()),
+        stage1_profile: Some("kali".into()),
+        ..MirrorConfig::default()
+    }
+}
+
+fn kali_profile() -> Stage1ProfileConfig {
+    Stage1ProfileConfig {
+        include: Some(vec!["by-hash/".into()]),
+        exclude: Some(vec!["Packages*".into(), "InRelease".into()]),
+        ..Stage1ProfileConfig::default()
+    }
+}
+
+#[test]
+fn accept_stage1_profile_from_config() {
+    let mut cfg = worker_with_single_mirror(kali_mirror());
+    cfg.stage1_profiles = Some(HashMap::from([("kali".to_string(), kali_profile())]));
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_stage1_profile_missing_from_config() {
+    let mut cfg = worker_with_single_mirror(kali_mirror());
+    cfg.stage1_profiles = Some(HashMap::from([("deepin".to_string(), kali_profile())]));
+    let err = validate_worker_config(&cfg).unwrap_err();
+    match err {
+        ConfigError::InvalidValue { field, reason } => {
+            assert_eq!(field, "mirrors.kali.stage1_profile");
+            assert!(
+                reason.contains("debian, debian-oldstyle, deepin"),
+                "{reason}"
+            );
+        }
+        other => panic!("expected InvalidValue, got {other:?}"),
+    }
+}
+
+#[test]
+fn reject_stage1_profile_without_stage1_rules() {
+    let mut cfg = worker_with_single_mirror(kali_mirror());
+    let profile = Stage1ProfileConfig {
+        stage2_exclude: Some(vec!["*-proposed/".into()]),
+        ..Stage1ProfileConfig::default()
+    };
+    cfg.stage1_profiles = Some(HashMap::from([("kali".to_string(), profile)]));
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field == "stage1_profiles.kali")
+    );
+}
+
+#[test]
+fn reject_stage1_profile_with_empty_pattern() {
+    let mut cfg = worker_with_single_mirror(kali_mirror());
+    let mut profile = kali_profile();
+    profile.stage2_include = Some(vec!["  ".into()]);
+    cfg.stage1_profiles = Some(HashMap::from([("kali".to_string(), profile)]));
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field == "stage1_profiles.kali.stage2_include")
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Edge: non-rsync provider is not checked for rsync rules
 // ---------------------------------------------------------------------------
@@ -289,6 +499,814 @@ fn cmd_provider_upstream_no_trailing_slash_is_ok() {
     assert!(validate_worker_config(&cfg).is_ok());
 }
 
+// ---------------------------------------------------------------------------
+// Rule 6 — memory_limit / cpu_weight must be usable cgroup values
+// ---------------------------------------------------------------------------
+
+#[test]
+fn accept_memory_limit_with_suffix() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example.org/archlinux/");
+    mirror.memory_limit = Some("512M".into());
+    mirror.cpu_weight = Some(50);
+    let cfg = worker_with_single_mirror(mirror);
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_memory_limit_garbage() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example.org/archlinux/");
+    mirror.memory_limit = Some("lots".into());
+    let cfg = worker_with_single_mirror(mirror);
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("memory_limit")),
+        "got {err:?}"
+    );
+}
+
+#[test]
+fn reject_cpu_weight_out_of_range() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example.org/archlinux/");
+    mirror.cpu_weight = Some(0);
+    let cfg = worker_with_single_mirror(mirror);
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("cpu_weight")),
+        "got {err:?}"
+    );
+}
+
+#[test]
+fn parse_memory_limit_units() {
+    assert_eq!(parse_memory_limit("1048576"), Some(1 << 20));
+    assert_eq!(parse_memory_limit("512M"), Some(512 << 20));
+    assert_eq!(parse_memory_limit("4g"), Some(4 << 30));
+    assert_eq!(parse_memory_limit("2GiB"), Some(2 << 30));
+    assert_eq!(parse_memory_limit("16kb"), Some(16 << 10));
+    assert_eq!(parse_memory_limit("0"), None);
+    assert_eq!(parse_memory_limit("1.5G"), None);
+    assert_eq!(parse_memory_limit("10X"), None);
+    assert_eq!(parse_memory_limit(""), None);
+}
+
+// ---------------------------------------------------------------------------
+// Rule 7 — http upstream must be an http(s) directory URL; excludes compile
+// ---------------------------------------------------------------------------
+
+fn named_http_mirror(name: &str, upstream: &str) -> MirrorConfig {
+    MirrorConfig {
+        name: Some(name.into()),
+        provider: Some("http".into()),
+        upstream: Some(upstream.into()),
+        ..MirrorConfig::default()
+    }
+}
+
+#[test]
+fn accept_http_upstream_with_excludes() {
+    let mut mirror = named_http_mirror("kernel", "https://cdn.example.org/pub/linux/");
+    mirror.exclude_patterns = Some(vec![r"^testing/".into(), r"\.iso$".into()]);
+    let cfg = worker_with_single_mirror(mirror);
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_http_upstream_missing_trailing_slash() {
+    let cfg = worker_with_single_mirror(named_http_mirror(
+        "kernel",
+        "https://cdn.example.org/pub/linux",
+    ));
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("upstream")),
+        "got {err:?}"
+    );
+}
+
+#[test]
+fn reject_http_upstream_with_rsync_scheme() {
+    let cfg = worker_with_single_mirror(named_http_mirror(
+        "kernel",
+        "rsync://cdn.example.org/pub/linux/",
+    ));
+    assert!(matches!(
+        validate_worker_config(&cfg).unwrap_err(),
+        ConfigError::InvalidValue { .. }
+    ));
+}
+
+#[test]
+fn reject_http_exclude_pattern_that_does_not_compile() {
+    let mut mirror = named_http_mirror("kernel", "https://cdn.example.org/pub/linux/");
+    mirror.exclude_patterns = Some(vec!["(unclosed".into()]);
+    let cfg = worker_with_single_mirror(mirror);
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("exclude_patterns")),
+        "got {err:?}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Rule 8 — git needs a repository source; relative repos need a base URL
+// ---------------------------------------------------------------------------
+
+fn named_git_mirror(name: &str, upstream: Option<&str>, repos: Option<&[&str]>) -> MirrorConfig {
+    MirrorConfig {
+        name: Some(name.into()),
+        provider: Some("git".into()),
+        upstream: upstream.map(Into::into),
+        git_repos: repos.map(|r| r.iter().map(|s| s.to_string()).collect()),
+        ..MirrorConfig::default()
+    }
+}
+
+#[test]
+fn accept_git_single_upstream_and_repo_list() {
+    for mirror in [
+        named_git_mirror("linux", Some("https://git.example.org/linux.git"), None),
+        named_git_mirror(
+            "tools",
+            Some("https://git.example.org/"),
+            Some(&[
+                "tools/a.git",
+                "git@other.example.org:b.git",
+                "https://x.example/c",
+            ]),
+        ),
+    ] {
+        let cfg = worker_with_single_mirror(mirror);
+        assert!(validate_worker_config(&cfg).is_ok());
+    }
+}
+
+#[test]
+fn reject_git_without_upstream_or_repos() {
+    let cfg = worker_with_single_mirror(named_git_mirror("linux", None, None));
+    assert!(matches!(
+        validate_worker_config(&cfg).unwrap_err(),
+        ConfigError::InvalidValue { .. }
+    ));
+}
+
+#[test]
+fn reject_git_relative_repo_without_base_url() {
+    let cfg = worker_with_single_mirror(named_git_mirror(
+        "tools",
+        Some("https://git.example.org/tools.git"),
+        Some(&["a.git"]),
+    ));
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("git_repos")),
+        "got {err:?}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Rule 9 — s3 upstream is s3://bucket/prefix/; credentials come in pairs
+// ---------------------------------------------------------------------------
+
+fn named_s3_mirror(name: &str, upstream: &str, env: &[(&str, &str)]) -> MirrorConfig {
+    MirrorConfig {
+        name: Some(name.into()),
+        provider: Some("s3".into()),
+        upstream: Some(upstream.into()),
+        env: Some(
+            env.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ),
+        ..MirrorConfig::default()
+    }
+}
+
+#[test]
+fn accept_s3_bucket_prefix_with_credentials() {
+    let mirror = named_s3_mirror(
+        "vendor",
+        "s3://vendor-releases/linux/",
+        &[
+            ("AWS_ACCESS_KEY_ID", "AKID"),
+            ("AWS_SECRET_ACCESS_KEY", "secret"),
+            ("AWS_ENDPOINT_URL", "https://minio.example.org"),
+        ],
+    );
+    let cfg = worker_with_single_mirror(mirror);
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_s3_upstream_that_is_not_a_bucket_url() {
+    for upstream in ["https://vendor.example/", "s3:///", "s3://bucket/prefix"] {
+        let cfg = worker_with_single_mirror(named_s3_mirror("vendor", upstream, &[]));
+        assert!(
+            matches!(
+                validate_worker_config(&cfg),
+                Err(ConfigError::InvalidValue { ref field, .. }) if field.ends_with("upstream")
+            ),
+            "{upstream} should be rejected"
+        );
+    }
+}
+
+#[test]
+fn reject_s3_half_credentials() {
+    let mirror = named_s3_mirror("vendor", "s3://bucket/", &[("AWS_ACCESS_KEY_ID", "AKID")]);
+    let cfg = worker_with_single_mirror(mirror);
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("env")),
+        "got {err:?}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Rule 10 — apt upstream is an http(s) archive root; suites are required
+// ---------------------------------------------------------------------------
+
+fn named_apt_mirror(name: &str, upstream: &str, suites: Option<&[&str]>) -> MirrorConfig {
+    MirrorConfig {
+        name: Some(name.into()),
+        provider: Some("apt".into()),
+        upstream: Some(upstream.into()),
+        apt_suites: suites.map(|s| s.iter().map(|x| x.to_string()).collect()),
+        ..MirrorConfig::default()
+    }
+}
+
+#[test]
+fn accept_apt_archive_with_suites() {
+    let mut mirror = named_apt_mirror(
+        "debian",
+        "https://deb.debian.org/debian/",
+        Some(&["bookworm", "bookworm-updates"]),
+    );
+    mirror.apt_components = Some(vec!["main".into(), "non-free-firmware".into()]);
+    mirror.apt_architectures = Some(vec!["amd64".into(), "source".into()]);
+    let cfg = worker_with_single_mirror(mirror);
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_apt_without_suites() {
+    for suites in [None, Some(&[][..])] {
+        let mirror = named_apt_mirror("debian", "https://deb.debian.org/debian/", suites);
+        let err = validate_worker_config(&worker_with_single_mirror(mirror)).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("apt_suites")),
+            "got {err:?}"
+        );
+    }
+}
+
+#[test]
+fn reject_apt_bad_upstream_or_names() {
+    let mirror = named_apt_mirror(
+        "debian",
+        "rsync://deb.debian.org/debian/",
+        Some(&["bookworm"]),
+    );
+    let err = validate_worker_config(&worker_with_single_mirror(mirror)).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("upstream")),
+        "got {err:?}"
+    );
+
+    let mut mirror = named_apt_mirror(
+        "debian",
+        "https://deb.debian.org/debian/",
+        Some(&["bookworm"]),
+    );
+    mirror.apt_components = Some(vec!["../main".into()]);
+    let err = validate_worker_config(&worker_with_single_mirror(mirror)).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field.ends_with("apt_components")),
+        "got {err:?}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Rule 11 — yum upstream is an http(s) repository root
+// ---------------------------------------------------------------------------
+
+fn named_yum_mirror(name: &str, upstream: &str) -> MirrorConfig {
+    MirrorConfig {
+        name: Some(name.into()),
+        provider: Some("yum".into()),
+        upstream: Some(upstream.into()),
+        ..MirrorConfig::default()
+    }
+}
+
+#[test]
+fn accept_yum_repository_root() {
+    let mirror = named_yum_mirror(
+        "epel",
+        "https://dl.fedoraproject.org/pub/epel/9/Everything/x86_64/",
+    );
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+}
+
+#[test]
+fn reject_yum_upstream_that_is_not_a_directory_url() {
+    for upstream in [
+        "rsync://mirror.example/epel/9/",
+        "https://mirror.example/epel/9/repodata/repomd.xml",
+    ] {
+        let cfg = worker_with_single_mirror(named_yum_mirror("epel", upstream));
+        assert!(
+            matches!(
+                validate_worker_config(&cfg),
+                Err(ConfigError::InvalidValue { ref field, .. }) if field.ends_with("upstream")
+            ),
+            "{upstream} should be rejected"
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Rule 12 — fallback_upstreams: supported providers, primary required
+// ---------------------------------------------------------------------------
+
+fn with_fallbacks(mut mirror: MirrorConfig, fallbacks: &[&str]) -> MirrorConfig {
+    mirror.fallback_upstreams = Some(fallbacks.iter().map(|s| s.to_string()).collect());
+    mirror
+}
+
+fn fallback_error_field(mirror: MirrorConfig) -> String {
+    match validate_worker_config(&worker_with_single_mirror(mirror)).unwrap_err() {
+        ConfigError::InvalidValue { field, .. } => field,
+        other => panic!("expected InvalidValue, got {other:?}"),
+    }
+}
+
+#[test]
+fn accept_rsync_fallback_upstreams() {
+    let mirror = with_fallbacks(
+        named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/"),
+        &[
+            "rsync://mirror.example.org/ubuntu/",
+            "rsync://[2001:db8::1]/ubuntu/",
+        ],
+    );
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+}
+
+#[test]
+fn accept_command_fallback_upstreams_of_any_shape() {
+    let mirror = MirrorConfig {
+        name: Some("pypi".into()),
+        provider: Some("command".into()),
+        command: Some("/usr/bin/bandersnatch mirror".into()),
+        upstream: Some("https://pypi.org".into()),
+        ..MirrorConfig::default()
+    };
+    let mirror = with_fallbacks(mirror, &["https://pypi.example.org"]);
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+}
+
+#[test]
+fn reject_rsync_fallback_without_trailing_slash() {
+    let mirror = with_fallbacks(
+        named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/"),
+        &[
+            "rsync://mirror.example.org/ubuntu/",
+            "rsync://backup.example.org/ubuntu",
+        ],
+    );
+    assert_eq!(
+        fallback_error_field(mirror),
+        "mirrors.ubuntu.fallback_upstreams[1]"
+    );
+}
+
+#[test]
+fn reject_rsync_fallback_with_bare_ipv6() {
+    let mirror = with_fallbacks(
+        named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/"),
+        &["rsync://2001:db8::1/ubuntu/"],
+    );
+    assert_eq!(
+        fallback_error_field(mirror),
+        "mirrors.ubuntu.fallback_upstreams[0]"
+    );
+}
+
+#[test]
+fn reject_fallback_upstreams_on_unsupported_provider() {
+    let mirror = with_fallbacks(
+        named_yum_mirror("epel", "https://dl.fedoraproject.org/pub/epel/9/x86_64/"),
+        &["https://mirror.example.org/epel/9/x86_64/"],
+    );
+    assert_eq!(
+        fallback_error_field(mirror),
+        "mirrors.epel.fallback_upstreams"
+    );
+}
+
+#[test]
+fn reject_fallback_upstreams_without_primary() {
+    let mirror = MirrorConfig {
+        name: Some("ubuntu".into()),
+        provider: Some("rsync".into()),
+        upstream: None,
+        ..MirrorConfig::default()
+    };
+    let mirror = with_fallbacks(mirror, &["rsync://mirror.example.org/ubuntu/"]);
+    assert_eq!(
+        fallback_error_field(mirror),
+        "mirrors.ubuntu.fallback_upstreams"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Atomic publish (rule 13)
+// ---------------------------------------------------------------------------
+
+fn invalid_field(mirror: MirrorConfig) -> String {
+    match validate_worker_config(&worker_with_single_mirror(mirror)).unwrap_err() {
+        ConfigError::InvalidValue { field, .. } => field,
+        other => panic!("expected InvalidValue, got {other:?}"),
+    }
+}
+
+#[test]
+fn accept_publish_dir_on_rsync_mirror() {
+    let mut mirror = named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/");
+    mirror.publish_dir = Some("/srv/www/{{.Name}}".into());
+    mirror.publish_keep = Some(3);
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+}
+
+#[test]
+fn reject_publish_dir_on_unsupported_provider() {
+    let mut mirror = named_yum_mirror("epel", "https://dl.fedoraproject.org/pub/epel/9/x86_64/");
+    mirror.publish_dir = Some("/srv/www/epel".into());
+    assert_eq!(invalid_field(mirror), "mirrors.epel.publish_dir");
+}
+
+#[test]
+fn reject_publish_dir_with_inplace() {
+    let mut mirror = named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/");
+    mirror.publish_dir = Some("/srv/www/ubuntu".into());
+    mirror.rsync_options = Some(vec!["--inplace".into()]);
+    assert_eq!(invalid_field(mirror), "mirrors.ubuntu.publish_dir");
+}
+
+#[test]
+fn reject_publish_keep_without_publish_dir() {
+    let mut mirror = named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/");
+    mirror.publish_keep = Some(2);
+    assert_eq!(invalid_field(mirror), "mirrors.ubuntu.publish_keep");
+}
+
+// ---------------------------------------------------------------------------
+// Mass-deletion guard (rule 14)
+// ---------------------------------------------------------------------------
+
+#[test]
+fn accept_delete_guard_on_rsync_mirror() {
+    let mut mirror = named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/");
+    mirror.max_delete = Some(5000);
+    mirror.max_delete_percent = Some(10);
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+}
+
+#[test]
+fn reject_delete_guard_on_unsupported_provider() {
+    let mut mirror = named_yum_mirror("epel", "https://dl.fedoraproject.org/pub/epel/9/x86_64/");
+    mirror.max_delete = Some(100);
+    assert_eq!(invalid_field(mirror), "mirrors.epel.max_delete");
+}
+
+#[test]
+fn reject_delete_percent_above_100() {
+    let mut mirror = named_rsync_mirror("ubuntu", "rsync://archive.ubuntu.com/ubuntu/");
+    mirror.max_delete_percent = Some(150);
+    assert_eq!(invalid_field(mirror), "mirrors.ubuntu.max_delete_percent");
+}
+
+// ---------------------------------------------------------------------------
+// Freshness probe (rule 15)
+// ---------------------------------------------------------------------------
+
+#[test]
+fn accept_freshness_probe_on_any_provider() {
+    for probe in [
+        "https://mirror.example/archlinux/lastupdate",
+        "rsync://mirror.example/archlinux/lastupdate",
+    ] {
+        let mut mirror =
+            named_yum_mirror("epel", "https://dl.fedoraproject.org/pub/epel/9/x86_64/");
+        mirror.freshness_probe = Some(probe.into());
+        mirror.freshness_mode = Some("mtime".into());
+        assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+    }
+}
+
+#[test]
+fn reject_freshness_probe_that_is_not_a_file_url() {
+    for probe in ["/srv/lastupdate", "https://mirror.example/archlinux/"] {
+        let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example/archlinux/");
+        mirror.freshness_probe = Some(probe.into());
+        assert_eq!(invalid_field(mirror), "mirrors.arch.freshness_probe");
+    }
+}
+
+#[test]
+fn reject_unknown_freshness_mode_or_mode_without_probe() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example/archlinux/");
+    mirror.freshness_mode = Some("content".into());
+    assert_eq!(invalid_field(mirror.clone()), "mirrors.arch.freshness_mode");
+
+    mirror.freshness_probe = Some("rsync://mirror.example/archlinux/lastupdate".into());
+    mirror.freshness_mode = Some("etag".into());
+    assert_eq!(invalid_field(mirror), "mirrors.arch.freshness_mode");
+}
+
+#[test]
+fn accept_secret_references_and_password_file() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example/archlinux/");
+    mirror.password = Some("credential:arch-rsync".into());
+    mirror.secret_env = Some(
+        [
+            ("TOKEN".to_string(), "env:ARCH_TOKEN".to_string()),
+            ("KEY".to_string(), "file:/etc/hustsync/key".to_string()),
+        ]
+        .into(),
+    );
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror.clone())).is_ok());
+
+    mirror.password = None;
+    mirror.password_file = Some("/etc/hustsync/arch.pass".into());
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+}
+
+#[test]
+fn plain_env_values_are_not_secret_references() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example/archlinux/");
+    mirror.env = Some(
+        [
+            ("REPO".to_string(), "file:///srv/mirror".to_string()),
+            ("MODE".to_string(), "env:".to_string()),
+            ("NOTE".to_string(), "credential:../x".to_string()),
+        ]
+        .into(),
+    );
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror.clone())).is_ok());
+
+    mirror.secret_env = Some([("REPO".to_string(), "env:REPO".to_string())].into());
+    assert_eq!(invalid_field(mirror), "mirrors.arch.secret_env.REPO");
+}
+
+#[test]
+fn reject_password_with_password_file() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example/archlinux/");
+    mirror.password = Some("hunter2".into());
+    mirror.password_file = Some("/etc/hustsync/arch.pass".into());
+    assert_eq!(invalid_field(mirror), "mirrors.arch.password_file");
+}
+
+#[test]
+fn reject_malformed_secret_reference_without_quoting_it() {
+    let mut mirror = named_rsync_mirror("arch", "rsync://mirror.example/archlinux/");
+    mirror.secret_env = Some([("TOKEN".to_string(), "env:".to_string())].into());
+    assert_eq!(
+        invalid_field(mirror.clone()),
+        "mirrors.arch.secret_env.TOKEN"
+    );
+
+    mirror.secret_env = None;
+    mirror.password = Some("file:hunter2".into());
+    let err = validate_worker_config(&worker_with_single_mirror(mirror)).unwrap_err();
+    let ConfigError::InvalidValue { field, reason } = err else {
+        panic!("expected InvalidValue");
+    };
+    assert_eq!(field, "mirrors.arch.password");
+    assert!(
+        !reason.contains("hunter2"),
+        "reason quotes the value: {reason}"
+    );
+}
+
+#[test]
+fn accept_cron_schedule_with_optional_timezone() {
+    for expr in ["0 2,14 * * *", "30 0 4 * * MON-FRI", "@daily"] {
+        let mut mirror = named_rsync_mirror("debian", "rsync://mirror.example/debian/");
+        mirror.schedule = Some(expr.into());
+        mirror.schedule_timezone = Some("Asia/Shanghai".into());
+        assert!(
+            validate_worker_config(&worker_with_single_mirror(mirror)).is_ok(),
+            "{expr}"
+        );
+    }
+}
+
+#[test]
+fn reject_bad_cron_schedule_or_timezone() {
+    let mut mirror = named_rsync_mirror("debian", "rsync://mirror.example/debian/");
+    mirror.schedule_timezone = Some("UTC".into());
+    assert_eq!(
+        invalid_field(mirror.clone()),
+        "mirrors.debian.schedule_timezone"
+    );
+
+    mirror.schedule = Some("0 25 * * *".into());
+    assert_eq!(invalid_field(mirror.clone()), "mirrors.debian.schedule");
+
+    mirror.schedule = Some("0 2 * * *".into());
+    mirror.schedule_timezone = Some("Mars/Olympus_Mons".into());
+    assert_eq!(invalid_field(mirror), "mirrors.debian.schedule_timezone");
+}
+
+#[test]
+fn accept_retry_backoff_settings() {
+    let mut mirror = named_rsync_mirror("debian", "rsync://mirror.example/debian/");
+    mirror.retry_backoff = Some(30);
+    mirror.retry_backoff_multiplier = Some(1.5);
+    mirror.retry_backoff_max = Some(600);
+    mirror.retry_backoff_jitter = Some(0.2);
+    assert!(validate_worker_config(&worker_with_single_mirror(mirror)).is_ok());
+}
+
+#[test]
+fn reject_out_of_range_retry_backoff() {
+    let mut mirror = named_rsync_mirror("debian", "rsync://mirror.example/debian/");
+    mirror.retry_backoff_multiplier = Some(0.5);
+    assert_eq!(
+        invalid_field(mirror.clone()),
+        "mirrors.debian.retry_backoff_multiplier"
+    );
+
+    mirror.retry_backoff_multiplier = None;
+    mirror.retry_backoff_jitter = Some(1.5);
+    assert_eq!(
+        invalid_field(mirror.clone()),
+        "mirrors.debian.retry_backoff_jitter"
+    );
+
+    mirror.retry_backoff_jitter = None;
+    mirror.retry_backoff = Some(60);
+    mirror.retry_backoff_max = Some(10);
+    assert_eq!(invalid_field(mirror), "mirrors.debian.retry_backoff_max");
+}
+
+fn mirror_after(name: &str, after: &[&str]) -> MirrorConfig {
+    let mut mirror = named_rsync_mirror(name, "rsync://mirror.example/debian/");
+    mirror.after = Some(after.iter().map(|s| s.to_string()).collect());
+    mirror
+}
+
+fn worker_with_mirrors(mirrors: Vec<MirrorConfig>) -> WorkerConfig {
+    WorkerConfig {
+        mirrors: Some(mirrors),
+        ..worker_with_single_mirror(MirrorConfig::default())
+    }
+}
+
+#[test]
+fn accept_after_naming_other_mirrors() {
+    let cfg = worker_with_mirrors(vec![
+        mirror_after("debian", &[]),
+        mirror_after("debian-cd", &["debian"]),
+        mirror_after("debian-subset", &["debian", "debian-cd"]),
+    ]);
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_after_naming_itself_or_an_unknown_mirror() {
+    assert_eq!(
+        invalid_field(mirror_after("debian", &["debian"])),
+        "mirrors.debian.after"
+    );
+    assert_eq!(
+        invalid_field(mirror_after("debian", &["ubuntu"])),
+        "mirrors.debian.after"
+    );
+}
+
+#[test]
+fn reject_after_cycle_naming_its_members() {
+    let cfg = worker_with_mirrors(vec![
+        mirror_after("a", &["c"]),
+        mirror_after("b", &["a"]),
+        mirror_after("c", &["b"]),
+        mirror_after("d", &["a"]),
+    ]);
+    match validate_worker_config(&cfg).unwrap_err() {
+        ConfigError::InvalidValue { field, reason } => {
+            assert_eq!(field, "mirrors.a.after");
+            assert!(reason.contains("a -> c -> b -> a"), "got {reason:?}");
+        }
+        other => panic!("expected InvalidValue, got {other:?}"),
+    }
+}
+
+#[test]
+fn accept_concurrency_group_listed_in_the_worker() {
+    let mut mirror = named_rsync_mirror("archlinux", "rsync://rsync.archlinux.org/ftp_tmp/");
+    mirror.concurrency_group = Some("tier1".into());
+    let mut cfg = worker_with_single_mirror(mirror);
+    cfg.concurrency_groups = Some([("tier1".to_string(), 2)].into());
+    cfg.global = Some(WorkerGlobalConfig {
+        concurrent_per_host: Some(4),
+        ..WorkerGlobalConfig::default()
+    });
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_unknown_concurrency_group_or_zero_limit() {
+    let mut mirror = named_rsync_mirror("archlinux", "rsync://rsync.archlinux.org/ftp_tmp/");
+    mirror.concurrency_group = Some("tier1".into());
+    assert_eq!(
+        invalid_field(mirror.clone()),
+        "mirrors.archlinux.concurrency_group"
+    );
+
+    let mut cfg = worker_with_single_mirror(mirror);
+    cfg.concurrency_groups = Some([("tier1".to_string(), 0)].into());
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field == "concurrency_groups.tier1"),
+        "got {err:?}"
+    );
+}
+
+fn blackout(start: &str, end: &str, days: &[&str]) -> BlackoutWindowConfig {
+    BlackoutWindowConfig {
+        start: Some(start.into()),
+        end: Some(end.into()),
+        days: (!days.is_empty()).then(|| days.iter().map(|d| d.to_string()).collect()),
+    }
+}
+
+#[test]
+fn accept_global_and_mirror_blackout_windows() {
+    let mut mirror = named_rsync_mirror("debian", "rsync://mirror.example/debian/");
+    mirror.blackout = Some(vec![blackout("22:00", "02:00", &["Fri", "sat"])]);
+    let mut cfg = worker_with_single_mirror(mirror);
+    cfg.global = Some(WorkerGlobalConfig {
+        blackout: Some(vec![blackout("08:00", "18:00", &["mon", "tue", "wed"])]),
+        ..WorkerGlobalConfig::default()
+    });
+    assert!(validate_worker_config(&cfg).is_ok());
+}
+
+#[test]
+fn reject_malformed_blackout_windows() {
+    let mut mirror = named_rsync_mirror("debian", "rsync://mirror.example/debian/");
+    mirror.blackout = Some(vec![blackout("08:00", "18:00", &["someday"])]);
+    assert_eq!(
+        invalid_field(mirror.clone()),
+        "mirrors.debian.blackout[0].days"
+    );
+
+    mirror.blackout = Some(vec![blackout("08:00", "08:00", &[])]);
+    assert_eq!(invalid_field(mirror), "mirrors.debian.blackout[0].end");
+
+    let mut cfg = worker_with_single_mirror(named_rsync_mirror(
+        "debian",
+        "rsync://mirror.example/debian/",
+    ));
+    cfg.global = Some(WorkerGlobalConfig {
+        blackout: Some(vec![blackout("8am", "18:00", &[])]),
+        ..WorkerGlobalConfig::default()
+    });
+    let err = validate_worker_config(&cfg).unwrap_err();
+    assert!(
+        matches!(err, ConfigError::InvalidValue { ref field, .. } if field == "global.blackout[0].start"),
+        "got {err:?}"
+    );
+}
+
+#[test]
+fn secret_reference_forms() {
+    assert_eq!(
+        parse_secret_ref("hunter2"),
+        Ok(SecretRef::Literal("hunter2"))
+    );
+    assert_eq!(
+        parse_secret_ref("literal:env:X"),
+        Ok(SecretRef::Literal("env:X"))
+    );
+    assert_eq!(
+        parse_secret_ref("env:RSYNC_PW"),
+        Ok(SecretRef::Env("RSYNC_PW"))
+    );
+    assert_eq!(
+        parse_secret_ref("file:/run/pw"),
+        Ok(SecretRef::File("/run/pw"))
+    );
+    assert_eq!(
+        parse_secret_ref("credential:pw"),
+        Ok(SecretRef::Credential("pw"))
+    );
+    assert!(parse_secret_ref("credential:../pw").is_err());
+}
+
 // ---------------------------------------------------------------------------
 // Error message quality: reason must be actionable
 // ---------------------------------------------------------------------------
@@ -31,6 +31,44 @@ pub struct MirrorStatus {
     pub next_scheduled: DateTime<Utc>,
     pub status: SyncStatus,
     pub is_master: bool,
+    /// Live transfer progress while `status` is `Syncing`; cleared by the
+    /// next status update.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<SyncProgress>,
+    /// Transfer statistics of the last successful rsync-based sync; kept
+    /// until the next success replaces them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_stats: Option<TransferStats>,
+}
+
+/// What one sync changed, parsed from rsync's `--stats` block. Runs that
+/// spawn rsync more than once (two-stage) report the sum.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct TransferStats {
+    pub files_transferred: u64,
+    pub files_deleted: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Data sent as-is because no matching block existed locally.
+    pub literal_data: u64,
+    /// Data rebuilt from blocks already present locally.
+    pub matched_data: u64,
+    /// Wall-clock duration of the run.
+    pub duration_secs: u64,
+}
+
+/// Transfer progress of a running sync, parsed from rsync's
+/// `--info=progress2` output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SyncProgress {
+    /// Bytes transferred so far in the current rsync run.
+    pub bytes: u64,
+    pub percent: u8,
+    /// Current rate as rsync prints it, e.g. `12.34MB/s`.
+    pub rate: String,
+    pub updated: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1,7 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize, Serializer};
 
-use crate::msg::MirrorStatus;
+use crate::msg::{MirrorStatus, SyncProgress, TransferStats};
 use crate::status::SyncStatus;
 
 pub mod web_time_format {
@@ -73,6 +73,10 @@ pub struct WebMirrorStatus {
 
     pub status: SyncStatus,
     pub is_master: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<SyncProgress>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_stats: Option<TransferStats>,
 }
 
 pub mod web_time_format_text {
@@ -103,6 +107,8 @@ impl From<MirrorStatus> for WebMirrorStatus {
             next_schedule_ts: ms.next_scheduled,
             status: ms.status,
             is_master: ms.is_master,
+            progress: ms.progress,
+            transfer_stats: ms.transfer_stats,
         }
     }
 }
@@ -132,6 +138,8 @@ mod tests {
             next_schedule_ts: t,
             status: SyncStatus::Success,
             is_master: false,
+            progress: None,
+            transfer_stats: None,
         };
 
         let b = serde_json::to_value(&m).expect("serialize should succeed");
@@ -162,6 +170,8 @@ mod tests {
             upstream: "mirrors.tuna.tsinghua.edu.cn".to_string(),
             size: "4GB".to_string(),
             error_msg: "Network error".to_string(),
+            progress: None,
+            transfer_stats: None,
         };
 
         let m2: WebMirrorStatus = WebMirrorStatus::from(m);
@@ -9,6 +9,7 @@ use std::process::ExitStatus;
 use std::time::Duration;
 
 use crate::error::InternalError;
+use crate::msg::TransferStats;
 
 /// Expand a leading `~/` to `$HOME/`. Leaves the path unchanged if
 /// `HOME` is unset or the path doesn't start with `~`.
@@ -173,6 +174,88 @@ pub fn extract_size_from_rsync_log(log_file: &str) -> Result<String, InternalErr
     Ok(extract_size_from_log(log_file, &re))
 }
 
+/// Parse a count as rsync prints it: plain (`5694`), with digit
+/// separators (`5,694`) or, under `-h`, with a decimal unit suffix
+/// (`780.62M`, powers of 1000).
+pub fn parse_rsync_count(raw: &str) -> Option<u64> {
+    let raw = raw.replace(',', "");
+    let (num, scale) = match raw.char_indices().last()? {
+        (idx, 'K') => (&raw[..idx], 1e3),
+        (idx, 'M') => (&raw[..idx], 1e6),
+        (idx, 'G') => (&raw[..idx], 1e9),
+        (idx, 'T') => (&raw[..idx], 1e12),
+        (idx, 'P') => (&raw[..idx], 1e15),
+        _ => return raw.parse().ok(),
+    };
+    let value: f64 = num.parse().ok()?;
+    // Finite, non-negative and far below u64::MAX, so the cast is exact enough.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    (value.is_finite() && value >= 0.0).then(|| (value * scale).round() as u64)
+}
+
+/// Collect the `--stats` blocks written to an rsync log from byte offset
+/// `from` on into one record, summing every block found so runs that
+/// spawn rsync more than once report the whole sync. `duration_secs` is
+/// left at zero for the caller to fill.
+///
+/// Returns `None` when that part of the log holds no stats block.
+pub fn extract_stats_from_rsync_log(
+    log_file: &str,
+    from: u64,
+) -> Result<Option<TransferStats>, InternalError> {
+    let re = Regex::new(
+        r"(?m)^(Number of (?:regular )?files transferred|Number of deleted files|Total bytes sent|Total bytes received|Literal data|Matched data): ([0-9,\.]+[KMGTP]?)",
+    )
+    .map_err(|e| InternalError::LogParse(e.to_string()))?;
+    if log_file == "/dev/null" {
+        return Ok(None);
+    }
+    let content = match fs::read(log_file) {
+        Ok(content) => content,
+        Err(_) => return Ok(None),
+    };
+    let start = usize::try_from(from)
+        .unwrap_or(usize::MAX)
+        .min(content.len());
+    let content = String::from_utf8_lossy(&content[start..]);
+
+    let mut found = false;
+    let mut stats = TransferStats::default();
+    for caps in re.captures_iter(&content) {
+        let Some(value) = parse_rsync_count(&caps[2]) else {
+            continue;
+        };
+        let field = match &caps[1] {
+            "Number of deleted files" => &mut stats.files_deleted,
+            "Total bytes sent" => &mut stats.bytes_sent,
+            "Total bytes received" => &mut stats.bytes_received,
+            "Literal data" => &mut stats.literal_data,
+            "Matched data" => &mut stats.matched_data,
+            _ => &mut stats.files_transferred,
+        };
+        *field = field.saturating_add(value);
+        found = true;
+    }
+    Ok(found.then_some(stats))
+}
+
+/// Format a byte count the way `rsync -h` reports sizes (`123`, `4.50K`,
+/// `1.23G`), so sizes from native providers read like the rsync ones the
+/// manager already displays.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2}{}", value, UNITS[unit])
+}
+
 pub fn translate_rsync_exit_status(status: &ExitStatus) -> (Option<i32>, Option<String>) {
     if let Some(code) = status.code() {
         let map = rsync_exit_values_map();
@@ -263,4 +346,74 @@ Total transferred file size: 99M bytes
         let res = extract_size_from_rsync_log("/dev/null").unwrap();
         assert_eq!(res, "");
     }
+
+    #[test]
+    fn test_extract_stats_from_rsync_log_sums_blocks() {
+        let log = r"
+Number of deleted files: 1,277 (reg: 1,277)
+Number of regular files transferred: 5,694
+Literal data: 780.62M bytes
+Matched data: 2.08G bytes
+Total bytes sent: 7.55M
+Total bytes received: 823.25M
+
+Number of deleted files: 0
+Number of files transferred: 6
+Literal data: 1,024 bytes
+Matched data: 0 bytes
+Total bytes sent: 120
+Total bytes received: 2,048
+";
+        let path = write_temp_file(log);
+        let res = extract_stats_from_rsync_log(path.to_str().unwrap(), 0).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            res,
+            Some(TransferStats {
+                files_transferred: 5_700,
+                files_deleted: 1_277,
+                bytes_sent: 7_550_120,
+                bytes_received: 823_252_048,
+                literal_data: 780_621_024,
+                matched_data: 2_080_000_000,
+                duration_secs: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_extract_stats_from_rsync_log_without_stats() {
+        let path = write_temp_file("receiving incremental file list\nfoo/bar\n");
+        let res = extract_stats_from_rsync_log(path.to_str().unwrap(), 0).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(res, None);
+        assert_eq!(extract_stats_from_rsync_log("/dev/null", 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_extract_stats_from_rsync_log_skips_earlier_runs() {
+        let earlier = "Number of regular files transferred: 40\n";
+        let log = format!("{earlier}Number of regular files transferred: 2\n");
+        let path = write_temp_file(&log);
+        let res = extract_stats_from_rsync_log(path.to_str().unwrap(), earlier.len() as u64);
+        let _ = fs::remove_file(&path);
+        assert_eq!(res.unwrap().unwrap().files_transferred, 2);
+    }
+
+    #[test]
+    fn test_parse_rsync_count() {
+        assert_eq!(parse_rsync_count("5,694"), Some(5_694));
+        assert_eq!(parse_rsync_count("606.46M"), Some(606_460_000));
+        assert_eq!(parse_rsync_count("1.33T"), Some(1_330_000_000_000));
+        assert_eq!(parse_rsync_count("n/a"), None);
+    }
+
+    #[test]
+    fn test_format_size_units() {
+        assert_eq!(format_size(0), "0");
+        assert_eq!(format_size(1023), "1023");
+        assert_eq!(format_size(4608), "4.50K");
+        assert_eq!(format_size(5 << 20), "5.00M");
+        assert_eq!(format_size(1_320_702_444), "1.23G");
+    }
 }
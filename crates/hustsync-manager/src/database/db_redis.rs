@@ -246,6 +246,8 @@ mod tests {
             next_scheduled: chrono::Utc::now(),
             status: SyncStatus::Success,
             is_master: true,
+            progress: None,
+            transfer_stats: None,
         };
 
         // Update/Create
@@ -286,6 +288,8 @@ mod tests {
             next_scheduled: chrono::Utc::now(),
             status: SyncStatus::Success,
             is_master: false,
+            progress: None,
+            transfer_stats: None,
         };
         let disabled = MirrorStatus {
             name: "arch".to_string(),
@@ -299,6 +303,8 @@ mod tests {
             next_scheduled: chrono::Utc::now(),
             status: SyncStatus::Disabled,
             is_master: false,
+            progress: None,
+            transfer_stats: None,
         };
 
         db.update_mirror_status("worker-1", "ubuntu", active)
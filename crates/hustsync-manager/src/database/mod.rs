@@ -159,6 +159,8 @@ mod tests {
             next_scheduled: chrono::Utc::now(),
             status: SyncStatus::Success,
             is_master: true,
+            progress: None,
+            transfer_stats: None,
         };
 
         // Update/Create
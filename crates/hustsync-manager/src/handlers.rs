@@ -5,7 +5,7 @@ use axum::http::request::Parts;
 use axum::response::{IntoResponse, Response};
 use chrono::Utc;
 use hustsync_internal::msg::{
-    ClientCmd, CmdVerb, MirrorSchedules, MirrorStatus, WorkerCmd, WorkerStatus,
+    ClientCmd, CmdVerb, MirrorSchedules, MirrorStatus, SyncProgress, WorkerCmd, WorkerStatus,
 };
 use hustsync_internal::status::SyncStatus;
 use serde::{Deserialize, Serialize};
@@ -223,6 +223,13 @@ pub async fn update_job_of_worker(
         status.size = cur.size.clone();
     }
 
+    // Stats describe the last successful sync; reports without any keep them
+    if status.transfer_stats.is_none()
+        && let Some(ref cur) = cur_status
+    {
+        status.transfer_stats = cur.transfer_stats.clone();
+    }
+
     match adapter.update_mirror_status(&worker_id, &mirror_id, status) {
         Ok(new_status) => {
             refresh_status_file(&manager, adapter.as_ref());
@@ -278,6 +285,39 @@ pub async fn update_mirror_size(
     }
 }
 
+/// Store live progress on a syncing job.
+///
+/// Progress arriving after the job left `Syncing` belongs to a finished
+/// run and is dropped. The status file is not refreshed: progress is only
+/// served live through `/jobs`.
+pub async fn update_job_progress(
+    Database(adapter): Database,
+    Path((worker_id, mirror_id)): Path<(String, String)>,
+    Json(progress): Json<SyncProgress>,
+) -> Response {
+    let _ = adapter.refresh_worker(&worker_id);
+
+    match adapter.get_mirror_status(&worker_id, &mirror_id) {
+        Ok(mut status) => {
+            if status.status != SyncStatus::Syncing {
+                return ok_json(status);
+            }
+            status.progress = Some(progress);
+            match adapter.update_mirror_status(&worker_id, &mirror_id, status) {
+                Ok(new_status) => ok_json(new_status),
+                Err(e) => error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to save progress: {}", e),
+                ),
+            }
+        }
+        Err(e) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Mirror not found: {}", e),
+        ),
+    }
+}
+
 pub async fn update_schedules_of_worker(
     State(manager): State<Arc<Manager>>,
     Database(adapter): Database,
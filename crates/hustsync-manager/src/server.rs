@@ -86,6 +86,10 @@ impl Manager {
             .route("/{id}/jobs", get(handlers::list_jobs_of_worker))
             .route("/{id}/jobs/{job}", post(handlers::update_job_of_worker))
             .route("/{id}/jobs/{job}/size", post(handlers::update_mirror_size))
+            .route(
+                "/{id}/jobs/{job}/progress",
+                post(handlers::update_job_progress),
+            )
             .route(
                 "/{id}/schedules",
                 post(handlers::update_schedules_of_worker),
@@ -2,7 +2,8 @@
 //!
 //! Covers (POST /workers), (DELETE /workers/{id}),
 //! (GET /workers/{id}/jobs), (POST /workers/{id}/jobs/{mirror}),
-//! (POST /workers/{id}/jobs/{mirror}/size), and
+//! (POST /workers/{id}/jobs/{mirror}/size),
+//! (POST /workers/{id}/jobs/{mirror}/progress), and
 //! (POST /workers/{id}/schedules).
 //!
 //! Each test drives the router via `tower::ServiceExt::oneshot` — no real
@@ -429,6 +430,138 @@ async fn update_mirror_size_happy_path() {
     );
 }
 
+// ---------------------------------------------------------------------------
+// POST /workers/{id}/jobs/{mirror}/progress: live sync progress
+// ---------------------------------------------------------------------------
+
+async fn post_progress(app: axum::Router, percent: u8) -> axum::response::Response {
+    let body = json!({
+    "bytes": 123456789,
+    "percent": percent,
+    "rate": "12.34MB/s",
+    "updated": "2024-01-01T00:00:00Z"
+    });
+    app.oneshot(
+        Request::builder()
+            .method("POST")
+            .uri("/workers/w-progress/jobs/fedora/progress")
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_vec(&body).unwrap()))
+            .unwrap(),
+    )
+    .await
+    .unwrap()
+}
+
+fn progress_status(status: &str) -> serde_json::Value {
+    json!({
+    "name": "fedora",
+    "worker": "w-progress",
+    "upstream": "rsync://mirror.example.com/fedora/",
+    "size": "unknown",
+    "error_msg": "",
+    "last_update": "1970-01-01T00:00:00Z",
+    "last_started": "1970-01-01T00:00:00Z",
+    "last_ended": "1970-01-01T00:00:00Z",
+    "next_schedule": "1970-01-01T00:00:00Z",
+    "status": status,
+    "is_master": true
+    })
+}
+
+/// Progress is stored while syncing, listed by /jobs, and cleared by the
+/// next status update; late progress for a finished run is ignored.
+#[tokio::test]
+async fn progress_is_kept_only_while_syncing() {
+    let (app, _dir) = contract::spawn_manager();
+    let app = setup_worker(app, "w-progress").await;
+
+    let resp = update_job(
+        app.clone(),
+        "w-progress",
+        "fedora",
+        progress_status("syncing"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = post_progress(app.clone(), 42).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let got = contract::body_json(resp).await;
+    assert_eq!(got["progress"]["percent"], 42);
+
+    let resp = app
+        .clone()
+        .oneshot(Request::builder().uri("/jobs").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let jobs = contract::body_json(resp).await;
+    assert_eq!(jobs[0]["progress"]["bytes"], 123456789);
+    assert_eq!(jobs[0]["progress"]["rate"], "12.34MB/s");
+
+    let resp = update_job(
+        app.clone(),
+        "w-progress",
+        "fedora",
+        progress_status("success"),
+    )
+    .await;
+    let got = contract::body_json(resp).await;
+    assert!(
+        got.get("progress").is_none(),
+        "status update must clear progress"
+    );
+
+    let resp = post_progress(app, 99).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let got = contract::body_json(resp).await;
+    assert!(
+        got.get("progress").is_none(),
+        "late progress must be ignored: {got}"
+    );
+}
+
+/// Transfer stats from a successful sync are stored, listed by /jobs and
+/// survive later reports that carry none.
+#[tokio::test]
+async fn transfer_stats_persist_until_replaced() {
+    let (app, _dir) = contract::spawn_manager();
+    let app = setup_worker(app, "w-progress").await;
+
+    let mut success = progress_status("success");
+    success["transfer_stats"] = json!({
+        "files_transferred": 12,
+        "files_deleted": 3,
+        "bytes_sent": 4096,
+        "bytes_received": 1048576,
+        "literal_data": 1000000,
+        "matched_data": 48576,
+        "duration_secs": 95
+    });
+    let resp = update_job(app.clone(), "w-progress", "fedora", success).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let got = contract::body_json(resp).await;
+    assert_eq!(got["transfer_stats"]["files_transferred"], 12);
+
+    let resp = update_job(
+        app.clone(),
+        "w-progress",
+        "fedora",
+        progress_status("syncing"),
+    )
+    .await;
+    let got = contract::body_json(resp).await;
+    assert_eq!(got["transfer_stats"]["bytes_received"], 1048576);
+
+    let resp = app
+        .oneshot(Request::builder().uri("/jobs").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let jobs = contract::body_json(resp).await;
+    assert_eq!(jobs[0]["transfer_stats"]["files_deleted"], 3);
+    assert_eq!(jobs[0]["transfer_stats"]["duration_secs"], 95);
+}
+
 // ---------------------------------------------------------------------------
 // POST /workers/{id}/schedules: update schedules
 //
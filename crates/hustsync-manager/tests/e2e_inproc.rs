@@ -75,9 +75,12 @@ fn worker_config(
             log_dir: Some(log_dir),
             mirror_dir: Some(mirror_dir),
             concurrent: Some(2),
+            concurrent_per_host: None,
             rsync_options: None,
             dangerous_global_success_exit_codes: None,
             dangerous_global_rsync_success_exit_codes: None,
+            blackout: None,
+            state_file: None,
             retry: None,
             exec_on_status: None,
         }),
@@ -88,6 +91,10 @@ fn worker_config(
             ca_cert: None,
         }),
         cgroup: None,
+        docker: None,
+        zfs: None,
+        btrfs_snapshot: None,
+        bandwidth: None,
         server: Some(WorkerServerConfig {
             hostname: Some("127.0.0.1".into()),
             // Bind worker on a fixed port — no ephemeral here because
@@ -101,11 +108,14 @@ fn worker_config(
             ssl_cert: None,
             ssl_key: None,
         }),
+        stage1_profiles: None,
+        concurrency_groups: None,
         mirrors: Some(vec![MirrorConfig {
             name: Some(mirror_name.into()),
             provider: Some("command".into()),
             command: Some("/bin/echo".into()),
             upstream: Some("unused://for-cmd-provider/".into()),
+            fallback_upstreams: None,
             retry: None,
             exec_on_status: None,
             exec_on_status_extra: None,
@@ -116,19 +126,48 @@ fn worker_config(
             mirror_type: None,
             log_dir: None,
             env: None,
+            secret_env: None,
             role: None,
             fail_on_match: None,
             size_pattern: None,
+            exclude_patterns: None,
+            git_repos: None,
+            delete_removed: None,
+            apt_suites: None,
+            apt_components: None,
+            apt_architectures: None,
             exclude_file: None,
             username: None,
             password: None,
+            password_file: None,
             rsync_no_timeout: None,
             rsync_timeout: None,
             rsync_options: None,
             rsync_override: None,
             rsync_override_only: None,
+            rsync_progress: None,
+            publish_dir: None,
+            publish_keep: None,
+            max_delete: None,
+            max_delete_percent: None,
+            freshness_probe: None,
+            freshness_mode: None,
+            lock_wait: None,
+            schedule: None,
+            schedule_timezone: None,
+            blackout: None,
+            retry_backoff: None,
+            retry_backoff_multiplier: None,
+            retry_backoff_max: None,
+            retry_backoff_jitter: None,
+            after: None,
+            concurrency_group: None,
             stage1_profile: None,
             memory_limit: None,
+            cpu_weight: None,
+            docker_image: None,
+            docker_volumes: None,
+            docker_options: None,
             success_exit_codes: None,
             rsync_success_exit_codes: None,
             mirrors: None,
@@ -0,0 +1,107 @@
+//! Exponential backoff between retry attempts.
+//!
+//! With `retry_backoff` set, a failed attempt is followed by a wait of
+//! `retry_backoff` seconds, multiplied by `retry_backoff_multiplier` after
+//! each further failure and capped at `retry_backoff_max`;
+//! `retry_backoff_jitter` spreads each wait by up to that fraction either
+//! way. The run loop reports the time of the next attempt to the manager as
+//! the mirror's `next_schedule`, and `stop`, `disable` and `restart` cut the
+//! wait short.
+
+use hustsync_config_parser::MirrorConfig;
+use tokio::time::Duration;
+
+const DEFAULT_MULTIPLIER: f64 = 2.0;
+const DEFAULT_MAX_SECS: u32 = 3600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryBackoff {
+    initial: Duration,
+    multiplier: f64,
+    max: Duration,
+    jitter: f64,
+}
+
+impl RetryBackoff {
+    /// The mirror's backoff settings; `None` when `retry_backoff` is unset
+    /// or 0, in which case attempts follow each other at once.
+    pub fn from_config(m_cfg: &MirrorConfig) -> Option<Self> {
+        let initial = m_cfg.retry_backoff.filter(|&secs| secs > 0)?;
+        let max = m_cfg
+            .retry_backoff_max
+            .unwrap_or(DEFAULT_MAX_SECS)
+            .max(initial);
+        Some(Self {
+            initial: Duration::from_secs(u64::from(initial)),
+            multiplier: m_cfg
+                .retry_backoff_multiplier
+                .unwrap_or(DEFAULT_MULTIPLIER)
+                .max(1.0),
+            max: Duration::from_secs(u64::from(max)),
+            jitter: m_cfg.retry_backoff_jitter.unwrap_or(0.0).clamp(0.0, 1.0),
+        })
+    }
+
+    /// The wait after the `failures`th failed attempt of a run, before
+    /// jitter.
+    fn base(&self, failures: u32) -> Duration {
+        let exp = i32::try_from(failures.saturating_sub(1)).unwrap_or(i32::MAX);
+        let secs = self.initial.as_secs_f64() * self.multiplier.powi(exp);
+        if secs.is_finite() && secs < self.max.as_secs_f64() {
+            Duration::from_secs_f64(secs)
+        } else {
+            self.max
+        }
+    }
+
+    /// The wait after the `failures`th failed attempt of a run, jittered
+    /// and never above `retry_backoff_max`.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let base = self.base(failures);
+        if self.jitter == 0.0 {
+            return base;
+        }
+        let spread = 1.0 + self.jitter * (2.0 * fastrand::f64() - 1.0);
+        base.mul_f64(spread).min(self.max)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    fn backoff(initial: u32, multiplier: f64, max: u32, jitter: f64) -> Option<RetryBackoff> {
+        RetryBackoff::from_config(&MirrorConfig {
+            retry_backoff: Some(initial),
+            retry_backoff_multiplier: Some(multiplier),
+            retry_backoff_max: Some(max),
+            retry_backoff_jitter: Some(jitter),
+            ..MirrorConfig::default()
+        })
+    }
+
+    #[test]
+    fn grows_geometrically_up_to_the_cap() {
+        let b = backoff(10, 3.0, 200, 0.0).expect("backoff configured");
+        let delays: Vec<u64> = (1..=5).map(|n| b.delay(n).as_secs()).collect();
+        assert_eq!(delays, [10, 30, 90, 200, 200]);
+        assert_eq!(b.delay(u32::MAX), Duration::from_secs(200));
+    }
+
+    #[test]
+    fn jitter_stays_within_its_fraction_and_the_cap() {
+        let b = backoff(100, 2.0, 150, 0.25).expect("backoff configured");
+        for _ in 0..200 {
+            let first = b.delay(1).as_secs_f64();
+            assert!((75.0..=125.0).contains(&first), "{first}");
+            assert!(b.delay(2) <= Duration::from_secs(150));
+        }
+    }
+
+    #[test]
+    fn unset_or_zero_means_no_backoff() {
+        assert_eq!(RetryBackoff::from_config(&MirrorConfig::default()), None);
+        assert_eq!(backoff(0, 2.0, 60, 0.0), None);
+    }
+}
@@ -0,0 +1,207 @@
+//! Worker-wide bandwidth budget for rsync-based providers.
+//!
+//! `[bandwidth]` caps what all running rsync and two-stage-rsync jobs pull
+//! together. Each provider run joins the budget for its duration and, at
+//! every rsync spawn, takes an equal share of the budget in force at that
+//! moment as its `--bwlimit`. rsync cannot change its rate mid-transfer,
+//! so a job keeps the share it started with; runs started later see a
+//! fresh split.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::Timelike;
+use hustsync_config_parser::{
+    BandwidthTierConfig, WorkerBandwidthConfig, parse_byte_rate, parse_time_of_day,
+};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// A time-of-day window with its own budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tier {
+    /// Minutes since local midnight, inclusive.
+    start: u32,
+    /// Minutes since local midnight, exclusive; before `start` when the
+    /// window runs past midnight.
+    end: u32,
+    /// Bytes per second.
+    limit: u64,
+}
+
+impl Tier {
+    fn from_config(cfg: &BandwidthTierConfig) -> Option<Self> {
+        Some(Self {
+            start: parse_time_of_day(cfg.start.as_deref()?)?,
+            end: parse_time_of_day(cfg.end.as_deref()?)?,
+            limit: parse_byte_rate(cfg.limit.as_deref()?)?,
+        })
+    }
+
+    fn covers(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+/// The budget plus the number of rsync runs currently sharing it.
+#[derive(Debug)]
+pub struct BandwidthBudget {
+    limit: Option<u64>,
+    tiers: Vec<Tier>,
+    active: AtomicUsize,
+}
+
+impl BandwidthBudget {
+    /// Build the budget from `[bandwidth]`, or `None` when it limits
+    /// nothing. Values were checked by `validate_worker_config`; entries
+    /// that still fail to parse are skipped with a warning.
+    pub fn from_config(cfg: &WorkerBandwidthConfig) -> Option<Self> {
+        let limit = cfg.limit.as_deref().and_then(|raw| {
+            let parsed = parse_byte_rate(raw);
+            if parsed.is_none() {
+                tracing::warn!("invalid bandwidth.limit `{}`, ignoring", raw);
+            }
+            parsed
+        });
+        let tiers: Vec<Tier> = cfg
+            .tiers
+            .iter()
+            .flatten()
+            .filter_map(|tier| {
+                let parsed = Tier::from_config(tier);
+                if parsed.is_none() {
+                    tracing::warn!("invalid bandwidth tier {:?}, ignoring", tier);
+                }
+                parsed
+            })
+            .collect();
+        if limit.is_none() && tiers.is_empty() {
+            return None;
+        }
+        Some(Self {
+            limit,
+            tiers,
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    /// Total budget in bytes per second at `minute` past local midnight.
+    fn limit_at(&self, minute: u32) -> Option<u64> {
+        self.tiers
+            .iter()
+            .find(|tier| tier.covers(minute % MINUTES_PER_DAY))
+            .map(|tier| tier.limit)
+            .or(self.limit)
+    }
+
+    /// Count one more running job until the returned share is dropped.
+    pub fn join(self: &Arc<Self>) -> BandwidthShare {
+        self.active.fetch_add(1, Ordering::AcqRel);
+        BandwidthShare {
+            budget: Arc::clone(self),
+        }
+    }
+}
+
+/// One running job's claim on a [`BandwidthBudget`].
+#[derive(Debug)]
+pub struct BandwidthShare {
+    budget: Arc<BandwidthBudget>,
+}
+
+impl BandwidthShare {
+    /// This job's rate for an rsync spawned now, in KiB/s as `--bwlimit`
+    /// expects; `None` when no limit is in force.
+    pub fn rsync_bwlimit(&self) -> Option<u64> {
+        let now = chrono::Local::now().time();
+        self.rsync_bwlimit_at(now.hour() * 60 + now.minute())
+    }
+
+    fn rsync_bwlimit_at(&self, minute: u32) -> Option<u64> {
+        let total = self.budget.limit_at(minute)?;
+        let jobs = self.budget.active.load(Ordering::Acquire).max(1) as u64;
+        // rsync treats 0 as "unlimited", so never round a share down to it.
+        Some((total / jobs / 1024).max(1))
+    }
+}
+
+impl Drop for BandwidthShare {
+    fn drop(&mut self) {
+        self.budget.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn tier(start: &str, end: &str, limit: &str) -> BandwidthTierConfig {
+        BandwidthTierConfig {
+            start: Some(start.into()),
+            end: Some(end.into()),
+            limit: Some(limit.into()),
+        }
+    }
+
+    fn budget(limit: Option<&str>, tiers: Vec<BandwidthTierConfig>) -> Arc<BandwidthBudget> {
+        let cfg = WorkerBandwidthConfig {
+            limit: limit.map(String::from),
+            tiers: Some(tiers),
+        };
+        Arc::new(BandwidthBudget::from_config(&cfg).unwrap())
+    }
+
+    #[test]
+    fn empty_section_limits_nothing() {
+        let cfg = WorkerBandwidthConfig {
+            limit: None,
+            tiers: None,
+        };
+        assert!(BandwidthBudget::from_config(&cfg).is_none());
+    }
+
+    #[test]
+    fn shares_follow_running_jobs() {
+        let budget = budget(Some("12M"), vec![]);
+        let first = budget.join();
+        assert_eq!(first.rsync_bwlimit_at(0), Some(12 * 1024));
+
+        let second = budget.join();
+        let third = budget.join();
+        assert_eq!(second.rsync_bwlimit_at(0), Some(4 * 1024));
+
+        drop(first);
+        drop(third);
+        assert_eq!(second.rsync_bwlimit_at(0), Some(12 * 1024));
+    }
+
+    #[test]
+    fn tiers_override_the_base_limit_by_time_of_day() {
+        let budget = budget(
+            Some("100M"),
+            vec![tier("22:00", "06:00", "10M"), tier("12:00", "13:00", "50M")],
+        );
+        let share = budget.join();
+        let at = |h: u32, m: u32| share.rsync_bwlimit_at(h * 60 + m);
+
+        assert_eq!(at(23, 30), Some(10 * 1024));
+        assert_eq!(at(5, 59), Some(10 * 1024));
+        assert_eq!(at(6, 0), Some(100 * 1024));
+        assert_eq!(at(12, 0), Some(50 * 1024));
+        assert_eq!(at(13, 0), Some(100 * 1024));
+    }
+
+    #[test]
+    fn tiers_without_base_limit_leave_other_hours_unlimited() {
+        let budget = budget(None, vec![tier("08:00", "18:00", "1K")]);
+        let share = budget.join();
+        let _other = budget.join();
+        assert_eq!(share.rsync_bwlimit_at(9 * 60), Some(1));
+        assert_eq!(share.rsync_bwlimit_at(20 * 60), None);
+    }
+}
@@ -0,0 +1,208 @@
+//! Blackout windows: local times at which scheduled runs do not start.
+//!
+//! `[[global.blackout]]`, or a mirror's own `blackout` in its place, lists
+//! time-of-day windows, optionally limited to some weekdays. The dispatch
+//! loop holds back a job that comes due inside one and requeues it for the
+//! moment the window closes; the new time reaches the manager with the
+//! schedule push that follows. A plain `start` waits the same way, while
+//! `start --force` goes through. Runs already in progress are not touched.
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike};
+use hustsync_config_parser::{
+    BlackoutWindowConfig, MirrorConfig, WorkerConfig, parse_time_of_day, parse_weekday,
+};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Every day of the week, as a `Window::days` mask.
+const ALL_DAYS: u8 = 0x7f;
+
+/// Upper bound on back-to-back windows followed by
+/// [`Blackout::deferred_until`].
+const MAX_CHAINED: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Window {
+    /// Minutes since local midnight, inclusive.
+    start: u32,
+    /// Minutes since local midnight, exclusive; before `start` when the
+    /// window runs past midnight.
+    end: u32,
+    /// Bit `n` set: the window opens on the `n`th day from Monday.
+    days: u8,
+}
+
+impl Window {
+    fn from_config(cfg: &BlackoutWindowConfig) -> Option<Self> {
+        let days = match cfg.days.as_deref() {
+            None => ALL_DAYS,
+            Some(names) => names
+                .iter()
+                .try_fold(0u8, |mask, name| Some(mask | 1 << parse_weekday(name)?))?,
+        };
+        Some(Self {
+            start: parse_time_of_day(cfg.start.as_deref()?)?,
+            end: parse_time_of_day(cfg.end.as_deref()?)?,
+            days,
+        })
+    }
+
+    fn opens_on(&self, weekday: u32) -> bool {
+        self.days & 1 << weekday != 0
+    }
+
+    /// Minutes until the window closes, when it is open at `minute` past
+    /// midnight on `weekday` (days from Monday).
+    fn remaining(&self, weekday: u32, minute: u32) -> Option<u32> {
+        if self.start < self.end {
+            (self.opens_on(weekday) && (self.start..self.end).contains(&minute))
+                .then(|| self.end - minute)
+        } else if minute >= self.start && self.opens_on(weekday) {
+            Some(MINUTES_PER_DAY - minute + self.end)
+        } else if minute < self.end && self.opens_on((weekday + 6) % 7) {
+            Some(self.end - minute)
+        } else {
+            None
+        }
+    }
+}
+
+/// The blackout windows in force for one mirror.
+#[derive(Debug, Clone)]
+pub struct Blackout {
+    windows: Vec<Window>,
+}
+
+impl Blackout {
+    /// The mirror's `blackout`, else the worker's; `None` when neither
+    /// lists a window. Values were checked by `validate_worker_config`;
+    /// windows that still fail to parse are skipped with a warning.
+    pub fn from_config(name: &str, m_cfg: &MirrorConfig, g_cfg: &WorkerConfig) -> Option<Self> {
+        let configured = m_cfg
+            .blackout
+            .as_deref()
+            .or_else(|| g_cfg.global.as_ref()?.blackout.as_deref())?;
+        let windows: Vec<Window> = configured
+            .iter()
+            .filter_map(|cfg| {
+                let parsed = Window::from_config(cfg);
+                if parsed.is_none() {
+                    tracing::warn!("{}: invalid blackout window {:?}, ignoring", name, cfg);
+                }
+                parsed
+            })
+            .collect();
+        (!windows.is_empty()).then_some(Self { windows })
+    }
+
+    /// When a run due at `at` may start: the close of the window covering
+    /// it (following windows that touch or overlap), or `None` when `at` is
+    /// outside every window.
+    pub fn deferred_until<Tz: TimeZone>(&self, at: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let mut t = at.with_second(0)?.with_nanosecond(0)?;
+        let mut deferred = false;
+        for _ in 0..MAX_CHAINED {
+            let weekday = t.weekday().num_days_from_monday();
+            let minute = t.hour() * 60 + t.minute();
+            let Some(remaining) = self
+                .windows
+                .iter()
+                .filter_map(|w| w.remaining(weekday, minute))
+                .max()
+            else {
+                break;
+            };
+            t += Duration::minutes(i64::from(remaining));
+            deferred = true;
+        }
+        deferred.then_some(t)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn window(start: &str, end: &str, days: Option<&[&str]>) -> BlackoutWindowConfig {
+        BlackoutWindowConfig {
+            start: Some(start.into()),
+            end: Some(end.into()),
+            days: days.map(|d| d.iter().map(|s| s.to_string()).collect()),
+        }
+    }
+
+    fn blackout(windows: &[BlackoutWindowConfig]) -> Blackout {
+        let mirror = MirrorConfig {
+            blackout: Some(windows.to_vec()),
+            ..MirrorConfig::default()
+        };
+        Blackout::from_config("debian", &mirror, &WorkerConfig::default()).unwrap()
+    }
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn defers_to_the_close_of_the_window() {
+        // 2024-05-01 is a Wednesday.
+        let b = blackout(&[window("08:00", "18:00", Some(&["wed"]))]);
+        assert_eq!(
+            b.deferred_until(at("2024-05-01T09:30:42Z")),
+            Some(at("2024-05-01T18:00:00Z"))
+        );
+        assert_eq!(b.deferred_until(at("2024-05-01T18:00:00Z")), None);
+        assert_eq!(b.deferred_until(at("2024-05-02T09:30:00Z")), None, "thu");
+    }
+
+    #[test]
+    fn overnight_window_belongs_to_the_day_it_opens() {
+        let b = blackout(&[window("22:00", "02:00", Some(&["fri"]))]);
+        // Friday 23:00 and the small hours of Saturday are covered.
+        assert_eq!(
+            b.deferred_until(at("2024-05-03T23:00:00Z")),
+            Some(at("2024-05-04T02:00:00Z"))
+        );
+        assert_eq!(
+            b.deferred_until(at("2024-05-04T01:00:00Z")),
+            Some(at("2024-05-04T02:00:00Z"))
+        );
+        // Friday 01:00 follows Thursday, which has no window.
+        assert_eq!(b.deferred_until(at("2024-05-03T01:00:00Z")), None);
+    }
+
+    #[test]
+    fn back_to_back_windows_chain() {
+        let b = blackout(&[
+            window("08:00", "12:00", None),
+            window("12:00", "14:00", None),
+        ]);
+        assert_eq!(
+            b.deferred_until(at("2024-05-01T09:00:00Z")),
+            Some(at("2024-05-01T14:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn mirror_windows_replace_global_ones() {
+        let worker = WorkerConfig {
+            global: Some(hustsync_config_parser::WorkerGlobalConfig {
+                blackout: Some(vec![window("08:00", "18:00", None)]),
+                ..Default::default()
+            }),
+            ..WorkerConfig::default()
+        };
+        let inherits = MirrorConfig::default();
+        assert!(Blackout::from_config("a", &inherits, &worker).is_some());
+
+        let exempt = MirrorConfig {
+            blackout: Some(vec![]),
+            ..MirrorConfig::default()
+        };
+        assert!(Blackout::from_config("b", &exempt, &worker).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//! Concurrency groups: run limits shared by some mirrors, on top of
+//! `global.concurrent`.
+//!
+//! A mirror counts against the `[concurrency_groups]` entry named by its
+//! `concurrency_group`, and against the limit on its upstream host: the
+//! `[concurrency_groups]` entry named after the host, else
+//! `global.concurrent_per_host`. Groups are shared by every job of the
+//! worker, including ones added by a reload.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use hustsync_config_parser::{MirrorConfig, WorkerConfig};
+use tokio::sync::Semaphore;
+
+/// One group a job takes a permit from before it runs.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyGroup {
+    pub name: String,
+    pub semaphore: Arc<Semaphore>,
+}
+
+#[derive(Debug, Default)]
+pub struct ConcurrencyGroups {
+    limits: HashMap<String, u32>,
+    per_host: Option<u32>,
+    /// Created on first use, so hosts need not be known up front.
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl ConcurrencyGroups {
+    pub fn from_config(cfg: &WorkerConfig) -> Self {
+        Self {
+            limits: cfg.concurrency_groups.clone().unwrap_or_default(),
+            per_host: cfg.global.as_ref().and_then(|g| g.concurrent_per_host),
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The groups `m_cfg` belongs to, ordered by name so that jobs taking
+    /// several permits take them in the same order.
+    pub fn for_mirror(&self, m_cfg: &MirrorConfig) -> Vec<ConcurrencyGroup> {
+        let named = m_cfg
+            .concurrency_group
+            .as_deref()
+            .and_then(|name| Some((name.to_string(), *self.limits.get(name)?)));
+        let host = m_cfg
+            .upstream
+            .as_deref()
+            .and_then(upstream_host)
+            .and_then(|host| {
+                let limit = self.limits.get(&host).copied().or(self.per_host)?;
+                Some((host, limit))
+            });
+
+        let mut members: Vec<(String, u32)> = named.into_iter().chain(host).collect();
+        members.sort();
+        members.dedup_by(|a, b| a.0 == b.0);
+
+        let mut semaphores = self
+            .semaphores
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        members
+            .into_iter()
+            .map(|(name, limit)| {
+                let semaphore = semaphores
+                    .entry(name.clone())
+                    .or_insert_with(|| Arc::new(Semaphore::new(limit as usize)));
+                ConcurrencyGroup {
+                    name,
+                    semaphore: Arc::clone(semaphore),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Host name of an upstream, lowercased: the authority of a URL
+/// (`rsync://host/...`, `https://host:8443/...`), or the part before the
+/// colon of rsync daemon (`host::module`) and remote-shell (`user@host:path`)
+/// addresses.
+pub fn upstream_host(upstream: &str) -> Option<String> {
+    let authority = match upstream.split_once("://") {
+        Some((_, rest)) => rest.split('/').next()?,
+        None => upstream.split_once(':')?.0,
+    };
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let host = match host_port.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next()?,
+        None => host_port.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirror(upstream: &str, group: Option<&str>) -> MirrorConfig {
+        MirrorConfig {
+            upstream: Some(upstream.into()),
+            concurrency_group: group.map(Into::into),
+            ..MirrorConfig::default()
+        }
+    }
+
+    fn names(groups: &[ConcurrencyGroup]) -> Vec<&str> {
+        groups.iter().map(|g| g.name.as_str()).collect()
+    }
+
+    #[test]
+    fn host_of_each_upstream_form() {
+        for (upstream, host) in [
+            (
+                "rsync://Rsync.ArchLinux.org/ftp_tmp/",
+                Some("rsync.archlinux.org"),
+            ),
+            ("https://user@mirror.test:8443/debian/", Some("mirror.test")),
+            ("rsync://[2001:db8::1]:873/debian/", Some("2001:db8::1")),
+            ("mirror.test::debian/", Some("mirror.test")),
+            ("sync@mirror.test:/srv/debian/", Some("mirror.test")),
+            ("/srv/local/debian/", None),
+        ] {
+            assert_eq!(upstream_host(upstream).as_deref(), host, "{upstream}");
+        }
+    }
+
+    #[test]
+    fn mirrors_share_group_and_host_semaphores() {
+        let cfg = WorkerConfig {
+            concurrency_groups: Some(
+                [
+                    ("rsync.archlinux.org".to_string(), 2),
+                    ("tier1".to_string(), 5),
+                ]
+                .into(),
+            ),
+            global: Some(hustsync_config_parser::WorkerGlobalConfig {
+                concurrent_per_host: Some(3),
+                ..Default::default()
+            }),
+            ..WorkerConfig::default()
+        };
+        let groups = ConcurrencyGroups::from_config(&cfg);
+
+        let arch = groups.for_mirror(&mirror("rsync://rsync.archlinux.org/ftp/", Some("tier1")));
+        assert_eq!(names(&arch), ["rsync.archlinux.org", "tier1"]);
+        assert_eq!(arch[0].semaphore.available_permits(), 2);
+
+        let arch32 = groups.for_mirror(&mirror("rsync://rsync.archlinux.org/arch32/", None));
+        assert!(Arc::ptr_eq(&arch[0].semaphore, &arch32[0].semaphore));
+
+        let debian = groups.for_mirror(&mirror("rsync://ftp.debian.org/debian/", None));
+        assert_eq!(names(&debian), ["ftp.debian.org"]);
+        assert_eq!(debian[0].semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn no_groups_without_limits() {
+        let groups = ConcurrencyGroups::from_config(&WorkerConfig::default());
+        assert!(
+            groups
+                .for_mirror(&mirror("rsync://ftp.debian.org/debian/", None))
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Freshness probe: skip a sync when upstream has not changed.
+//!
+//! Many upstreams publish a small file that changes on every update (Arch
+//! `lastupdate`, Debian `project/trace/*`). A mirror with
+//! `freshness_probe` set reads that file before each run; when it matches
+//! what the probe saw before the last successful sync, the job reports
+//! success without running the provider.
+//!
+//! The marker compared is a SHA-256 of the file (`content`) or its
+//! modification time (`mtime`). Only a run that succeeds records its
+//! marker, and a failed run clears it, so a skip always follows a sync
+//! that completed against the same upstream state. A probe that cannot be
+//! read never skips anything.
+
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hustsync_config_parser::MirrorConfig;
+use reqwest::header::{ETAG, LAST_MODIFIED};
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+
+/// Status note for a skipped run.
+pub const SKIPPED_MSG: &str = "skipped: upstream unchanged";
+
+/// Upper bound on one probe, so a stalled upstream delays the sync by at
+/// most this much.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreshnessMode {
+    /// SHA-256 of the probed file.
+    Content,
+    /// `Last-Modified` (or `ETag`) over HTTP; the `--list-only` line over
+    /// rsync.
+    Mtime,
+}
+
+/// One mirror's probe plus the marker of its last successful sync.
+pub struct FreshnessProbe {
+    name: String,
+    url: String,
+    mode: FreshnessMode,
+    /// rsync binary for `rsync://` probes.
+    rsync: String,
+    /// Sent as `RSYNC_PASSWORD` to rsync probes.
+    password: Option<String>,
+    client: reqwest::Client,
+    last: Mutex<Option<String>>,
+}
+
+impl FreshnessProbe {
+    pub fn new(name: &str, url: &str, mode: FreshnessMode) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(PROBE_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            name: name.to_string(),
+            url: url.to_string(),
+            mode,
+            rsync: "rsync".to_string(),
+            password: None,
+            client,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// The probe configured for `m_cfg`, if any. Rsync-family mirrors probe
+    /// with their own rsync command and password.
+    pub fn from_config(name: &str, m_cfg: &MirrorConfig) -> Option<Self> {
+        let url = m_cfg.freshness_probe.as_deref()?;
+        let mode = match m_cfg.freshness_mode.as_deref() {
+            Some("mtime") => FreshnessMode::Mtime,
+            _ => FreshnessMode::Content,
+        };
+        let mut probe = Self::new(name, url, mode);
+        let provider = m_cfg.provider.as_deref().unwrap_or("rsync");
+        if matches!(provider, "rsync" | "two-stage-rsync") {
+            if let Some(command) = m_cfg.command.as_deref() {
+                probe.rsync = command.to_string();
+            }
+            probe.password = crate::secret::resolve_password(m_cfg).unwrap_or_else(|e| {
+                tracing::warn!("{}: freshness probe without password: {}", name, e);
+                None
+            });
+        }
+        Some(probe)
+    }
+
+    /// Use `command` instead of `rsync` for `rsync://` probes.
+    pub fn with_rsync_command(mut self, command: &str) -> Self {
+        self.rsync = command.to_string();
+        self
+    }
+
+    /// Read the probe now. `None` (logged) when it cannot be read.
+    pub async fn observe(&self) -> Option<String> {
+        let observed = if self.url.starts_with("rsync://") {
+            self.observe_rsync().await
+        } else {
+            self.observe_http().await
+        };
+        match observed {
+            Ok(marker) => Some(marker),
+            Err(e) => {
+                tracing::warn!(
+                    "{}: freshness probe {} failed, syncing anyway: {}",
+                    self.name,
+                    self.url,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Whether `marker` matches the one recorded by the last successful
+    /// sync.
+    pub fn is_unchanged(&self, marker: &str) -> bool {
+        self.last
+            .lock()
+            .is_ok_and(|last| last.as_deref() == Some(marker))
+    }
+
+    /// Remember the marker a sync started from once it has succeeded;
+    /// `None` (probe unreadable, or the sync failed) forgets it.
+    pub fn record(&self, marker: Option<String>) {
+        if let Ok(mut last) = self.last.lock() {
+            *last = marker;
+        }
+    }
+
+    async fn observe_http(&self) -> Result<String, String> {
+        let request = match self.mode {
+            FreshnessMode::Content => self.client.get(&self.url),
+            FreshnessMode::Mtime => self.client.head(&self.url),
+        };
+        let response = request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| e.to_string())?;
+        match self.mode {
+            FreshnessMode::Content => {
+                let body = response.bytes().await.map_err(|e| e.to_string())?;
+                Ok(hex::encode(Sha256::digest(&body)))
+            }
+            FreshnessMode::Mtime => [LAST_MODIFIED, ETAG]
+                .iter()
+                .find_map(|h| response.headers().get(h)?.to_str().ok())
+                .map(str::to_string)
+                .ok_or_else(|| "response has neither Last-Modified nor ETag".to_string()),
+        }
+    }
+
+    async fn observe_rsync(&self) -> Result<String, String> {
+        let mut cmd = Command::new(&self.rsync);
+        cmd.arg("--no-motd").arg("--timeout=30");
+        if let Some(password) = &self.password {
+            cmd.env("RSYNC_PASSWORD", password);
+        }
+        let marker = match self.mode {
+            FreshnessMode::Content => {
+                let copy = std::env::temp_dir().join(format!(
+                    "hustsync-probe-{}-{}",
+                    std::process::id(),
+                    self.name
+                ));
+                cmd.arg(&self.url).arg(&copy);
+                let result = run_probe(cmd).await;
+                let content = tokio::fs::read(&copy).await;
+                let _ = tokio::fs::remove_file(&copy).await;
+                result?;
+                hex::encode(Sha256::digest(content.map_err(|e| e.to_string())?))
+            }
+            FreshnessMode::Mtime => {
+                cmd.arg("--list-only").arg(&self.url);
+                let listing = run_probe(cmd).await?;
+                listing
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .ok_or_else(|| "rsync listed nothing".to_string())?
+            }
+        };
+        Ok(marker)
+    }
+}
+
+/// Run a probe command to completion and return its stdout.
+async fn run_probe(mut cmd: Command) -> Result<String, String> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(PROBE_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| format!("no answer within {PROBE_TIMEOUT:?}"))?
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "{}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
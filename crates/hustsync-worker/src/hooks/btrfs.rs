@@ -0,0 +1,254 @@
+//! `[btrfs_snapshot]` hook: publish a read-only snapshot per mirror.
+//!
+//! Port of Go `worker/btrfs_snapshot_hook.go`, tightened so readers never
+//! see a half-replaced view. The provider syncs into the working
+//! subvolume; the web server serves the snapshot path.
+//!
+//! - `pre_job`: create the working subvolume if it is missing, and refuse
+//!   to sync into a working path that exists but is not a subvolume (a
+//!   snapshot of a plain directory is impossible).
+//! - `post_success`: take a read-only snapshot of the working subvolume
+//!   next to the published one, then swap the two with
+//!   `renameat2(RENAME_EXCHANGE)` so the snapshot path flips from the old
+//!   view to the new one in a single step. The old snapshot is parked in
+//!   the gc directory and deleted; one that cannot be deleted yet stays
+//!   there and is retried on the next success.
+//!
+//! A failed sync leaves the published snapshot untouched.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Local;
+use tokio::fs;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use super::{HookCtx, HookError, JobHook};
+
+const HOOK_NAME: &str = "btrfs_snapshot";
+
+/// Per-invocation budget for the `btrfs` CLI.
+const BTRFS_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Paths a `BtrfsSnapshotHook` operates on, all resolved for one mirror.
+pub struct BtrfsSnapshotPaths {
+    /// Subvolume the provider syncs into (the mirror's working dir).
+    pub working: PathBuf,
+    /// Published read-only snapshot.
+    pub snapshot: PathBuf,
+    /// Directory that holds retired snapshots until they are deleted.
+    pub gc: PathBuf,
+}
+
+pub struct BtrfsSnapshotHook {
+    /// `btrfs` binary (or a test stub).
+    command: String,
+    paths: BtrfsSnapshotPaths,
+}
+
+impl BtrfsSnapshotHook {
+    pub fn new(command: impl Into<String>, paths: BtrfsSnapshotPaths) -> Self {
+        Self {
+            command: command.into(),
+            paths,
+        }
+    }
+
+    /// Staging name for the next snapshot: a hidden sibling of the
+    /// published path, so the exchange stays on one filesystem.
+    fn staging_path(&self) -> PathBuf {
+        let name = self
+            .paths
+            .snapshot
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.paths.snapshot.with_file_name(format!(".{name}.new"))
+    }
+
+    async fn btrfs(&self, args: &[&str]) -> Result<bool, HookError> {
+        let mut cmd = Command::new(&self.command);
+        cmd.args(args).kill_on_drop(true);
+        let output = match timeout(BTRFS_TIMEOUT, cmd.output()).await {
+            Ok(res) => res.map_err(|e| HookError::io(HOOK_NAME, e))?,
+            Err(_) => {
+                return Err(HookError::config(
+                    HOOK_NAME,
+                    format!(
+                        "`{} {}` exceeded {:?}",
+                        self.command,
+                        args.join(" "),
+                        BTRFS_TIMEOUT
+                    ),
+                ));
+            }
+        };
+        if !output.status.success() {
+            tracing::warn!(
+                "{} {} failed: {}",
+                self.command,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output.status.success())
+    }
+
+    async fn delete_subvolume(&self, path: &Path) -> Result<bool, HookError> {
+        self.btrfs(&["subvolume", "delete", &path.to_string_lossy()])
+            .await
+    }
+
+    /// Delete every retired snapshot still parked in the gc directory.
+    async fn sweep_gc(&self) -> Result<(), HookError> {
+        let mut entries = match fs::read_dir(&self.paths.gc).await {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(HookError::io(HOOK_NAME, e)),
+        };
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))?
+        {
+            self.delete_subvolume(&entry.path()).await?;
+        }
+        Ok(())
+    }
+
+    /// Move a replaced snapshot into the gc dir and try to delete it.
+    async fn retire(&self, old: &Path) -> Result<(), HookError> {
+        fs::create_dir_all(&self.paths.gc)
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))?;
+        let parked = self
+            .paths
+            .gc
+            .join(Local::now().format("%Y-%m-%d_%H_%M_%S").to_string());
+        let target = match fs::rename(old, &parked).await {
+            Ok(()) => parked,
+            Err(e) => {
+                // gc on another filesystem: delete where it lies instead.
+                tracing::warn!(
+                    "cannot park {} in {}: {}",
+                    old.display(),
+                    self.paths.gc.display(),
+                    e
+                );
+                old.to_path_buf()
+            }
+        };
+        if !self.delete_subvolume(&target).await? {
+            tracing::warn!(
+                "old snapshot {} left for the next gc sweep",
+                target.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Atomically swap two paths. Falls back to a two-step rename (with a
+/// short window where `b` is missing) on filesystems without
+/// `RENAME_EXCHANGE`; btrfs supports it.
+fn exchange(a: &Path, b: &Path) -> std::io::Result<()> {
+    use nix::errno::Errno;
+    use nix::fcntl::{AT_FDCWD, RenameFlags, renameat2};
+
+    match renameat2(AT_FDCWD, a, AT_FDCWD, b, RenameFlags::RENAME_EXCHANGE) {
+        Ok(()) => Ok(()),
+        Err(Errno::EINVAL) | Err(Errno::ENOSYS) => {
+            tracing::warn!(
+                "RENAME_EXCHANGE unsupported for {}; swapping non-atomically",
+                b.display()
+            );
+            let tmp = a.with_extension("swap");
+            std::fs::rename(b, &tmp)?;
+            std::fs::rename(a, b)?;
+            std::fs::rename(&tmp, a)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[async_trait]
+impl JobHook for BtrfsSnapshotHook {
+    fn name(&self) -> &str {
+        HOOK_NAME
+    }
+
+    async fn pre_job(&self, _ctx: &mut HookCtx) -> Result<(), HookError> {
+        let working = &self.paths.working;
+        let working_str = working.to_string_lossy();
+        if fs::symlink_metadata(working).await.is_ok() {
+            if !self.btrfs(&["subvolume", "show", &working_str]).await? {
+                return Err(HookError::config(
+                    HOOK_NAME,
+                    format!(
+                        "{} exists but is not a btrfs subvolume; move it aside and \
+                         let the worker create it, or `btrfs subvolume create` it",
+                        working.display()
+                    ),
+                ));
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = working.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| HookError::io(HOOK_NAME, e))?;
+        }
+        tracing::info!("creating btrfs subvolume {}", working.display());
+        if !self.btrfs(&["subvolume", "create", &working_str]).await? {
+            return Err(HookError::config(
+                HOOK_NAME,
+                format!("cannot create subvolume {}", working.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn post_success(&self, _ctx: &mut HookCtx) -> Result<(), HookError> {
+        self.sweep_gc().await?;
+
+        let staging = self.staging_path();
+        if fs::symlink_metadata(&staging).await.is_ok() {
+            // Left behind by a run that died mid-publish.
+            self.delete_subvolume(&staging).await?;
+        }
+        if let Some(parent) = self.paths.snapshot.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| HookError::io(HOOK_NAME, e))?;
+        }
+
+        let taken = self
+            .btrfs(&[
+                "subvolume",
+                "snapshot",
+                "-r",
+                &self.paths.working.to_string_lossy(),
+                &staging.to_string_lossy(),
+            ])
+            .await?;
+        if !taken {
+            return Err(HookError::config(
+                HOOK_NAME,
+                format!("cannot snapshot {}", self.paths.working.display()),
+            ));
+        }
+
+        if fs::symlink_metadata(&self.paths.snapshot).await.is_err() {
+            return fs::rename(&staging, &self.paths.snapshot)
+                .await
+                .map_err(|e| HookError::io(HOOK_NAME, e));
+        }
+
+        exchange(&staging, &self.paths.snapshot).map_err(|e| HookError::io(HOOK_NAME, e))?;
+        // `staging` now holds the previous snapshot.
+        self.retire(&staging).await
+    }
+}
@@ -20,13 +20,19 @@ use async_trait::async_trait;
 
 pub use crate::error::{HookError, HookErrorKind};
 
+pub mod btrfs;
 pub mod exec;
 pub mod loglimit;
+pub mod publish;
 pub mod working_dir;
+pub mod zfs;
 
+pub use btrfs::{BtrfsSnapshotHook, BtrfsSnapshotPaths};
 pub use exec::ExecPostHook;
 pub use loglimit::LogLimitHook;
+pub use publish::PublishHook;
 pub use working_dir::WorkingDirHook;
+pub use zfs::ZfsHook;
 
 /// Context passed through the hook pipeline.
 ///
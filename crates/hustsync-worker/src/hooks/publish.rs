@@ -0,0 +1,226 @@
+//! `publish_dir` hook: atomic publish for rsync-based mirrors.
+//!
+//! Clients read the mirror through a symlink at `publish_dir` that points
+//! at one complete tree (a generation) under the working dir. Each run
+//! syncs into a fresh staging directory seeded with `--link-dest` from the
+//! live generation, so unchanged files become hard links instead of
+//! transfers, and clients never see a partly synced tree.
+//!
+//! - `pre_job`: refuse a `publish_dir` that exists but is not a symlink.
+//! - `pre_exec`: recreate the staging directory and point the provider at
+//!   it and at the live generation through `ctx.env`.
+//! - `post_success`: rename staging to a timestamped generation, swap the
+//!   symlink to it with a single `rename`, then prune generations beyond
+//!   `keep`.
+//! - `post_fail`: delete the staging directory. The live generation is
+//!   untouched.
+//!
+//! After either post phase, later hooks see `publish_dir` as the working
+//! dir.
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use tokio::fs;
+
+use super::{HookCtx, HookError, JobHook};
+use crate::provider::LINK_DEST_ENV;
+
+const HOOK_NAME: &str = "publish";
+
+/// Staging directory name inside the working dir.
+const STAGING_NAME: &str = ".staging";
+
+/// Generation names: the publish time, plus `.<n>` when two publishes
+/// fall in the same second.
+const GENERATION_FORMAT: &str = "%Y-%m-%d_%H_%M_%S";
+
+pub struct PublishHook {
+    /// Symlink clients are served from.
+    link: PathBuf,
+    /// Previous generations kept next to the live one.
+    keep: usize,
+}
+
+impl PublishHook {
+    pub fn new(link: impl Into<PathBuf>, keep: usize) -> Self {
+        Self {
+            link: link.into(),
+            keep,
+        }
+    }
+
+    /// Generation the symlink points at, if it resolves to a directory.
+    async fn live(&self) -> Option<PathBuf> {
+        let target = fs::read_link(&self.link).await.ok()?;
+        let target = match self.link.parent() {
+            Some(parent) if target.is_relative() => parent.join(target),
+            _ => target,
+        };
+        fs::metadata(&target)
+            .await
+            .is_ok_and(|m| m.is_dir())
+            .then_some(target)
+    }
+
+    /// Point the symlink at `generation` by renaming a fresh link over it,
+    /// so readers see either the old tree or the new one.
+    async fn swap_link(&self, generation: &Path) -> Result<(), HookError> {
+        let name = self
+            .link
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let fresh = self.link.with_file_name(format!(".{name}.new"));
+        if fs::symlink_metadata(&fresh).await.is_ok() {
+            fs::remove_file(&fresh)
+                .await
+                .map_err(|e| HookError::io(HOOK_NAME, e))?;
+        }
+        fs::symlink(generation, &fresh)
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))?;
+        fs::rename(&fresh, &self.link)
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))
+    }
+
+    /// Delete generations in `root` beyond the `keep` newest, never
+    /// touching `live` or anything not named like a generation.
+    async fn prune(&self, root: &Path, live: &Path) -> Result<(), HookError> {
+        let mut entries = fs::read_dir(root)
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))?;
+        let mut generations = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))?
+        {
+            let path = entry.path();
+            let is_dir = entry.file_type().await.is_ok_and(|t| t.is_dir());
+            let Some(order) = generation_order(&entry.file_name().to_string_lossy()) else {
+                continue;
+            };
+            if is_dir && path != live {
+                generations.push((order, path));
+            }
+        }
+        generations.sort_unstable_by_key(|(order, _)| std::cmp::Reverse(*order));
+        for (_, old) in generations.into_iter().skip(self.keep) {
+            tracing::info!("{}: pruning old generation {}", HOOK_NAME, old.display());
+            if let Err(e) = fs::remove_dir_all(&old).await {
+                tracing::warn!("cannot prune {}: {}", old.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Later hooks (e.g. exec_on_success) act on the published path.
+    fn expose_link(&self, ctx: &mut HookCtx) {
+        let link = self.link.to_string_lossy().into_owned();
+        ctx.env.insert("HUSTSYNC_WORKING_DIR".into(), link.clone());
+        ctx.env.insert("TUNASYNC_WORKING_DIR".into(), link);
+        ctx.env.remove(LINK_DEST_ENV);
+    }
+}
+
+/// Sort key of a generation directory name; `None` for anything else.
+fn generation_order(name: &str) -> Option<(NaiveDateTime, u32)> {
+    let (stamp, seq) = match name.split_once('.') {
+        Some((stamp, seq)) => (stamp, seq.parse().ok()?),
+        None => (name, 0),
+    };
+    let at = NaiveDateTime::parse_from_str(stamp, GENERATION_FORMAT).ok()?;
+    Some((at, seq))
+}
+
+/// Remove `path` and everything below it; a missing path is fine.
+async fn remove_tree(path: &Path) -> Result<(), HookError> {
+    match fs::remove_dir_all(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(HookError::io(HOOK_NAME, e)),
+    }
+}
+
+#[async_trait]
+impl JobHook for PublishHook {
+    fn name(&self) -> &str {
+        HOOK_NAME
+    }
+
+    async fn pre_job(&self, _ctx: &mut HookCtx) -> Result<(), HookError> {
+        match fs::symlink_metadata(&self.link).await {
+            Ok(meta) if !meta.file_type().is_symlink() => Err(HookError::config(
+                HOOK_NAME,
+                format!(
+                    "{} exists but is not a symlink; move it aside so the worker \
+                     can publish there",
+                    self.link.display()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    async fn pre_exec(&self, ctx: &mut HookCtx) -> Result<(), HookError> {
+        let staging = ctx.working_dir.join(STAGING_NAME);
+        // Left behind by a run that died before its post phase.
+        remove_tree(&staging).await?;
+        fs::create_dir_all(&staging)
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))?;
+
+        let staging = staging.to_string_lossy().into_owned();
+        ctx.env
+            .insert("HUSTSYNC_WORKING_DIR".into(), staging.clone());
+        ctx.env.insert("TUNASYNC_WORKING_DIR".into(), staging);
+        match self.live().await {
+            Some(live) => {
+                ctx.env
+                    .insert(LINK_DEST_ENV.into(), live.to_string_lossy().into_owned());
+            }
+            None => {
+                ctx.env.remove(LINK_DEST_ENV);
+            }
+        }
+        Ok(())
+    }
+
+    async fn post_success(&self, ctx: &mut HookCtx) -> Result<(), HookError> {
+        let staging = ctx.working_dir.join(STAGING_NAME);
+        let stamp = Local::now().format(GENERATION_FORMAT).to_string();
+        let mut generation = ctx.working_dir.join(&stamp);
+        let mut seq = 0;
+        while fs::symlink_metadata(&generation).await.is_ok() {
+            seq += 1;
+            generation = ctx.working_dir.join(format!("{stamp}.{seq}"));
+        }
+        fs::rename(&staging, &generation)
+            .await
+            .map_err(|e| HookError::io(HOOK_NAME, e))?;
+
+        if let Some(parent) = self.link.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| HookError::io(HOOK_NAME, e))?;
+        }
+        self.swap_link(&generation).await?;
+        tracing::info!(
+            "{}: published {} at {}",
+            ctx.mirror_name,
+            generation.display(),
+            self.link.display()
+        );
+        self.expose_link(ctx);
+
+        self.prune(&ctx.working_dir, &generation).await
+    }
+
+    async fn post_fail(&self, ctx: &mut HookCtx) -> Result<(), HookError> {
+        self.expose_link(ctx);
+        remove_tree(&ctx.working_dir.join(STAGING_NAME)).await
+    }
+}
@@ -0,0 +1,151 @@
+//! `[zfs]` hook: one dataset per mirror.
+//!
+//! Port of Go `worker/zfs_hook.go`. On `pre_job` the dataset
+//! `<zpool>/<mirror>` is created if missing (mounted at the mirror's
+//! working directory), then checked: the sync is refused unless the
+//! dataset exists, is mounted, and is mounted at `working_dir`. Syncing
+//! into a plain directory on the parent filesystem would silently fill the
+//! wrong pool, which is what Go's `mountpoint -q` guard prevents.
+//!
+//! Runs before `WorkingDirHook` so the working directory is the dataset's
+//! mountpoint rather than a directory created underneath it.
+
+use std::path::Path;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use super::{HookCtx, HookError, JobHook};
+
+const HOOK_NAME: &str = "zfs";
+
+/// Per-invocation budget for the `zfs` CLI.
+const ZFS_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct ZfsHook {
+    /// `zfs` binary (or a test stub).
+    command: String,
+    /// Parent dataset; the mirror's dataset is `<zpool>/<mirror>`.
+    zpool: String,
+}
+
+impl ZfsHook {
+    pub fn new(command: impl Into<String>, zpool: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            zpool: zpool.into(),
+        }
+    }
+
+    fn dataset(&self, mirror: &str) -> String {
+        format!("{}/{}", self.zpool.trim_end_matches('/'), mirror)
+    }
+
+    /// Run `zfs <args>` and return (exit success, stdout).
+    async fn zfs(&self, args: &[&str]) -> Result<(bool, String), HookError> {
+        let mut cmd = Command::new(&self.command);
+        cmd.args(args).kill_on_drop(true);
+        let output = match timeout(ZFS_TIMEOUT, cmd.output()).await {
+            Ok(res) => res.map_err(|e| HookError::io(HOOK_NAME, e))?,
+            Err(_) => {
+                return Err(HookError::config(
+                    HOOK_NAME,
+                    format!(
+                        "`{} {}` exceeded {:?}",
+                        self.command,
+                        args.join(" "),
+                        ZFS_TIMEOUT
+                    ),
+                ));
+            }
+        };
+        if !output.status.success() {
+            tracing::debug!(
+                "{} {}: {}",
+                self.command,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok((
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        ))
+    }
+
+    async fn ensure_dataset(&self, dataset: &str, working_dir: &Path) -> Result<(), HookError> {
+        let (exists, _) = self.zfs(&["list", "-H", "-o", "name", dataset]).await?;
+        if exists {
+            return Ok(());
+        }
+
+        tracing::info!(
+            "creating zfs dataset {} mounted at {}",
+            dataset,
+            working_dir.display()
+        );
+        let mountpoint = format!("mountpoint={}", working_dir.display());
+        let (created, _) = self
+            .zfs(&["create", "-p", "-o", &mountpoint, dataset])
+            .await?;
+        if !created {
+            return Err(HookError::config(
+                HOOK_NAME,
+                format!(
+                    "dataset {dataset} is missing and could not be created; \
+                     try `zfs create -p -o {mountpoint} {dataset}` and chown it \
+                     to the worker user"
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn check_mounted(&self, dataset: &str, working_dir: &Path) -> Result<(), HookError> {
+        let (ok, out) = self
+            .zfs(&["get", "-H", "-o", "value", "mounted,mountpoint", dataset])
+            .await?;
+        let mut lines = out.lines().map(str::trim);
+        let (mounted, mountpoint) = match (ok, lines.next(), lines.next()) {
+            (true, Some(m), Some(p)) => (m, p),
+            _ => {
+                return Err(HookError::config(
+                    HOOK_NAME,
+                    format!("cannot read mount state of dataset {dataset}"),
+                ));
+            }
+        };
+
+        if mounted != "yes" {
+            return Err(HookError::config(
+                HOOK_NAME,
+                format!("dataset {dataset} is not mounted; try `zfs mount {dataset}`"),
+            ));
+        }
+        if Path::new(mountpoint) != working_dir {
+            return Err(HookError::config(
+                HOOK_NAME,
+                format!(
+                    "dataset {dataset} is mounted at {mountpoint}, not at working dir {}",
+                    working_dir.display()
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl JobHook for ZfsHook {
+    fn name(&self) -> &str {
+        HOOK_NAME
+    }
+
+    async fn pre_job(&self, ctx: &mut HookCtx) -> Result<(), HookError> {
+        let dataset = self.dataset(&ctx.mirror_name);
+        self.ensure_dataset(&dataset, &ctx.working_dir).await?;
+        self.check_mounted(&dataset, &ctx.working_dir).await
+    }
+}
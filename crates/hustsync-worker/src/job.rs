@@ -1,11 +1,21 @@
+use chrono::{DateTime, Local, Utc};
 use hustsync_internal::status::SyncStatus;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU32, Ordering};
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc};
 use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 use crate::JobMessage;
+use crate::backoff::RetryBackoff;
+use crate::bandwidth::BandwidthBudget;
+use crate::blackout::Blackout;
+use crate::concurrency::ConcurrencyGroup;
+use crate::freshness::{self, FreshnessProbe};
+use crate::lock::{LockError, MirrorLock};
+use crate::provider::progress::ProgressReporter;
 use crate::provider::{MirrorProvider, ProviderError};
+use crate::schedule::CronSchedule;
 
 #[derive(Clone, Copy)]
 #[allow(clippy::enum_variant_names)]
@@ -45,7 +55,18 @@ pub struct MirrorJob {
     pub tx: mpsc::Sender<CtrlAction>,
     pub state: Arc<AtomicU32>,
     pub disabled: Arc<tokio::sync::Notify>,
+    /// Set by `start` with `allow_mass_delete` on an idle job; the run it
+    /// starts takes it and skips the mass-deletion guard. Cleared when a
+    /// run ends, so no later run inherits it.
+    pub allow_mass_delete: Arc<AtomicBool>,
     pub interval: Duration,
+    /// Cron run times; replaces `interval` when set.
+    pub schedule: Option<Arc<CronSchedule>>,
+    /// Windows in which scheduled runs are held back.
+    pub blackout: Option<Arc<Blackout>>,
+    /// Mirrors whose successful syncs start this one; when set, it is not
+    /// scheduled by `interval` or `schedule`.
+    pub after: Arc<[String]>,
 }
 
 impl MirrorJob {
@@ -57,6 +78,32 @@ impl MirrorJob {
         self.state.store(state, Ordering::Release);
     }
 
+    /// When to run next, given the last run ended (or was recorded) at
+    /// `last`.
+    pub fn next_run(&self, last: DateTime<Utc>) -> DateTime<Utc> {
+        self.schedule
+            .as_ref()
+            .and_then(|s| s.next_after(last))
+            .unwrap_or(last + self.interval)
+    }
+
+    /// Whether this job runs after other mirrors rather than on a timer.
+    pub fn is_dependent(&self) -> bool {
+        !self.after.is_empty()
+    }
+
+    /// Whether a successful sync of `source` starts this job.
+    pub fn runs_after(&self, source: &str) -> bool {
+        self.after.iter().any(|s| s == source)
+    }
+
+    /// When a run due at `now` may start, if a blackout window holds it
+    /// back.
+    pub fn blackout_until(&self, now: DateTime<Local>) -> Option<DateTime<Utc>> {
+        let until = self.blackout.as_ref()?.deferred_until(now)?;
+        Some(until.with_timezone(&Utc))
+    }
+
     pub async fn send_ctrl(
         &self,
         action: CtrlAction,
@@ -65,19 +112,63 @@ impl MirrorJob {
     }
 }
 
+/// Per-mirror policies for [`JobActor::new`], read from the mirror's
+/// config.
+#[derive(Default)]
+pub struct JobOptions {
+    /// Concurrency groups taken before the worker-wide semaphore, in name
+    /// order.
+    pub groups: Vec<ConcurrencyGroup>,
+    pub freshness: Option<FreshnessProbe>,
+    /// Wait for a run lock held by another process instead of failing.
+    pub lock_wait: bool,
+    /// Wait between failed attempts; `None` retries at once.
+    pub backoff: Option<RetryBackoff>,
+    /// Cron run times; replaces the provider's interval when set.
+    pub schedule: Option<CronSchedule>,
+    /// Windows in which scheduled runs are held back.
+    pub blackout: Option<Blackout>,
+    /// Mirrors whose successful syncs start this one.
+    pub after: Vec<String>,
+}
+
+/// What one run does besides calling the provider: the job's policies and
+/// how the run was started.
+#[derive(Clone)]
+struct RunOptions {
+    groups: Arc<[ConcurrencyGroup]>,
+    freshness: Option<Arc<FreshnessProbe>>,
+    lock_wait: bool,
+    backoff: Option<Arc<RetryBackoff>>,
+    /// Bypass the concurrency limits (ForceStart).
+    force: bool,
+    /// An operator asked for the run (ForceStart, Restart); the freshness
+    /// probe does not skip it.
+    by_hand: bool,
+    /// Skip the mass-deletion guard.
+    allow_mass_delete: bool,
+}
+
 pub struct JobActor {
     pub name: Box<str>,
     pub rx: mpsc::Receiver<CtrlAction>,
     pub state: Arc<AtomicU32>,
     pub disabled: Arc<tokio::sync::Notify>,
+    pub allow_mass_delete: Arc<AtomicBool>,
     pub manager_tx: mpsc::Sender<JobMessage>,
     pub semaphore: Arc<tokio::sync::Semaphore>,
+    pub bandwidth: Option<Arc<BandwidthBudget>>,
     pub provider: Arc<dyn MirrorProvider>,
     pub hooks: Arc<Vec<Arc<dyn crate::hooks::JobHook>>>,
+    /// Policies every run starts from; the per-run flags are all unset.
+    options: RunOptions,
 }
 
 struct RunningJob {
     done: tokio::task::JoinHandle<Result<(), ProviderError>>,
+    /// Cancelled by `Stop`, `Disable`, `Restart` and `Halt` to cut a
+    /// pending retry backoff short.
+    cancel: CancellationToken,
 }
 
 impl JobActor {
@@ -85,12 +176,15 @@ impl JobActor {
         name: String,
         manager_tx: mpsc::Sender<JobMessage>,
         semaphore: Arc<tokio::sync::Semaphore>,
+        bandwidth: Option<Arc<BandwidthBudget>>,
         provider: Box<dyn MirrorProvider>,
         hooks: Vec<Arc<dyn crate::hooks::JobHook>>,
+        options: JobOptions,
     ) -> (MirrorJob, Self) {
         let (tx, rx) = mpsc::channel(32);
         let state = Arc::new(AtomicU32::new(STATE_NONE));
         let disabled = Arc::new(tokio::sync::Notify::new());
+        let allow_mass_delete = Arc::new(AtomicBool::new(false));
         let interval = provider.interval();
 
         let name: Box<str> = name.into_boxed_str();
@@ -100,7 +194,11 @@ impl JobActor {
             tx,
             state: Arc::clone(&state),
             disabled: Arc::clone(&disabled),
+            allow_mass_delete: Arc::clone(&allow_mass_delete),
             interval,
+            schedule: options.schedule.map(Arc::new),
+            blackout: options.blackout.map(Arc::new),
+            after: options.after.into(),
         };
 
         let actor = JobActor {
@@ -108,10 +206,21 @@ impl JobActor {
             rx,
             state,
             disabled,
+            allow_mass_delete,
             manager_tx,
             semaphore,
+            bandwidth,
             provider: Arc::from(provider),
             hooks: Arc::new(hooks),
+            options: RunOptions {
+                groups: options.groups.into(),
+                freshness: options.freshness.map(Arc::new),
+                lock_wait: options.lock_wait,
+                backoff: options.backoff.map(Arc::new),
+                force: false,
+                by_hand: false,
+                allow_mass_delete: false,
+            },
         };
 
         (job, actor)
@@ -182,66 +291,190 @@ impl JobActor {
         status: SyncStatus,
         msg: String,
         schedule: bool,
+        retry_at: Option<DateTime<Utc>>,
         provider: &Arc<dyn MirrorProvider>,
     ) {
-        let _ = manager_tx
-            .send(JobMessage {
-                status,
-                name: name.to_string(),
-                msg,
-                schedule,
-                upstream: provider.upstream().to_string(),
-                size: provider.data_size().await,
-                is_master: provider.is_master(),
-            })
-            .await;
+        let msg = Self::status_message(name, status, msg, schedule, retry_at, provider).await;
+        let _ = manager_tx.send(msg).await;
+    }
+
+    async fn status_message(
+        name: &str,
+        status: SyncStatus,
+        msg: String,
+        schedule: bool,
+        retry_at: Option<DateTime<Utc>>,
+        provider: &Arc<dyn MirrorProvider>,
+    ) -> JobMessage {
+        JobMessage {
+            status,
+            name: name.to_string(),
+            msg,
+            schedule,
+            upstream: provider.upstream().to_string(),
+            size: provider.data_size().await,
+            is_master: provider.is_master(),
+            progress: None,
+            stats: provider.transfer_stats().await,
+            retry_at,
+            skipped: false,
+        }
     }
 
     /// Narrow shim between the retry loop and the provider trait.
     ///
     /// Collects every call-site parameter that the provider contract's
     /// `RunContext` will carry (attempt index, cancellation token, env
-    /// overrides) in one function so that when the trait signature grows
+    /// overrides, bandwidth budget, progress relay, mass-deletion
+    /// override) in one function so that when the trait signature grows
     /// the diff is contained here.
     async fn invoke_provider(
         provider: &dyn MirrorProvider,
         attempt: u32,
         env: std::collections::HashMap<String, String>,
+        bandwidth: Option<Arc<BandwidthBudget>>,
+        progress: ProgressReporter,
+        allow_mass_delete: bool,
     ) -> Result<(), ProviderError> {
         use crate::provider::RunContext;
         let ctx = RunContext {
             attempt,
             env,
+            bandwidth,
+            progress: Some(progress),
+            allow_mass_delete,
             ..RunContext::default()
         };
         provider.run(ctx).await
     }
 
-    #[allow(clippy::cognitive_complexity)]
+    /// Take a permit from each of the job's concurrency groups, in name
+    /// order, then from the worker-wide semaphore. Gives up once `cancel`
+    /// fires.
+    async fn acquire_permits(
+        name: &str,
+        semaphore: &Arc<Semaphore>,
+        groups: &[ConcurrencyGroup],
+        cancel: &CancellationToken,
+    ) -> Result<Vec<OwnedSemaphorePermit>, ProviderError> {
+        let mut permits = Vec::with_capacity(groups.len() + 1);
+        let semaphores = groups
+            .iter()
+            .map(|g| (Some(g.name.as_str()), &g.semaphore))
+            .chain([(None, semaphore)]);
+        for (group, semaphore) in semaphores {
+            match group {
+                Some(group) => tracing::debug!("Job {} waiting for group {}...", name, group),
+                None => tracing::debug!("Job {} waiting for semaphore...", name),
+            }
+            let permit = tokio::select! {
+                p = Arc::clone(semaphore).acquire_owned() => p.map_err(|_| ProviderError::Execution {
+                    code: -1,
+                    msg: "semaphore closed".into(),
+                })?,
+                _ = cancel.cancelled() => return Err(ProviderError::Terminated),
+            };
+            permits.push(permit);
+        }
+        Ok(permits)
+    }
+
+    #[allow(
+        clippy::cognitive_complexity,
+        clippy::too_many_arguments,
+        clippy::significant_drop_tightening
+    )]
     async fn run_sync_loop(
         name: Box<str>,
         provider: Arc<dyn MirrorProvider>,
-        semaphore: Arc<tokio::sync::Semaphore>,
+        semaphore: Arc<Semaphore>,
+        bandwidth: Option<Arc<BandwidthBudget>>,
         manager_tx: mpsc::Sender<JobMessage>,
         state: Arc<AtomicU32>,
         hooks: Arc<Vec<Arc<dyn crate::hooks::JobHook>>>,
-        force: bool,
+        options: RunOptions,
+        cancel: CancellationToken,
     ) -> Result<(), ProviderError> {
-        // 1. Acquire semaphore (Concurrency control)
-        let _permit = if !force {
-            tracing::debug!("Job {} waiting for semaphore...", name);
-            Some(
-                semaphore
-                    .acquire_owned()
+        let RunOptions {
+            groups,
+            freshness,
+            lock_wait,
+            backoff,
+            force,
+            by_hand,
+            allow_mass_delete,
+        } = options;
+
+        // 0. Freshness probe — an unchanged upstream needs no slot at all.
+        // A run forced or restarted by hand syncs anyway; it still observes
+        // the marker so its success records it.
+        let marker = match &freshness {
+            Some(probe) => probe.observe().await,
+            None => None,
+        };
+        if let (Some(probe), Some(marker)) = (&freshness, &marker) {
+            if !by_hand && probe.is_unchanged(marker) {
+                tracing::info!("Job {} {}", name, freshness::SKIPPED_MSG);
+                let is_ready = state.load(Ordering::Acquire) == STATE_READY;
+                let msg = JobMessage {
+                    skipped: true,
+                    ..Self::status_message(
+                        &name,
+                        SyncStatus::Success,
+                        freshness::SKIPPED_MSG.into(),
+                        is_ready,
+                        None,
+                        &provider,
+                    )
                     .await
-                    .map_err(|_| ProviderError::Execution {
-                        code: -1,
-                        msg: "semaphore closed".into(),
-                    })?,
-            )
+                };
+                let _ = manager_tx.send(msg).await;
+                return Ok(());
+            }
+            // Until this run succeeds the mirror may not match any marker.
+            probe.record(None);
+        }
+
+        // 1. Cross-process run lock, held until this function returns. Taken
+        // before any slot, so waiting on another process holds none.
+        let lock_path = crate::lock::lock_path(provider.log_dir(), &name);
+        let stopped = || {
+            cancel.is_cancelled()
+                || matches!(
+                    state.load(Ordering::Acquire),
+                    STATE_PAUSED | STATE_DISABLED | STATE_HALTING
+                )
+        };
+        let _lock = match MirrorLock::acquire(&lock_path, lock_wait, stopped).await {
+            Ok(lock) => lock,
+            Err(LockError::Cancelled) => return Err(ProviderError::Terminated),
+            Err(e) => {
+                tracing::warn!("Job {} not started: {}", name, e);
+                let is_ready = state.load(Ordering::Acquire) == STATE_READY;
+                Self::report_status(
+                    &manager_tx,
+                    &name,
+                    SyncStatus::Failed,
+                    e.to_string(),
+                    is_ready,
+                    None,
+                    &provider,
+                )
+                .await;
+                return Err(ProviderError::Execution {
+                    code: -1,
+                    msg: e.to_string(),
+                });
+            }
+        };
+
+        // 1b. Acquire semaphores (Concurrency control)
+        // Held for the whole run, bar retry backoffs.
+        let mut permits = if !force {
+            Self::acquire_permits(&name, &semaphore, &groups, &cancel).await?
         } else {
             tracing::info!("Job {} bypassing semaphore (ForceStart)", name);
-            None
+            Vec::new()
         };
 
         // 2. pre_job — runs once before the retry loop.
@@ -258,10 +491,13 @@ impl JobActor {
             }
         };
 
+        let progress =
+            ProgressReporter::new(name.to_string(), provider.is_master(), manager_tx.clone());
+
         // Closure that binds the invariant parameters so each call site only
         // supplies the per-invocation values (status, msg, schedule).
         let report = |status: SyncStatus, msg: String, schedule: bool| {
-            Self::report_status(&manager_tx, &name, status, msg, schedule, &provider)
+            Self::report_status(&manager_tx, &name, status, msg, schedule, None, &provider)
         };
 
         // 3. Retry Loop
@@ -293,7 +529,15 @@ impl JobActor {
 
             // 3b. Provider run with hook-injected env.
             let provider_env = hook_ctx.env.clone();
-            let run_result = Self::invoke_provider(provider.as_ref(), i, provider_env).await;
+            let run_result = Self::invoke_provider(
+                provider.as_ref(),
+                i,
+                provider_env,
+                bandwidth.clone(),
+                progress.clone(),
+                allow_mass_delete,
+            )
+            .await;
 
             // 3c. post_exec always runs, regardless of success/fail.
             Self::run_post(PostPhase::PostExec, &hooks, pre_exec_count, &mut hook_ctx).await;
@@ -308,6 +552,9 @@ impl JobActor {
                         &mut hook_ctx,
                     )
                     .await;
+                    if let Some(probe) = &freshness {
+                        probe.record(marker);
+                    }
                     let is_ready = state.load(Ordering::Acquire) == STATE_READY;
                     report(SyncStatus::Success, "".into(), is_ready).await;
                     return Ok(());
@@ -332,14 +579,54 @@ impl JobActor {
                         return Err(ProviderError::Terminated);
                     }
 
-                    let is_last_retry = i == retries - 1;
+                    // A refused mass deletion would be refused again; give
+                    // up on this run and wait for the operator.
+                    let is_final = matches!(e, ProviderError::MassDeletion(_));
+                    let is_last_retry = i == retries - 1 || is_final;
+                    let gives_up = is_final || matches!(e, ProviderError::Terminated);
                     let is_ready = current_state == STATE_READY;
 
-                    report(SyncStatus::Failed, e.to_string(), is_last_retry && is_ready).await;
+                    let delay = match &backoff {
+                        Some(b) if !is_last_retry && !gives_up => Some(b.delay(i + 1)),
+                        _ => None,
+                    };
+                    let retry_at = delay
+                        .and_then(|d| chrono::TimeDelta::from_std(d).ok())
+                        .map(|d| Utc::now() + d);
+                    Self::report_status(
+                        &manager_tx,
+                        &name,
+                        SyncStatus::Failed,
+                        e.to_string(),
+                        is_last_retry && is_ready,
+                        retry_at,
+                        &provider,
+                    )
+                    .await;
 
-                    if let ProviderError::Terminated = e {
+                    if gives_up {
                         return Err(e);
                     }
+
+                    if let Some(delay) = delay {
+                        tracing::info!(
+                            "Job {} backing off {:?} before the next attempt",
+                            name,
+                            delay
+                        );
+                        // Free the concurrency slots for other mirrors while
+                        // waiting.
+                        let released = !permits.is_empty();
+                        permits.clear();
+                        tokio::select! {
+                            _ = tokio::time::sleep(delay) => {}
+                            _ = cancel.cancelled() => return Err(ProviderError::Terminated),
+                        }
+                        if released {
+                            permits =
+                                Self::acquire_permits(&name, &semaphore, &groups, &cancel).await?;
+                        }
+                    }
                 }
             }
         }
@@ -347,19 +634,40 @@ impl JobActor {
         Ok(())
     }
 
-    fn spawn_run(&self, force: bool) -> RunningJob {
+    /// `by_hand` marks a run an operator asked for (ForceStart, Restart),
+    /// which the freshness probe does not skip.
+    fn spawn_run(&self, force: bool, by_hand: bool) -> RunningJob {
         let name = self.name.clone();
         let provider = Arc::clone(&self.provider);
         let semaphore = Arc::clone(&self.semaphore);
+        let bandwidth = self.bandwidth.clone();
         let manager_tx = self.manager_tx.clone();
         let state = Arc::clone(&self.state);
         let hooks = Arc::clone(&self.hooks);
+        let cancel = CancellationToken::new();
+        let run_cancel = cancel.clone();
+        let allow_mass_delete = self.allow_mass_delete.swap(false, Ordering::AcqRel);
+        if allow_mass_delete {
+            tracing::warn!(
+                "Job {} starting with the mass-deletion guard overridden",
+                name
+            );
+        }
+        let options = RunOptions {
+            force,
+            by_hand,
+            allow_mass_delete,
+            ..self.options.clone()
+        };
 
         let done = tokio::spawn(async move {
-            Self::run_sync_loop(name, provider, semaphore, manager_tx, state, hooks, force).await
+            Self::run_sync_loop(
+                name, provider, semaphore, bandwidth, manager_tx, state, hooks, options, run_cancel,
+            )
+            .await
         });
 
-        RunningJob { done }
+        RunningJob { done, cancel }
     }
 
     #[allow(clippy::cognitive_complexity)]
@@ -391,6 +699,7 @@ impl JobActor {
                                 self.state.store(STATE_PAUSED, Ordering::Release);
                                 let _ = self.provider.terminate().await;
                                 if let Some(r) = running.take() {
+                                    r.cancel.cancel();
                                     let _ = r.done.await;
                                 }
                             }
@@ -398,6 +707,7 @@ impl JobActor {
                                 self.state.store(STATE_DISABLED, Ordering::Release);
                                 let _ = self.provider.terminate().await;
                                 if let Some(r) = running.take() {
+                                    r.cancel.cancel();
                                     let _ = r.done.await;
                                 }
                                 self.disabled.notify_waiters();
@@ -406,11 +716,13 @@ impl JobActor {
                                 tracing::info!("Job {} restarting, terminating current process...", self.name);
                                 let _ = self.provider.terminate().await;
                                 if let Some(r) = running.take() {
+                                    r.cancel.cancel();
                                     let _ = r.done.await;
                                 }
                                 tokio::time::sleep(Duration::from_secs(1)).await;
                                 self.state.store(STATE_READY, Ordering::Release);
-                                running = Some(self.spawn_run(false));
+                                self.allow_mass_delete.store(false, Ordering::Release);
+                                running = Some(self.spawn_run(false, true));
                             }
                             CtrlAction::ForceStart => {
                                 tracing::warn!("Job {} received ForceStart while already running. Treating as deferred.", self.name);
@@ -420,6 +732,7 @@ impl JobActor {
                                 self.state.store(STATE_HALTING, Ordering::Release);
                                 let _ = self.provider.terminate().await;
                                 if let Some(r) = running.take() {
+                                    r.cancel.cancel();
                                     let _ = r.done.await;
                                 }
                                 return;
@@ -428,18 +741,24 @@ impl JobActor {
                         }
                     }
                 }
+                if running.is_none() {
+                    // An override armed while this run was going is for
+                    // no run at all.
+                    self.allow_mass_delete.store(false, Ordering::Release);
+                }
             } else {
                 match self.rx.recv().await {
-                    Some(CtrlAction::Start) | Some(CtrlAction::Restart) => {
+                    Some(action @ (CtrlAction::Start | CtrlAction::Restart)) => {
                         self.state.store(STATE_READY, Ordering::Release);
                         let force = force_next;
                         force_next = false;
-                        running = Some(self.spawn_run(force));
+                        let by_hand = force || action == CtrlAction::Restart;
+                        running = Some(self.spawn_run(force, by_hand));
                     }
                     Some(CtrlAction::ForceStart) => {
                         self.state.store(STATE_READY, Ordering::Release);
                         force_next = false;
-                        running = Some(self.spawn_run(true));
+                        running = Some(self.spawn_run(true, true));
                     }
                     Some(CtrlAction::Stop) => {
                         self.state.store(STATE_PAUSED, Ordering::Release);
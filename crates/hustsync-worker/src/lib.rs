@@ -1,9 +1,10 @@
 #![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
 
-use chrono::Utc;
+use chrono::{DateTime, Local, Utc};
 use hustsync_config_parser::WorkerConfig;
-use hustsync_internal::msg::WorkerStatus;
+use hustsync_internal::msg::{MirrorSchedule, SyncProgress, TransferStats, WorkerStatus};
 use hustsync_internal::status::SyncStatus;
+use hustsync_internal::util::{expand_tilde, format_path};
 use reqwest::Client;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -11,12 +12,20 @@ use std::time::Duration;
 use tokio::sync::{Mutex, RwLock, Semaphore, mpsc};
 use tokio_util::sync::CancellationToken;
 
+pub mod backoff;
+pub mod bandwidth;
+pub mod blackout;
+pub mod concurrency;
 pub mod error;
+pub mod freshness;
 pub mod hooks;
 pub mod job;
+pub mod lock;
 pub mod provider;
 pub mod schedule;
+pub mod secret;
 pub mod server;
+pub mod state;
 
 pub use error::{HookError, HookErrorKind, WorkerError};
 
@@ -34,6 +43,18 @@ pub struct JobMessage {
     pub upstream: String,
     pub size: Option<String>,
     pub is_master: bool,
+    /// Set on live progress updates from a running provider. The relay
+    /// forwards these to the manager's progress endpoint instead of
+    /// reporting a status change.
+    pub progress: Option<SyncProgress>,
+    /// Transfer statistics of the provider's last successful sync.
+    pub stats: Option<TransferStats>,
+    /// Set on a failed attempt that will be retried after a backoff: when
+    /// the next attempt starts. Sent to the manager as `next_schedule`.
+    pub retry_at: Option<DateTime<Utc>>,
+    /// Set on a `Success` that skipped the sync because the upstream had
+    /// not changed. Dependents are not queued after one.
+    pub skipped: bool,
 }
 
 use tokio::task::JoinSet;
@@ -88,6 +109,123 @@ fn format_manager_url(base: &str, path: &str) -> String {
     )
 }
 
+/// Broadcast one progress update for `job` to every configured manager.
+///
+/// Progress is best-effort: a newer update follows shortly, so failures are
+/// only logged at debug level.
+async fn push_progress(
+    client: &Client,
+    api_bases: &[String],
+    worker_name: &str,
+    job: &str,
+    progress: &SyncProgress,
+) {
+    for root in api_bases {
+        let url = format_manager_url(
+            root,
+            &format!("workers/{}/jobs/{}/progress", worker_name, job),
+        );
+        match client.post(&url).json(progress).send().await {
+            Ok(resp) if resp.status().is_success() => {}
+            Ok(resp) => tracing::debug!("Progress push to {} returned {}", url, resp.status()),
+            Err(e) => tracing::debug!("Progress push to {} failed: {}", url, e),
+        }
+    }
+}
+
+/// Broadcast the schedule queue's snapshot to every configured manager,
+/// with `retry` added for a running job waiting out a retry backoff.
+// The complexity lint counts each `tracing` macro expansion; the body is a
+// single snapshot and a loop over managers.
+#[allow(clippy::cognitive_complexity)]
+async fn push_schedules(
+    client: &Client,
+    api_bases: &[String],
+    worker_name: &str,
+    schedule_queue: &Mutex<ScheduleQueue>,
+    retry: Option<MirrorSchedule>,
+) {
+    // Take the snapshot first so the mutex is not held across the network
+    // calls.
+    let sched_infos = schedule_queue.lock().await.get_jobs();
+    let s: Vec<_> = sched_infos
+        .into_iter()
+        .map(|info| MirrorSchedule {
+            name: info.job_name,
+            next_schedule: info.next_scheduled,
+        })
+        .chain(retry)
+        .collect();
+    let sched_msg = hustsync_internal::msg::MirrorSchedules { schedules: s };
+
+    let mut sched_sent = false;
+    for root in api_bases {
+        let url = format_manager_url(root, &format!("workers/{}/schedules", worker_name));
+        match client.post(&url).json(&sched_msg).send().await {
+            Ok(resp) if resp.status().is_success() => {
+                sched_sent = true;
+            }
+            Ok(resp) => {
+                tracing::warn!("Schedule push to {} returned {}", url, resp.status());
+            }
+            Err(e) => {
+                tracing::warn!("Schedule push to {} failed: {}", url, e);
+            }
+        }
+    }
+    if !sched_sent {
+        tracing::error!("Failed to push schedule to any manager");
+    }
+}
+
+/// Queue every job that runs after `source` to start now, bar paused and
+/// disabled ones. A source skipped by its freshness probe has not changed
+/// and does not count.
+async fn queue_dependents(
+    jobs: &RwLock<HashMap<String, MirrorJob>>,
+    schedule_queue: &Mutex<ScheduleQueue>,
+    source: &str,
+) {
+    let jobs = jobs.read().await;
+    let mut queue = schedule_queue.lock().await;
+    for job in jobs.values().filter(|job| job.runs_after(source)) {
+        let state = job.state();
+        if state == crate::job::STATE_PAUSED || state == crate::job::STATE_DISABLED {
+            continue;
+        }
+        tracing::info!("Job {} queued after {} succeeded", job.name, source);
+        queue.add_job(Utc::now(), job.clone());
+    }
+}
+
+/// Job states and queued runs, as the state file keeps them.
+async fn capture_state(
+    jobs: &RwLock<HashMap<String, MirrorJob>>,
+    schedule_queue: &Mutex<ScheduleQueue>,
+) -> state::SavedState {
+    let jobs = jobs.read().await;
+    let queue = schedule_queue.lock().await;
+    state::SavedState::capture(&jobs, &queue)
+}
+
+/// Write `state` to `file` off the async runtime. Returns `false`, after
+/// logging why, if it could not be written.
+async fn write_state(file: &Arc<state::StateFile>, state: state::SavedState) -> bool {
+    let path = file.path().display().to_string();
+    let file = Arc::clone(file);
+    match tokio::task::spawn_blocking(move || file.save(&state)).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            tracing::warn!("Cannot write state file {}: {}", path, e);
+            false
+        }
+        Err(e) => {
+            tracing::warn!("State file writer for {} failed: {}", path, e);
+            false
+        }
+    }
+}
+
 /// Records the names of mirrors that differ between the current mirror
 /// config snapshot and a freshly-loaded config slice. Names that appear
 /// only in the old snapshot are `removed`; names only in the new config are
@@ -185,7 +323,13 @@ pub struct Worker {
     pub manager_tx: mpsc::Sender<JobMessage>,
     pub manager_rx: Mutex<Option<mpsc::Receiver<JobMessage>>>,
     pub semaphore: Arc<Semaphore>,
+    /// `[concurrency_groups]` and per-host limits shared by every job.
+    pub concurrency_groups: Arc<concurrency::ConcurrencyGroups>,
+    /// Worker-wide `[bandwidth]` budget shared by every rsync-based job.
+    pub bandwidth: Option<Arc<bandwidth::BandwidthBudget>>,
     pub schedule_queue: Arc<Mutex<ScheduleQueue>>,
+    /// `global.state_file`, where job states and the queue outlive a restart.
+    pub state_file: Option<Arc<state::StateFile>>,
     pub exit_token: CancellationToken,
 
     pub http_client: Option<Client>,
@@ -219,6 +363,12 @@ impl Worker {
         let (manager_tx, manager_rx) = mpsc::channel(32);
 
         let semaphore = Arc::new(Semaphore::new(concurrent));
+        let concurrency_groups = Arc::new(concurrency::ConcurrencyGroups::from_config(&cfg));
+        let bandwidth = cfg
+            .bandwidth
+            .as_ref()
+            .and_then(bandwidth::BandwidthBudget::from_config)
+            .map(Arc::new);
         let exit_token = CancellationToken::new();
 
         let mut jobs_map = HashMap::new();
@@ -236,13 +386,15 @@ impl Worker {
                         }
                     };
 
-                    let hooks = Self::build_hooks(m_cfg, &cfg);
+                    let hooks = Self::build_hooks(name, m_cfg, &cfg);
                     let (job, actor) = job::JobActor::new(
                         name.clone(),
                         manager_tx.clone(),
                         Arc::clone(&semaphore),
+                        bandwidth.clone(),
                         provider,
                         hooks,
+                        Self::job_options(name, m_cfg, &cfg, &concurrency_groups),
                     );
                     jobs_map.insert(name.clone(), job);
                     mirror_configs.insert(name.clone(), m_cfg.clone());
@@ -259,7 +411,10 @@ impl Worker {
             manager_tx,
             manager_rx: Mutex::new(Some(manager_rx)),
             semaphore,
+            concurrency_groups,
+            bandwidth,
             schedule_queue: Arc::new(Mutex::new(ScheduleQueue::new())),
+            state_file: state::StateFile::from_config(&cfg).map(Arc::new),
             exit_token,
             http_client: None,
         };
@@ -277,10 +432,10 @@ impl Worker {
         worker
     }
 
-    async fn fetch_job_status(&self) -> Vec<hustsync_internal::msg::MirrorStatus> {
-        let Some(manager_cfg) = &self.cfg.manager else {
-            return vec![];
-        };
+    /// The manager's view of this worker's jobs; `None` without a manager
+    /// or when it cannot be reached.
+    async fn fetch_job_status(&self) -> Option<Vec<hustsync_internal::msg::MirrorStatus>> {
+        let manager_cfg = self.cfg.manager.as_ref()?;
         // Reads always use the first (primary) manager only.
         let api_bases = resolve_api_bases(manager_cfg);
         let root = api_bases
@@ -295,10 +450,10 @@ impl Worker {
         )
         .await
         {
-            Ok(statuses) => statuses,
+            Ok(statuses) => Some(statuses),
             Err(e) => {
                 tracing::error!("Failed to fetch job status from manager: {}", e);
-                vec![]
+                None
             }
         }
     }
@@ -311,13 +466,60 @@ impl Worker {
         provider::build_provider(name, m_cfg, g_cfg)
     }
 
+    /// The run policies set in one mirror's config.
+    fn job_options(
+        name: &str,
+        m_cfg: &hustsync_config_parser::MirrorConfig,
+        g_cfg: &WorkerConfig,
+        groups: &concurrency::ConcurrencyGroups,
+    ) -> job::JobOptions {
+        job::JobOptions {
+            groups: groups.for_mirror(m_cfg),
+            freshness: freshness::FreshnessProbe::from_config(name, m_cfg),
+            lock_wait: m_cfg.lock_wait.unwrap_or(false),
+            backoff: backoff::RetryBackoff::from_config(m_cfg),
+            schedule: schedule::CronSchedule::from_config(name, m_cfg),
+            blackout: blackout::Blackout::from_config(name, m_cfg, g_cfg),
+            after: m_cfg.after.clone().unwrap_or_default(),
+        }
+    }
+
     /// Assemble the hook chain for one mirror. Order matters — `pre_*`
     /// runs in this vec order, `post_*` in reverse (LIFO).
     fn build_hooks(
+        name: &str,
         m_cfg: &hustsync_config_parser::MirrorConfig,
         g_cfg: &WorkerConfig,
     ) -> Vec<Arc<dyn hooks::JobHook>> {
         let mut chain: Vec<Arc<dyn hooks::JobHook>> = Vec::new();
+        // 0. Optional: mount the mirror's ZFS dataset at working_dir, so the
+        //    working_dir hook below finds the mountpoint instead of creating
+        //    a plain directory on the parent filesystem.
+        if let Some(zfs) = g_cfg.zfs.as_ref().filter(|z| z.enable.unwrap_or(false)) {
+            chain.push(Arc::new(hooks::ZfsHook::new(
+                expand_tilde(zfs.command.as_deref().unwrap_or("zfs")),
+                zfs.zpool.clone().unwrap_or_default(),
+            )));
+        }
+        //    The btrfs working subvolume is set up for the same reason; its
+        //    post_success publishes the snapshot.
+        if let Some(btrfs) = g_cfg
+            .btrfs_snapshot
+            .as_ref()
+            .filter(|b| b.enable.unwrap_or(false))
+        {
+            let dir = |path: &Option<String>| {
+                std::path::Path::new(&expand_tilde(path.as_deref().unwrap_or(""))).join(name)
+            };
+            chain.push(Arc::new(hooks::BtrfsSnapshotHook::new(
+                expand_tilde(btrfs.command.as_deref().unwrap_or("btrfs")),
+                hooks::BtrfsSnapshotPaths {
+                    working: provider::resolve_working_dir(name, m_cfg, g_cfg).into(),
+                    snapshot: dir(&btrfs.snapshot_path),
+                    gc: dir(&btrfs.gc_path),
+                },
+            )));
+        }
         // 1. Built-in: ensure working_dir exists before anything else.
         chain.push(Arc::new(hooks::WorkingDirHook::new()));
         // 2. Built-in: rotate and stamp the log file before the provider
@@ -331,6 +533,16 @@ impl Worker {
         if !on_success.is_empty() || !on_failure.is_empty() {
             chain.push(Arc::new(hooks::ExecPostHook::new(on_success, on_failure)));
         }
+        // 4. Optional: atomic publish. Last, so its pre_exec redirect is
+        //    what the provider sees and its post phases swap the tree in
+        //    before exec hooks run.
+        if let Some(publish_dir) = m_cfg.publish_dir.as_deref() {
+            let keep = m_cfg.publish_keep.unwrap_or(1);
+            chain.push(Arc::new(hooks::PublishHook::new(
+                format_path(publish_dir, name),
+                usize::try_from(keep).unwrap_or(usize::MAX),
+            )));
+        }
         chain
     }
 
@@ -398,9 +610,19 @@ impl Worker {
         }
     }
 
-    /// Bootstrap the schedule queue: apply statuses fetched from manager, schedule
-    /// unknown jobs immediately, and push the initial schedule snapshot back.
-    async fn bootstrap_queue(&self, initial_statuses: Vec<hustsync_internal::msg::MirrorStatus>) {
+    /// Bootstrap the schedule queue: apply statuses fetched from manager,
+    /// restore the jobs it does not report from the state file, schedule the
+    /// remaining ones immediately, and push the initial schedule snapshot
+    /// back.
+    async fn bootstrap_queue(
+        &self,
+        initial_statuses: Option<Vec<hustsync_internal::msg::MirrorStatus>>,
+    ) {
+        let saved = self
+            .state_file
+            .as_ref()
+            .and_then(|file| file.load())
+            .unwrap_or_default();
         let mut queue = self.schedule_queue.lock().await;
         let jobs = self.jobs.read().await;
         let mut unset = jobs
@@ -408,7 +630,7 @@ impl Worker {
             .map(|k| (k.clone(), true))
             .collect::<HashMap<String, bool>>();
 
-        for s in initial_statuses {
+        for s in initial_statuses.into_iter().flatten() {
             if let Some(job) = jobs.get(&s.name) {
                 unset.remove(&s.name);
                 match s.status {
@@ -420,15 +642,27 @@ impl Worker {
                     }
                     _ => {
                         job.set_state(crate::job::STATE_NONE);
-                        let next = s.last_update + job.interval;
-                        queue.add_job(next, job.clone());
+                        if !job.is_dependent() {
+                            let next = job.next_run(s.last_update);
+                            queue.add_job(next, job.clone());
+                        }
                     }
                 }
             }
         }
+        // Jobs the manager did not report resume from the state file, if
+        // it has them. Dependent jobs wait for their sources instead.
         for (name, _) in unset {
-            if let Some(job) = jobs.get(&name) {
-                queue.add_job(Utc::now(), job.clone());
+            let Some(job) = jobs.get(&name) else {
+                continue;
+            };
+            let saved = saved.jobs.get(&name);
+            if let Some(held) = saved.and_then(|s| s.held) {
+                tracing::info!("Job {} restored as {:?} from the state file", name, held);
+                job.set_state(held.state());
+            } else if !job.is_dependent() {
+                let next = saved.and_then(|s| s.next_run).unwrap_or_else(Utc::now);
+                queue.add_job(next, job.clone());
             }
         }
 
@@ -597,6 +831,12 @@ impl Worker {
                     continue;
                 };
 
+                if let Some(progress) = &msg.progress {
+                    let api_bases = resolve_api_bases(manager_cfg);
+                    push_progress(client, &api_bases, &worker_name, &msg.name, progress).await;
+                    continue;
+                }
+
                 // Skip Failed reports for paused/disabled jobs (mirrors Go behaviour).
                 {
                     let jobs = jobs_handle.read().await;
@@ -625,9 +865,11 @@ impl Worker {
                     last_update: Utc::now(),
                     last_started: Utc::now(),
                     last_ended: Utc::now(),
-                    next_scheduled: Utc::now(),
+                    next_scheduled: msg.retry_at.unwrap_or_else(Utc::now),
                     status: msg.status,
                     is_master: msg.is_master,
+                    progress: None,
+                    transfer_stats: msg.stats.clone(),
                 };
 
                 let api_bases = resolve_api_bases(manager_cfg);
@@ -657,51 +899,34 @@ impl Worker {
 
                 if msg.schedule {
                     let jobs = jobs_handle.read().await;
-                    if let Some(job) = jobs.get(&msg.name) {
-                        let next = Utc::now() + job.interval;
+                    if let Some(job) = jobs.get(&msg.name)
+                        && !job.is_dependent()
+                    {
+                        let next = job.next_run(Utc::now());
                         schedule_queue.lock().await.add_job(next, job.clone());
                     }
                 }
-
-                // Push updated schedule snapshot. Extract jobs first, drop lock before
-                // the network calls to avoid holding the mutex across await points.
-                let sched_infos = schedule_queue.lock().await.get_jobs();
-                let s: Vec<_> = sched_infos
-                    .into_iter()
-                    .map(|info| hustsync_internal::msg::MirrorSchedule {
-                        name: info.job_name,
-                        next_schedule: info.next_scheduled,
-                    })
-                    .collect();
-                let sched_msg = hustsync_internal::msg::MirrorSchedules { schedules: s };
-
-                // Schedule push: broadcast to every configured manager.
-                let mut sched_sent = false;
-                for root in &api_bases {
-                    let url =
-                        format_manager_url(root, &format!("workers/{}/schedules", worker_name));
-                    match client.post(&url).json(&sched_msg).send().await {
-                        Ok(resp) if resp.status().is_success() => {
-                            sched_sent = true;
-                        }
-                        Ok(resp) => {
-                            tracing::warn!("Schedule push to {} returned {}", url, resp.status());
-                        }
-                        Err(e) => {
-                            tracing::warn!("Schedule push to {} failed: {}", url, e);
-                        }
-                    }
-                }
-                if !sched_sent {
-                    tracing::error!("Failed to push schedule to any manager");
+                if msg.status == SyncStatus::Success && !msg.skipped {
+                    queue_dependents(&jobs_handle, &schedule_queue, &msg.name).await;
                 }
+
+                let retry = msg.retry_at.map(|next_schedule| MirrorSchedule {
+                    name: msg.name.clone(),
+                    next_schedule,
+                });
+                push_schedules(client, &api_bases, &worker_name, &schedule_queue, retry).await;
             }
         });
     }
 
     /// Spawn the dispatch ticker that fires ready jobs every 5 seconds.
+    /// Jobs inside a blackout window are requeued for its close instead,
+    /// and the new schedule is pushed to the manager.
     fn start_dispatch_loop(&self) {
         let schedule_queue = Arc::clone(&self.schedule_queue);
+        let http_client = self.http_client.clone();
+        let cfg = Arc::clone(&self.cfg);
+        let worker_name = self.name();
         let mut interval = tokio::time::interval(Duration::from_secs(5));
         let exit_token = self.exit_token.clone();
 
@@ -710,15 +935,65 @@ impl Worker {
                 tokio::select! {
                     _ = interval.tick() => {
                         let mut ready_jobs = Vec::new();
+                        let mut deferred = false;
                         {
                             let mut queue = schedule_queue.lock().await;
+                            let now = Local::now();
                             while let Some(job) = queue.pop_if_ready() {
-                                ready_jobs.push(job);
+                                match job.blackout_until(now) {
+                                    Some(until) => {
+                                        tracing::info!(
+                                            "Job {} held back by a blackout window until {}",
+                                            job.name,
+                                            until
+                                        );
+                                        queue.add_job(until, job);
+                                        deferred = true;
+                                    }
+                                    None => ready_jobs.push(job),
+                                }
                             }
+                            drop(queue);
                         }
                         for job in ready_jobs {
                             let _ = job.send_ctrl(crate::job::CtrlAction::Start).await;
                         }
+                        if deferred
+                            && let (Some(client), Some(manager_cfg)) = (&http_client, &cfg.manager)
+                        {
+                            let api_bases = resolve_api_bases(manager_cfg);
+                            push_schedules(client, &api_bases, &worker_name, &schedule_queue, None)
+                                .await;
+                        }
+                    }
+                    _ = exit_token.cancelled() => break,
+                }
+            }
+        });
+    }
+
+    /// Spawn the task that writes job states and the schedule queue to the
+    /// state file every 5 seconds, whenever they changed.
+    fn start_state_saver(&self) {
+        let Some(state_file) = self.state_file.clone() else {
+            return;
+        };
+        let jobs = Arc::clone(&self.jobs);
+        let schedule_queue = Arc::clone(&self.schedule_queue);
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        let exit_token = self.exit_token.clone();
+
+        tokio::spawn(async move {
+            let mut written = None;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let state = capture_state(&jobs, &schedule_queue).await;
+                        if written.as_ref() != Some(&state)
+                            && write_state(&state_file, state.clone()).await
+                        {
+                            written = Some(state);
+                        }
                     }
                     _ = exit_token.cancelled() => break,
                 }
@@ -732,6 +1007,11 @@ impl Worker {
     #[allow(clippy::cognitive_complexity)]
     async fn shutdown(&self) {
         tracing::info!("Worker exit signal received. Halting all jobs gracefully...");
+        // Save before halting, while jobs still hold their own states.
+        if let Some(state_file) = &self.state_file {
+            let state = capture_state(&self.jobs, &self.schedule_queue).await;
+            write_state(state_file, state).await;
+        }
         {
             let jobs = self.jobs.read().await;
             for (name, job) in jobs.iter() {
@@ -777,13 +1057,15 @@ impl Worker {
             }
         };
 
-        let hooks = Self::build_hooks(m_cfg, &self.cfg);
+        let hooks = Self::build_hooks(name, m_cfg, &self.cfg);
         let (job, actor) = job::JobActor::new(
             name.to_owned(),
             self.manager_tx.clone(),
             Arc::clone(&self.semaphore),
+            self.bandwidth.clone(),
             provider,
             hooks,
+            Self::job_options(name, m_cfg, &self.cfg, &self.concurrency_groups),
         );
 
         if initial_state == crate::job::STATE_DISABLED {
@@ -796,10 +1078,12 @@ impl Worker {
                 crate::job::STATE_NONE
             };
             job.set_state(resolved);
-            self.schedule_queue
-                .lock()
-                .await
-                .add_job(chrono::Utc::now(), job.clone());
+            if !job.is_dependent() {
+                self.schedule_queue
+                    .lock()
+                    .await
+                    .add_job(chrono::Utc::now(), job.clone());
+            }
         }
 
         self.job_handles.lock().await.spawn(actor.run());
@@ -898,6 +1182,7 @@ impl Worker {
 
         self.start_message_relay().await;
         self.start_dispatch_loop();
+        self.start_state_saver();
 
         self.exit_token.cancelled().await;
         self.shutdown().await;
@@ -913,23 +1198,27 @@ impl Worker {
 mod tests {
     use std::collections::HashMap;
     use std::sync::Arc;
-    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::{AtomicBool, AtomicU32};
     use std::time::Duration;
 
     use axum::extract::State;
     use axum::http::{StatusCode, Uri};
     use axum::routing::any;
+    use chrono::{Timelike, Utc};
     use hustsync_config_parser::{
-        ExecOnStatus, ExecOnStatusExtra, MirrorConfig, WorkerConfig, WorkerGlobalConfig,
-        WorkerManagerConfig, WorkerServerConfig,
+        BlackoutWindowConfig, ExecOnStatus, ExecOnStatusExtra, MirrorConfig,
+        WorkerBtrfsSnapshotConfig, WorkerConfig, WorkerGlobalConfig, WorkerManagerConfig,
+        WorkerServerConfig, WorkerZfsConfig,
     };
     use hustsync_internal::status::SyncStatus;
     use tokio::sync::{Mutex, mpsc};
     use tokio::time::{Instant, sleep};
 
+    use super::blackout::Blackout;
     use super::job::{CtrlAction, STATE_NONE};
+    use super::state::{Held, SavedJob, SavedState, StateFile};
     use super::{
-        JobMessage, MirrorJob, Worker, diff_mirror_configs, resolve_api_bases,
+        JobMessage, MirrorJob, Worker, capture_state, diff_mirror_configs, resolve_api_bases,
         resolve_exec_commands,
     };
 
@@ -1005,7 +1294,11 @@ mod tests {
             tx,
             state: Arc::new(AtomicU32::new(STATE_NONE)),
             disabled: Arc::new(tokio::sync::Notify::new()),
+            allow_mass_delete: Arc::new(AtomicBool::new(false)),
             interval: tokio::time::Duration::from_secs(60),
+            schedule: None,
+            blackout: None,
+            after: Arc::from([]),
         };
         (job, rx)
     }
@@ -1192,6 +1485,10 @@ mod tests {
                 upstream: "rsync://mirror.example/archlinux/".to_string(),
                 size: Some("1".to_string()),
                 is_master: true,
+                progress: None,
+                stats: None,
+                retry_at: None,
+                skipped: false,
             })
             .await
             .unwrap();
@@ -1207,6 +1504,179 @@ mod tests {
         handle_b.abort();
     }
 
+    fn success(name: &str, msg: &str) -> JobMessage {
+        JobMessage {
+            status: SyncStatus::Success,
+            name: name.to_string(),
+            msg: msg.to_string(),
+            schedule: true,
+            upstream: String::new(),
+            size: None,
+            is_master: true,
+            progress: None,
+            stats: None,
+            retry_at: None,
+            skipped: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn relay_queues_dependents_when_their_source_succeeds() {
+        let (manager, log, handle) = spawn_recording_manager().await;
+        let worker = Worker::new(worker_config_with_managers(vec![manager]));
+
+        let dependent = |name: &str| {
+            let (job, _rx) = make_mirror_job(name);
+            MirrorJob {
+                after: Arc::from(["debian".to_string()]),
+                ..job
+            }
+        };
+        let paused = dependent("debian-paused");
+        paused.set_state(crate::job::STATE_PAUSED);
+        {
+            let mut jobs = worker.jobs.write().await;
+            jobs.insert("debian".into(), make_mirror_job("debian").0);
+            jobs.insert("debian-cd".into(), dependent("debian-cd"));
+            jobs.insert("debian-paused".into(), paused);
+        }
+        worker.start_message_relay().await;
+        let queued = || async {
+            let mut names: Vec<String> = worker
+                .schedule_queue
+                .lock()
+                .await
+                .get_jobs()
+                .into_iter()
+                .map(|info| info.job_name)
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Neither a freshness skip of the source nor the dependent's own
+        // success queues the dependent.
+        let skip = JobMessage {
+            skipped: true,
+            ..success("debian", crate::freshness::SKIPPED_MSG)
+        };
+        for msg in [skip, success("debian-cd", "")] {
+            worker.manager_tx.send(msg).await.unwrap();
+        }
+        let schedule_path = "/workers/broadcast-worker/schedules";
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while log
+            .lock()
+            .await
+            .iter()
+            .filter(|p| *p == schedule_path)
+            .count()
+            < 2
+        {
+            assert!(Instant::now() < deadline, "schedules not pushed");
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(queued().await, ["debian"]);
+
+        worker.manager_tx.send(success("debian", "")).await.unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while queued().await.len() < 2 && Instant::now() < deadline {
+            sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(queued().await, ["debian", "debian-cd"]);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn bootstrap_without_manager_resumes_from_state_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("worker-state.json");
+        let later = Utc::now() + chrono::TimeDelta::hours(3);
+        let saved = |next_run, held| SavedJob { next_run, held };
+        StateFile::new(&path)
+            .save(&SavedState {
+                jobs: [
+                    ("arch".to_string(), saved(Some(later), None)),
+                    ("debian".to_string(), saved(None, Some(Held::Paused))),
+                ]
+                .into(),
+            })
+            .unwrap();
+
+        let worker = Worker::new(WorkerConfig {
+            global: Some(WorkerGlobalConfig {
+                state_file: Some(path.to_string_lossy().into_owned()),
+                ..WorkerGlobalConfig::default()
+            }),
+            ..worker_with_mirrors(vec![
+                command_mirror("arch", "true"),
+                command_mirror("debian", "true"),
+                command_mirror("fedora", "true"),
+            ])
+        });
+        let before = Utc::now();
+        worker
+            .bootstrap_queue(worker.fetch_job_status().await)
+            .await;
+
+        let queued: HashMap<String, _> = worker
+            .schedule_queue
+            .lock()
+            .await
+            .get_jobs()
+            .into_iter()
+            .map(|info| (info.job_name, info.next_scheduled))
+            .collect();
+        assert_eq!(queued.get("arch"), Some(&later));
+        assert!(queued["fedora"] >= before && queued["fedora"] <= Utc::now());
+        assert!(!queued.contains_key("debian"));
+        assert_eq!(
+            worker.jobs.read().await["debian"].state(),
+            crate::job::STATE_PAUSED
+        );
+
+        let state = capture_state(&worker.jobs, &worker.schedule_queue).await;
+        assert_eq!(state.jobs["arch"], saved(Some(later), None));
+        assert_eq!(state.jobs["debian"], saved(None, Some(Held::Paused)));
+        assert_eq!(state.jobs["fedora"].held, None);
+    }
+
+    #[tokio::test]
+    async fn dispatch_defers_blacked_out_job_and_pushes_schedule() {
+        let (manager, log, handle) = spawn_recording_manager().await;
+        let worker = Worker::new(worker_config_with_managers(vec![manager]));
+
+        // One window spanning the whole day except the minute after next.
+        let now = chrono::Local::now();
+        let minute = now.hour() * 60 + now.minute();
+        let hhmm = |m: u32| format!("{:02}:{:02}", m / 60 % 24, m % 60);
+        let mirror = MirrorConfig {
+            blackout: Some(vec![BlackoutWindowConfig {
+                start: Some(hhmm(minute + 3)),
+                end: Some(hhmm(minute + 2)),
+                days: None,
+            }]),
+            ..MirrorConfig::default()
+        };
+        let (job, mut rx) = make_mirror_job("archlinux");
+        let job = MirrorJob {
+            blackout: Blackout::from_config("archlinux", &mirror, &worker.cfg).map(Arc::new),
+            ..job
+        };
+        worker.schedule_queue.lock().await.add_job(Utc::now(), job);
+        worker.start_dispatch_loop();
+
+        wait_for_paths(&log, &["/workers/broadcast-worker/schedules"]).await;
+        assert!(rx.try_recv().is_err(), "job must not start in a blackout");
+        let queued = worker.schedule_queue.lock().await.get_jobs();
+        assert_eq!(queued.len(), 1);
+        assert!(queued[0].next_scheduled > Utc::now());
+
+        worker.exit_token.cancel();
+        handle.abort();
+    }
+
     fn strings(items: &[&str]) -> Vec<String> {
         items.iter().map(|s| (*s).to_string()).collect()
     }
@@ -1296,4 +1766,53 @@ mod tests {
         assert_eq!(success, strings(&["mirror-success", "extra-success"]));
         assert_eq!(failure, strings(&["global-failure", "extra-failure"]));
     }
+
+    #[test]
+    fn build_hooks_puts_storage_hooks_ahead_of_working_dir() {
+        let mut cfg = WorkerConfig::default();
+        let names = |cfg: &WorkerConfig| -> Vec<String> {
+            Worker::build_hooks("m", &MirrorConfig::default(), cfg)
+                .iter()
+                .map(|h| h.name().to_string())
+                .collect()
+        };
+        assert!(!names(&cfg).contains(&"zfs".to_string()));
+
+        cfg.zfs = Some(WorkerZfsConfig {
+            enable: Some(true),
+            zpool: Some("tank/mirrors".into()),
+            command: None,
+        });
+        assert_eq!(&names(&cfg)[..2], ["zfs", "working_dir"]);
+
+        cfg.btrfs_snapshot = Some(WorkerBtrfsSnapshotConfig {
+            enable: Some(true),
+            command: None,
+            snapshot_path: Some("/srv/snapshots".into()),
+            gc_path: Some("/srv/snapshots/.gc".into()),
+        });
+        assert_eq!(&names(&cfg)[..3], ["zfs", "btrfs_snapshot", "working_dir"]);
+    }
+
+    #[test]
+    fn build_hooks_runs_publish_last() {
+        let mirror = MirrorConfig {
+            publish_dir: Some("/srv/www/{{.Name}}".into()),
+            exec_on_status: Some(ExecOnStatus {
+                exec_on_success: Some(vec!["true".into()]),
+                exec_on_failure: None,
+            }),
+            ..MirrorConfig::default()
+        };
+        let names: Vec<String> = Worker::build_hooks("m", &mirror, &WorkerConfig::default())
+            .iter()
+            .map(|h| h.name().to_string())
+            .collect();
+        assert_eq!(names.last().map(String::as_str), Some("publish"));
+        assert!(
+            !Worker::build_hooks("m", &MirrorConfig::default(), &WorkerConfig::default())
+                .iter()
+                .any(|h| h.name() == "publish")
+        );
+    }
 }
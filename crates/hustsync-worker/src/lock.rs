@@ -0,0 +1,125 @@
+//! Cross-process run lock for a mirror.
+//!
+//! Two workers configured for the same mirror, or an operator running
+//! rsync by hand with the same wrapper, must not write one tree at once.
+//! Each run holds an advisory `flock` on `<log_dir>/<name>.lock` until it
+//! ends. The lock lives in the log dir rather than the mirror itself so a
+//! `--delete` sync cannot remove it from under its holder.
+//!
+//! The holder writes its PID and host into the file, so a run that finds
+//! the lock taken can say who has it.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+use thiserror::Error;
+
+/// How often a waiting run retries a held lock.
+const WAIT_POLL: Duration = Duration::from_secs(1);
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error("mirror is locked by {holder} (lock file {})", path.display())]
+    Held { holder: String, path: PathBuf },
+    #[error("cannot lock {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("stopped while waiting for the mirror lock")]
+    Cancelled,
+}
+
+/// Lock file for mirror `name` logging into `log_dir`.
+pub fn lock_path(log_dir: &Path, name: &str) -> PathBuf {
+    log_dir.join(format!("{name}.lock"))
+}
+
+/// A held run lock; released when dropped.
+#[derive(Debug)]
+pub struct MirrorLock {
+    _flock: Flock<File>,
+}
+
+impl MirrorLock {
+    /// Take the lock at `path`. When another process holds it, fail at
+    /// once, or with `wait` retry until it is free or `cancelled()` turns
+    /// true.
+    pub async fn acquire(
+        path: &Path,
+        wait: bool,
+        cancelled: impl Fn() -> bool,
+    ) -> Result<Self, LockError> {
+        loop {
+            match Self::try_acquire(path)? {
+                Some(lock) => return Ok(lock),
+                None if !wait => {
+                    return Err(LockError::Held {
+                        holder: holder(path),
+                        path: path.to_path_buf(),
+                    });
+                }
+                None => {
+                    if cancelled() {
+                        return Err(LockError::Cancelled);
+                    }
+                    tracing::debug!("waiting for {} held by {}", path.display(), holder(path));
+                    tokio::time::sleep(WAIT_POLL).await;
+                }
+            }
+        }
+    }
+
+    /// One non-blocking attempt; `None` when the lock is held elsewhere.
+    fn try_acquire(path: &Path) -> Result<Option<Self>, LockError> {
+        let io_err = |source| LockError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        // Not truncated on open: that would wipe the holder's note.
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_err)?;
+        let mut flock = match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(flock) => flock,
+            Err((_, Errno::EWOULDBLOCK)) => return Ok(None),
+            Err((_, errno)) => return Err(io_err(errno.into())),
+        };
+        let note = format!("{} {}\n", std::process::id(), hostname());
+        flock
+            .set_len(0)
+            .and_then(|()| flock.seek(SeekFrom::Start(0)))
+            .and_then(|_| flock.write_all(note.as_bytes()))
+            .map_err(io_err)?;
+        Ok(Some(Self { _flock: flock }))
+    }
+}
+
+/// "pid <pid> on <host>" from the lock file, as far as it can be read.
+fn holder(path: &Path) -> String {
+    let mut note = String::new();
+    let _ = File::open(path).and_then(|mut f| f.read_to_string(&mut note));
+    match note.split_whitespace().collect::<Vec<_>>()[..] {
+        [pid, host, ..] => format!("pid {pid} on {host}"),
+        [pid] => format!("pid {pid}"),
+        [] => "another process".to_string(),
+    }
+}
+
+fn hostname() -> String {
+    nix::unistd::gethostname()
+        .ok()
+        .and_then(|h| h.into_string().ok())
+        .unwrap_or_else(|| "unknown-host".to_string())
+}
@@ -0,0 +1,811 @@
+//! APT provider: mirror a Debian-style archive natively over HTTP(S).
+//!
+//! For every configured suite the provider fetches `dists/<suite>/InRelease`
+//! (plus `Release` and `Release.gpg` when upstream has them), picks the
+//! indices of the configured components and architectures from its SHA256
+//! list, and downloads every pool file those indices reference. Each
+//! download is checked against the SHA256 sum that references it.
+//!
+//! Clients must never see metadata pointing at packages that are not there
+//! yet. New indices and Release files are therefore downloaded into a
+//! hidden staging directory and moved into `dists/` only once every pool
+//! file is present: indices first, then `Release.gpg`, `Release` and
+//! `InRelease` last. When the archive sets `Acquire-By-Hash: yes`, indices
+//! are fetched by hash, the names the signed Release pins, and are also
+//! published under `by-hash/SHA256/`.
+//!
+//! A pool file already present with the expected size is not downloaded
+//! again; pool file names are versioned, so a size match is taken as a
+//! match. Files that drop out of the archive are left in place.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use reqwest::Url;
+use tokio::fs::{self, File, create_dir_all};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use hustsync_internal::util::format_size;
+
+use super::transfer::{
+    decompress, fetch_to, has_sha256, is_safe_relative, join_url, run_cancellable,
+};
+use super::{
+    CommonProviderConfig, MirrorProvider, ProviderError, ProviderType, RunContext, RunSlot,
+    disk_usage, impl_provider_getters, log_provider_failure, resolve_log_file,
+};
+
+/// Hidden directory under the working dir holding not-yet-published
+/// metadata. It is on the same filesystem, so publishing is a rename.
+const STAGING_DIR: &str = ".hustsync-apt-staging";
+
+/// Release files in publish order: `InRelease` goes last because it is
+/// what clients read first.
+const RELEASE_FILES: [&str; 3] = ["Release.gpg", "Release", "InRelease"];
+
+/// Compression suffixes of an index, in the order they are preferred when
+/// reading one for pool file references.
+const INDEX_SUFFIXES: [&str; 3] = ["", ".gz", ".xz"];
+
+pub struct AptProviderConfig {
+    pub common: CommonProviderConfig,
+    /// Suites under `dists/`, e.g. `bookworm`.
+    pub suites: Vec<String>,
+    /// Components of each suite, e.g. `main`.
+    pub components: Vec<String>,
+    /// Architectures; `source` selects source indices.
+    pub architectures: Vec<String>,
+}
+
+/// A file referenced by a Release file or an index, with its expected
+/// size and SHA256 sum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRef {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// The parts of a `Release` file the provider uses.
+#[derive(Debug, Default)]
+pub struct Release {
+    /// `Acquire-By-Hash: yes`.
+    pub by_hash: bool,
+    /// Entries of the `SHA256` field, relative to `dists/<suite>/`.
+    pub files: Vec<FileRef>,
+}
+
+pub struct AptProvider {
+    config: AptProviderConfig,
+    base: Url,
+    client: reqwest::Client,
+    data_size: Mutex<Option<String>>,
+    // Cancels the in-process transfer on `terminate()`.
+    run_slot: RunSlot,
+}
+
+/// Per-run bookkeeping.
+struct SyncState {
+    log: File,
+    /// Pool files already handled by an earlier suite of this run.
+    pool_seen: HashSet<String>,
+    fetched: usize,
+    fetched_bytes: u64,
+    unchanged: usize,
+}
+
+impl SyncState {
+    async fn log(&mut self, line: &str) {
+        let _ = self.log.write_all(format!("{line}\n").as_bytes()).await;
+    }
+}
+
+/// An index selected from a suite's Release file, and where its current
+/// content lives: already published, or staged by this run.
+struct IndexFile {
+    file: FileRef,
+    local: PathBuf,
+    staged: bool,
+}
+
+impl AptProvider {
+    pub fn new(mut config: AptProviderConfig) -> Result<Self, ProviderError> {
+        let base = Url::parse(&config.common.upstream_url)
+            .map_err(|e| ProviderError::Config(format!("invalid apt upstream: {e}")))?;
+        if !matches!(base.scheme(), "http" | "https") || !base.path().ends_with('/') {
+            return Err(ProviderError::Config(
+                "apt upstream URL should be http(s) and end with /".into(),
+            ));
+        }
+        if config.suites.is_empty() {
+            return Err(ProviderError::Config(
+                "apt provider needs at least one suite".into(),
+            ));
+        }
+        if config.common.retry == 0 {
+            config.common.retry = 2;
+        }
+
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("hustsync/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| ProviderError::Config(format!("http client: {e}")))?;
+
+        Ok(Self {
+            config,
+            base,
+            client,
+            data_size: Mutex::new(None),
+            run_slot: RunSlot::default(),
+        })
+    }
+
+    fn working_root(&self) -> &Path {
+        Path::new(&self.config.common.working_dir)
+    }
+
+    /// Download `rel` (relative to the archive root) into `dest`, checking
+    /// `sha256` when given. Returns `None` when upstream answers 404.
+    async fn fetch(
+        &self,
+        state: &mut SyncState,
+        rel: &str,
+        dest: &Path,
+        sha256: Option<&str>,
+    ) -> Result<Option<u64>, ProviderError> {
+        let url = join_url(&self.base, rel)?;
+        let Some(written) = fetch_to(&self.client, url, dest, sha256).await? else {
+            return Ok(None);
+        };
+        state.fetched += 1;
+        state.fetched_bytes += written;
+        state
+            .log(&format!("fetched {rel} ({})", format_size(written)))
+            .await;
+        Ok(Some(written))
+    }
+
+    /// Stage the suite's Release files and parse the signed one.
+    async fn fetch_release(
+        &self,
+        state: &mut SyncState,
+        suite: &str,
+        staging: &Path,
+    ) -> Result<(Release, Vec<&'static str>), ProviderError> {
+        let mut found = Vec::new();
+        for name in RELEASE_FILES {
+            let rel = format!("dists/{suite}/{name}");
+            if self
+                .fetch(state, &rel, &staging.join(name), None)
+                .await?
+                .is_some()
+            {
+                found.push(name);
+            }
+        }
+        let source = if found.contains(&"InRelease") {
+            "InRelease"
+        } else if found.contains(&"Release") {
+            "Release"
+        } else {
+            return Err(ProviderError::Http(format!(
+                "dists/{suite}: upstream has neither InRelease nor Release"
+            )));
+        };
+        let text = fs::read_to_string(staging.join(source)).await?;
+        let release = parse_release(&text)
+            .map_err(|e| ProviderError::Http(format!("dists/{suite}/{source}: {e}")))?;
+        if let Some(bad) = release.files.iter().find(|f| !is_safe_relative(&f.path)) {
+            return Err(ProviderError::Http(format!(
+                "dists/{suite}/{source} references unsafe path `{}`",
+                bad.path
+            )));
+        }
+        Ok((release, found))
+    }
+
+    /// Whether `path` (relative to `dists/<suite>/`) belongs to one of the
+    /// configured components and architectures.
+    fn wanted_index(&self, path: &str) -> bool {
+        self.config.components.iter().any(|component| {
+            let Some(rest) = path
+                .strip_prefix(component.as_str())
+                .and_then(|r| r.strip_prefix('/'))
+            else {
+                return false;
+            };
+            rest.starts_with("i18n/")
+                || self.config.architectures.iter().any(|arch| {
+                    if arch == "source" {
+                        rest.starts_with("source/")
+                    } else {
+                        rest.strip_prefix("binary-")
+                            .and_then(|r| r.strip_prefix(arch.as_str()))
+                            .is_some_and(|r| r.starts_with('/'))
+                    }
+                })
+        })
+    }
+
+    /// Make the suite's selected indices available: reuse published ones
+    /// whose sum still matches, stage the rest. Indices that the Release
+    /// lists but upstream does not carry (typically the uncompressed
+    /// variants) are skipped.
+    async fn sync_indices(
+        &self,
+        state: &mut SyncState,
+        suite: &str,
+        release: &Release,
+        staging: &Path,
+    ) -> Result<Vec<IndexFile>, ProviderError> {
+        let published = self.working_root().join("dists").join(suite);
+        let mut out = Vec::new();
+        for file in release.files.iter().filter(|f| self.wanted_index(&f.path)) {
+            let local = published.join(&file.path);
+            if has_sha256(&local, Some(file.size), &file.sha256).await {
+                state.unchanged += 1;
+                out.push(IndexFile {
+                    file: file.clone(),
+                    local,
+                    staged: false,
+                });
+                continue;
+            }
+
+            let dest = staging.join(&file.path);
+            let canonical = format!("dists/{suite}/{}", file.path);
+            let mut got = None;
+            if release.by_hash {
+                let by_hash = by_hash_path(&canonical, &file.sha256);
+                got = self
+                    .fetch(state, &by_hash, &dest, Some(&file.sha256))
+                    .await?;
+            }
+            if got.is_none() {
+                got = self
+                    .fetch(state, &canonical, &dest, Some(&file.sha256))
+                    .await?;
+            }
+            if got.is_some() {
+                out.push(IndexFile {
+                    file: file.clone(),
+                    local: dest,
+                    staged: true,
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    /// Read one variant of every `Packages`/`Sources` index and collect the
+    /// pool files they reference.
+    async fn pool_refs(
+        &self,
+        suite: &str,
+        indices: &[IndexFile],
+    ) -> Result<Vec<FileRef>, ProviderError> {
+        // Stem (path without compression suffix) → available variants.
+        let mut groups: BTreeMap<&str, Vec<&IndexFile>> = BTreeMap::new();
+        for index in indices {
+            let stem = strip_compression(&index.file.path);
+            if stem.ends_with("/Packages") || stem.ends_with("/Sources") {
+                groups.entry(stem).or_default().push(index);
+            }
+        }
+
+        let mut refs = Vec::new();
+        for (stem, variants) in groups {
+            let readable = INDEX_SUFFIXES.iter().find_map(|suffix| {
+                variants.iter().find(|v| {
+                    v.file.path.len() == stem.len() + suffix.len() && v.file.path.ends_with(suffix)
+                })
+            });
+            let Some(index) = readable else {
+                return Err(ProviderError::Http(format!(
+                    "dists/{suite}/{stem}: no variant in a supported compression"
+                )));
+            };
+            let raw = fs::read(&index.local).await?;
+            let text = decompress(&index.file.path, &raw)
+                .and_then(|b| String::from_utf8(b).map_err(|e| format!("not UTF-8: {e}")))
+                .map_err(|e| {
+                    ProviderError::Http(format!("dists/{suite}/{}: {e}", index.file.path))
+                })?;
+            refs.extend(parse_index(&text).map_err(|e| {
+                ProviderError::Http(format!("dists/{suite}/{}: {e}", index.file.path))
+            })?);
+        }
+        Ok(refs)
+    }
+
+    /// Download every referenced pool file that is missing or has the
+    /// wrong size. A referenced file upstream does not have fails the run.
+    async fn sync_pool(
+        &self,
+        state: &mut SyncState,
+        refs: &[FileRef],
+    ) -> Result<(), ProviderError> {
+        for file in refs {
+            if !state.pool_seen.insert(file.path.clone()) {
+                continue;
+            }
+            if !is_safe_relative(&file.path) {
+                return Err(ProviderError::Http(format!(
+                    "index references unsafe path `{}`",
+                    file.path
+                )));
+            }
+            let dest = self.working_root().join(&file.path);
+            let local_size = fs::metadata(&dest)
+                .await
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len());
+            if local_size == Some(file.size) {
+                state.unchanged += 1;
+                continue;
+            }
+            if self
+                .fetch(state, &file.path, &dest, Some(&file.sha256))
+                .await?
+                .is_none()
+            {
+                return Err(ProviderError::Http(format!(
+                    "{}: 404 Not Found (referenced by the indices)",
+                    file.path
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the staged indices, then the Release files, into `dists/`.
+    async fn publish(
+        &self,
+        suite: &str,
+        release: &Release,
+        indices: &[IndexFile],
+        release_files: &[&str],
+        staging: &Path,
+    ) -> Result<(), ProviderError> {
+        let published = self.working_root().join("dists").join(suite);
+        for index in indices {
+            let dest = published.join(&index.file.path);
+            if index.staged {
+                if let Some(parent) = dest.parent() {
+                    create_dir_all(parent).await?;
+                }
+                fs::rename(&index.local, &dest).await?;
+            }
+            if release.by_hash {
+                let by_hash = published.join(by_hash_path(&index.file.path, &index.file.sha256));
+                link_or_copy(&dest, &by_hash).await?;
+            }
+        }
+
+        create_dir_all(&published).await?;
+        for name in RELEASE_FILES {
+            let dest = published.join(name);
+            if release_files.contains(&name) {
+                fs::rename(staging.join(name), &dest).await?;
+            } else if fs::metadata(&dest).await.is_ok() {
+                // Upstream dropped it; a stale copy would not match.
+                fs::remove_file(&dest).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync_suite(&self, state: &mut SyncState, suite: &str) -> Result<(), ProviderError> {
+        let staging = self
+            .working_root()
+            .join(STAGING_DIR)
+            .join("dists")
+            .join(suite);
+        create_dir_all(&staging).await?;
+
+        let (release, release_files) = self.fetch_release(state, suite, &staging).await?;
+        let indices = self.sync_indices(state, suite, &release, &staging).await?;
+        if indices.is_empty() {
+            state
+                .log(&format!(
+                    "warning: dists/{suite} has no indices for the configured \
+                     components and architectures"
+                ))
+                .await;
+        }
+        let refs = self.pool_refs(suite, &indices).await?;
+        self.sync_pool(state, &refs).await?;
+        self.publish(suite, &release, &indices, &release_files, &staging)
+            .await?;
+        state
+            .log(&format!(
+                "published dists/{suite} ({} indices, {} pool files)",
+                indices.len(),
+                refs.len()
+            ))
+            .await;
+        Ok(())
+    }
+
+    async fn sync(&self, state: &mut SyncState) -> Result<(), ProviderError> {
+        let staging_root = self.working_root().join(STAGING_DIR);
+        // Left over from an interrupted run.
+        let _ = fs::remove_dir_all(&staging_root).await;
+        for suite in &self.config.suites {
+            self.sync_suite(state, suite).await?;
+        }
+        fs::remove_dir_all(&staging_root).await?;
+        Ok(())
+    }
+
+    async fn run_inner(
+        &self,
+        ctx: &RunContext,
+        cancel: &CancellationToken,
+    ) -> Result<(), ProviderError> {
+        create_dir_all(&self.config.common.working_dir).await?;
+        create_dir_all(&self.config.common.log_dir).await?;
+
+        let effective_log_file = resolve_log_file(ctx, &self.config.common.log_file);
+        let mut state = SyncState {
+            log: File::create(&effective_log_file).await?,
+            pool_seen: HashSet::new(),
+            fetched: 0,
+            fetched_bytes: 0,
+            unchanged: 0,
+        };
+
+        tracing::info!("Starting apt provider for {}", self.config.common.name);
+        state
+            .log(&format!(
+                "mirroring {} suites [{}] components [{}] architectures [{}]",
+                self.base,
+                self.config.suites.join(", "),
+                self.config.components.join(", "),
+                self.config.architectures.join(", ")
+            ))
+            .await;
+
+        let result = run_cancellable(
+            self.sync(&mut state),
+            self.config.common.timeout,
+            cancel,
+            &self.config.common.name,
+        )
+        .await;
+        match result {
+            Ok(()) => {
+                let size = disk_usage(self.working_root()).await?;
+                state
+                    .log(&format!(
+                        "done: {} fetched ({}), {} unchanged, total size {}",
+                        state.fetched,
+                        format_size(state.fetched_bytes),
+                        state.unchanged,
+                        format_size(size)
+                    ))
+                    .await;
+                *self.data_size.lock().await = Some(format_size(size));
+                Ok(())
+            }
+            Err(e) => {
+                state.log(&format!("error: {e}")).await;
+                let _ = state.log.flush().await;
+                log_provider_failure(
+                    "Apt",
+                    &self.config.common.name,
+                    &e.to_string(),
+                    &effective_log_file,
+                )
+                .await;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Split deb822 text into paragraphs of `(field, value)` pairs. Folded
+/// continuation lines are kept in the value, one per line.
+fn paragraphs(text: &str) -> Vec<Vec<(String, String)>> {
+    let mut out = Vec::new();
+    let mut current: Vec<(String, String)> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                out.push(std::mem::take(&mut current));
+            }
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = current.last_mut() {
+                value.push('\n');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            current.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+fn field<'a>(paragraph: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    paragraph
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// The signed text of a clearsigned `InRelease`; other text is returned
+/// unchanged. The signature itself is not checked here: clients verify
+/// it against their keyring, and they get the file byte for byte.
+fn strip_clearsign(text: &str) -> String {
+    let Some(start) = text.find("-----BEGIN PGP SIGNED MESSAGE-----") else {
+        return text.to_string();
+    };
+    // Armor headers (`Hash: ...`) end at the first blank line.
+    let body = text[start..]
+        .replace("\r\n", "\n")
+        .split_once("\n\n")
+        .map(|(_, b)| b.to_string())
+        .unwrap_or_default();
+    body.split("\n-----BEGIN PGP SIGNATURE-----")
+        .next()
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.strip_prefix("- ").unwrap_or(l))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse `<sha256> <size> <path>` lines.
+fn parse_sums(block: &str) -> Result<Vec<FileRef>, String> {
+    block
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            match (
+                parts.next(),
+                parts.next().and_then(|s| s.parse().ok()),
+                parts.next(),
+            ) {
+                (Some(sha256), Some(size), Some(path)) => Ok(FileRef {
+                    path: path.to_string(),
+                    size,
+                    sha256: sha256.to_ascii_lowercase(),
+                }),
+                _ => Err(format!("malformed checksum line `{line}`")),
+            }
+        })
+        .collect()
+}
+
+/// Parse a `Release` or `InRelease` file.
+pub fn parse_release(text: &str) -> Result<Release, String> {
+    let text = strip_clearsign(text);
+    let paragraphs = paragraphs(&text);
+    let paragraph = paragraphs.first().ok_or("empty Release file")?;
+    let sums = field(paragraph, "SHA256").ok_or("no SHA256 field")?;
+    Ok(Release {
+        by_hash: field(paragraph, "Acquire-By-Hash").is_some_and(|v| v == "yes"),
+        files: parse_sums(sums)?,
+    })
+}
+
+/// Pool files referenced by a `Packages` (`Filename`, `Size`, `SHA256`) or
+/// `Sources` (`Directory`, `Checksums-Sha256`) index.
+pub fn parse_index(text: &str) -> Result<Vec<FileRef>, String> {
+    let mut out = Vec::new();
+    for paragraph in paragraphs(text) {
+        if let Some(filename) = field(&paragraph, "Filename") {
+            let size = field(&paragraph, "Size").and_then(|s| s.parse().ok());
+            let Some((size, sha256)) = size.zip(field(&paragraph, "SHA256")) else {
+                return Err(format!("`{filename}` has no Size or SHA256"));
+            };
+            out.push(FileRef {
+                path: filename.to_string(),
+                size,
+                sha256: sha256.to_ascii_lowercase(),
+            });
+        } else if let Some(dir) = field(&paragraph, "Directory") {
+            let sums = field(&paragraph, "Checksums-Sha256")
+                .ok_or_else(|| format!("source in `{dir}` has no Checksums-Sha256"))?;
+            for file in parse_sums(sums)? {
+                out.push(FileRef {
+                    path: format!("{}/{}", dir.trim_end_matches('/'), file.path),
+                    ..file
+                });
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn strip_compression(path: &str) -> &str {
+    [".gz", ".xz", ".bz2", ".lzma", ".zst"]
+        .iter()
+        .find_map(|ext| path.strip_suffix(ext))
+        .unwrap_or(path)
+}
+
+/// `a/b/Packages.xz` → `a/b/by-hash/SHA256/<sha256>`.
+fn by_hash_path(path: &str, sha256: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((dir, _)) => format!("{dir}/by-hash/SHA256/{sha256}"),
+        None => format!("by-hash/SHA256/{sha256}"),
+    }
+}
+
+/// Hard-link `src` to `dest` unless `dest` exists; copy across devices.
+async fn link_or_copy(src: &Path, dest: &Path) -> Result<(), ProviderError> {
+    if fs::metadata(dest).await.is_ok() {
+        return Ok(());
+    }
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent).await?;
+    }
+    if fs::hard_link(src, dest).await.is_err() {
+        fs::copy(src, dest).await?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl MirrorProvider for AptProvider {
+    impl_provider_getters!(AptProvider, ProviderType::Apt);
+
+    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
+        if ctx.attempt > 1 {
+            tracing::debug!(
+                "Apt provider {} re-entering on attempt {}",
+                self.config.common.name,
+                ctx.attempt
+            );
+        }
+
+        let cancel = self.run_slot.begin(&ctx.cancel)?;
+        let result = self.run_inner(&ctx, &cancel).await;
+        self.run_slot.end();
+        result
+    }
+
+    async fn terminate(&self) -> Result<(), ProviderError> {
+        if self.run_slot.cancel() {
+            tracing::warn!("Terminating apt provider for {}", self.config.common.name);
+        }
+        Ok(())
+    }
+
+    async fn data_size(&self) -> Option<String> {
+        self.data_size.lock().await.clone()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+mod tests {
+    use super::*;
+
+    const SHA_A: &str = "a3f1c4c5b5f7e1f0d5f5e59d1c1f0f2c6b0e7f3a2d9c8b7a6f5e4d3c2b1a0f9e";
+    const SHA_B: &str = "0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f5e4d3c2b1a0f9e";
+
+    #[test]
+    fn parses_clearsigned_release() {
+        let text = format!(
+            "-----BEGIN PGP SIGNED MESSAGE-----\n\
+             Hash: SHA512\n\
+             \n\
+             Origin: Debian\n\
+             Suite: stable\n\
+             Acquire-By-Hash: yes\n\
+             MD5Sum:\n \
+             0123 10 main/binary-amd64/Packages\n\
+             SHA256:\n \
+             {SHA_A}   1234 main/binary-amd64/Packages\n \
+             {SHA_B}    321 main/binary-amd64/Packages.xz\n\
+             - -dash-escaped: line\n\
+             -----BEGIN PGP SIGNATURE-----\n\
+             \n\
+             iQIzBAEBCgAdFiEE\n\
+             -----END PGP SIGNATURE-----\n"
+        );
+        let release = parse_release(&text).unwrap();
+        assert!(release.by_hash);
+        assert_eq!(
+            release.files,
+            vec![
+                FileRef {
+                    path: "main/binary-amd64/Packages".into(),
+                    size: 1234,
+                    sha256: SHA_A.into(),
+                },
+                FileRef {
+                    path: "main/binary-amd64/Packages.xz".into(),
+                    size: 321,
+                    sha256: SHA_B.into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn release_without_sha256_is_rejected() {
+        assert!(parse_release("Origin: x\nMD5Sum:\n 00 1 a\n").is_err());
+    }
+
+    #[test]
+    fn parses_packages_and_sources() {
+        let text = format!(
+            "Package: hello\nVersion: 2.10-3\n\
+             Filename: pool/main/h/hello/hello_2.10-3_amd64.deb\n\
+             Size: 53000\nSHA256: {SHA_A}\nDescription: hi\n there\n\n\
+             Package: hello\nDirectory: pool/main/h/hello\n\
+             Checksums-Sha256:\n {SHA_B} 1900 hello_2.10-3.dsc\n {SHA_A} 725000 hello_2.10.orig.tar.gz\n"
+        );
+        let refs = parse_index(&text).unwrap();
+        let paths: Vec<_> = refs.iter().map(|r| (r.path.as_str(), r.size)).collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("pool/main/h/hello/hello_2.10-3_amd64.deb", 53000),
+                ("pool/main/h/hello/hello_2.10-3.dsc", 1900),
+                ("pool/main/h/hello/hello_2.10.orig.tar.gz", 725000),
+            ]
+        );
+    }
+
+    #[test]
+    fn by_hash_path_sits_next_to_the_index() {
+        assert_eq!(
+            by_hash_path("main/binary-amd64/Packages.xz", "abc"),
+            "main/binary-amd64/by-hash/SHA256/abc"
+        );
+        assert_eq!(
+            strip_compression("main/source/Sources.gz"),
+            "main/source/Sources"
+        );
+    }
+
+    #[test]
+    fn index_selection_follows_components_and_architectures() {
+        let provider = AptProvider::new(AptProviderConfig {
+            common: CommonProviderConfig {
+                name: "debian".to_string(),
+                upstream_url: "http://deb.example/debian/".to_string(),
+                working_dir: "/tmp/debian".to_string(),
+                log_dir: "/tmp/log".to_string(),
+                log_file: "/tmp/log/debian.log".to_string(),
+                interval: std::time::Duration::from_secs(60),
+                retry: 0,
+                timeout: std::time::Duration::ZERO,
+                env: std::collections::HashMap::new(),
+                is_master: true,
+                success_exit_codes: Vec::new(),
+                cgroup: None,
+            },
+            suites: vec!["stable".into()],
+            components: vec!["main".into(), "non-free-firmware".into()],
+            architectures: vec!["amd64".into(), "source".into()],
+        })
+        .unwrap();
+        for (path, wanted) in [
+            ("main/binary-amd64/Packages.xz", true),
+            ("main/binary-amd64/Release", true),
+            ("main/binary-arm64/Packages.xz", false),
+            ("main/binary-amd64x/Packages.xz", false),
+            ("main/source/Sources.xz", true),
+            ("main/i18n/Translation-en.bz2", true),
+            ("non-free-firmware/binary-amd64/Packages.gz", true),
+            ("contrib/binary-amd64/Packages.gz", false),
+            ("main/Contents-amd64.gz", false),
+        ] {
+            assert_eq!(provider.wanted_index(path), wanted, "{path}");
+        }
+    }
+}
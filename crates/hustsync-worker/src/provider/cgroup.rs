@@ -0,0 +1,289 @@
+//! cgroup v2 confinement for provider processes.
+//!
+//! Port of Go `worker/cgroup.go` (v2 path only): each mirror gets a child
+//! cgroup at `<base_path>/<group>/<mirror>`. The provider's process group
+//! leader joins it between fork and exec, before it can start anything, so
+//! everything it forks is accounted and limited there. When the run ends — normally, on timeout,
+//! or on cancel — every process still in the cgroup is killed and the
+//! directory removed, which catches daemonised stragglers that escaped the
+//! process group.
+//!
+//! All paths are derived from `base_path`, so tests can point it at a plain
+//! temp directory laid out like cgroupfs (a "fake root").
+
+#[cfg(unix)]
+use std::ffi::{CStr, CString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use nix::sys::signal::{self, Signal};
+#[cfg(unix)]
+use nix::unistd::Pid;
+
+pub struct CgroupConfig {
+    /// cgroup v2 mount point (or a fake root in tests).
+    pub base_path: PathBuf,
+    /// Worker-level group under `base_path`; holds no processes itself.
+    pub group: String,
+    /// Mirror name; becomes the leaf cgroup directory.
+    pub name: String,
+    /// Written to `memory.max` when set.
+    pub memory_max: Option<u64>,
+    /// Written to `cpu.weight` when set.
+    pub cpu_weight: Option<u32>,
+}
+
+impl CgroupConfig {
+    fn group_path(&self) -> PathBuf {
+        self.base_path.join(&self.group)
+    }
+
+    /// Leaf cgroup directory for this mirror.
+    pub fn path(&self) -> PathBuf {
+        self.group_path().join(&self.name)
+    }
+
+    /// Create the mirror's cgroup and apply its limits.
+    ///
+    /// Fails when `base_path` is not a cgroup v2 hierarchy (no
+    /// `cgroup.controllers`), so a v1-only host surfaces a clear error
+    /// instead of silently running unconfined.
+    pub(crate) async fn prepare(&self) -> io::Result<()> {
+        if !tokio::fs::try_exists(self.base_path.join("cgroup.controllers")).await? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{} is not a cgroup v2 hierarchy (missing cgroup.controllers)",
+                    self.base_path.display()
+                ),
+            ));
+        }
+
+        let group = self.group_path();
+        tokio::fs::create_dir_all(&group).await?;
+
+        // Limits in the leaf are only writable once every level above it
+        // delegates the controller: `base_path` to the group, then the
+        // group to the leaf. The group dir never holds processes, so
+        // enabling controllers there cannot hit the no-internal-process rule.
+        let controllers = [
+            self.memory_max.map(|_| "memory"),
+            self.cpu_weight.map(|_| "cpu"),
+        ];
+        for controller in controllers.into_iter().flatten() {
+            self.delegate(controller).await?;
+            write_control(&group, "cgroup.subtree_control", &format!("+{controller}")).await?;
+        }
+
+        let leaf = self.path();
+        tokio::fs::create_dir_all(&leaf).await?;
+        if let Some(bytes) = self.memory_max {
+            write_control(&leaf, "memory.max", &bytes.to_string()).await?;
+        }
+        if let Some(weight) = self.cpu_weight {
+            write_control(&leaf, "cpu.weight", &weight.to_string()).await?;
+        }
+        Ok(())
+    }
+
+    /// Enable `controller` for the children of `base_path`. Fails with a
+    /// message naming the missing delegation when `base_path` itself does
+    /// not have the controller, or refuses to hand it down (for instance
+    /// because processes live in it).
+    async fn delegate(&self, controller: &str) -> io::Result<()> {
+        let available =
+            tokio::fs::read_to_string(self.base_path.join("cgroup.controllers")).await?;
+        if !available.split_whitespace().any(|c| c == controller) {
+            return Err(io::Error::other(format!(
+                "the {controller} controller is not delegated to {}; enable it in the parent's \
+                 cgroup.subtree_control",
+                self.base_path.display()
+            )));
+        }
+        write_control(
+            &self.base_path,
+            "cgroup.subtree_control",
+            &format!("+{controller}"),
+        )
+        .await
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "cannot delegate the {controller} controller from {}: {e}",
+                    self.base_path.display()
+                ),
+            )
+        })
+    }
+
+    /// Have the child spawned from `cmd` write its own pid to the leaf's
+    /// `cgroup.procs` before it execs. If the write fails, the spawn fails.
+    #[cfg(unix)]
+    pub(crate) fn join_on_exec(&self, cmd: &mut tokio::process::Command) -> io::Result<()> {
+        use std::os::unix::ffi::OsStringExt;
+
+        let procs = CString::new(self.path().join("cgroup.procs").into_os_string().into_vec())?;
+        // SAFETY: the hook runs in the forked child and only calls getpid,
+        // open, write and close, all async-signal-safe; it allocates
+        // nothing.
+        unsafe {
+            cmd.pre_exec(move || join_cgroup(&procs));
+        }
+        Ok(())
+    }
+
+    /// SIGKILL every process in the cgroup.
+    ///
+    /// Uses `cgroup.kill` (Linux 5.14+) when present and falls back to
+    /// signalling each PID listed in `cgroup.procs`.
+    pub(crate) async fn kill(&self) {
+        match write_existing(&self.path().join("cgroup.kill"), "1").await {
+            Ok(()) => return,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                tracing::debug!("{}: cgroup.kill failed: {}", self.name, e);
+            }
+        }
+        self.kill_listed_procs().await;
+    }
+
+    async fn kill_listed_procs(&self) {
+        let procs = match tokio::fs::read_to_string(self.path().join("cgroup.procs")).await {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::debug!("{}: reading cgroup.procs: {}", self.name, e);
+                return;
+            }
+        };
+        #[cfg(unix)]
+        for pid in procs.lines().filter_map(|l| l.trim().parse::<i32>().ok()) {
+            if let Err(e) = signal::kill(Pid::from_raw(pid), Signal::SIGKILL) {
+                tracing::debug!("{}: SIGKILL pid {}: {}", self.name, pid, e);
+            }
+        }
+    }
+
+    /// Kill any leftovers and remove the mirror's cgroup. Best-effort: the
+    /// next `prepare` recreates the directory if removal failed.
+    pub(crate) async fn release(&self) {
+        self.kill().await;
+        if let Err(e) = tokio::fs::remove_dir(self.path()).await {
+            tracing::debug!(
+                "{}: removing cgroup {}: {}",
+                self.name,
+                self.path().display(),
+                e
+            );
+        }
+    }
+}
+
+/// Write the calling process's pid to `procs`. Runs between fork and exec,
+/// so the pid is formatted into a stack buffer rather than a `String`.
+#[cfg(unix)]
+fn join_cgroup(procs: &CStr) -> io::Result<()> {
+    use nix::fcntl::{OFlag, open};
+    use nix::sys::stat::Mode;
+
+    let mut pid = nix::unistd::getpid().as_raw().unsigned_abs();
+    let mut buf = [0u8; 11];
+    let mut start = buf.len() - 1;
+    buf[start] = b'\n';
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (pid % 10) as u8;
+        pid /= 10;
+        if pid == 0 {
+            break;
+        }
+    }
+    // Created when missing only for the fake roots tests use; cgroupfs
+    // always has the file.
+    let fd = open(
+        procs,
+        OFlag::O_WRONLY | OFlag::O_APPEND | OFlag::O_CREAT | OFlag::O_CLOEXEC,
+        Mode::from_bits_truncate(0o644),
+    )?;
+    nix::unistd::write(&fd, &buf[start..])?;
+    Ok(())
+}
+
+async fn write_control(dir: &Path, file: &str, value: &str) -> io::Result<()> {
+    let path = dir.join(file);
+    tokio::fs::write(&path, value)
+        .await
+        .map_err(|e| io::Error::new(e.kind(), format!("writing {}: {}", path.display(), e)))
+}
+
+/// Write to a control file without creating it, so an absent interface
+/// file is distinguishable from a failed write.
+async fn write_existing(path: &Path, value: &str) -> io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut f = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    f.write_all(value.as_bytes()).await
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fake_root() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("cgroup.controllers"), "cpu memory pids\n").unwrap();
+        dir
+    }
+
+    fn make_cgroup(root: &Path) -> CgroupConfig {
+        CgroupConfig {
+            base_path: root.to_path_buf(),
+            group: "hustsync".into(),
+            name: "debian".into(),
+            memory_max: Some(512 << 20),
+            cpu_weight: Some(50),
+        }
+    }
+
+    #[tokio::test]
+    async fn prepare_writes_limits_and_delegates_controllers() {
+        let root = fake_root();
+        let cg = make_cgroup(root.path());
+        cg.prepare().await.unwrap();
+
+        let leaf = root.path().join("hustsync/debian");
+        assert_eq!(cg.path(), leaf);
+        assert_eq!(
+            std::fs::read_to_string(leaf.join("memory.max")).unwrap(),
+            "536870912"
+        );
+        assert_eq!(
+            std::fs::read_to_string(leaf.join("cpu.weight")).unwrap(),
+            "50"
+        );
+        assert!(root.path().join("hustsync/cgroup.subtree_control").exists());
+        assert!(root.path().join("cgroup.subtree_control").exists());
+    }
+
+    #[tokio::test]
+    async fn prepare_reports_missing_delegation() {
+        let root = TempDir::new().unwrap();
+        std::fs::write(root.path().join("cgroup.controllers"), "cpu pids\n").unwrap();
+        let err = make_cgroup(root.path()).prepare().await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("memory controller is not delegated"),
+            "got: {err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn prepare_rejects_non_v2_hierarchy() {
+        let root = TempDir::new().unwrap();
+        let err = make_cgroup(root.path()).prepare().await.unwrap_err();
+        assert!(err.to_string().contains("cgroup v2"), "got: {err}");
+    }
+}
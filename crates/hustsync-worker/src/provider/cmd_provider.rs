@@ -8,10 +8,12 @@ use tokio::sync::Mutex;
 
 use std::sync::atomic::{AtomicU32, Ordering};
 
+use super::docker::DockerConfig;
+use super::failover::UpstreamFailover;
 use super::{
     CommonProviderConfig, MirrorProvider, ProviderError, ProviderType, RunContext,
     impl_provider_getters, inject_provider_env, log_provider_failure, resolve_log_file,
-    run_child_with_cancellation,
+    run_child_with_cancellation, spawn_in_cgroup,
 };
 
 pub struct CmdProviderConfig {
@@ -19,10 +21,16 @@ pub struct CmdProviderConfig {
     pub command: String,
     pub fail_on_match: Option<String>,
     pub size_pattern: Option<String>,
+    /// Run the command inside a container when set.
+    pub docker: Option<DockerConfig>,
+    /// Exported in turn as `HUSTSYNC_UPSTREAM_URL` after the command exits
+    /// with a connection-class code against `common.upstream_url`.
+    pub fallback_upstreams: Vec<String>,
 }
 
 pub struct CmdProvider {
     config: CmdProviderConfig,
+    upstreams: UpstreamFailover,
     cmd_args: Vec<String>,
     fail_on_match: Option<Regex>,
     size_pattern: Option<Regex>,
@@ -55,8 +63,12 @@ impl CmdProvider {
             _ => None,
         };
 
+        let upstreams =
+            UpstreamFailover::new(&config.common.upstream_url, &config.fallback_upstreams);
+
         Ok(Self {
             config,
+            upstreams,
             cmd_args,
             fail_on_match,
             size_pattern,
@@ -64,51 +76,44 @@ impl CmdProvider {
             running_pgid: AtomicU32::new(0),
         })
     }
-}
 
-#[async_trait]
-impl MirrorProvider for CmdProvider {
-    impl_provider_getters!(CmdProvider, ProviderType::Command);
-
-    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
-        if ctx.attempt > 1 {
-            tracing::debug!(
-                "Cmd provider {} re-entering on attempt {}",
-                self.config.common.name,
-                ctx.attempt
-            );
-        }
-
-        // Prevent concurrent runs of the same provider instance
-        if self.running_pgid.load(Ordering::Acquire) != 0 {
-            return Err(ProviderError::AlreadyRunning);
-        }
-
-        {
-            let mut size_guard = self.data_size.lock().await;
-            *size_guard = None;
-        }
-
-        // Ensure directories exist
-        create_dir_all(&self.config.common.working_dir).await?;
-        create_dir_all(&self.config.common.log_dir).await?;
-
-        // Loglimit hook (or any other pre_exec hook) may redirect the log
-        // file to a rotated timestamped path; honor it via ctx.env before
-        // opening the file handle. Falls back to the config default when
-        // no hook has set it.
-        let effective_log_file = resolve_log_file(&ctx, &self.config.common.log_file);
-
-        // Setup log file
-        let log_file = File::create(&effective_log_file).await?;
-        let std_out_log = log_file.into_std().await;
-        let std_err_log = std_out_log.try_clone()?;
+    /// One command invocation with `upstream` exported, logging into
+    /// `log_file`.
+    async fn run_upstream(
+        &self,
+        upstream: &str,
+        log_file: &std::fs::File,
+        effective_log_file: &str,
+        ctx: &RunContext,
+    ) -> Result<(), ProviderError> {
+        let std_out_log = log_file.try_clone()?;
+        let std_err_log = log_file.try_clone()?;
 
         let mut cmd = Command::new(&self.cmd_args[0]);
         if self.cmd_args.len() > 1 {
             cmd.args(&self.cmd_args[1..]);
         }
 
+        inject_provider_env(
+            &mut cmd,
+            &self.config.common,
+            upstream,
+            effective_log_file,
+            &ctx.env,
+        );
+
+        let mut cmd = match &self.config.docker {
+            Some(docker) => {
+                docker.remove_stale().await;
+                docker.wrap(
+                    &cmd,
+                    &self.config.common.working_dir,
+                    &self.config.common.log_dir,
+                )
+            }
+            None => cmd,
+        };
+
         cmd.current_dir(&self.config.common.working_dir)
             .stdout(Stdio::from(std_out_log))
             .stderr(Stdio::from(std_err_log));
@@ -118,11 +123,14 @@ impl MirrorProvider for CmdProvider {
             cmd.process_group(0);
         }
 
-        inject_provider_env(&mut cmd, &self.config.common, &effective_log_file, &ctx.env);
-
-        tracing::info!("Starting command provider for {}", self.config.common.name);
+        tracing::info!(
+            "Starting command provider for {} (upstream {})",
+            self.config.common.name,
+            upstream
+        );
 
-        let mut spawned_child = cmd.spawn()?;
+        let mut spawned_child =
+            spawn_in_cgroup(&mut cmd, self.config.common.cgroup.as_ref()).await?;
 
         // Record the PID so terminate() can kill it without locks
         if let Some(pid) = spawned_child.id() {
@@ -137,6 +145,8 @@ impl MirrorProvider for CmdProvider {
             &ctx.cancel,
             &self.running_pgid,
             &self.config.common.name,
+            self.config.docker.as_ref(),
+            self.config.common.cgroup.as_ref(),
         )
         .await
         {
@@ -155,13 +165,8 @@ impl MirrorProvider for CmdProvider {
                 } else {
                     let code = status.code().unwrap_or(-1);
                     let msg = format!("Command exited with status: {}", status);
-                    log_provider_failure(
-                        "Cmd",
-                        &self.config.common.name,
-                        &msg,
-                        &effective_log_file,
-                    )
-                    .await;
+                    log_provider_failure("Cmd", &self.config.common.name, &msg, effective_log_file)
+                        .await;
                     Err(ProviderError::Execution { code, msg })
                 }
             }
@@ -170,6 +175,60 @@ impl MirrorProvider for CmdProvider {
 
         // Clear the PID
         self.running_pgid.store(0, Ordering::Release);
+        result
+    }
+}
+
+#[async_trait]
+impl MirrorProvider for CmdProvider {
+    impl_provider_getters!(CmdProvider, ProviderType::Command, failover);
+
+    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
+        if ctx.attempt > 1 {
+            tracing::debug!(
+                "Cmd provider {} re-entering on attempt {}",
+                self.config.common.name,
+                ctx.attempt
+            );
+        }
+
+        // Prevent concurrent runs of the same provider instance
+        if self.running_pgid.load(Ordering::Acquire) != 0 {
+            return Err(ProviderError::AlreadyRunning);
+        }
+
+        {
+            let mut size_guard = self.data_size.lock().await;
+            *size_guard = None;
+        }
+
+        // Ensure directories exist
+        create_dir_all(&self.config.common.working_dir).await?;
+        create_dir_all(&self.config.common.log_dir).await?;
+
+        // Loglimit hook (or any other pre_exec hook) may redirect the log
+        // file to a rotated timestamped path; honor it via ctx.env before
+        // opening the file handle. Falls back to the config default when
+        // no hook has set it.
+        let effective_log_file = resolve_log_file(&ctx, &self.config.common.log_file);
+
+        // Setup log file, shared by every upstream the run tries
+        let log_file = File::create(&effective_log_file).await?.into_std().await;
+
+        let mut upstream = self.upstreams.restart();
+        let result = loop {
+            let result = self
+                .run_upstream(upstream, &log_file, &effective_log_file, &ctx)
+                .await;
+            let Err(e) = &result else { break result };
+            match self
+                .upstreams
+                .fail_over(&self.config.common.name, e, &ctx.cancel)
+            {
+                Some(next) => upstream = next,
+                None => break result,
+            }
+        };
 
         // If execution succeeded, check logs for patterns
         if result.is_ok() {
@@ -207,13 +266,20 @@ impl MirrorProvider for CmdProvider {
     async fn terminate(&self) -> Result<(), ProviderError> {
         #[cfg(unix)]
         {
-            if self.running_pgid.load(Ordering::Acquire) != 0 {
+            let running = self.running_pgid.load(Ordering::Acquire) != 0;
+            if running {
                 tracing::warn!(
                     "Terminating command provider for {}",
                     self.config.common.name
                 );
+                if let Some(docker) = &self.config.docker {
+                    docker.stop().await;
+                }
             }
             super::terminate_pgid(&self.running_pgid, &self.config.common.name).await;
+            if running && let Some(cg) = &self.config.common.cgroup {
+                cg.kill().await;
+            }
         }
         Ok(())
     }
@@ -251,10 +317,13 @@ mod tests {
                 env: HashMap::new(),
                 is_master: true,
                 success_exit_codes: vec![],
+                cgroup: None,
             },
             command: command.to_string(),
             fail_on_match: None,
             size_pattern: None,
+            docker: None,
+            fallback_upstreams: vec![],
         };
         (CmdProvider::new(config).unwrap(), dir)
     }
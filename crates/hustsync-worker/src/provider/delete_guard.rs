@@ -0,0 +1,215 @@
+//! Mass-deletion guard for rsync-based providers.
+//!
+//! An upstream that comes back truncated (a half-mounted volume, a broken
+//! rsync module) makes `--delete` wipe the local mirror. With `max_delete`
+//! or `max_delete_percent` set, each attempt first runs rsync with
+//! `--dry-run --itemize-changes` into the run's log, counts the
+//! `*deleting` lines, and refuses the real transfer when the plan is over
+//! the limit. An operator lets one run through with
+//! `hustsynctl start --allow-mass-delete`.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use hustsync_internal::util::parse_rsync_count;
+
+use super::{ProviderError, SyncTarget};
+
+/// Flags that turn a sync argv into a plan; `--stats` supplies the
+/// upstream file count the percentage is taken against.
+const DRY_RUN_ARGS: &[&str] = &["--dry-run", "--itemize-changes", "--stats"];
+
+/// Per-mirror deletion limits; either or both may be set.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DeleteGuard {
+    max_count: Option<u64>,
+    max_percent: Option<u8>,
+}
+
+/// What a dry run would do to the local tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DeletePlan {
+    pub(crate) deleted: u64,
+    /// Files present locally before the sync, when the stats say.
+    pub(crate) local: Option<u64>,
+}
+
+impl DeletePlan {
+    fn percent(&self) -> Option<u64> {
+        match self.local? {
+            0 => Some(if self.deleted > 0 { 100 } else { 0 }),
+            local => Some(self.deleted.saturating_mul(100) / local),
+        }
+    }
+}
+
+impl DeleteGuard {
+    /// `None` when the mirror sets no limit.
+    pub(crate) fn new(max_count: Option<u64>, max_percent: Option<u8>) -> Option<Self> {
+        (max_count.is_some() || max_percent.is_some()).then_some(Self {
+            max_count,
+            max_percent,
+        })
+    }
+
+    /// `args` (ending in source and destination) turned into a dry run.
+    pub(crate) fn dry_run_args(args: &[String]) -> Vec<String> {
+        let split = args.len().saturating_sub(2);
+        let (options, paths) = args.split_at(split);
+        let mut planned = options.to_vec();
+        planned.extend(DRY_RUN_ARGS.iter().map(|s| s.to_string()));
+        planned.extend_from_slice(paths);
+        planned
+    }
+
+    /// The tree a dry run compares against: in publish mode the live
+    /// generation clients are served (staging starts empty, so rsync would
+    /// report nothing to delete there), otherwise the sync target itself.
+    pub(crate) fn dry_run_target(target: &SyncTarget) -> SyncTarget {
+        SyncTarget {
+            dir: target
+                .link_dest
+                .clone()
+                .unwrap_or_else(|| target.dir.clone()),
+            link_dest: None,
+        }
+    }
+
+    /// Refuse `plan` when it is over either limit.
+    pub(crate) fn check(&self, plan: &DeletePlan) -> Result<(), ProviderError> {
+        if let Some(max) = self.max_count
+            && plan.deleted > max
+        {
+            return Err(ProviderError::MassDeletion(format!(
+                "sync would delete {} files, over max_delete = {}",
+                plan.deleted, max
+            )));
+        }
+        if let Some(max) = self.max_percent {
+            match plan.percent() {
+                Some(percent) if percent > u64::from(max) => {
+                    return Err(ProviderError::MassDeletion(format!(
+                        "sync would delete {} files ({}% of the mirror), over \
+                         max_delete_percent = {}",
+                        plan.deleted, percent, max
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    tracing::warn!("dry run printed no file count; max_delete_percent not checked")
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the plan rsync wrote to `log_file` from byte offset `from` on.
+pub(crate) fn read_plan(log_file: &str, from: u64) -> Result<DeletePlan, ProviderError> {
+    let mut file = std::fs::File::open(log_file)?;
+    file.seek(SeekFrom::Start(from))?;
+    let mut raw = Vec::new();
+    file.read_to_end(&mut raw)?;
+    Ok(parse_plan(&String::from_utf8_lossy(&raw)))
+}
+
+/// Count `*deleting` items and estimate the local file count as
+/// upstream files, minus those the sync would create, plus those it
+/// would delete.
+fn parse_plan(output: &str) -> DeletePlan {
+    let mut deleted = 0;
+    let mut files = None;
+    let mut created = 0;
+    for line in output.lines() {
+        if line.starts_with("*deleting") {
+            deleted += 1;
+        } else if let Some(rest) = line.strip_prefix("Number of files:") {
+            files = first_count(rest);
+        } else if let Some(rest) = line.strip_prefix("Number of created files:") {
+            created = first_count(rest).unwrap_or(0);
+        }
+    }
+    DeletePlan {
+        deleted,
+        local: files.map(|f: u64| f.saturating_sub(created).saturating_add(deleted)),
+    }
+}
+
+fn first_count(rest: &str) -> Option<u64> {
+    rest.split_whitespace().next().and_then(parse_rsync_count)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = "\
+receiving incremental file list
+*deleting   pool/main/a/old.deb
+*deleting   pool/main/b/
+>f+++++++++ dists/stable/Release
+
+Number of files: 10 (reg: 8, dir: 2)
+Number of created files: 1 (reg: 1)
+Number of deleted files: 2 (reg: 1, dir: 1)
+";
+
+    #[test]
+    fn plan_counts_deletions_against_local_files() {
+        let plan = parse_plan(PLAN);
+        assert_eq!(
+            plan,
+            DeletePlan {
+                deleted: 2,
+                local: Some(11),
+            }
+        );
+        assert_eq!(plan.percent(), Some(18));
+    }
+
+    #[test]
+    fn check_applies_each_limit() {
+        let plan = parse_plan(PLAN);
+        assert!(
+            DeleteGuard::new(Some(2), None)
+                .unwrap()
+                .check(&plan)
+                .is_ok()
+        );
+        assert!(matches!(
+            DeleteGuard::new(Some(1), None).unwrap().check(&plan),
+            Err(ProviderError::MassDeletion(_))
+        ));
+        assert!(
+            DeleteGuard::new(None, Some(20))
+                .unwrap()
+                .check(&plan)
+                .is_ok()
+        );
+        assert!(matches!(
+            DeleteGuard::new(None, Some(10)).unwrap().check(&plan),
+            Err(ProviderError::MassDeletion(_))
+        ));
+        assert!(DeleteGuard::new(None, None).is_none());
+    }
+
+    #[test]
+    fn dry_run_flags_go_before_source_and_destination() {
+        let args: Vec<String> = ["-aH", "--delete", "rsync://up/x/", "/srv/x"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            DeleteGuard::dry_run_args(&args),
+            [
+                "-aH",
+                "--delete",
+                "--dry-run",
+                "--itemize-changes",
+                "--stats",
+                "rsync://up/x/",
+                "/srv/x"
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Container wrapping for provider commands.
+//!
+//! Port of Go `worker/docker.go`: when a mirror sets `docker_image` (and
+//! `[docker] enable = true`), the provider's argv is run through
+//! `<runtime> run --rm ...` instead of being spawned directly. The
+//! container gets a fixed name (`hustsync-job-<mirror>`) so it can be
+//! stopped by name when the job is terminated — killing the runtime client
+//! alone does not reliably stop the container it started.
+//!
+//! Environment variables are forwarded as bare `-e KEY` flags and set on
+//! the runtime client process, so credentials such as `RSYNC_PASSWORD`
+//! never appear in the runtime's argv.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+/// Grace period handed to `<runtime> stop -t`, matching Go's `docker stop -t 2`.
+const STOP_GRACE_SECS: u64 = 2;
+
+/// Upper bound on a single `stop` / `rm` invocation so a wedged runtime
+/// daemon cannot block `terminate()` indefinitely.
+const RUNTIME_CMD_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct DockerConfig {
+    /// Runtime binary (`docker`, `podman`, or a test stub path).
+    pub runtime: String,
+    pub image: String,
+    /// Name given to the container via `--name`.
+    pub container_name: String,
+    /// Global volumes followed by per-mirror volumes, each `src:dst[:opts]`.
+    pub volumes: Vec<String>,
+    /// Global options followed by per-mirror options, passed verbatim.
+    pub options: Vec<String>,
+    /// Passed to `--memory` when set.
+    pub memory_limit: Option<String>,
+}
+
+impl DockerConfig {
+    /// Container name used for mirror `name`.
+    pub fn container_name_for(name: &str) -> String {
+        format!("hustsync-job-{name}")
+    }
+
+    /// Build the runtime argv that runs `argv` inside the container.
+    ///
+    /// `working_dir` and `log_dir` are bind-mounted at the same path so
+    /// the wrapped command and its env vars see identical paths inside
+    /// and outside the container.
+    pub fn run_args<'a>(
+        &self,
+        argv: &[String],
+        working_dir: &str,
+        log_dir: &str,
+        env_keys: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "run",
+            "--rm",
+            "-a",
+            "STDOUT",
+            "-a",
+            "STDERR",
+            "--name",
+            &self.container_name,
+            "-w",
+            working_dir,
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        #[cfg(unix)]
+        {
+            let uid = nix::unistd::getuid();
+            let gid = nix::unistd::getgid();
+            args.push("-u".to_string());
+            args.push(format!("{uid}:{gid}"));
+        }
+
+        for vol in &self.volumes {
+            args.push("-v".to_string());
+            args.push(vol.clone());
+        }
+        args.push("-v".to_string());
+        args.push(format!("{log_dir}:{log_dir}"));
+        args.push("-v".to_string());
+        args.push(format!("{working_dir}:{working_dir}"));
+
+        let mut keys: Vec<&str> = env_keys.into_iter().collect();
+        keys.sort_unstable();
+        for key in keys {
+            args.push("-e".to_string());
+            args.push(key.to_string());
+        }
+
+        if let Some(limit) = &self.memory_limit {
+            args.push("--memory".to_string());
+            args.push(limit.clone());
+        }
+
+        args.extend(self.options.iter().cloned());
+        args.push(self.image.clone());
+        args.extend(argv.iter().cloned());
+        args
+    }
+
+    /// Turn a fully prepared provider `Command` (program, args, env) into
+    /// the equivalent container run. Stdio, working directory and process
+    /// group must be configured on the returned command by the caller.
+    pub(crate) fn wrap(&self, inner: &Command, working_dir: &str, log_dir: &str) -> Command {
+        let std_inner = inner.as_std();
+        let mut argv = vec![std_inner.get_program().to_string_lossy().into_owned()];
+        argv.extend(
+            std_inner
+                .get_args()
+                .map(|a| a.to_string_lossy().into_owned()),
+        );
+
+        let envs: Vec<(String, String)> = std_inner
+            .get_envs()
+            .filter_map(|(k, v)| {
+                Some((
+                    k.to_string_lossy().into_owned(),
+                    v?.to_string_lossy().into_owned(),
+                ))
+            })
+            .collect();
+
+        let mut cmd = Command::new(&self.runtime);
+        cmd.args(self.run_args(
+            &argv,
+            working_dir,
+            log_dir,
+            envs.iter().map(|(k, _)| k.as_str()),
+        ));
+        cmd.envs(envs);
+        cmd
+    }
+
+    /// Remove a leftover container with our name, e.g. after a worker
+    /// crash. A stale container would make the next `run --name` fail.
+    pub(crate) async fn remove_stale(&self) {
+        self.runtime_cmd(&["rm", "-f", &self.container_name]).await;
+    }
+
+    /// Stop the container, giving it `STOP_GRACE_SECS` before the runtime
+    /// kills it. Best-effort: a container that already exited is not an
+    /// error.
+    pub(crate) async fn stop(&self) {
+        let grace = STOP_GRACE_SECS.to_string();
+        self.runtime_cmd(&["stop", "-t", &grace, &self.container_name])
+            .await;
+    }
+
+    async fn runtime_cmd(&self, args: &[&str]) {
+        let mut cmd = Command::new(&self.runtime);
+        cmd.args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        match tokio::time::timeout(RUNTIME_CMD_TIMEOUT, cmd.status()).await {
+            Ok(Ok(status)) if !status.success() => {
+                tracing::debug!(
+                    "{} {} exited with {} (container likely gone)",
+                    self.runtime,
+                    args.join(" "),
+                    status
+                );
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                tracing::warn!("failed to run {} {}: {}", self.runtime, args.join(" "), e);
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "{} {} did not finish within {:?}",
+                    self.runtime,
+                    args.join(" "),
+                    RUNTIME_CMD_TIMEOUT
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+mod tests {
+    use super::*;
+
+    fn make_docker() -> DockerConfig {
+        DockerConfig {
+            runtime: "docker".to_string(),
+            image: "alpine:3".to_string(),
+            container_name: DockerConfig::container_name_for("alpine"),
+            volumes: vec!["/etc/ssl:/etc/ssl:ro".to_string()],
+            options: vec!["--network=host".to_string()],
+            memory_limit: Some("512M".to_string()),
+        }
+    }
+
+    #[test]
+    fn run_args_place_image_between_options_and_command() {
+        let docker = make_docker();
+        let args = docker.run_args(
+            &["rsync".to_string(), "-a".to_string()],
+            "/srv/alpine",
+            "/var/log/alpine",
+            ["RSYNC_PASSWORD"],
+        );
+
+        assert_eq!(&args[..2], &["run", "--rm"]);
+        let name_idx = args.iter().position(|a| a == "--name").unwrap();
+        assert_eq!(args[name_idx + 1], "hustsync-job-alpine");
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "-v" && w[1] == "/srv/alpine:/srv/alpine")
+        );
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "-v" && w[1] == "/etc/ssl:/etc/ssl:ro")
+        );
+        // Env is forwarded by key only; the value stays out of argv.
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "-e" && w[1] == "RSYNC_PASSWORD")
+        );
+        assert!(
+            args.windows(2)
+                .any(|w| w[0] == "--memory" && w[1] == "512M")
+        );
+
+        let image_idx = args.iter().position(|a| a == "alpine:3").unwrap();
+        assert_eq!(args[image_idx - 1], "--network=host");
+        assert_eq!(&args[image_idx + 1..], &["rsync", "-a"]);
+    }
+
+    #[test]
+    fn wrap_forwards_inner_program_args_and_env() {
+        let docker = make_docker();
+        let mut inner = Command::new("sh");
+        inner.arg("-c").arg("echo hi").env("FOO", "bar");
+
+        let wrapped = docker.wrap(&inner, "/srv/alpine", "/var/log/alpine");
+        let std_cmd = wrapped.as_std();
+        assert_eq!(std_cmd.get_program(), "docker");
+
+        let args: Vec<String> = std_cmd
+            .get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(&args[args.len() - 3..], &["sh", "-c", "echo hi"]);
+        assert!(args.windows(2).any(|w| w[0] == "-e" && w[1] == "FOO"));
+        assert!(!args.iter().any(|a| a.contains("bar")));
+
+        let envs: Vec<_> = std_cmd.get_envs().collect();
+        assert!(
+            envs.iter()
+                .any(|(k, v)| *k == "FOO" && *v == Some("bar".as_ref()))
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Ordered upstream failover for process-based providers.
+//!
+//! A mirror may list fallback upstreams behind its primary one. Each run
+//! starts at the primary and moves down the list only when an attempt
+//! fails in a way that points at the upstream (unreachable daemon, dropped
+//! socket, I/O timeout); any other failure ends the run as before. The
+//! upstream of the last attempt stays active so status reports name the
+//! one that actually served the sync.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tokio_util::sync::CancellationToken;
+
+use super::ProviderError;
+
+/// rsync exit codes that mean the upstream could not be talked to:
+/// 5 (error starting client-server protocol), 10 (socket I/O),
+/// 12 (protocol data stream), 30 (timeout in data send/receive) and
+/// 35 (timeout waiting for daemon connection).
+pub(crate) const CONNECTION_EXIT_CODES: &[i32] = &[5, 10, 12, 30, 35];
+
+/// Whether `err` is a connection-class failure worth retrying elsewhere.
+pub(crate) fn is_connection_failure(err: &ProviderError) -> bool {
+    matches!(err, ProviderError::Execution { code, .. } if CONNECTION_EXIT_CODES.contains(code))
+}
+
+/// The primary upstream followed by its fallbacks, plus the index of the
+/// one in use.
+pub(crate) struct UpstreamFailover {
+    urls: Vec<String>,
+    active: AtomicUsize,
+}
+
+impl UpstreamFailover {
+    pub(crate) fn new(primary: &str, fallbacks: &[String]) -> Self {
+        let mut urls = Vec::with_capacity(fallbacks.len() + 1);
+        urls.push(primary.to_string());
+        urls.extend(fallbacks.iter().cloned());
+        Self {
+            urls,
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Upstream of the current (or most recent) attempt.
+    pub(crate) fn active(&self) -> &str {
+        let idx = self.active.load(Ordering::Acquire);
+        self.urls.get(idx).map_or("", String::as_str)
+    }
+
+    /// Make the primary upstream active for a new run and return it.
+    pub(crate) fn restart(&self) -> &str {
+        self.active.store(0, Ordering::Release);
+        self.active()
+    }
+
+    /// Decide whether a failed attempt moves on to the next upstream.
+    ///
+    /// Returns the newly active upstream when `err` is connection-class,
+    /// the run was not cancelled and a fallback is left; `None` means the
+    /// failure is final.
+    pub(crate) fn fail_over(
+        &self,
+        name: &str,
+        err: &ProviderError,
+        cancel: &CancellationToken,
+    ) -> Option<&str> {
+        let idx = self.active.load(Ordering::Acquire);
+        let next = self.urls.get(idx + 1)?;
+        if cancel.is_cancelled() || !is_connection_failure(err) {
+            return None;
+        }
+        tracing::warn!(
+            "{}: upstream {} unreachable ({}), trying {}",
+            name,
+            self.urls[idx],
+            err,
+            next
+        );
+        self.active.store(idx + 1, Ordering::Release);
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn exec_err(code: i32) -> ProviderError {
+        ProviderError::Execution {
+            code,
+            msg: format!("exit {code}"),
+        }
+    }
+
+    fn failover() -> UpstreamFailover {
+        UpstreamFailover::new(
+            "rsync://a.example/m/",
+            &[
+                "rsync://b.example/m/".to_string(),
+                "rsync://c.example/m/".to_string(),
+            ],
+        )
+    }
+
+    #[test]
+    fn walks_fallbacks_on_connection_failures_only() {
+        let f = failover();
+        let cancel = CancellationToken::new();
+        assert_eq!(f.restart(), "rsync://a.example/m/");
+
+        assert_eq!(f.fail_over("m", &exec_err(23), &cancel), None);
+        assert_eq!(f.active(), "rsync://a.example/m/");
+
+        assert_eq!(
+            f.fail_over("m", &exec_err(10), &cancel),
+            Some("rsync://b.example/m/")
+        );
+        assert_eq!(
+            f.fail_over("m", &exec_err(35), &cancel),
+            Some("rsync://c.example/m/")
+        );
+        assert_eq!(f.fail_over("m", &exec_err(5), &cancel), None);
+        assert_eq!(f.active(), "rsync://c.example/m/");
+
+        assert_eq!(f.restart(), "rsync://a.example/m/");
+    }
+
+    #[test]
+    fn cancellation_and_timeouts_are_final() {
+        let f = failover();
+        let cancel = CancellationToken::new();
+        let timeout = ProviderError::Timeout(std::time::Duration::from_secs(1));
+        assert_eq!(f.fail_over("m", &timeout, &cancel), None);
+
+        cancel.cancel();
+        assert_eq!(f.fail_over("m", &exec_err(10), &cancel), None);
+        assert_eq!(f.active(), "rsync://a.example/m/");
+    }
+}
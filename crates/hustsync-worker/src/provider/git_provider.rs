@@ -0,0 +1,470 @@
+//! Git provider: keep bare mirrors of one or more repositories.
+//!
+//! A repository that is not there yet is cloned with `git clone --mirror`;
+//! an existing one is refreshed with `git remote update --prune`, so refs
+//! deleted upstream go away locally too. Without `git_repos` the mirror's
+//! `upstream` is the single repository and the working dir is its bare
+//! mirror; with `git_repos` each entry gets its own `<name>.git` directory
+//! under the working dir. A clone goes to a `<dest>.partial` sibling and is
+//! moved into place once it succeeds, so an interrupted clone leaves
+//! nothing in the mirror; the next clone starts by removing that sibling.
+//! The single-upstream working dir may already exist and hold files of the
+//! operator's: they are kept, and the clone is moved in beside them.
+//!
+//! Repositories are updated one after another, each git process in its own
+//! process group so `terminate()` stops the current one the same way
+//! `CmdProvider` does. One failing repository does not stop the others;
+//! the run fails at the end, naming every repository that did.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::fs::{File, create_dir_all};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use hustsync_config_parser::is_absolute_git_url;
+use hustsync_internal::util::format_size;
+
+use super::{
+    CommonProviderConfig, MirrorProvider, ProviderError, ProviderType, RunContext, RunSlot,
+    disk_usage, impl_provider_getters, inject_provider_env, log_provider_failure, resolve_log_file,
+    run_child_with_cancellation, spawn_in_cgroup,
+};
+
+pub struct GitProviderConfig {
+    pub common: CommonProviderConfig,
+    /// `git` binary (or a test stub).
+    pub command: String,
+    /// Repositories to mirror; empty means `upstream` itself.
+    pub repos: Vec<String>,
+}
+
+/// One repository and the bare mirror it is kept in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRepo {
+    pub url: String,
+    pub dest: PathBuf,
+}
+
+pub struct GitProvider {
+    config: GitProviderConfig,
+    repos: Vec<GitRepo>,
+    data_size: Mutex<Option<String>>,
+    running_pgid: AtomicU32,
+    // Stops the repository loop on `terminate()`.
+    run_slot: RunSlot,
+}
+
+impl GitProvider {
+    pub fn new(mut config: GitProviderConfig) -> Result<Self, ProviderError> {
+        if config.common.retry == 0 {
+            config.common.retry = 2;
+        }
+        if config.command.is_empty() {
+            config.command = "git".to_string();
+        }
+        let repos = resolve_repos(
+            &config.common.upstream_url,
+            &config.repos,
+            Path::new(&config.common.working_dir),
+        )?;
+
+        Ok(Self {
+            config,
+            repos,
+            data_size: Mutex::new(None),
+            running_pgid: AtomicU32::new(0),
+            run_slot: RunSlot::default(),
+        })
+    }
+
+    pub fn repos(&self) -> &[GitRepo] {
+        &self.repos
+    }
+
+    fn git_args(repo: &GitRepo) -> Vec<String> {
+        if is_bare_repo(&repo.dest) {
+            vec!["remote".into(), "update".into(), "--prune".into()]
+        } else {
+            vec![
+                "clone".into(),
+                "--mirror".into(),
+                repo.url.clone(),
+                partial_path(&repo.dest).to_string_lossy().into_owned(),
+            ]
+        }
+    }
+
+    /// Run git for one repository with whatever is left of the time budget.
+    async fn sync_repo(
+        &self,
+        repo: &GitRepo,
+        log_file: &mut File,
+        effective_log_file: &str,
+        ctx: &RunContext,
+        cancel: &CancellationToken,
+        deadline: Option<Instant>,
+    ) -> Result<std::process::ExitStatus, ProviderError> {
+        let remaining = match deadline {
+            Some(d) => {
+                let left = d.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Err(ProviderError::Timeout(self.config.common.timeout));
+                }
+                left
+            }
+            None => Duration::ZERO,
+        };
+
+        let args = Self::git_args(repo);
+        let partial = partial_path(&repo.dest);
+        let cloning = args[0] == "clone";
+        if cloning && remove_partial_clone(&partial).await? {
+            tracing::warn!(
+                "{}: removed an incomplete clone in {}",
+                self.config.common.name,
+                partial.display()
+            );
+            log_file
+                .write_all(
+                    format!("==> removed incomplete clone in {}\n", partial.display()).as_bytes(),
+                )
+                .await?;
+        }
+        log_file
+            .write_all(format!("==> {} {}\n", self.config.command, args.join(" ")).as_bytes())
+            .await?;
+        log_file.flush().await?;
+
+        let workdir = if args[0] == "clone" {
+            Path::new(&self.config.common.working_dir)
+        } else {
+            repo.dest.as_path()
+        };
+        let mut cmd = Command::new(&self.config.command);
+        cmd.args(&args)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .current_dir(workdir)
+            .stdout(Stdio::from(log_file.try_clone().await?.into_std().await))
+            .stderr(Stdio::from(log_file.try_clone().await?.into_std().await));
+        inject_provider_env(
+            &mut cmd,
+            &self.config.common,
+            &self.config.common.upstream_url,
+            effective_log_file,
+            &ctx.env,
+        );
+
+        #[cfg(unix)]
+        {
+            cmd.process_group(0);
+        }
+
+        let mut child = spawn_in_cgroup(&mut cmd, self.config.common.cgroup.as_ref()).await?;
+        if let Some(pid) = child.id() {
+            self.running_pgid.store(pid, Ordering::Release);
+        }
+        let result = run_child_with_cancellation(
+            &mut child,
+            remaining,
+            cancel,
+            &self.running_pgid,
+            &self.config.common.name,
+            None,
+            self.config.common.cgroup.as_ref(),
+        )
+        .await;
+        self.running_pgid.store(0, Ordering::Release);
+
+        match result {
+            Ok(status) if cloning && status.success() => {
+                publish_clone(&partial, &repo.dest).await?;
+                Ok(status)
+            }
+            Err(ProviderError::Timeout(_)) => {
+                Err(ProviderError::Timeout(self.config.common.timeout))
+            }
+            other => other,
+        }
+    }
+
+    async fn sync_all(
+        &self,
+        ctx: &RunContext,
+        cancel: &CancellationToken,
+    ) -> Result<(), ProviderError> {
+        create_dir_all(&self.config.common.working_dir).await?;
+        create_dir_all(&self.config.common.log_dir).await?;
+
+        let effective_log_file = resolve_log_file(ctx, &self.config.common.log_file);
+        let mut log_file = File::create(&effective_log_file).await?;
+        let timeout = self.config.common.timeout;
+        let deadline = (!timeout.is_zero()).then(|| Instant::now() + timeout);
+
+        tracing::info!(
+            "Starting git provider for {} ({} repositories)",
+            self.config.common.name,
+            self.repos.len()
+        );
+
+        let mut failed = Vec::new();
+        let mut last_code = -1;
+        for repo in &self.repos {
+            let status = self
+                .sync_repo(
+                    repo,
+                    &mut log_file,
+                    &effective_log_file,
+                    ctx,
+                    cancel,
+                    deadline,
+                )
+                .await?;
+            let accepted = status.success()
+                || status
+                    .code()
+                    .is_some_and(|c| self.config.common.success_exit_codes.contains(&c));
+            if !accepted {
+                tracing::warn!(
+                    "{}: git failed for {} ({})",
+                    self.config.common.name,
+                    repo.url,
+                    status
+                );
+                last_code = status.code().unwrap_or(-1);
+                failed.push(repo.url.as_str());
+            }
+        }
+
+        if !failed.is_empty() {
+            let msg = format!(
+                "{} of {} repositories failed: {}",
+                failed.len(),
+                self.repos.len(),
+                failed.join(", ")
+            );
+            log_provider_failure("Git", &self.config.common.name, &msg, &effective_log_file).await;
+            return Err(ProviderError::Execution {
+                code: last_code,
+                msg,
+            });
+        }
+
+        let size = disk_usage(Path::new(&self.config.common.working_dir)).await?;
+        *self.data_size.lock().await = Some(format_size(size));
+        Ok(())
+    }
+}
+
+/// A bare repository has `HEAD` and `objects/` at its top level.
+fn is_bare_repo(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir()
+}
+
+/// Where the clone of `dest` is made before it is moved into place.
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    dest.with_file_name(name)
+}
+
+/// Remove what an interrupted clone left in `partial`. Returns whether
+/// there was anything.
+async fn remove_partial_clone(partial: &Path) -> std::io::Result<bool> {
+    match tokio::fs::remove_dir_all(partial).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Move a finished clone from `partial` to `dest`. An existing `dest` (the
+/// working dir, which hooks create or mount before the run) keeps its own
+/// entries; the clone's are moved in beside them, and none is replaced.
+async fn publish_clone(partial: &Path, dest: &Path) -> std::io::Result<()> {
+    if tokio::fs::rename(partial, dest).await.is_ok() {
+        return Ok(());
+    }
+    create_dir_all(dest).await?;
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(partial).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let target = dest.join(entry.file_name());
+        if tokio::fs::symlink_metadata(&target).await.is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} is in the way of the clone in {}",
+                    target.display(),
+                    partial.display()
+                ),
+            ));
+        }
+        names.push(entry.file_name());
+    }
+    for name in names {
+        tokio::fs::rename(partial.join(&name), dest.join(&name)).await?;
+    }
+    tokio::fs::remove_dir(partial).await
+}
+
+/// Map `git_repos` entries (or the lone `upstream`) to clone URLs and
+/// mirror directories. Relative entries are joined onto `upstream`; the
+/// directory is the entry's last path component with `.git` appended when
+/// missing, the naming cgit and gitweb expect for bare repositories.
+pub fn resolve_repos(
+    upstream: &str,
+    repos: &[String],
+    working_dir: &Path,
+) -> Result<Vec<GitRepo>, ProviderError> {
+    if repos.is_empty() {
+        if upstream.is_empty() {
+            return Err(ProviderError::Config(
+                "git provider needs `upstream` or `git_repos`".into(),
+            ));
+        }
+        return Ok(vec![GitRepo {
+            url: upstream.to_string(),
+            dest: working_dir.to_path_buf(),
+        }]);
+    }
+
+    let mut names = HashSet::new();
+    let mut out = Vec::with_capacity(repos.len());
+    for entry in repos {
+        let entry = entry.trim();
+        let url = if is_absolute_git_url(entry) {
+            entry.to_string()
+        } else if upstream.ends_with('/') {
+            format!("{upstream}{}", entry.trim_start_matches('/'))
+        } else {
+            return Err(ProviderError::Config(format!(
+                "git repo `{entry}` is relative but upstream does not end with /"
+            )));
+        };
+
+        let base = entry
+            .trim_end_matches('/')
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or_default();
+        if base.is_empty() || base == "." || base == ".." {
+            return Err(ProviderError::Config(format!(
+                "cannot derive a directory name from git repo `{entry}`"
+            )));
+        }
+        let name = if base.ends_with(".git") {
+            base.to_string()
+        } else {
+            format!("{base}.git")
+        };
+        if !names.insert(name.clone()) {
+            return Err(ProviderError::Config(format!(
+                "git repos map to the same directory `{name}`"
+            )));
+        }
+        out.push(GitRepo {
+            url,
+            dest: working_dir.join(name),
+        });
+    }
+    Ok(out)
+}
+
+#[async_trait]
+impl MirrorProvider for GitProvider {
+    impl_provider_getters!(GitProvider, ProviderType::Git);
+
+    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
+        if ctx.attempt > 1 {
+            tracing::debug!(
+                "Git provider {} re-entering on attempt {}",
+                self.config.common.name,
+                ctx.attempt
+            );
+        }
+
+        let cancel = self.run_slot.begin(&ctx.cancel)?;
+        let result = self.sync_all(&ctx, &cancel).await;
+        self.run_slot.end();
+        result
+    }
+
+    async fn terminate(&self) -> Result<(), ProviderError> {
+        if self.run_slot.cancel() {
+            // The current git process group is signalled by
+            // `run_child_with_cancellation`; the remaining repos are skipped.
+            tracing::warn!("Terminating git provider for {}", self.config.common.name);
+        }
+        Ok(())
+    }
+
+    async fn data_size(&self) -> Option<String> {
+        self.data_size.lock().await.clone()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+mod tests {
+    use super::*;
+
+    fn repos(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn single_upstream_mirrors_into_working_dir() {
+        let got =
+            resolve_repos("https://git.example/linux.git", &[], Path::new("/m/linux")).unwrap();
+        assert_eq!(
+            got,
+            vec![GitRepo {
+                url: "https://git.example/linux.git".into(),
+                dest: "/m/linux".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn repo_list_resolves_urls_and_dirs() {
+        let got = resolve_repos(
+            "https://git.example/",
+            &repos(&["tools/a", "b.git", "git@host.example:team/c.git", "/srv/d/"]),
+            Path::new("/m"),
+        )
+        .unwrap();
+        let pairs: Vec<_> = got
+            .iter()
+            .map(|r| (r.url.as_str(), r.dest.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("https://git.example/tools/a", "/m/a.git"),
+                ("https://git.example/b.git", "/m/b.git"),
+                ("git@host.example:team/c.git", "/m/c.git"),
+                ("/srv/d/", "/m/d.git"),
+            ]
+        );
+    }
+
+    #[test]
+    fn colliding_directory_names_are_rejected() {
+        let err = resolve_repos(
+            "https://git.example/",
+            &repos(&["x/a.git", "y/a"]),
+            Path::new("/m"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ProviderError::Config(_)), "got {err:?}");
+    }
+}
@@ -0,0 +1,383 @@
+//! Native HTTP provider: mirror a directory tree published as autoindex
+//! listings (nginx `autoindex`, Apache `mod_autoindex`, lighttpd
+//! `dir-listing`) without shelling out to `wget`/`lftp`.
+//!
+//! Each run walks the listing from the upstream URL down, fetches every
+//! file that is new or changed, then deletes local files that vanished
+//! upstream. Change detection uses conditional requests: the `ETag` seen
+//! on the previous download (kept in `<log_dir>/.<mirror>.http-cache.json`,
+//! outside the published tree) goes out as `If-None-Match`, and the local
+//! mtime — set from `Last-Modified` on download — as `If-Modified-Since`.
+//!
+//! Downloads land in a hidden temp file next to the target and are renamed
+//! into place, so readers never see a partial file. A cancelled run can
+//! leave such a temp file behind; the next run deletes it as a file that is
+//! not upstream.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{StatusCode, Url};
+use tokio::fs::{self, File, create_dir_all};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use hustsync_internal::util::format_size;
+
+use super::transfer::{EtagCache, TMP_SUFFIX, download_atomically, prune_tree, run_cancellable};
+use super::{
+    CommonProviderConfig, MirrorProvider, ProviderError, ProviderType, RunContext, RunSlot,
+    impl_provider_getters, log_provider_failure, resolve_log_file,
+};
+
+/// `href` targets in an autoindex page. Listings are generated HTML, so a
+/// regex is enough; anything that is not a child of the listed directory
+/// is filtered out afterwards.
+const HREF_PATTERN: &str = r#"(?i)<a\s[^>]*href\s*=\s*["']([^"']+)["']"#;
+
+pub struct HttpProviderConfig {
+    pub common: CommonProviderConfig,
+    /// Regexes matched against paths relative to the upstream root
+    /// (directories with a trailing `/`). Matches are neither fetched nor
+    /// deleted locally.
+    pub exclude_patterns: Vec<String>,
+}
+
+pub struct HttpProvider {
+    config: HttpProviderConfig,
+    base: Url,
+    client: reqwest::Client,
+    href: Regex,
+    exclude: Vec<Regex>,
+    data_size: Mutex<Option<String>>,
+    // Cancels the in-process transfer on `terminate()`.
+    run_slot: RunSlot,
+}
+
+/// Per-run bookkeeping.
+struct SyncState {
+    log: File,
+    etags: EtagCache,
+    files: HashSet<String>,
+    dirs: HashSet<String>,
+    total_bytes: u64,
+    fetched: usize,
+    unchanged: usize,
+}
+
+impl SyncState {
+    async fn log(&mut self, line: &str) {
+        let _ = self.log.write_all(format!("{line}\n").as_bytes()).await;
+    }
+}
+
+/// A file or directory found in a listing, relative to the upstream root.
+enum Entry {
+    Dir(String, Url),
+    File(String, Url),
+}
+
+impl HttpProvider {
+    pub fn new(mut config: HttpProviderConfig) -> Result<Self, ProviderError> {
+        let base = Url::parse(&config.common.upstream_url)
+            .map_err(|e| ProviderError::Config(format!("invalid http upstream: {e}")))?;
+        if !matches!(base.scheme(), "http" | "https") || !base.path().ends_with('/') {
+            return Err(ProviderError::Config(
+                "http upstream URL should be http(s) and end with /".into(),
+            ));
+        }
+        if config.common.retry == 0 {
+            config.common.retry = 2;
+        }
+
+        let exclude = config
+            .exclude_patterns
+            .iter()
+            .map(|p| Regex::new(p))
+            .collect::<Result<Vec<_>, _>>()?;
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("hustsync/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| ProviderError::Config(format!("http client: {e}")))?;
+
+        Ok(Self {
+            config,
+            base,
+            client,
+            href: Regex::new(HREF_PATTERN)?,
+            exclude,
+            data_size: Mutex::new(None),
+            run_slot: RunSlot::default(),
+        })
+    }
+
+    fn is_excluded(&self, rel: &str) -> bool {
+        self.exclude.iter().any(|re| re.is_match(rel))
+    }
+
+    /// Parse one listing page into its direct children.
+    fn parse_listing(&self, dir_url: &Url, prefix: &str, body: &str) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        for cap in self.href.captures_iter(body) {
+            let href = cap[1].replace("&amp;", "&");
+            if href.starts_with('?') || href.starts_with('#') {
+                continue;
+            }
+            let Ok(mut url) = dir_url.join(&href) else {
+                continue;
+            };
+            url.set_fragment(None);
+            if url.query().is_some() || url.origin() != dir_url.origin() {
+                continue;
+            }
+            let Some(child) = url.path().strip_prefix(dir_url.path()) else {
+                continue;
+            };
+            let (raw_name, is_dir) = match child.strip_suffix('/') {
+                Some(n) => (n, true),
+                None => (child, false),
+            };
+            if raw_name.is_empty() || raw_name.contains('/') {
+                continue;
+            }
+            let Ok(name) = percent_decode_str(raw_name).decode_utf8() else {
+                continue;
+            };
+            if name == "." || name == ".." || name.contains('/') || name.ends_with(TMP_SUFFIX) {
+                continue;
+            }
+            if !seen.insert(name.to_string()) {
+                continue;
+            }
+            entries.push(if is_dir {
+                Entry::Dir(format!("{prefix}{name}/"), url)
+            } else {
+                Entry::File(format!("{prefix}{name}"), url)
+            });
+        }
+        entries
+    }
+
+    /// Walk the listing tree depth-first and fetch every file in it.
+    async fn mirror_tree(&self, state: &mut SyncState) -> Result<(), ProviderError> {
+        let mut queue = vec![(self.base.clone(), String::new())];
+        while let Some((dir_url, prefix)) = queue.pop() {
+            let resp = self
+                .client
+                .get(dir_url.clone())
+                .send()
+                .await
+                .map_err(|e| http_error(&dir_url, e))?;
+            if !resp.status().is_success() {
+                return Err(ProviderError::Http(format!(
+                    "listing {dir_url}: {}",
+                    resp.status()
+                )));
+            }
+            let body = resp.text().await.map_err(|e| http_error(&dir_url, e))?;
+
+            for entry in self.parse_listing(&dir_url, &prefix, &body) {
+                match entry {
+                    Entry::Dir(rel, url) => {
+                        if self.is_excluded(&rel) {
+                            continue;
+                        }
+                        state.dirs.insert(rel.clone());
+                        queue.push((url, rel));
+                    }
+                    Entry::File(rel, url) => {
+                        if self.is_excluded(&rel) {
+                            continue;
+                        }
+                        self.fetch_file(state, &rel, url).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_file(
+        &self,
+        state: &mut SyncState,
+        rel: &str,
+        url: Url,
+    ) -> Result<(), ProviderError> {
+        let dest = Path::new(&self.config.common.working_dir).join(rel);
+        let local = fs::metadata(&dest).await.ok().filter(|m| m.is_file());
+
+        let mut req = self.client.get(url.clone());
+        if let Some(meta) = &local {
+            if let Some(etag) = state.etags.entries.get(rel) {
+                req = req.header(IF_NONE_MATCH, etag.as_str());
+            }
+            if let Ok(mtime) = meta.modified() {
+                req = req.header(IF_MODIFIED_SINCE, http_date(mtime));
+            }
+        }
+        let resp = req.send().await.map_err(|e| http_error(&url, e))?;
+
+        if resp.status() == StatusCode::NOT_MODIFIED
+            && let Some(meta) = &local
+        {
+            state.files.insert(rel.to_string());
+            state.total_bytes += meta.len();
+            state.unchanged += 1;
+            return Ok(());
+        }
+        if !resp.status().is_success() {
+            return Err(ProviderError::Http(format!("{url}: {}", resp.status())));
+        }
+
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED)
+            .and_then(|v| DateTime::parse_from_rfc2822(&v).ok())
+            .map(SystemTime::from);
+        let written = download_atomically(resp, &dest, last_modified, None).await?;
+
+        match etag {
+            Some(tag) => state.etags.entries.insert(rel.to_string(), tag),
+            None => state.etags.entries.remove(rel),
+        };
+        state.files.insert(rel.to_string());
+        state.total_bytes += written;
+        state.fetched += 1;
+        state
+            .log(&format!("fetched {rel} ({})", format_size(written)))
+            .await;
+        Ok(())
+    }
+
+    async fn sync(&self, state: &mut SyncState) -> Result<(), ProviderError> {
+        self.mirror_tree(state).await?;
+        // Delete what vanished upstream; excluded paths are left alone.
+        let deleted = prune_tree(
+            Path::new(&self.config.common.working_dir),
+            &state.files,
+            &state.dirs,
+            |rel| self.is_excluded(rel),
+        )
+        .await?;
+        for rel in deleted {
+            state.log(&format!("deleted {rel}")).await;
+        }
+        state.etags.save(&state.files).await
+    }
+
+    async fn run_inner(
+        &self,
+        ctx: &RunContext,
+        cancel: &CancellationToken,
+    ) -> Result<(), ProviderError> {
+        create_dir_all(&self.config.common.working_dir).await?;
+        create_dir_all(&self.config.common.log_dir).await?;
+
+        let effective_log_file = resolve_log_file(ctx, &self.config.common.log_file);
+        let mut state = SyncState {
+            log: File::create(&effective_log_file).await?,
+            etags: EtagCache::load(
+                &self.config.common.log_dir,
+                &self.config.common.name,
+                "http",
+            )
+            .await,
+            files: HashSet::new(),
+            dirs: HashSet::new(),
+            total_bytes: 0,
+            fetched: 0,
+            unchanged: 0,
+        };
+
+        tracing::info!("Starting http provider for {}", self.config.common.name);
+        state.log(&format!("mirroring {}", self.base)).await;
+
+        let result = run_cancellable(
+            self.sync(&mut state),
+            self.config.common.timeout,
+            cancel,
+            &self.config.common.name,
+        )
+        .await;
+        match result {
+            Ok(()) => {
+                state
+                    .log(&format!(
+                        "done: {} fetched, {} unchanged, total size {}",
+                        state.fetched,
+                        state.unchanged,
+                        format_size(state.total_bytes)
+                    ))
+                    .await;
+                *self.data_size.lock().await = Some(format_size(state.total_bytes));
+                Ok(())
+            }
+            Err(e) => {
+                state.log(&format!("error: {e}")).await;
+                let _ = state.log.flush().await;
+                log_provider_failure(
+                    "Http",
+                    &self.config.common.name,
+                    &e.to_string(),
+                    &effective_log_file,
+                )
+                .await;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn http_date(t: SystemTime) -> String {
+    DateTime::<Utc>::from(t)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn http_error(url: &Url, e: reqwest::Error) -> ProviderError {
+    ProviderError::Http(format!("{url}: {e}"))
+}
+
+#[async_trait]
+impl MirrorProvider for HttpProvider {
+    impl_provider_getters!(HttpProvider, ProviderType::Http);
+
+    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
+        if ctx.attempt > 1 {
+            tracing::debug!(
+                "Http provider {} re-entering on attempt {}",
+                self.config.common.name,
+                ctx.attempt
+            );
+        }
+
+        let cancel = self.run_slot.begin(&ctx.cancel)?;
+        let result = self.run_inner(&ctx, &cancel).await;
+        self.run_slot.end();
+        result
+    }
+
+    async fn terminate(&self) -> Result<(), ProviderError> {
+        if self.run_slot.cancel() {
+            tracing::warn!("Terminating http provider for {}", self.config.common.name);
+        }
+        Ok(())
+    }
+
+    async fn data_size(&self) -> Option<String> {
+        self.data_size.lock().await.clone()
+    }
+}
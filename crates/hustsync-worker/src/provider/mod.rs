@@ -1,8 +1,9 @@
 use async_trait::async_trait;
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
@@ -12,16 +13,39 @@ use nix::sys::signal::{self, Signal};
 #[cfg(unix)]
 use nix::unistd::Pid;
 
-use hustsync_config_parser::{MirrorConfig, WorkerConfig};
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, parse_memory_limit};
+use hustsync_internal::msg::TransferStats;
 use hustsync_internal::util::{expand_tilde, format_path};
 
+use crate::bandwidth::BandwidthBudget;
+use crate::secret::{self, SecretError};
+
+use self::apt_provider::{AptProvider, AptProviderConfig};
+use self::cgroup::CgroupConfig;
 use self::cmd_provider::{CmdProvider, CmdProviderConfig};
+use self::docker::DockerConfig;
+use self::git_provider::{GitProvider, GitProviderConfig};
+use self::http_provider::{HttpProvider, HttpProviderConfig};
+use self::progress::ProgressReporter;
 use self::rsync_provider::{RsyncProvider, RsyncProviderConfig};
+use self::s3_provider::{S3Provider, S3ProviderConfig};
 use self::two_stage_rsync_provider::{TwoStageRsyncProvider, TwoStageRsyncProviderConfig};
+use self::yum_provider::{YumProvider, YumProviderConfig};
 
+pub mod apt_provider;
+pub mod cgroup;
 pub mod cmd_provider;
+pub(crate) mod delete_guard;
+pub mod docker;
+pub(crate) mod failover;
+pub mod git_provider;
+pub mod http_provider;
+pub mod progress;
 pub mod rsync_provider;
+pub mod s3_provider;
+pub(crate) mod transfer;
 pub mod two_stage_rsync_provider;
+pub mod yum_provider;
 
 /// Base rsync arguments shared by both the standard rsync provider (full
 /// sync) and the two-stage provider's stage-2 pass.
@@ -77,6 +101,8 @@ pub struct CommonProviderConfig {
     /// Rsync-specific codes are only included for rsync/two-stage-rsync
     /// providers.
     pub success_exit_codes: Vec<i32>,
+    /// Confine spawned processes to a per-mirror cgroup when set.
+    pub cgroup: Option<CgroupConfig>,
 }
 
 /// Read the last `max_lines` non-empty lines from a log file.
@@ -193,7 +219,9 @@ fn apply_env_layer(
 }
 
 /// Inject the standard HUSTSYNC_* environment variables into `cmd`, plus
-/// TUNASYNC_* legacy aliases with the same values.
+/// TUNASYNC_* legacy aliases with the same values. `upstream` is the URL the
+/// current attempt targets, which differs from the configured one once a
+/// provider has failed over.
 ///
 /// Called after any provider-specific credentials (USER, RSYNC_PASSWORD) have
 /// already been set, so those are not overwritten here. Hook-injected variables
@@ -203,6 +231,7 @@ fn apply_env_layer(
 pub(crate) fn inject_provider_env(
     cmd: &mut tokio::process::Command,
     common: &CommonProviderConfig,
+    upstream: &str,
     effective_log_file: &str,
     ctx_env: &HashMap<String, String>,
 ) {
@@ -210,7 +239,7 @@ pub(crate) fn inject_provider_env(
     for (suffix, value) in [
         ("MIRROR_NAME", common.name.clone()),
         ("WORKING_DIR", common.working_dir.clone()),
-        ("UPSTREAM_URL", common.upstream_url.clone()),
+        ("UPSTREAM_URL", upstream.to_string()),
         ("LOG_DIR", common.log_dir.clone()),
         ("LOG_FILE", effective_log_file.to_string()),
     ] {
@@ -225,6 +254,22 @@ pub(crate) fn inject_provider_env(
     }
 }
 
+/// Look up a hook-injected `HUSTSYNC_<suffix>` / `TUNASYNC_<suffix>` value
+/// in `ctx.env`; HUSTSYNC_* wins when both are present and differ.
+fn hook_env_value<'a>(ctx: &'a RunContext, suffix: &str) -> Option<&'a String> {
+    let (canonical, legacy) = sync_env_keys(suffix);
+    match (ctx.env.get(&canonical), ctx.env.get(&legacy)) {
+        (Some(canonical_value), Some(legacy_value)) => {
+            if canonical_value != legacy_value {
+                tracing::warn!("conflicting {canonical}/{legacy}; using {canonical}");
+            }
+            Some(canonical_value)
+        }
+        (Some(value), None) | (None, Some(value)) => Some(value),
+        (None, None) => None,
+    }
+}
+
 /// Resolve the effective log file path for a sync run.
 ///
 /// A pre-exec hook (e.g. loglimit) may have rotated the log to a
@@ -232,20 +277,44 @@ pub(crate) fn inject_provider_env(
 /// key is present it takes precedence; otherwise the provider's
 /// configured default is used.
 pub(crate) fn resolve_log_file(ctx: &RunContext, default: &str) -> String {
-    match (
-        ctx.env.get("HUSTSYNC_LOG_FILE"),
-        ctx.env.get("TUNASYNC_LOG_FILE"),
-    ) {
-        (Some(canonical), Some(legacy)) => {
-            if canonical != legacy {
-                tracing::warn!(
-                    "conflicting HUSTSYNC_LOG_FILE/TUNASYNC_LOG_FILE; using HUSTSYNC_LOG_FILE"
-                );
-            }
-            canonical.clone()
+    hook_env_value(ctx, "LOG_FILE").map_or_else(|| default.to_string(), String::clone)
+}
+
+/// Hook env key naming the published tree an rsync run hard-links
+/// unchanged files from (`--link-dest`).
+pub(crate) const LINK_DEST_ENV: &str = "HUSTSYNC_LINK_DEST";
+
+/// Where an rsync run writes. The publish hook redirects a run into a
+/// staging directory through `HUSTSYNC_WORKING_DIR` in `ctx.env` and
+/// names the live tree to seed it from; otherwise the run writes into the
+/// configured working dir.
+pub(crate) struct SyncTarget {
+    pub(crate) dir: String,
+    pub(crate) link_dest: Option<String>,
+}
+
+impl SyncTarget {
+    pub(crate) fn resolve(ctx: &RunContext, working_dir: &str) -> Self {
+        Self {
+            dir: hook_env_value(ctx, "WORKING_DIR")
+                .map_or_else(|| working_dir.to_string(), String::clone),
+            link_dest: ctx.env.get(LINK_DEST_ENV).cloned(),
+        }
+    }
+
+    /// The configured working dir, for argv built outside a run.
+    pub(crate) fn working_dir(working_dir: &str) -> Self {
+        Self {
+            dir: working_dir.to_string(),
+            link_dest: None,
         }
-        (Some(value), None) | (None, Some(value)) => value.clone(),
-        (None, None) => default.to_string(),
+    }
+
+    /// `--link-dest` for the live tree, if the run is seeded from one.
+    pub(crate) fn link_dest_arg(&self) -> Option<String> {
+        self.link_dest
+            .as_ref()
+            .map(|live| format!("--link-dest={live}"))
     }
 }
 
@@ -262,6 +331,93 @@ pub(crate) async fn store_rsync_data_size(data_size: &Mutex<Option<String>>, log
     }
 }
 
+/// Current length of `log_file`, i.e. where the next child's output will
+/// start; 0 when it cannot be read.
+pub(crate) async fn log_offset(log_file: &str) -> u64 {
+    tokio::fs::metadata(log_file)
+        .await
+        .map_or(0, |meta| meta.len())
+}
+
+/// Store the `--stats` totals rsync wrote to `log_file` from byte offset
+/// `from` on, stamped with the run's wall-clock duration. A run whose log
+/// holds no stats block stores nothing.
+pub(crate) async fn store_rsync_stats(
+    stats: &Mutex<Option<TransferStats>>,
+    log_file: &str,
+    from: u64,
+    started: Instant,
+) {
+    if let Ok(Some(mut parsed)) =
+        hustsync_internal::util::extract_stats_from_rsync_log(log_file, from)
+    {
+        parsed.duration_secs = started.elapsed().as_secs();
+        *stats.lock().await = Some(parsed);
+    }
+}
+
+/// Cancellation handle for providers whose `terminate()` must stop a run
+/// that is more than one child process (a repository loop, an in-process
+/// transfer). Holds a child of the run's token while a run is active.
+#[derive(Default)]
+pub(crate) struct RunSlot(std::sync::Mutex<Option<CancellationToken>>);
+
+impl RunSlot {
+    /// Claim the slot for a new run; fails with `AlreadyRunning` if taken.
+    pub(crate) fn begin(
+        &self,
+        parent: &CancellationToken,
+    ) -> Result<CancellationToken, ProviderError> {
+        let mut slot = self
+            .0
+            .lock()
+            .map_err(|_| ProviderError::Config("provider run state poisoned".into()))?;
+        if slot.is_some() {
+            return Err(ProviderError::AlreadyRunning);
+        }
+        let token = parent.child_token();
+        *slot = Some(token.clone());
+        drop(slot);
+        Ok(token)
+    }
+
+    pub(crate) fn end(&self) {
+        if let Ok(mut slot) = self.0.lock() {
+            *slot = None;
+        }
+    }
+
+    /// Cancel the active run, if any. Returns whether one was running.
+    pub(crate) fn cancel(&self) -> bool {
+        match self.0.lock().ok().and_then(|s| s.clone()) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Total size in bytes of the regular files under `root`, for providers
+/// that report `data_size` from what is on disk. Symlinks are not followed.
+pub(crate) async fn disk_usage(root: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let meta = entry.metadata().await?;
+            if meta.is_dir() {
+                stack.push(entry.path());
+            } else if meta.is_file() {
+                total += meta.len();
+            }
+        }
+    }
+    Ok(total)
+}
+
 /// Generate the 10 boilerplate `MirrorProvider` getter methods that every
 /// provider delegates to `self.config.common`.
 ///
@@ -274,14 +430,28 @@ pub(crate) async fn store_rsync_data_size(data_size: &Mutex<Option<String>>, log
 /// as a standalone item — it only generates the listed methods so that each
 /// provider can still implement `run()`, `terminate()`, and `data_size()`
 /// manually.
+///
+/// Providers with fallback upstreams pass `failover` as a third argument;
+/// `upstream()` then reports `self.upstreams.active()` instead of the
+/// configured primary.
 macro_rules! impl_provider_getters {
     ($ty:ty, $variant:expr) => {
-        fn name(&self) -> &str {
-            &self.config.common.name
+        fn upstream(&self) -> &str {
+            &self.config.common.upstream_url
         }
 
+        impl_provider_getters!(@common $ty, $variant);
+    };
+    ($ty:ty, $variant:expr, failover) => {
         fn upstream(&self) -> &str {
-            &self.config.common.upstream_url
+            self.upstreams.active()
+        }
+
+        impl_provider_getters!(@common $ty, $variant);
+    };
+    (@common $ty:ty, $variant:expr) => {
+        fn name(&self) -> &str {
+            &self.config.common.name
         }
 
         fn provider_type(&self) -> $crate::provider::ProviderType {
@@ -320,6 +490,24 @@ macro_rules! impl_provider_getters {
 
 pub(crate) use impl_provider_getters;
 
+/// Spawn `cmd` and, when `cgroup` is set, have the new process group
+/// leader join the mirror's cgroup before it execs.
+///
+/// The cgroup is created (and its limits applied) first; a child that
+/// cannot join it is never started, rather than left running unconfined.
+pub(crate) async fn spawn_in_cgroup(
+    cmd: &mut tokio::process::Command,
+    cgroup: Option<&CgroupConfig>,
+) -> Result<tokio::process::Child, ProviderError> {
+    let Some(cg) = cgroup else {
+        return Ok(cmd.spawn()?);
+    };
+    cg.prepare().await?;
+    #[cfg(unix)]
+    cg.join_on_exec(cmd)?;
+    Ok(cmd.spawn()?)
+}
+
 /// Await a spawned child process, honoring both timeout and cancellation.
 ///
 /// Returns `Ok(ExitStatus)` on normal exit, or `Err(ProviderError)` for
@@ -330,13 +518,38 @@ pub(crate) use impl_provider_getters;
 /// Pass `Duration::ZERO` for `timeout_dur` to disable timeout (cancellation
 /// still applies). Providers that manage timeout at an outer layer (e.g.
 /// two-stage wrapping both stages in one budget) should pass `ZERO` here.
-#[allow(clippy::cognitive_complexity)]
+///
+/// When the child is a container runtime client (`container` is set), the
+/// container is stopped by name before the client's process group is
+/// signalled.
+///
+/// When the child was spawned into a `cgroup`, the cgroup is released once
+/// the child is gone, whatever the outcome, so processes that left the
+/// process group do not outlive the run.
 pub(crate) async fn run_child_with_cancellation(
     child: &mut tokio::process::Child,
     timeout_dur: Duration,
     cancel: &CancellationToken,
     pgid: &AtomicU32,
     name: &str,
+    container: Option<&DockerConfig>,
+    cgroup: Option<&CgroupConfig>,
+) -> Result<std::process::ExitStatus, ProviderError> {
+    let result = wait_child(child, timeout_dur, cancel, pgid, name, container).await;
+    if let Some(cg) = cgroup {
+        cg.release().await;
+    }
+    result
+}
+
+#[allow(clippy::cognitive_complexity)]
+async fn wait_child(
+    child: &mut tokio::process::Child,
+    timeout_dur: Duration,
+    cancel: &CancellationToken,
+    pgid: &AtomicU32,
+    name: &str,
+    container: Option<&DockerConfig>,
 ) -> Result<std::process::ExitStatus, ProviderError> {
     if timeout_dur == Duration::ZERO {
         tokio::select! {
@@ -345,6 +558,7 @@ pub(crate) async fn run_child_with_cancellation(
             }
             _ = cancel.cancelled() => {
                 tracing::warn!("Provider {} cancelled", name);
+                stop_container(container).await;
                 terminate_pgid(pgid, name).await;
                 let _ = child.wait().await;
                 Err(ProviderError::Terminated)
@@ -362,6 +576,7 @@ pub(crate) async fn run_child_with_cancellation(
             Ok(Ok(Some(status))) => Ok(status),
             Ok(Ok(None)) => {
                 tracing::warn!("Provider {} cancelled", name);
+                stop_container(container).await;
                 terminate_pgid(pgid, name).await;
                 let _ = child.wait().await;
                 Err(ProviderError::Terminated)
@@ -369,6 +584,7 @@ pub(crate) async fn run_child_with_cancellation(
             Ok(Err(e)) => Err(ProviderError::Io(e)),
             Err(_elapsed) => {
                 tracing::warn!("Timeout occurred for {}", name);
+                stop_container(container).await;
                 terminate_pgid(pgid, name).await;
                 let _ = child.wait().await;
                 Err(ProviderError::Timeout(timeout_dur))
@@ -377,6 +593,12 @@ pub(crate) async fn run_child_with_cancellation(
     }
 }
 
+async fn stop_container(container: Option<&DockerConfig>) {
+    if let Some(docker) = container {
+        docker.stop().await;
+    }
+}
+
 /// Execution context passed into every `run()` call.
 ///
 /// `cancel` carries the operator cancellation signal — providers must select on
@@ -392,11 +614,25 @@ pub(crate) async fn run_child_with_cancellation(
 /// `HUSTSYNC_LOG_FILE` from the loglimit hook). Providers layer it on
 /// top of their standard env vars so hook overrides win; TUNASYNC_* aliases
 /// are accepted as fallback but HUSTSYNC_* wins on conflict.
+///
+/// `bandwidth` is the worker-wide `[bandwidth]` budget, if any. rsync-based
+/// providers join it for the duration of a run and pass their share to
+/// rsync as `--bwlimit`; other providers ignore it.
+///
+/// `progress` relays live transfer progress to the manager. Only rsync-based
+/// providers with `rsync_progress` enabled use it.
+///
+/// `allow_mass_delete` is the operator's one-shot override of the
+/// `max_delete` / `max_delete_percent` guard; rsync-based providers skip
+/// their dry run when it is set.
 #[derive(Debug, Clone, Default)]
 pub struct RunContext {
     pub cancel: CancellationToken,
     pub attempt: u32,
     pub env: HashMap<String, String>,
+    pub bandwidth: Option<Arc<BandwidthBudget>>,
+    pub progress: Option<ProgressReporter>,
+    pub allow_mass_delete: bool,
 }
 
 #[derive(Error, Debug)]
@@ -415,6 +651,14 @@ pub enum ProviderError {
     Terminated,
     #[error("config: {0}")]
     Config(String),
+    #[error("http: {0}")]
+    Http(String),
+    #[error("checksum mismatch: {0}")]
+    Checksum(String),
+    /// The pre-sync dry run tripped the mirror's deletion limit. Final for
+    /// the run: retrying would plan the same deletions.
+    #[error("mass deletion refused: {0}; start with allow_mass_delete to let it through once")]
+    MassDeletion(String),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -422,6 +666,11 @@ pub enum ProviderType {
     Command,
     Rsync,
     TwoStageRsync,
+    Http,
+    Git,
+    S3,
+    Apt,
+    Yum,
 }
 
 #[async_trait]
@@ -462,21 +711,188 @@ pub trait MirrorProvider: Send + Sync {
     /// Data size from last sync (if known/extracted)
     async fn data_size(&self) -> Option<String>;
 
+    /// Transfer statistics of the last successful sync, for providers that
+    /// can report them.
+    async fn transfer_stats(&self) -> Option<TransferStats> {
+        None
+    }
+
     /// Is this a master mirror node?
     fn is_master(&self) -> bool;
 }
 
+/// Merge success exit code allowlists.  Order mirrors Go worker/provider.go:
+/// global generic → per-mirror generic → global rsync-specific →
+/// per-mirror rsync-specific.  Rsync-specific codes only apply to
+/// rsync/two-stage-rsync; a non-rsync mirror that sets
+/// rsync_success_exit_codes gets a warning and the codes are dropped.
+fn merge_success_exit_codes(
+    name: &str,
+    p_type: &str,
+    m_cfg: &MirrorConfig,
+    g_cfg: &WorkerConfig,
+) -> Vec<i32> {
+    let global = g_cfg.global.as_ref();
+    let mut codes: Vec<i32> = Vec::new();
+
+    if let Some(gc) = global.and_then(|g| g.dangerous_global_success_exit_codes.as_ref()) {
+        codes.extend(gc);
+    }
+    if let Some(mc) = m_cfg.success_exit_codes.as_ref() {
+        codes.extend(mc);
+    }
+
+    let is_rsync_provider = matches!(p_type, "rsync" | "two-stage-rsync");
+    if is_rsync_provider {
+        if let Some(grc) = global.and_then(|g| g.dangerous_global_rsync_success_exit_codes.as_ref())
+        {
+            codes.extend(grc);
+        }
+        if let Some(mrc) = m_cfg.rsync_success_exit_codes.as_ref() {
+            codes.extend(mrc);
+        }
+    } else if m_cfg.rsync_success_exit_codes.is_some() {
+        tracing::warn!(
+            "mirror {}: rsync_success_exit_codes is set but provider is '{}', ignoring",
+            name,
+            p_type
+        );
+    }
+
+    // Deduplicate while preserving insertion order.
+    let mut seen = std::collections::HashSet::new();
+    codes.retain(|c| seen.insert(*c));
+    codes
+}
+
+/// The directory a mirror syncs into. Hooks that manage the working dir
+/// as a storage object (ZFS dataset, btrfs subvolume) resolve it the same
+/// way the provider does.
+pub(crate) fn resolve_working_dir(
+    name: &str,
+    m_cfg: &MirrorConfig,
+    g_cfg: &WorkerConfig,
+) -> String {
+    if let Some(ref explicit) = m_cfg.mirror_dir {
+        // Per-mirror mirror_dir is set — use as-is (template-expanded).
+        format_path(explicit, name)
+    } else {
+        // No per-mirror mirror_dir: Go joins global.mirror_dir / sub_dir / name.
+        let base_raw = g_cfg
+            .global
+            .as_ref()
+            .and_then(|g| g.mirror_dir.as_deref())
+            .unwrap_or("/tmp/hustsync");
+        let base = expand_tilde(base_raw);
+        let sub = m_cfg.mirror_subdir.as_deref().unwrap_or("");
+        let mut p = std::path::PathBuf::from(base);
+        if !sub.is_empty() {
+            p.push(sub);
+        }
+        p.push(name);
+        p.to_string_lossy().into_owned()
+    }
+}
+
+/// A secret that cannot be resolved makes the mirror unbuildable. The
+/// message names the reference only.
+fn secret_error(name: &str, e: SecretError) -> ProviderError {
+    ProviderError::Config(format!("mirror {name}: {e}"))
+}
+
+/// Resolve the container wrapping for one mirror, matching Go's gate:
+/// the worker must enable `[docker]` and the mirror must name an image.
+/// Global volumes/options come first so per-mirror entries can override
+/// them (the runtime honours the last occurrence of a flag).
+fn resolve_docker(name: &str, m_cfg: &MirrorConfig, g_cfg: &WorkerConfig) -> Option<DockerConfig> {
+    let image = m_cfg.docker_image.as_deref().filter(|i| !i.is_empty())?;
+    let docker = g_cfg.docker.as_ref();
+    if !docker.and_then(|d| d.enable).unwrap_or(false) {
+        tracing::warn!(
+            "mirror {}: docker_image is set but [docker] enable is false, ignoring",
+            name
+        );
+        return None;
+    }
+
+    let runtime = docker
+        .and_then(|d| d.runtime.as_deref())
+        .filter(|r| !r.is_empty())
+        .unwrap_or("docker");
+
+    let mut volumes = docker.and_then(|d| d.volumes.clone()).unwrap_or_default();
+    volumes.extend(m_cfg.docker_volumes.iter().flatten().cloned());
+    let mut options = docker.and_then(|d| d.options.clone()).unwrap_or_default();
+    options.extend(m_cfg.docker_options.iter().flatten().cloned());
+
+    Some(DockerConfig {
+        runtime: expand_tilde(runtime),
+        image: image.to_string(),
+        container_name: DockerConfig::container_name_for(name),
+        volumes,
+        options,
+        memory_limit: m_cfg.memory_limit.clone().filter(|m| !m.is_empty()),
+    })
+}
+
+/// Resolve the per-mirror cgroup when `[cgroup] enable = true`.
+///
+/// `memory_limit` has already been checked by `validate_worker_config`; an
+/// unparsable value reaching here is logged and left unlimited.
+fn resolve_cgroup(name: &str, m_cfg: &MirrorConfig, g_cfg: &WorkerConfig) -> Option<CgroupConfig> {
+    let cgroup = g_cfg.cgroup.as_ref()?;
+    if !cgroup.enable.unwrap_or(false) {
+        return None;
+    }
+
+    let memory_max = m_cfg
+        .memory_limit
+        .as_deref()
+        .filter(|m| !m.is_empty())
+        .and_then(|m| {
+            let parsed = parse_memory_limit(m);
+            if parsed.is_none() {
+                tracing::warn!("mirror {}: invalid memory_limit `{}`, ignoring", name, m);
+            }
+            parsed
+        });
+
+    Some(CgroupConfig {
+        base_path: expand_tilde(cgroup.base_path.as_deref().unwrap_or("/sys/fs/cgroup")).into(),
+        group: cgroup
+            .group
+            .clone()
+            .unwrap_or_else(|| "hustsync".to_string()),
+        name: name.to_string(),
+        memory_max,
+        cpu_weight: m_cfg.cpu_weight,
+    })
+}
+
 /// Construct a concrete provider from a `MirrorConfig` + the worker's
 /// global config. Dispatches on the `provider` field:
 ///
 /// - `"rsync"` (default) → `RsyncProvider`
 /// - `"command"`          → `CmdProvider`
 /// - `"two-stage-rsync"`  → `TwoStageRsyncProvider`
+/// - `"http"`             → `HttpProvider`
+/// - `"git"`              → `GitProvider`
+/// - `"s3"`               → `S3Provider`
+/// - `"apt"`              → `AptProvider`
+/// - `"yum"`              → `YumProvider`
 /// - anything else        → `ProviderError::Config`
 ///
 /// All per-mirror values that inherit from globals (retry timings,
 /// log/mirror dirs) are resolved here so providers receive ready-to-use
 /// absolute paths and durations.
+///
+/// Secret references in `password`, `password_file` and `env` are read
+/// here (see [`secret`]).
+///
+/// `command` and `rsync` mirrors with a `docker_image` run inside a
+/// container (see [`docker`]); other providers ignore it with a warning.
+/// With `[cgroup] enable = true`, every other mirror runs in its own
+/// cgroup (see [`cgroup`]).
 pub fn build_provider(
     name: &str,
     m_cfg: &MirrorConfig,
@@ -513,64 +929,30 @@ pub fn build_provider(
     let log_dir = format_path(log_dir_base, name);
     let log_file = format!("{}/latest.log", log_dir.trim_end_matches('/'));
 
-    let mirror_dir = if let Some(ref explicit) = m_cfg.mirror_dir {
-        // Per-mirror mirror_dir is set — use as-is (template-expanded).
-        format_path(explicit, name)
-    } else {
-        // No per-mirror mirror_dir: Go joins global.mirror_dir / sub_dir / name.
-        let base_raw = global
-            .and_then(|g| g.mirror_dir.as_deref())
-            .unwrap_or("/tmp/hustsync");
-        let base = expand_tilde(base_raw);
-        let sub = m_cfg.mirror_subdir.as_deref().unwrap_or("");
-        let mut p = std::path::PathBuf::from(base);
-        if !sub.is_empty() {
-            p.push(sub);
-        }
-        p.push(name);
-        p.to_string_lossy().into_owned()
-    };
+    let mirror_dir = resolve_working_dir(name, m_cfg, g_cfg);
 
     let is_master = m_cfg.role.as_deref() != Some("slave");
     let p_type = m_cfg.provider.as_deref().unwrap_or("rsync");
 
-    // Merge success exit code allowlists.  Order mirrors Go worker/provider.go:
-    // global generic → per-mirror generic → global rsync-specific →
-    // per-mirror rsync-specific.  Rsync-specific codes only apply to
-    // rsync/two-stage-rsync; a non-rsync mirror that sets
-    // rsync_success_exit_codes gets a warning and the codes are dropped.
-    let success_exit_codes = {
-        let mut codes: Vec<i32> = Vec::new();
+    let success_exit_codes = merge_success_exit_codes(name, p_type, m_cfg, g_cfg);
 
-        if let Some(gc) = global.and_then(|g| g.dangerous_global_success_exit_codes.as_ref()) {
-            codes.extend(gc);
-        }
-        if let Some(mc) = m_cfg.success_exit_codes.as_ref() {
-            codes.extend(mc);
-        }
+    let docker = resolve_docker(name, m_cfg, g_cfg);
+    let docker_applies = matches!(p_type, "command" | "rsync");
+    if docker.is_some() && !docker_applies {
+        tracing::warn!(
+            "mirror {}: docker_image is set but provider is '{}', ignoring",
+            name,
+            p_type
+        );
+    }
 
-        let is_rsync_provider = matches!(p_type, "rsync" | "two-stage-rsync");
-        if is_rsync_provider {
-            if let Some(grc) =
-                global.and_then(|g| g.dangerous_global_rsync_success_exit_codes.as_ref())
-            {
-                codes.extend(grc);
-            }
-            if let Some(mrc) = m_cfg.rsync_success_exit_codes.as_ref() {
-                codes.extend(mrc);
-            }
-        } else if m_cfg.rsync_success_exit_codes.is_some() {
-            tracing::warn!(
-                "mirror {}: rsync_success_exit_codes is set but provider is '{}', ignoring",
-                name,
-                p_type
-            );
-        }
-
-        // Deduplicate while preserving insertion order.
-        let mut seen = std::collections::HashSet::new();
-        codes.retain(|c| seen.insert(*c));
-        codes
+    // A containerised job's processes live under the container runtime's
+    // cgroup, not ours; limits go through `--memory` instead (Go skips the
+    // cgroup hook for docker jobs the same way).
+    let cgroup = if docker.is_some() && docker_applies {
+        None
+    } else {
+        resolve_cgroup(name, m_cfg, g_cfg)
     };
 
     let common = CommonProviderConfig {
@@ -582,9 +964,10 @@ pub fn build_provider(
         interval: Duration::from_secs(interval as u64 * 60),
         retry,
         timeout: Duration::from_secs(timeout as u64),
-        env: m_cfg.env.clone().unwrap_or_default(),
+        env: secret::resolve_env(m_cfg).map_err(|e| secret_error(name, e))?,
         is_master,
         success_exit_codes,
+        cgroup,
     };
 
     match p_type {
@@ -593,6 +976,8 @@ pub fn build_provider(
                 command: m_cfg.command.clone().unwrap_or_default(),
                 fail_on_match: m_cfg.fail_on_match.clone(),
                 size_pattern: m_cfg.size_pattern.clone(),
+                docker,
+                fallback_upstreams: m_cfg.fallback_upstreams.clone().unwrap_or_default(),
                 common,
             };
             Ok(Box::new(CmdProvider::new(cfg)?))
@@ -601,7 +986,7 @@ pub fn build_provider(
             let cfg = RsyncProviderConfig {
                 command: m_cfg.command.clone().unwrap_or_else(|| "rsync".to_string()),
                 username: m_cfg.username.clone(),
-                password: m_cfg.password.clone(),
+                password: secret::resolve_password(m_cfg).map_err(|e| secret_error(name, e))?,
                 exclude_file: m_cfg.exclude_file.as_deref().map(expand_tilde),
                 rsync_options: m_cfg.rsync_options.clone().unwrap_or_default(),
                 global_options: global
@@ -611,8 +996,13 @@ pub fn build_provider(
                 rsync_override_only: m_cfg.rsync_override_only.unwrap_or(false),
                 rsync_no_timeout: m_cfg.rsync_no_timeout.unwrap_or(false),
                 rsync_timeout: m_cfg.rsync_timeout,
+                rsync_progress: m_cfg.rsync_progress.unwrap_or(false),
                 use_ipv6: m_cfg.use_ipv6.unwrap_or(false),
                 use_ipv4: m_cfg.use_ipv4.unwrap_or(false),
+                docker,
+                fallback_upstreams: m_cfg.fallback_upstreams.clone().unwrap_or_default(),
+                max_delete: m_cfg.max_delete,
+                max_delete_percent: m_cfg.max_delete_percent,
                 common,
             };
             Ok(Box::new(RsyncProvider::new(cfg)?))
@@ -624,18 +1014,61 @@ pub fn build_provider(
                     .stage1_profile
                     .clone()
                     .unwrap_or_else(|| "debian".to_string()),
+                stage1_profiles: g_cfg.stage1_profiles.clone().unwrap_or_default(),
                 username: m_cfg.username.clone(),
-                password: m_cfg.password.clone(),
+                password: secret::resolve_password(m_cfg).map_err(|e| secret_error(name, e))?,
                 exclude_file: m_cfg.exclude_file.as_deref().map(expand_tilde),
                 extra_options: m_cfg.rsync_options.clone().unwrap_or_default(),
                 rsync_no_timeout: m_cfg.rsync_no_timeout.unwrap_or(false),
                 rsync_timeout: m_cfg.rsync_timeout,
+                rsync_progress: m_cfg.rsync_progress.unwrap_or(false),
                 use_ipv6: m_cfg.use_ipv6.unwrap_or(false),
                 use_ipv4: m_cfg.use_ipv4.unwrap_or(false),
+                fallback_upstreams: m_cfg.fallback_upstreams.clone().unwrap_or_default(),
+                max_delete: m_cfg.max_delete,
+                max_delete_percent: m_cfg.max_delete_percent,
                 common,
             };
             Ok(Box::new(TwoStageRsyncProvider::new(cfg)?))
         }
+        "http" => {
+            let cfg = HttpProviderConfig {
+                exclude_patterns: m_cfg.exclude_patterns.clone().unwrap_or_default(),
+                common,
+            };
+            Ok(Box::new(HttpProvider::new(cfg)?))
+        }
+        "git" => {
+            let cfg = GitProviderConfig {
+                command: m_cfg.command.clone().unwrap_or_else(|| "git".to_string()),
+                repos: m_cfg.git_repos.clone().unwrap_or_default(),
+                common,
+            };
+            Ok(Box::new(GitProvider::new(cfg)?))
+        }
+        "s3" => {
+            let cfg = S3ProviderConfig {
+                delete_removed: m_cfg.delete_removed.unwrap_or(false),
+                common,
+            };
+            Ok(Box::new(S3Provider::new(cfg)?))
+        }
+        "apt" => {
+            let cfg = AptProviderConfig {
+                suites: m_cfg.apt_suites.clone().unwrap_or_default(),
+                components: m_cfg
+                    .apt_components
+                    .clone()
+                    .unwrap_or_else(|| vec!["main".to_string()]),
+                architectures: m_cfg
+                    .apt_architectures
+                    .clone()
+                    .unwrap_or_else(|| vec!["amd64".to_string()]),
+                common,
+            };
+            Ok(Box::new(AptProvider::new(cfg)?))
+        }
+        "yum" => Ok(Box::new(YumProvider::new(YumProviderConfig { common })?)),
         _ => Err(ProviderError::Config(format!(
             "unknown provider type `{p_type}`"
         ))),
@@ -0,0 +1,230 @@
+//! Live transfer progress for rsync-based providers.
+//!
+//! With `rsync_progress = true` the provider adds `--info=progress2` and
+//! reads rsync's stdout itself instead of pointing it at the log file.
+//! Progress updates are carriage-return terminated; they are parsed and
+//! relayed to the manager at most once per [`REPORT_INTERVAL`], and every
+//! other line is copied to the log unchanged.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use chrono::Utc;
+use hustsync_internal::msg::SyncProgress;
+use hustsync_internal::status::SyncStatus;
+use hustsync_internal::util::parse_rsync_count;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdout};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::JobMessage;
+
+/// rsync flag that prints one whole-transfer progress line.
+pub(crate) const PROGRESS2_ARG: &str = "--info=progress2";
+
+/// Minimum gap between two progress reports for the same run.
+pub(crate) const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sends a job's progress updates through the worker's `JobMessage` relay.
+#[derive(Debug, Clone)]
+pub struct ProgressReporter {
+    name: String,
+    is_master: bool,
+    tx: mpsc::Sender<JobMessage>,
+}
+
+impl ProgressReporter {
+    pub fn new(name: String, is_master: bool, tx: mpsc::Sender<JobMessage>) -> Self {
+        Self {
+            name,
+            is_master,
+            tx,
+        }
+    }
+
+    /// Queue `progress` for the manager. Dropped when the relay is backed
+    /// up, since a newer update follows anyway.
+    pub fn report(&self, progress: SyncProgress) {
+        let msg = JobMessage {
+            status: SyncStatus::Syncing,
+            name: self.name.clone(),
+            msg: String::new(),
+            schedule: false,
+            upstream: String::new(),
+            size: None,
+            is_master: self.is_master,
+            progress: Some(progress),
+            stats: None,
+            retry_at: None,
+            skipped: false,
+        };
+        if self.tx.try_send(msg).is_err() {
+            tracing::debug!("{}: relay busy, dropping progress update", self.name);
+        }
+    }
+}
+
+/// Parse one `--info=progress2` update, e.g.
+/// `  1,234,567  45%   12.34MB/s    0:01:23 (xfr#12, to-chk=100/2000)`.
+///
+/// The byte count may carry digit separators or, under `-h`, a decimal
+/// unit suffix (`606.46M`).
+pub(crate) fn parse_progress2(line: &str) -> Option<SyncProgress> {
+    let mut fields = line.split_whitespace();
+    let bytes = parse_rsync_count(fields.next()?)?;
+    let percent = fields.next()?.strip_suffix('%')?.parse().ok()?;
+    let rate = fields.next()?;
+    if !rate.ends_with("/s") {
+        return None;
+    }
+    Some(SyncProgress {
+        bytes,
+        percent,
+        rate: rate.to_string(),
+        updated: Utc::now(),
+    })
+}
+
+/// Where one rsync spawn's stdout goes: straight into the log, or through
+/// the progress relay when a reporter is attached.
+pub(crate) struct StdoutRoute {
+    relay: Option<(File, ProgressReporter)>,
+}
+
+impl StdoutRoute {
+    /// Route for a spawn logging into `log`, plus the `Stdio` to give rsync.
+    pub(crate) async fn new(
+        log: &File,
+        reporter: Option<&ProgressReporter>,
+    ) -> std::io::Result<(Self, Stdio)> {
+        let log = log.try_clone().await?;
+        Ok(match reporter {
+            Some(reporter) => (
+                Self {
+                    relay: Some((log, reporter.clone())),
+                },
+                Stdio::piped(),
+            ),
+            None => (Self { relay: None }, Stdio::from(log.into_std().await)),
+        })
+    }
+
+    /// Start relaying the spawned `child`'s stdout, if this route relays.
+    pub(crate) fn start(self, child: &mut Child) -> Option<JoinHandle<()>> {
+        let (log, reporter) = self.relay?;
+        let stdout = child.stdout.take()?;
+        Some(spawn_relay(stdout, log, reporter))
+    }
+}
+
+/// Wait for a relay to copy rsync's remaining output into the log.
+pub(crate) async fn finish(relay: Option<JoinHandle<()>>) {
+    if let Some(task) = relay {
+        let _ = task.await;
+    }
+}
+
+/// Start copying `stdout` into `log`, reporting progress lines instead of
+/// logging them. The task ends when rsync closes its stdout.
+fn spawn_relay(stdout: ChildStdout, log: File, reporter: ProgressReporter) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(e) = relay(stdout, log, &reporter).await {
+            tracing::warn!("{}: progress relay stopped: {}", reporter.name, e);
+        }
+    })
+}
+
+async fn relay(
+    stdout: ChildStdout,
+    mut log: File,
+    reporter: &ProgressReporter,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stdout);
+    let mut segment = Vec::new();
+    let mut last_report: Option<Instant> = None;
+    loop {
+        let chunk = reader.fill_buf().await?;
+        if chunk.is_empty() {
+            break;
+        }
+        let (used, complete) = match chunk.iter().position(|&b| b == b'\r' || b == b'\n') {
+            Some(idx) => {
+                segment.extend_from_slice(&chunk[..idx]);
+                (idx + 1, true)
+            }
+            None => {
+                segment.extend_from_slice(chunk);
+                (chunk.len(), false)
+            }
+        };
+        reader.consume(used);
+        if complete {
+            handle_segment(&segment, &mut log, reporter, &mut last_report).await?;
+            segment.clear();
+        }
+    }
+    handle_segment(&segment, &mut log, reporter, &mut last_report).await?;
+    log.flush().await
+}
+
+async fn handle_segment(
+    segment: &[u8],
+    log: &mut File,
+    reporter: &ProgressReporter,
+    last_report: &mut Option<Instant>,
+) -> std::io::Result<()> {
+    let line = String::from_utf8_lossy(segment);
+    if let Some(progress) = parse_progress2(&line) {
+        let due = last_report.is_none_or(|at| at.elapsed() >= REPORT_INTERVAL);
+        if due {
+            reporter.report(progress);
+            *last_report = Some(Instant::now());
+        }
+        return Ok(());
+    }
+    if line.trim().is_empty() {
+        return Ok(());
+    }
+    log.write_all(segment).await?;
+    log.write_all(b"\n").await
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_and_human_readable_counts() {
+        let p =
+            parse_progress2("  1,234,567  45%   12.34MB/s    0:01:23 (xfr#12, to-chk=100/2000)")
+                .unwrap();
+        assert_eq!(
+            (p.bytes, p.percent, p.rate.as_str()),
+            (1_234_567, 45, "12.34MB/s")
+        );
+
+        let p = parse_progress2("        606.46M  43%  101.04MB/s    0:00:05").unwrap();
+        assert_eq!(p.bytes, 606_460_000);
+        assert_eq!(p.percent, 43);
+
+        let p = parse_progress2("              0   0%    0.00kB/s    0:00:00").unwrap();
+        assert_eq!(p.bytes, 0);
+    }
+
+    #[test]
+    fn ignores_other_output() {
+        for line in [
+            "receiving incremental file list",
+            "pool/main/h/hello_2.10-3_amd64.deb",
+            "Number of files: 12 (reg: 10, dir: 2)",
+            "sent 1,234 bytes  received 5,678 bytes  1,382.40 bytes/sec",
+            "",
+        ] {
+            assert!(parse_progress2(line).is_none(), "{line:?}");
+        }
+    }
+}
@@ -1,17 +1,26 @@
 use std::process::Stdio;
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
 
 use async_trait::async_trait;
 use tokio::fs::{File, create_dir_all};
 use tokio::process::Command;
 use tokio::sync::Mutex;
 
+use hustsync_internal::msg::TransferStats;
 use hustsync_internal::util::translate_rsync_exit_status;
 
+use crate::bandwidth::BandwidthShare;
+
+use super::delete_guard::{self, DeleteGuard};
+use super::docker::DockerConfig;
+use super::failover::UpstreamFailover;
+use super::progress::{self, StdoutRoute};
 use super::{
-    BASE_RSYNC_ARGS, CommonProviderConfig, MirrorProvider, ProviderError, ProviderType,
-    RunContext, impl_provider_getters, inject_provider_env, log_provider_failure,
-    resolve_log_file, run_child_with_cancellation, store_rsync_data_size,
+    BASE_RSYNC_ARGS, CommonProviderConfig, MirrorProvider, ProviderError, ProviderType, RunContext,
+    SyncTarget, impl_provider_getters, inject_provider_env, log_offset, log_provider_failure,
+    resolve_log_file, run_child_with_cancellation, spawn_in_cgroup, store_rsync_data_size,
+    store_rsync_stats,
 };
 
 pub struct RsyncProviderConfig {
@@ -26,23 +35,41 @@ pub struct RsyncProviderConfig {
     pub rsync_override_only: bool,
     pub rsync_no_timeout: bool,
     pub rsync_timeout: Option<u32>,
+    /// Add `--info=progress2` and relay progress through `RunContext::progress`.
+    pub rsync_progress: bool,
     pub use_ipv6: bool,
     pub use_ipv4: bool,
+    /// Run rsync inside a container when set.
+    pub docker: Option<DockerConfig>,
+    /// Tried in order after `common.upstream_url` on connection failures.
+    pub fallback_upstreams: Vec<String>,
+    /// Refuse a sync whose dry run deletes more files than this.
+    pub max_delete: Option<u64>,
+    /// Refuse a sync whose dry run deletes more than this share (percent)
+    /// of the local files.
+    pub max_delete_percent: Option<u8>,
 }
 
 pub struct RsyncProvider {
     config: RsyncProviderConfig,
+    upstreams: UpstreamFailover,
+    delete_guard: Option<DeleteGuard>,
     data_size: Mutex<Option<String>>,
+    stats: Mutex<Option<TransferStats>>,
     run_lock: Mutex<()>,
     running_pgid: AtomicU32,
 }
 
 impl RsyncProvider {
     pub fn new(mut config: RsyncProviderConfig) -> Result<Self, ProviderError> {
-        if !config.common.upstream_url.ends_with('/') {
-            return Err(ProviderError::Config(
-                "rsync upstream URL should end with /".into(),
-            ));
+        let all_upstreams =
+            std::iter::once(&config.common.upstream_url).chain(&config.fallback_upstreams);
+        for upstream in all_upstreams {
+            if !upstream.ends_with('/') {
+                return Err(ProviderError::Config(
+                    "rsync upstream URL should end with /".into(),
+                ));
+            }
         }
         if config.rsync_override_only && config.rsync_override.is_none() {
             return Err(ProviderError::Config(
@@ -56,15 +83,37 @@ impl RsyncProvider {
             config.command = "rsync".to_string();
         }
 
+        let upstreams =
+            UpstreamFailover::new(&config.common.upstream_url, &config.fallback_upstreams);
+
+        let delete_guard = DeleteGuard::new(config.max_delete, config.max_delete_percent);
+
         Ok(Self {
             config,
+            upstreams,
+            delete_guard,
             data_size: Mutex::new(None),
+            stats: Mutex::new(None),
             run_lock: Mutex::new(()),
             running_pgid: AtomicU32::new(0),
         })
     }
 
+    /// Argv for a sync from the primary upstream.
     pub fn build_args(&self) -> Vec<String> {
+        let target = SyncTarget::working_dir(&self.config.common.working_dir);
+        self.build_args_for(&self.config.common.upstream_url, &target, None)
+    }
+
+    /// Argv for a sync from `upstream` into `target`, capped at `bwlimit`
+    /// KiB/s when the worker has a bandwidth budget. A `--bwlimit` in the
+    /// mirror's own options comes later on the command line and wins.
+    fn build_args_for(
+        &self,
+        upstream: &str,
+        target: &SyncTarget,
+        bwlimit: Option<u64>,
+    ) -> Vec<String> {
         let mut options = if let Some(overridden) = &self.config.rsync_override {
             overridden.clone()
         } else {
@@ -90,65 +139,107 @@ impl RsyncProvider {
                 options.push(exclude_file.clone());
             }
 
+            if self.config.rsync_progress {
+                options.push(progress::PROGRESS2_ARG.to_string());
+            }
+
+            if let Some(kib) = bwlimit {
+                options.push(format!("--bwlimit={}", kib));
+            }
+
             options.extend(self.config.global_options.clone());
             options.extend(self.config.rsync_options.clone());
         }
 
+        // Publish mode applies even to override-only argv: without it the
+        // staging tree would be fetched from scratch.
+        options.extend(target.link_dest_arg());
+
         let mut args = options;
-        args.push(self.config.common.upstream_url.clone());
-        args.push(self.config.common.working_dir.clone());
+        args.push(upstream.to_string());
+        args.push(target.dir.clone());
 
         args
     }
-}
 
-#[async_trait]
-impl MirrorProvider for RsyncProvider {
-    impl_provider_getters!(RsyncProvider, ProviderType::Rsync);
+    /// Argv for a spawn right now, taking the current split of `share`.
+    fn build_args_with_share(
+        &self,
+        upstream: &str,
+        target: &SyncTarget,
+        share: Option<&BandwidthShare>,
+    ) -> Vec<String> {
+        let bwlimit = share.and_then(BandwidthShare::rsync_bwlimit);
+        if let Some(kib) = bwlimit {
+            tracing::info!("{}: bandwidth share {} KiB/s", self.config.common.name, kib);
+        }
+        self.build_args_for(upstream, target, bwlimit)
+    }
 
-    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
-        if ctx.attempt > 1 {
-            tracing::debug!(
-                "Rsync provider {} re-entering on attempt {}",
-                self.config.common.name,
-                ctx.attempt
+    /// One attempt against `upstream`: the deletion dry run when the
+    /// mirror has a guard and the operator did not override it, then the
+    /// transfer. Returns the log offset the transfer's output starts at.
+    async fn sync_from(
+        &self,
+        upstream: &str,
+        target: &SyncTarget,
+        share: Option<&BandwidthShare>,
+        log_file: &File,
+        effective_log_file: &str,
+        ctx: &RunContext,
+    ) -> Result<u64, ProviderError> {
+        if let Some(guard) = self.delete_guard.filter(|_| !ctx.allow_mass_delete) {
+            let planned = DeleteGuard::dry_run_target(target);
+            let args = DeleteGuard::dry_run_args(&self.build_args_for(upstream, &planned, None));
+            let from = log_offset(effective_log_file).await;
+            tracing::info!(
+                "{}: dry run to check planned deletions",
+                self.config.common.name
             );
+            self.run_upstream(
+                upstream,
+                &planned.dir,
+                args,
+                log_file,
+                effective_log_file,
+                ctx,
+            )
+            .await?;
+            guard.check(&delete_guard::read_plan(effective_log_file, from)?)?;
         }
 
-        let _run_guard = self.run_lock.lock().await;
+        let from = log_offset(effective_log_file).await;
+        let args = self.build_args_with_share(upstream, target, share);
+        self.run_upstream(
+            upstream,
+            &target.dir,
+            args,
+            log_file,
+            effective_log_file,
+            ctx,
+        )
+        .await?;
+        Ok(from)
+    }
 
-        if self.running_pgid.load(Ordering::Acquire) != 0 {
-            return Err(ProviderError::AlreadyRunning);
-        }
+    /// One rsync invocation of `args` against `upstream` in `dir`, logging
+    /// into `log_file`.
+    async fn run_upstream(
+        &self,
+        upstream: &str,
+        dir: &str,
+        args: Vec<String>,
+        log_file: &File,
+        effective_log_file: &str,
+        ctx: &RunContext,
+    ) -> Result<(), ProviderError> {
         self.running_pgid.store(u32::MAX, Ordering::Release);
-
-        {
-            let mut size_guard = self.data_size.lock().await;
-            *size_guard = None;
-        }
-
-        create_dir_all(&self.config.common.working_dir).await?;
-        create_dir_all(&self.config.common.log_dir).await?;
-
-        // Loglimit (or any pre_exec hook) may have rotated the log path;
-        // honor it before opening the file handle.
-        let effective_log_file = resolve_log_file(&ctx, &self.config.common.log_file);
-
-        let mut log_file = File::create(&effective_log_file).await?;
-        let std_out_log = log_file.try_clone().await?.into_std().await;
+        let reporter = ctx.progress.as_ref().filter(|_| self.config.rsync_progress);
+        let (stdout_route, std_out) = StdoutRoute::new(log_file, reporter).await?;
         let std_err_log = log_file.try_clone().await?.into_std().await;
 
         let mut cmd = Command::new(&self.config.command);
-        cmd.args(self.build_args());
-
-        cmd.current_dir(&self.config.common.working_dir)
-            .stdout(Stdio::from(std_out_log))
-            .stderr(Stdio::from(std_err_log));
-
-        #[cfg(unix)]
-        {
-            cmd.process_group(0);
-        }
+        cmd.args(args);
 
         if let Some(user) = &self.config.username {
             cmd.env("USER", user);
@@ -157,18 +248,49 @@ impl MirrorProvider for RsyncProvider {
             cmd.env("RSYNC_PASSWORD", password);
         }
 
-        inject_provider_env(&mut cmd, &self.config.common, &effective_log_file, &ctx.env);
-
-        tracing::info!("Starting rsync provider for {}", self.config.common.name);
+        inject_provider_env(
+            &mut cmd,
+            &self.config.common,
+            upstream,
+            effective_log_file,
+            &ctx.env,
+        );
 
-        let mut spawned_child = match cmd.spawn() {
-            Ok(child) => child,
-            Err(err) => {
-                self.running_pgid.store(0, Ordering::Release);
-                return Err(ProviderError::Io(err));
+        let mut cmd = match &self.config.docker {
+            Some(docker) => {
+                docker.remove_stale().await;
+                docker.wrap(
+                    &cmd,
+                    &self.config.common.working_dir,
+                    &self.config.common.log_dir,
+                )
             }
+            None => cmd,
         };
 
+        cmd.current_dir(dir)
+            .stdout(std_out)
+            .stderr(Stdio::from(std_err_log));
+
+        #[cfg(unix)]
+        {
+            cmd.process_group(0);
+        }
+
+        tracing::info!(
+            "Starting rsync provider for {} from {}",
+            self.config.common.name,
+            upstream
+        );
+        let mut spawned_child =
+            match spawn_in_cgroup(&mut cmd, self.config.common.cgroup.as_ref()).await {
+                Ok(child) => child,
+                Err(err) => {
+                    self.running_pgid.store(0, Ordering::Release);
+                    return Err(err);
+                }
+            };
+
         if let Some(pid) = spawned_child.id() {
             self.running_pgid.store(pid, Ordering::Release);
         } else {
@@ -179,69 +301,153 @@ impl MirrorProvider for RsyncProvider {
             });
         }
 
-        let result = match run_child_with_cancellation(
+        let relay = stdout_route.start(&mut spawned_child);
+        let waited = run_child_with_cancellation(
             &mut spawned_child,
             self.config.common.timeout,
             &ctx.cancel,
             &self.running_pgid,
             &self.config.common.name,
+            self.config.docker.as_ref(),
+            self.config.common.cgroup.as_ref(),
         )
-        .await
-        {
-            Ok(status) => {
-                if status.success() {
-                    Ok(())
-                } else if let Some(code) = status.code()
-                    && self.config.common.success_exit_codes.contains(&code)
-                {
-                    tracing::info!(
-                        "{} exited with code {} (in success_exit_codes allowlist)",
-                        self.config.common.name,
-                        code
-                    );
-                    Ok(())
-                } else {
-                    let (code, msg) = translate_rsync_exit_status(&status);
-                    let code = code.unwrap_or(-1);
-                    if let Some(ref m) = msg {
-                        use tokio::io::AsyncWriteExt;
-                        let _ = log_file.write_all(m.as_bytes()).await;
-                        let _ = log_file.write_all(b"\n").await;
-                    }
-                    let msg =
-                        msg.unwrap_or_else(|| format!("rsync exited with status: {}", status));
-                    log_provider_failure(
-                        "Rsync",
-                        &self.config.common.name,
-                        &msg,
-                        &effective_log_file,
-                    )
-                    .await;
-                    Err(ProviderError::Execution { code, msg })
-                }
-            }
+        .await;
+        progress::finish(relay).await;
+        let result = match waited {
+            Ok(status) => self.exit_result(status, log_file, effective_log_file).await,
             Err(e) => Err(e),
         };
 
         self.running_pgid.store(0, Ordering::Release);
+        result
+    }
+
+    /// Map rsync's exit status to the run result, noting rsync's own
+    /// explanation of a failure in the log.
+    async fn exit_result(
+        &self,
+        status: std::process::ExitStatus,
+        log_file: &File,
+        effective_log_file: &str,
+    ) -> Result<(), ProviderError> {
+        if status.success() {
+            Ok(())
+        } else if let Some(code) = status.code()
+            && self.config.common.success_exit_codes.contains(&code)
+        {
+            tracing::info!(
+                "{} exited with code {} (in success_exit_codes allowlist)",
+                self.config.common.name,
+                code
+            );
+            Ok(())
+        } else {
+            let (code, msg) = translate_rsync_exit_status(&status);
+            let code = code.unwrap_or(-1);
+            if let Some(ref m) = msg {
+                use tokio::io::AsyncWriteExt;
+                if let Ok(mut log) = log_file.try_clone().await {
+                    let _ = log.write_all(m.as_bytes()).await;
+                    let _ = log.write_all(b"\n").await;
+                    let _ = log.flush().await;
+                }
+            }
+            let msg = msg.unwrap_or_else(|| format!("rsync exited with status: {}", status));
+            log_provider_failure("Rsync", &self.config.common.name, &msg, effective_log_file).await;
+            Err(ProviderError::Execution { code, msg })
+        }
+    }
+}
+
+#[async_trait]
+impl MirrorProvider for RsyncProvider {
+    impl_provider_getters!(RsyncProvider, ProviderType::Rsync, failover);
 
-        if result.is_ok() {
-            store_rsync_data_size(&self.data_size, &effective_log_file).await;
+    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
+        if ctx.attempt > 1 {
+            tracing::debug!(
+                "Rsync provider {} re-entering on attempt {}",
+                self.config.common.name,
+                ctx.attempt
+            );
         }
 
-        result
+        let _run_guard = self.run_lock.lock().await;
+
+        if self.running_pgid.load(Ordering::Acquire) != 0 {
+            return Err(ProviderError::AlreadyRunning);
+        }
+        self.running_pgid.store(u32::MAX, Ordering::Release);
+
+        {
+            let mut size_guard = self.data_size.lock().await;
+            *size_guard = None;
+        }
+        *self.stats.lock().await = None;
+        let started = Instant::now();
+
+        let target = SyncTarget::resolve(&ctx, &self.config.common.working_dir);
+        create_dir_all(&target.dir).await?;
+        create_dir_all(&self.config.common.log_dir).await?;
+
+        // Loglimit (or any pre_exec hook) may have rotated the log path;
+        // honor it before opening the file handle.
+        let effective_log_file = resolve_log_file(&ctx, &self.config.common.log_file);
+
+        // One log per run: attempts against fallback upstreams append to it.
+        let log_file = File::create(&effective_log_file).await?;
+
+        // Held for the whole run so fallback attempts keep their place in
+        // the split. Override-only mode owns the argv, so it stays out.
+        let share = match &ctx.bandwidth {
+            Some(budget) if !self.config.rsync_override_only => Some(budget.join()),
+            _ => None,
+        };
+
+        let mut upstream = self.upstreams.restart();
+        let result = loop {
+            let result = self
+                .sync_from(
+                    upstream,
+                    &target,
+                    share.as_ref(),
+                    &log_file,
+                    &effective_log_file,
+                    &ctx,
+                )
+                .await;
+            let Err(e) = &result else { break result };
+            match self
+                .upstreams
+                .fail_over(&self.config.common.name, e, &ctx.cancel)
+            {
+                Some(next) => upstream = next,
+                None => break result,
+            }
+        };
+
+        self.running_pgid.store(0, Ordering::Release);
+
+        let from = result?;
+        store_rsync_data_size(&self.data_size, &effective_log_file).await;
+        store_rsync_stats(&self.stats, &effective_log_file, from, started).await;
+        Ok(())
     }
 
     async fn terminate(&self) -> Result<(), ProviderError> {
         #[cfg(unix)]
         {
-            if self.running_pgid.load(Ordering::Acquire) != 0 {
-                tracing::warn!(
-                    "Terminating rsync provider for {}",
-                    self.config.common.name
-                );
+            let running = self.running_pgid.load(Ordering::Acquire) != 0;
+            if running {
+                tracing::warn!("Terminating rsync provider for {}", self.config.common.name);
+                if let Some(docker) = &self.config.docker {
+                    docker.stop().await;
+                }
             }
             super::terminate_pgid(&self.running_pgid, &self.config.common.name).await;
+            if running && let Some(cg) = &self.config.common.cgroup {
+                cg.kill().await;
+            }
         }
         Ok(())
     }
@@ -249,6 +455,10 @@ impl MirrorProvider for RsyncProvider {
     async fn data_size(&self) -> Option<String> {
         self.data_size.lock().await.clone()
     }
+
+    async fn transfer_stats(&self) -> Option<TransferStats> {
+        self.stats.lock().await.clone()
+    }
 }
 
 #[cfg(test)]
@@ -322,6 +532,7 @@ mod tests {
                 env: HashMap::new(),
                 is_master: true,
                 success_exit_codes,
+                cgroup: None,
             },
             command: command.to_string(),
             username: None,
@@ -333,8 +544,13 @@ mod tests {
             rsync_override_only,
             rsync_no_timeout,
             rsync_timeout,
+            rsync_progress: false,
             use_ipv6,
             use_ipv4,
+            docker: None,
+            fallback_upstreams: vec![],
+            max_delete: None,
+            max_delete_percent: None,
         }
     }
 
@@ -0,0 +1,277 @@
+//! Plumbing shared by the providers that transfer files in-process over
+//! HTTP (`http`, `s3`, `apt`, `yum`) instead of spawning a sync tool.
+//!
+//! - downloads are streamed into a hidden temp file next to the target and
+//!   renamed into place, so readers never see a partial file;
+//! - validators from the previous run live in a small JSON cache in the
+//!   log dir, outside the published tree;
+//! - local files that are gone upstream are pruned bottom-up.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::DateTime;
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use reqwest::header::LAST_MODIFIED;
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+use tokio::fs::{self, File, create_dir_all};
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+
+use super::ProviderError;
+
+/// Suffix of in-flight downloads. Temp files are also hidden, so no
+/// upstream name can collide with one; a cancelled run may leave one
+/// behind, and the next prune removes it.
+pub(crate) const TMP_SUFFIX: &str = ".hustsync-tmp";
+
+/// Stream `resp` into `dest` via a temp file, then rename it into place
+/// with `mtime` applied. Returns the number of bytes written.
+///
+/// A body shorter or longer than `Content-Length`, or whose SHA-256 does
+/// not match `sha256` (lower-case hex) when given, is discarded.
+pub(crate) async fn download_atomically(
+    mut resp: reqwest::Response,
+    dest: &Path,
+    mtime: Option<SystemTime>,
+    sha256: Option<&str>,
+) -> Result<u64, ProviderError> {
+    let url = resp.url().clone();
+    let expected = resp.content_length();
+
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent).await?;
+    }
+    let file_name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = dest.with_file_name(format!(".{file_name}{TMP_SUFFIX}"));
+    let mut out = File::create(&tmp).await?;
+    let mut written: u64 = 0;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| ProviderError::Http(format!("{url}: {e}")))?
+    {
+        out.write_all(&chunk).await?;
+        if sha256.is_some() {
+            hasher.update(&chunk);
+        }
+        written += chunk.len() as u64;
+    }
+    out.flush().await?;
+    if let Some(len) = expected
+        && len != written
+    {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(ProviderError::Http(format!(
+            "{url}: short body ({written} of {len} bytes)"
+        )));
+    }
+    if let Some(want) = sha256 {
+        let got = hex::encode(hasher.finalize());
+        if !got.eq_ignore_ascii_case(want) {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(ProviderError::Checksum(format!(
+                "{url}: SHA256 is {got}, expected {want}"
+            )));
+        }
+    }
+    if let Some(mtime) = mtime {
+        out.into_std().await.set_modified(mtime)?;
+    }
+    fs::rename(&tmp, dest).await?;
+    Ok(written)
+}
+
+/// Characters escaped when turning a repository-relative path into a URL.
+const PATH_ESCAPE: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'%').add(b'?');
+
+/// `base` (a directory URL ending with `/`) joined with the relative
+/// `path`. Unlike `Url::join`, a `:` in the first segment is not taken
+/// for a scheme.
+pub(crate) fn join_url(base: &Url, path: &str) -> Result<Url, ProviderError> {
+    let raw = format!("{base}{}", utf8_percent_encode(path, PATH_ESCAPE));
+    Url::parse(&raw).map_err(|e| ProviderError::Http(format!("{raw}: {e}")))
+}
+
+/// Whether a path taken from upstream metadata stays inside the mirror:
+/// relative, with no empty, `.` or `..` segments.
+pub(crate) fn is_safe_relative(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with('/')
+        && path
+            .split('/')
+            .all(|seg| !seg.is_empty() && seg != "." && seg != "..")
+}
+
+/// GET `url` into `dest` via `download_atomically`, with the mtime taken
+/// from `Last-Modified`. Returns `None` when upstream answers 404; any
+/// other non-success status is an error.
+pub(crate) async fn fetch_to(
+    client: &reqwest::Client,
+    url: Url,
+    dest: &Path,
+    sha256: Option<&str>,
+) -> Result<Option<u64>, ProviderError> {
+    let resp = client
+        .get(url.clone())
+        .send()
+        .await
+        .map_err(|e| ProviderError::Http(format!("{url}: {e}")))?;
+    if resp.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        return Err(ProviderError::Http(format!("{url}: {}", resp.status())));
+    }
+    let mtime = resp
+        .headers()
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        .map(SystemTime::from);
+    download_atomically(resp, dest, mtime, sha256)
+        .await
+        .map(Some)
+}
+
+/// Whether `path` is a file whose SHA-256 is `sha256` and, when `size`
+/// is given, of that many bytes (checked first, as it is cheap).
+pub(crate) async fn has_sha256(path: &Path, size: Option<u64>, sha256: &str) -> bool {
+    match fs::metadata(path).await {
+        Ok(m) if m.is_file() && size.is_none_or(|s| s == m.len()) => {}
+        _ => return false,
+    }
+    match fs::read(path).await {
+        Ok(raw) => hex::encode(Sha256::digest(&raw)).eq_ignore_ascii_case(sha256),
+        Err(_) => false,
+    }
+}
+
+/// Decompress repository metadata by its file name: `.gz`, `.xz` and
+/// `.zst` are unpacked, anything else is returned as is.
+pub(crate) fn decompress(path: &str, raw: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    if path.ends_with(".gz") {
+        flate2::read::GzDecoder::new(raw)
+            .read_to_end(&mut out)
+            .map_err(|e| format!("gzip: {e}"))?;
+    } else if path.ends_with(".xz") {
+        lzma_rs::xz_decompress(&mut &raw[..], &mut out).map_err(|e| format!("xz: {e}"))?;
+    } else if path.ends_with(".zst") {
+        out = zstd::stream::decode_all(raw).map_err(|e| format!("zstd: {e}"))?;
+    } else {
+        out = raw.to_vec();
+    }
+    Ok(out)
+}
+
+/// Per-mirror validator cache: relative path → ETag.
+pub(crate) struct EtagCache {
+    path: PathBuf,
+    pub(crate) entries: HashMap<String, String>,
+}
+
+impl EtagCache {
+    /// Load `<log_dir>/.<mirror>.<kind>-cache.json`; a missing or corrupt
+    /// cache starts empty, which only costs full downloads.
+    pub(crate) async fn load(log_dir: &str, mirror: &str, kind: &str) -> Self {
+        let path = Path::new(log_dir).join(format!(".{mirror}.{kind}-cache.json"));
+        let entries = match fs::read(&path).await {
+            Ok(raw) => serde_json::from_slice(&raw).unwrap_or_else(|e| {
+                tracing::warn!("{}: ignoring unreadable {} cache: {}", mirror, kind, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self { path, entries }
+    }
+
+    /// Drop entries for paths not in `keep` and write the cache atomically.
+    pub(crate) async fn save(&mut self, keep: &HashSet<String>) -> Result<(), ProviderError> {
+        self.entries.retain(|rel, _| keep.contains(rel));
+        let tmp = self.path.with_extension("json.tmp");
+        let raw = serde_json::to_vec(&self.entries).map_err(|e| ProviderError::Io(e.into()))?;
+        fs::write(&tmp, raw).await?;
+        fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+}
+
+/// Delete files under `root` whose relative path is not in `keep_files`,
+/// then directories left empty unless listed in `keep_dirs` (relative,
+/// with a trailing `/`). Paths for which `skip` returns true are left
+/// alone, directories included. Returns the deleted relative paths.
+pub(crate) async fn prune_tree(
+    root: &Path,
+    keep_files: &HashSet<String>,
+    keep_dirs: &HashSet<String>,
+    skip: impl Fn(&str) -> bool,
+) -> std::io::Result<Vec<String>> {
+    let mut deleted = Vec::new();
+    let mut stack = vec![(root.to_path_buf(), String::new())];
+    let mut dirs = Vec::new();
+    while let Some((dir, prefix)) = stack.pop() {
+        let mut iter = fs::read_dir(&dir).await?;
+        while let Some(entry) = iter.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                let rel = format!("{prefix}{name}/");
+                if !skip(&rel) {
+                    dirs.push((path.clone(), rel.clone()));
+                    stack.push((path, rel));
+                }
+                continue;
+            }
+            let rel = format!("{prefix}{name}");
+            if keep_files.contains(&rel) || skip(&rel) {
+                continue;
+            }
+            fs::remove_file(&path).await?;
+            deleted.push(rel);
+        }
+    }
+
+    // Deepest first, so a parent emptied by its children goes too.
+    dirs.sort_by_key(|(_, rel)| std::cmp::Reverse(rel.len()));
+    for (path, rel) in dirs {
+        if !keep_dirs.contains(&rel) && fs::remove_dir(&path).await.is_ok() {
+            deleted.push(rel);
+        }
+    }
+    Ok(deleted)
+}
+
+/// Drive an in-process transfer under the provider's timeout and the
+/// run's cancellation token. Dropping `work` aborts any request in flight.
+pub(crate) async fn run_cancellable(
+    work: impl Future<Output = Result<(), ProviderError>>,
+    timeout_dur: Duration,
+    cancel: &CancellationToken,
+    name: &str,
+) -> Result<(), ProviderError> {
+    let bounded = async {
+        if timeout_dur.is_zero() {
+            work.await
+        } else {
+            tokio::time::timeout(timeout_dur, work)
+                .await
+                .unwrap_or(Err(ProviderError::Timeout(timeout_dur)))
+        }
+    };
+    tokio::select! {
+        res = bounded => res,
+        _ = cancel.cancelled() => {
+            tracing::warn!("Provider {} cancelled", name);
+            Err(ProviderError::Terminated)
+        }
+    }
+}
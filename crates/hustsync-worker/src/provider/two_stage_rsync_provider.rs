@@ -1,6 +1,7 @@
+use std::collections::HashMap;
 use std::process::Stdio;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use tokio::fs::{File, OpenOptions, create_dir_all};
@@ -8,12 +9,20 @@ use tokio::process::Command;
 use tokio::sync::Mutex;
 use tokio::time::timeout;
 
+use hustsync_config_parser::Stage1ProfileConfig;
+use hustsync_internal::msg::TransferStats;
 use hustsync_internal::util::translate_rsync_exit_status;
 
+use crate::bandwidth::BandwidthShare;
+
+use super::delete_guard::{self, DeleteGuard};
+use super::failover::UpstreamFailover;
+use super::progress::{self, StdoutRoute};
 use super::{
     BASE_RSYNC_ARGS, BASE_RSYNC_STAGE1_ARGS, CommonProviderConfig, MirrorProvider, ProviderError,
-    ProviderType, RunContext, impl_provider_getters, inject_provider_env, log_provider_failure,
-    resolve_log_file, run_child_with_cancellation, store_rsync_data_size,
+    ProviderType, RunContext, SyncTarget, impl_provider_getters, inject_provider_env, log_offset,
+    log_provider_failure, resolve_log_file, run_child_with_cancellation, spawn_in_cgroup,
+    store_rsync_data_size, store_rsync_stats,
 };
 
 /// Stage-1 filter rules for the `debian` profile.
@@ -49,9 +58,9 @@ const STAGE1_PROFILE_DEBIAN_OLDSTYLE: &[&str] = &[
     "--exclude=dep11/*",
 ];
 
-/// Return the compile-time filter-rule slice for a named stage-1 profile,
-/// or `None` when the profile is unrecognised.
-fn stage1_profile_options(profile: &str) -> Option<&'static [&'static str]> {
+/// Return the compile-time filter-rule slice for a built-in stage-1
+/// profile, or `None` when the name is not built in.
+fn builtin_stage1_profile(profile: &str) -> Option<&'static [&'static str]> {
     match profile {
         "debian" => Some(STAGE1_PROFILE_DEBIAN),
         "debian-oldstyle" => Some(STAGE1_PROFILE_DEBIAN_OLDSTYLE),
@@ -59,23 +68,79 @@ fn stage1_profile_options(profile: &str) -> Option<&'static [&'static str]> {
     }
 }
 
+/// Render include/exclude patterns as rsync filter options, includes first.
+fn filter_options(include: Option<&Vec<String>>, exclude: Option<&Vec<String>>) -> Vec<String> {
+    let includes = include
+        .into_iter()
+        .flatten()
+        .map(|p| format!("--include={p}"));
+    let excludes = exclude
+        .into_iter()
+        .flatten()
+        .map(|p| format!("--exclude={p}"));
+    includes.chain(excludes).collect()
+}
+
+/// Resolve `name` to its (stage-1, stage-2) filter options.
+///
+/// A configured profile takes precedence over a built-in of the same name;
+/// built-in profiles leave stage 2 unfiltered.
+fn resolve_profile(
+    name: &str,
+    profiles: &HashMap<String, Stage1ProfileConfig>,
+) -> Result<(Vec<String>, Vec<String>), ProviderError> {
+    if let Some(profile) = profiles.get(name) {
+        let stage1 = filter_options(profile.include.as_ref(), profile.exclude.as_ref());
+        if stage1.is_empty() {
+            return Err(ProviderError::Config(format!(
+                "stage1_profile {name} defines no stage-1 filter rules"
+            )));
+        }
+        let stage2 = filter_options(
+            profile.stage2_include.as_ref(),
+            profile.stage2_exclude.as_ref(),
+        );
+        return Ok((stage1, stage2));
+    }
+    match builtin_stage1_profile(name) {
+        Some(opts) => Ok((opts.iter().map(|s| s.to_string()).collect(), Vec::new())),
+        None => Err(ProviderError::Config(format!(
+            "unknown stage1_profile: {name}"
+        ))),
+    }
+}
+
 /// Configuration for the two-stage rsync provider.
 ///
 /// Both stages share most fields; only `stage1_profile` governs the
-/// stage-1 filter set. The stage-2 argv is the standard rsync base
-/// plus `extra_options` — identical to `RsyncProvider`.
+/// stage-1 filter set. The stage-2 argv is the standard rsync base plus
+/// the profile's stage-2 filters (if any) and `extra_options`.
 pub struct TwoStageRsyncProviderConfig {
     pub common: CommonProviderConfig,
     pub command: String,
     pub stage1_profile: String,
+    /// Profiles from the worker's `[stage1_profiles]` table; consulted
+    /// before the built-in `debian` / `debian-oldstyle` lists.
+    pub stage1_profiles: HashMap<String, Stage1ProfileConfig>,
     pub username: Option<String>,
     pub password: Option<String>,
     pub exclude_file: Option<String>,
     pub extra_options: Vec<String>,
     pub rsync_no_timeout: bool,
     pub rsync_timeout: Option<u32>,
+    /// Add `--info=progress2` to both stages and relay progress through
+    /// `RunContext::progress`.
+    pub rsync_progress: bool,
     pub use_ipv6: bool,
     pub use_ipv4: bool,
+    /// Tried in order after `common.upstream_url` on connection failures;
+    /// both stages of a run always use the same upstream.
+    pub fallback_upstreams: Vec<String>,
+    /// Refuse a sync whose stage-2 dry run deletes more files than this.
+    pub max_delete: Option<u64>,
+    /// Refuse a sync whose stage-2 dry run deletes more than this share
+    /// (percent) of the local files.
+    pub max_delete_percent: Option<u8>,
 }
 
 /// Two-stage rsync provider.
@@ -87,7 +152,12 @@ pub struct TwoStageRsyncProviderConfig {
 /// file paired with matching package files — never a half-updated state.
 pub struct TwoStageRsyncProvider {
     config: TwoStageRsyncProviderConfig,
+    upstreams: UpstreamFailover,
+    delete_guard: Option<DeleteGuard>,
+    stage1_filters: Vec<String>,
+    stage2_filters: Vec<String>,
     data_size: Mutex<Option<String>>,
+    stats: Mutex<Option<TransferStats>>,
     run_lock: Mutex<()>,
     /// PID of the currently running rsync process group.
     /// 0 = not running, u32::MAX = spawning (transient), otherwise live pgid.
@@ -96,10 +166,14 @@ pub struct TwoStageRsyncProvider {
 
 impl TwoStageRsyncProvider {
     pub fn new(mut config: TwoStageRsyncProviderConfig) -> Result<Self, ProviderError> {
-        if !config.common.upstream_url.ends_with('/') {
-            return Err(ProviderError::Config(
-                "rsync upstream URL should end with /".into(),
-            ));
+        let all_upstreams =
+            std::iter::once(&config.common.upstream_url).chain(&config.fallback_upstreams);
+        for upstream in all_upstreams {
+            if !upstream.ends_with('/') {
+                return Err(ProviderError::Config(
+                    "rsync upstream URL should end with /".into(),
+                ));
+            }
         }
         if config.common.retry == 0 {
             config.common.retry = 2;
@@ -110,48 +184,62 @@ impl TwoStageRsyncProvider {
 
         // Validate the profile at construction time so failures are reported
         // before any sync attempt rather than mid-run.
-        if stage1_profile_options(config.stage1_profile.as_str()).is_none() {
-            return Err(ProviderError::Config(format!(
-                "unknown stage1_profile: {}",
-                config.stage1_profile
-            )));
-        }
+        let (stage1_filters, stage2_filters) =
+            resolve_profile(&config.stage1_profile, &config.stage1_profiles)?;
+
+        let upstreams =
+            UpstreamFailover::new(&config.common.upstream_url, &config.fallback_upstreams);
+
+        let delete_guard = DeleteGuard::new(config.max_delete, config.max_delete_percent);
 
         Ok(Self {
             config,
+            upstreams,
+            delete_guard,
+            stage1_filters,
+            stage2_filters,
             data_size: Mutex::new(None),
+            stats: Mutex::new(None),
             run_lock: Mutex::new(()),
             running_pgid: AtomicU32::new(0),
         })
     }
 
-    /// Build argv for a single stage.
+    /// Build argv for a single stage syncing from `upstream`.
     ///
     /// Stage 1 uses the named profile's filter set (no `--delete`).
-    /// Stage 2 uses the standard rsync base plus `extra_options`.
+    /// Stage 2 uses the standard rsync base plus the profile's stage-2
+    /// filters and `extra_options`.
     /// Both stages apply the timeout / IP / exclude-file options
     /// verbatim to match Go's `Options(stage int)` method.
-    pub(crate) fn build_args_for_stage(&self, stage: u8) -> Result<Vec<String>, ProviderError> {
+    /// A `bwlimit` share from the worker budget follows the base args, so
+    /// a `--bwlimit` in `extra_options` still wins for stage 2. Both stages
+    /// write into `target`.
+    pub(crate) fn build_args_for_stage(
+        &self,
+        stage: u8,
+        upstream: &str,
+        target: &SyncTarget,
+        bwlimit: Option<u64>,
+    ) -> Result<Vec<String>, ProviderError> {
+        let bwlimit = bwlimit.map(|kib| format!("--bwlimit={}", kib));
         let mut options: Vec<String> = match stage {
             1 => {
                 // Stage-1 base — subset without --delete/--delete-after/--delay-updates
-                let mut opts: Vec<String> =
-                    BASE_RSYNC_STAGE1_ARGS.iter().map(|s| s.to_string()).collect();
+                let mut opts: Vec<String> = BASE_RSYNC_STAGE1_ARGS
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect();
+                opts.extend(bwlimit);
                 // Append profile filter rules
-                let profile_opts = stage1_profile_options(self.config.stage1_profile.as_str())
-                    .ok_or_else(|| {
-                        ProviderError::Config(format!(
-                            "unknown stage1_profile: {}",
-                            self.config.stage1_profile
-                        ))
-                    })?;
-                opts.extend(profile_opts.iter().map(|s| s.to_string()));
+                opts.extend(self.stage1_filters.iter().cloned());
                 opts
             }
             2 => {
                 // Stage-2 base — full sync including --delete and --delay-updates
-                let mut opts: Vec<String> =
-                    BASE_RSYNC_ARGS.iter().map(|s| s.to_string()).collect();
+                let mut opts: Vec<String> = BASE_RSYNC_ARGS.iter().map(|s| s.to_string()).collect();
+                opts.extend(bwlimit);
+                opts.extend(self.stage2_filters.iter().cloned());
                 // Stage 2 appends extra_options (Go's p.extraOptions)
                 opts.extend(self.config.extra_options.iter().cloned());
                 opts
@@ -180,33 +268,107 @@ impl TwoStageRsyncProvider {
             options.push(exclude_file.clone());
         }
 
+        if self.config.rsync_progress {
+            options.push(progress::PROGRESS2_ARG.to_string());
+        }
+
+        options.extend(target.link_dest_arg());
+
         // Positional args
-        options.push(self.config.common.upstream_url.clone());
-        options.push(self.config.common.working_dir.clone());
+        options.push(upstream.to_string());
+        options.push(target.dir.clone());
 
         Ok(options)
     }
 
-    /// Spawn one rsync stage and await its completion, honoring the shared
-    /// cancellation token.  The `log_file` handle is passed in so that both
-    /// stages append to the same file (Go appends; no separator written).
-    #[allow(clippy::cognitive_complexity)]
+    /// Stage 1 then, only if it succeeded, stage 2 against `upstream`.
+    ///
+    /// When the mirror has a deletion guard the operator did not override,
+    /// a dry run of stage 2 (the only stage that deletes) comes first.
+    /// Each stage takes the bandwidth share in force when it starts.
+    /// Returns the log offset stage 1's output starts at.
+    async fn run_stages(
+        &self,
+        upstream: &str,
+        target: &SyncTarget,
+        share: Option<&BandwidthShare>,
+        log_file: &mut File,
+        effective_log_file: &str,
+        ctx: &RunContext,
+    ) -> Result<u64, ProviderError> {
+        if let Some(guard) = self.delete_guard.filter(|_| !ctx.allow_mass_delete) {
+            let planned = DeleteGuard::dry_run_target(target);
+            let args =
+                DeleteGuard::dry_run_args(&self.build_args_for_stage(2, upstream, &planned, None)?);
+            let from = log_offset(effective_log_file).await;
+            tracing::info!(
+                "{}: dry run of stage 2 to check planned deletions",
+                self.config.common.name
+            );
+            self.run_stage(
+                2,
+                upstream,
+                &planned.dir,
+                args,
+                log_file,
+                effective_log_file,
+                ctx,
+            )
+            .await?;
+            guard.check(&delete_guard::read_plan(effective_log_file, from)?)?;
+        }
+
+        let from = log_offset(effective_log_file).await;
+        for stage in [1, 2] {
+            // Stage 1 quickly syncs metadata-critical files; stage 2, the
+            // full sync, is only reached if stage 1 succeeded.
+            let bwlimit = share.and_then(BandwidthShare::rsync_bwlimit);
+            if let Some(kib) = bwlimit {
+                tracing::info!(
+                    "{}: stage {} bandwidth share {} KiB/s",
+                    self.config.common.name,
+                    stage,
+                    kib
+                );
+            }
+            let args = self.build_args_for_stage(stage, upstream, target, bwlimit)?;
+            self.run_stage(
+                stage,
+                upstream,
+                &target.dir,
+                args,
+                log_file,
+                effective_log_file,
+                ctx,
+            )
+            .await?;
+        }
+        Ok(from)
+    }
+
+    /// Spawn rsync with one stage's `args` in `dir` and await its
+    /// completion, honoring the shared cancellation token.  The `log_file`
+    /// handle is passed in so that both stages append to the same file (Go
+    /// appends; no separator written).
+    #[allow(clippy::cognitive_complexity, clippy::too_many_arguments)]
     async fn run_stage(
         &self,
         stage: u8,
+        upstream: &str,
+        dir: &str,
+        args: Vec<String>,
         log_file: &mut File,
         effective_log_file: &str,
         ctx: &RunContext,
     ) -> Result<(), ProviderError> {
-        let args = self.build_args_for_stage(stage)?;
-
-        let std_out_log = log_file.try_clone().await?.into_std().await;
+        let reporter = ctx.progress.as_ref().filter(|_| self.config.rsync_progress);
+        let (stdout_route, std_out) = StdoutRoute::new(log_file, reporter).await?;
         let std_err_log = log_file.try_clone().await?.into_std().await;
 
         let mut cmd = Command::new(&self.config.command);
         cmd.args(&args)
-            .current_dir(&self.config.common.working_dir)
-            .stdout(Stdio::from(std_out_log))
+            .current_dir(dir)
+            .stdout(std_out)
             .stderr(Stdio::from(std_err_log));
 
         #[cfg(unix)]
@@ -221,21 +383,28 @@ impl TwoStageRsyncProvider {
             cmd.env("RSYNC_PASSWORD", password);
         }
 
-        inject_provider_env(&mut cmd, &self.config.common, effective_log_file, &ctx.env);
+        inject_provider_env(
+            &mut cmd,
+            &self.config.common,
+            upstream,
+            effective_log_file,
+            &ctx.env,
+        );
 
         tracing::info!(
-            "Starting two-stage-rsync provider for {} (stage {})",
+            "Starting two-stage-rsync provider for {} (stage {}) from {}",
             self.config.common.name,
-            stage
+            stage,
+            upstream
         );
-
-        let mut spawned_child = match cmd.spawn() {
-            Ok(child) => child,
-            Err(err) => {
-                self.running_pgid.store(0, Ordering::Release);
-                return Err(ProviderError::Io(err));
-            }
-        };
+        let mut spawned_child =
+            match spawn_in_cgroup(&mut cmd, self.config.common.cgroup.as_ref()).await {
+                Ok(child) => child,
+                Err(err) => {
+                    self.running_pgid.store(0, Ordering::Release);
+                    return Err(err);
+                }
+            };
 
         if let Some(pid) = spawned_child.id() {
             self.running_pgid.store(pid, Ordering::Release);
@@ -248,15 +417,19 @@ impl TwoStageRsyncProvider {
         }
 
         // No per-stage timeout — the outer run() wraps both stages in the shared budget.
-        let wait_result = match run_child_with_cancellation(
+        let relay = stdout_route.start(&mut spawned_child);
+        let waited = run_child_with_cancellation(
             &mut spawned_child,
             std::time::Duration::ZERO,
             &ctx.cancel,
             &self.running_pgid,
             &self.config.common.name,
+            None,
+            self.config.common.cgroup.as_ref(),
         )
-        .await
-        {
+        .await;
+        progress::finish(relay).await;
+        let wait_result = match waited {
             Ok(status) => {
                 if status.success() {
                     Ok(())
@@ -299,7 +472,7 @@ impl TwoStageRsyncProvider {
 
 #[async_trait]
 impl MirrorProvider for TwoStageRsyncProvider {
-    impl_provider_getters!(TwoStageRsyncProvider, ProviderType::TwoStageRsync);
+    impl_provider_getters!(TwoStageRsyncProvider, ProviderType::TwoStageRsync, failover);
 
     async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
         if ctx.attempt > 1 {
@@ -321,8 +494,11 @@ impl MirrorProvider for TwoStageRsyncProvider {
             let mut size_guard = self.data_size.lock().await;
             *size_guard = None;
         }
+        *self.stats.lock().await = None;
+        let started = Instant::now();
 
-        create_dir_all(&self.config.common.working_dir).await?;
+        let target = SyncTarget::resolve(&ctx, &self.config.common.working_dir);
+        create_dir_all(&target.dir).await?;
         create_dir_all(&self.config.common.log_dir).await?;
 
         // Pre_exec hook may rotate the log path; honor `ctx.env`
@@ -341,14 +517,33 @@ impl MirrorProvider for TwoStageRsyncProvider {
         use tokio::io::AsyncSeekExt;
         log_file.seek(std::io::SeekFrom::End(0)).await?;
 
+        // Joined for the whole run, fallback attempts included.
+        let share = ctx.bandwidth.as_ref().map(|budget| budget.join());
+
         // Wrap the entire two-stage run in a single timeout budget.
+        // Fallback upstreams share the budget; each one reruns both stages.
         let run_body = async {
-            // Stage 1: quick sync of metadata-critical files
-            self.run_stage(1, &mut log_file, &effective_log_file, &ctx)
-                .await?;
-            // Stage 2: full sync — only reached if stage 1 succeeded
-            self.run_stage(2, &mut log_file, &effective_log_file, &ctx)
-                .await
+            let mut upstream = self.upstreams.restart();
+            loop {
+                let result = self
+                    .run_stages(
+                        upstream,
+                        &target,
+                        share.as_ref(),
+                        &mut log_file,
+                        &effective_log_file,
+                        &ctx,
+                    )
+                    .await;
+                let Err(e) = &result else { break result };
+                match self
+                    .upstreams
+                    .fail_over(&self.config.common.name, e, &ctx.cancel)
+                {
+                    Some(next) => upstream = next,
+                    None => break result,
+                }
+            }
         };
 
         let result = if self.config.common.timeout == Duration::ZERO {
@@ -366,24 +561,27 @@ impl MirrorProvider for TwoStageRsyncProvider {
 
         self.running_pgid.store(0, Ordering::Release);
 
-        if result.is_ok() {
-            store_rsync_data_size(&self.data_size, &effective_log_file).await;
-        }
-
-        result
+        let from = result?;
+        store_rsync_data_size(&self.data_size, &effective_log_file).await;
+        store_rsync_stats(&self.stats, &effective_log_file, from, started).await;
+        Ok(())
     }
 
     async fn terminate(&self) -> Result<(), ProviderError> {
         #[cfg(unix)]
         {
             let raw = self.running_pgid.load(Ordering::Acquire);
-            if raw != 0 && raw != u32::MAX {
+            let running = raw != 0 && raw != u32::MAX;
+            if running {
                 tracing::warn!(
                     "Terminating two-stage-rsync provider for {}",
                     self.config.common.name
                 );
             }
             super::terminate_pgid(&self.running_pgid, &self.config.common.name).await;
+            if running && let Some(cg) = &self.config.common.cgroup {
+                cg.kill().await;
+            }
         }
         Ok(())
     }
@@ -391,4 +589,8 @@ impl MirrorProvider for TwoStageRsyncProvider {
     async fn data_size(&self) -> Option<String> {
         self.data_size.lock().await.clone()
     }
+
+    async fn transfer_stats(&self) -> Option<TransferStats> {
+        self.stats.lock().await.clone()
+    }
 }
@@ -0,0 +1,710 @@
+//! YUM provider: mirror an RPM repository (yum/dnf `repodata/`) natively
+//! over HTTP(S).
+//!
+//! Each run fetches `repodata/repomd.xml`, downloads every metadata file it
+//! lists, reads the `primary` metadata and downloads the packages that are
+//! missing locally. Metadata files and packages are checked against the
+//! SHA-256 sums that reference them.
+//!
+//! The new metadata is downloaded into a hidden staging directory and
+//! moved into `repodata/` only after every package is present, with
+//! `repomd.xml` and then its detached signature last, so clients never see
+//! metadata for packages that are not there yet, nor a signature over
+//! metadata they cannot fetch yet. Metadata files referenced by neither the new nor the
+//! previous `repomd.xml` are deleted afterwards; the previous generation is
+//! kept one more run for clients that fetched the old `repomd.xml`.
+//!
+//! A package already present with the expected size is not downloaded
+//! again; package file names carry the version, so a size match is taken
+//! as a match. Packages that drop out of the repository are left in place.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use async_trait::async_trait;
+use quick_xml::events::{BytesStart, Event};
+use reqwest::Url;
+use tokio::fs::{self, File, create_dir_all};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use hustsync_internal::util::format_size;
+
+use super::transfer::{
+    decompress, fetch_to, has_sha256, is_safe_relative, join_url, run_cancellable,
+};
+use super::{
+    CommonProviderConfig, MirrorProvider, ProviderError, ProviderType, RunContext, RunSlot,
+    impl_provider_getters, log_provider_failure, resolve_log_file,
+};
+
+/// Hidden directory under the working dir holding not-yet-published
+/// metadata. It is on the same filesystem, so publishing is a rename.
+const STAGING_DIR: &str = ".hustsync-yum-staging";
+
+const REPOMD: &str = "repodata/repomd.xml";
+
+/// Detached signature of `repomd.xml`.
+const REPOMD_ASC: &str = "repodata/repomd.xml.asc";
+
+/// Detached signature and key published next to `repomd.xml` by signed
+/// repositories.
+const REPOMD_EXTRAS: [&str; 2] = [REPOMD_ASC, "repodata/repomd.xml.key"];
+
+/// One change to `repodata/` when publishing `repomd.xml`.
+#[derive(Debug, PartialEq, Eq)]
+enum RepomdStep<'a> {
+    /// Move the staged file into place.
+    Move(&'a str),
+    /// Remove a file upstream no longer has.
+    Remove(&'a str),
+}
+
+/// The order `repomd.xml` and its extras are published in, given the extras
+/// upstream has. Dropped extras go first, since a stale signature would not
+/// match the new `repomd.xml`; a new signature goes last, so it never sits
+/// next to the `repomd.xml` it was not made for.
+fn repomd_steps(extras: &[&str]) -> Vec<RepomdStep<'static>> {
+    let mut steps: Vec<_> = REPOMD_EXTRAS
+        .into_iter()
+        .filter(|rel| !extras.contains(rel))
+        .map(RepomdStep::Remove)
+        .collect();
+    steps.extend(
+        REPOMD_EXTRAS
+            .into_iter()
+            .filter(|rel| *rel != REPOMD_ASC && extras.contains(rel))
+            .map(RepomdStep::Move),
+    );
+    steps.push(RepomdStep::Move(REPOMD));
+    if extras.contains(&REPOMD_ASC) {
+        steps.push(RepomdStep::Move(REPOMD_ASC));
+    }
+    steps
+}
+
+pub struct YumProviderConfig {
+    pub common: CommonProviderConfig,
+}
+
+/// A metadata file listed in `repomd.xml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoData {
+    /// `primary`, `filelists`, `other`, `group`, ...
+    pub kind: String,
+    pub href: String,
+    pub size: Option<u64>,
+    pub sha256: String,
+}
+
+/// A package listed in the primary metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpmPackage {
+    pub href: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+pub struct YumProvider {
+    config: YumProviderConfig,
+    base: Url,
+    client: reqwest::Client,
+    data_size: Mutex<Option<String>>,
+    // Cancels the in-process transfer on `terminate()`.
+    run_slot: RunSlot,
+}
+
+/// Per-run bookkeeping.
+struct SyncState {
+    log: File,
+    fetched: usize,
+    fetched_bytes: u64,
+    unchanged: usize,
+    total_bytes: u64,
+}
+
+impl SyncState {
+    async fn log(&mut self, line: &str) {
+        let _ = self.log.write_all(format!("{line}\n").as_bytes()).await;
+    }
+}
+
+impl YumProvider {
+    pub fn new(mut config: YumProviderConfig) -> Result<Self, ProviderError> {
+        let base = Url::parse(&config.common.upstream_url)
+            .map_err(|e| ProviderError::Config(format!("invalid yum upstream: {e}")))?;
+        if !matches!(base.scheme(), "http" | "https") || !base.path().ends_with('/') {
+            return Err(ProviderError::Config(
+                "yum upstream URL should be http(s) and end with /".into(),
+            ));
+        }
+        if config.common.retry == 0 {
+            config.common.retry = 2;
+        }
+
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("hustsync/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| ProviderError::Config(format!("http client: {e}")))?;
+
+        Ok(Self {
+            config,
+            base,
+            client,
+            data_size: Mutex::new(None),
+            run_slot: RunSlot::default(),
+        })
+    }
+
+    fn working_root(&self) -> &Path {
+        Path::new(&self.config.common.working_dir)
+    }
+
+    /// Download `rel` into `dest`, checking `sha256` when given. Returns
+    /// `None` when upstream answers 404.
+    async fn fetch(
+        &self,
+        state: &mut SyncState,
+        rel: &str,
+        dest: &Path,
+        sha256: Option<&str>,
+    ) -> Result<Option<u64>, ProviderError> {
+        let url = join_url(&self.base, rel)?;
+        let Some(written) = fetch_to(&self.client, url, dest, sha256).await? else {
+            return Ok(None);
+        };
+        state.fetched += 1;
+        state.fetched_bytes += written;
+        state
+            .log(&format!("fetched {rel} ({})", format_size(written)))
+            .await;
+        Ok(Some(written))
+    }
+
+    /// Fetch a file the repository metadata references; a 404 is an error.
+    async fn fetch_required(
+        &self,
+        state: &mut SyncState,
+        rel: &str,
+        dest: &Path,
+        sha256: Option<&str>,
+    ) -> Result<u64, ProviderError> {
+        self.fetch(state, rel, dest, sha256)
+            .await?
+            .ok_or_else(|| ProviderError::Http(format!("{rel}: 404 Not Found")))
+    }
+
+    /// Stage `repomd.xml` (and its signature, if any) and parse it.
+    async fn fetch_repomd(
+        &self,
+        state: &mut SyncState,
+        staging: &Path,
+    ) -> Result<(Vec<RepoData>, Vec<&'static str>), ProviderError> {
+        let size = self
+            .fetch_required(state, REPOMD, &staging.join(REPOMD), None)
+            .await?;
+        state.total_bytes += size;
+        let mut extras = Vec::new();
+        for rel in REPOMD_EXTRAS {
+            if self
+                .fetch(state, rel, &staging.join(rel), None)
+                .await?
+                .is_some()
+            {
+                extras.push(rel);
+            }
+        }
+        let text = fs::read_to_string(staging.join(REPOMD)).await?;
+        let data =
+            parse_repomd(&text).map_err(|e| ProviderError::Http(format!("{REPOMD}: {e}")))?;
+        if let Some(bad) = data.iter().find(|d| !is_safe_relative(&d.href)) {
+            return Err(ProviderError::Http(format!(
+                "{REPOMD} references unsafe path `{}`",
+                bad.href
+            )));
+        }
+        Ok((data, extras))
+    }
+
+    /// Reuse published metadata files whose sum still matches; stage the
+    /// rest. Returns the hrefs that were staged.
+    async fn sync_repodata(
+        &self,
+        state: &mut SyncState,
+        data: &[RepoData],
+        staging: &Path,
+    ) -> Result<HashSet<String>, ProviderError> {
+        let mut staged = HashSet::new();
+        for entry in data {
+            let published = self.working_root().join(&entry.href);
+            if has_sha256(&published, entry.size, &entry.sha256).await {
+                state.unchanged += 1;
+                state.total_bytes += fs::metadata(&published).await?.len();
+                continue;
+            }
+            let size = self
+                .fetch_required(
+                    state,
+                    &entry.href,
+                    &staging.join(&entry.href),
+                    Some(&entry.sha256),
+                )
+                .await?;
+            state.total_bytes += size;
+            staged.insert(entry.href.clone());
+        }
+        Ok(staged)
+    }
+
+    /// Download every package in the primary metadata that is missing or
+    /// has the wrong size.
+    async fn sync_packages(
+        &self,
+        state: &mut SyncState,
+        packages: &[RpmPackage],
+    ) -> Result<(), ProviderError> {
+        for pkg in packages {
+            if !is_safe_relative(&pkg.href) {
+                return Err(ProviderError::Http(format!(
+                    "primary metadata references unsafe path `{}`",
+                    pkg.href
+                )));
+            }
+            state.total_bytes += pkg.size;
+            let dest = self.working_root().join(&pkg.href);
+            let local_size = fs::metadata(&dest)
+                .await
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len());
+            if local_size == Some(pkg.size) {
+                state.unchanged += 1;
+                continue;
+            }
+            self.fetch_required(state, &pkg.href, &dest, Some(&pkg.sha256))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Read the primary metadata, from staging or from the published tree.
+    async fn read_primary(
+        &self,
+        data: &[RepoData],
+        staged: &HashSet<String>,
+        staging: &Path,
+    ) -> Result<Vec<RpmPackage>, ProviderError> {
+        let primary = data
+            .iter()
+            .find(|d| d.kind == "primary")
+            .ok_or_else(|| ProviderError::Http(format!("{REPOMD} lists no primary metadata")))?;
+        let root = if staged.contains(&primary.href) {
+            staging
+        } else {
+            self.working_root()
+        };
+        let raw = fs::read(root.join(&primary.href)).await?;
+        let xml = decompress(&primary.href, &raw)
+            .and_then(|b| String::from_utf8(b).map_err(|e| format!("not UTF-8: {e}")))
+            .map_err(|e| ProviderError::Http(format!("{}: {e}", primary.href)))?;
+        parse_primary(&xml).map_err(|e| ProviderError::Http(format!("{}: {e}", primary.href)))
+    }
+
+    /// Move the staged metadata into `repodata/`, `repomd.xml` and its
+    /// signature last, then delete metadata files neither it nor the
+    /// previous one references.
+    async fn publish(
+        &self,
+        state: &mut SyncState,
+        data: &[RepoData],
+        staged: &HashSet<String>,
+        extras: &[&str],
+        staging: &Path,
+    ) -> Result<(), ProviderError> {
+        let root = self.working_root();
+        let mut keep: HashSet<String> = data.iter().map(|d| d.href.clone()).collect();
+        if let Ok(old) = fs::read_to_string(root.join(REPOMD)).await
+            && let Ok(old) = parse_repomd(&old)
+        {
+            keep.extend(old.into_iter().map(|d| d.href));
+        }
+
+        for href in staged {
+            let dest = root.join(href);
+            if let Some(parent) = dest.parent() {
+                create_dir_all(parent).await?;
+            }
+            fs::rename(staging.join(href), &dest).await?;
+        }
+        for step in repomd_steps(extras) {
+            match step {
+                RepomdStep::Move(rel) => fs::rename(staging.join(rel), root.join(rel)).await?,
+                RepomdStep::Remove(rel) => {
+                    if fs::metadata(root.join(rel)).await.is_ok() {
+                        fs::remove_file(root.join(rel)).await?;
+                    }
+                }
+            }
+        }
+
+        let mut entries = fs::read_dir(root.join("repodata")).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let rel = format!("repodata/{}", entry.file_name().to_string_lossy());
+            let is_repomd = rel == REPOMD || REPOMD_EXTRAS.contains(&rel.as_str());
+            if !is_repomd && !keep.contains(&rel) && entry.file_type().await?.is_file() {
+                fs::remove_file(entry.path()).await?;
+                state.log(&format!("deleted {rel}")).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn sync(&self, state: &mut SyncState) -> Result<(), ProviderError> {
+        let staging = self.working_root().join(STAGING_DIR);
+        // Left over from an interrupted run.
+        let _ = fs::remove_dir_all(&staging).await;
+        create_dir_all(&staging).await?;
+
+        let (data, extras) = self.fetch_repomd(state, &staging).await?;
+        let staged = self.sync_repodata(state, &data, &staging).await?;
+        let packages = self.read_primary(&data, &staged, &staging).await?;
+        self.sync_packages(state, &packages).await?;
+        self.publish(state, &data, &staged, &extras, &staging)
+            .await?;
+        state
+            .log(&format!(
+                "published repodata ({} files, {} packages)",
+                data.len(),
+                packages.len()
+            ))
+            .await;
+        fs::remove_dir_all(&staging).await?;
+        Ok(())
+    }
+
+    async fn run_inner(
+        &self,
+        ctx: &RunContext,
+        cancel: &CancellationToken,
+    ) -> Result<(), ProviderError> {
+        create_dir_all(&self.config.common.working_dir).await?;
+        create_dir_all(&self.config.common.log_dir).await?;
+
+        let effective_log_file = resolve_log_file(ctx, &self.config.common.log_file);
+        let mut state = SyncState {
+            log: File::create(&effective_log_file).await?,
+            fetched: 0,
+            fetched_bytes: 0,
+            unchanged: 0,
+            total_bytes: 0,
+        };
+
+        tracing::info!("Starting yum provider for {}", self.config.common.name);
+        state.log(&format!("mirroring {}", self.base)).await;
+
+        let result = run_cancellable(
+            self.sync(&mut state),
+            self.config.common.timeout,
+            cancel,
+            &self.config.common.name,
+        )
+        .await;
+        match result {
+            Ok(()) => {
+                state
+                    .log(&format!(
+                        "done: {} fetched ({}), {} unchanged, repository size {}",
+                        state.fetched,
+                        format_size(state.fetched_bytes),
+                        state.unchanged,
+                        format_size(state.total_bytes)
+                    ))
+                    .await;
+                *self.data_size.lock().await = Some(format_size(state.total_bytes));
+                Ok(())
+            }
+            Err(e) => {
+                state.log(&format!("error: {e}")).await;
+                let _ = state.log.flush().await;
+                log_provider_failure(
+                    "Yum",
+                    &self.config.common.name,
+                    &e.to_string(),
+                    &effective_log_file,
+                )
+                .await;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn attr(e: &BytesStart<'_>, name: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.local_name().as_ref() == name)
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+fn sha256_of(checksum_type: Option<&str>, value: String) -> Result<String, String> {
+    match checksum_type {
+        Some("sha256") => Ok(value.trim().to_ascii_lowercase()),
+        other => Err(format!(
+            "unsupported checksum type `{}`; only sha256 is verified",
+            other.unwrap_or("")
+        )),
+    }
+}
+
+/// Parse `repomd.xml` into the metadata files it lists.
+pub fn parse_repomd(xml: &str) -> Result<Vec<RepoData>, String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut out = Vec::new();
+    let mut current: Option<(RepoData, Option<String>)> = None;
+    let mut element = String::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) | Event::Empty(e) => {
+                element = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match (element.as_str(), current.as_mut()) {
+                    ("data", _) => {
+                        let kind = attr(&e, b"type").unwrap_or_default();
+                        let data = RepoData {
+                            kind,
+                            href: String::new(),
+                            size: None,
+                            sha256: String::new(),
+                        };
+                        current = Some((data, None));
+                    }
+                    ("checksum", Some((_, ty))) => *ty = attr(&e, b"type"),
+                    ("location", Some((data, _))) => {
+                        data.href = attr(&e, b"href").unwrap_or_default();
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(t) => {
+                let text = t.unescape().map_err(|e| e.to_string())?.into_owned();
+                match (element.as_str(), current.as_mut()) {
+                    ("checksum", Some((data, _))) => data.sha256 = text,
+                    ("size", Some((data, _))) => data.size = text.trim().parse().ok(),
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"data"
+                    && let Some((mut data, ty)) = current.take()
+                {
+                    data.sha256 = sha256_of(ty.as_deref(), data.sha256)
+                        .map_err(|e| format!("{}: {e}", data.kind))?;
+                    if data.href.is_empty() {
+                        return Err(format!("{} has no location", data.kind));
+                    }
+                    out.push(data);
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Parse primary metadata into the packages it lists.
+pub fn parse_primary(xml: &str) -> Result<Vec<RpmPackage>, String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut out = Vec::new();
+    // (href, size, checksum type, checksum) of the package being read.
+    let mut current: Option<(String, Option<u64>, Option<String>, String)> = None;
+    let mut element = String::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) | Event::Empty(e) => {
+                element = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                match (element.as_str(), current.as_mut()) {
+                    ("package", None) => current = Some((String::new(), None, None, String::new())),
+                    ("location", Some(pkg)) => pkg.0 = attr(&e, b"href").unwrap_or_default(),
+                    ("size", Some(pkg)) => {
+                        pkg.1 = attr(&e, b"package").and_then(|s| s.parse().ok());
+                    }
+                    ("checksum", Some(pkg)) => pkg.2 = attr(&e, b"type"),
+                    _ => {}
+                }
+            }
+            Event::Text(t) => {
+                if element == "checksum"
+                    && let Some(pkg) = current.as_mut()
+                {
+                    pkg.3 = t.unescape().map_err(|e| e.to_string())?.into_owned();
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"package"
+                    && let Some((href, size, ty, sum)) = current.take()
+                {
+                    let size = size.ok_or_else(|| format!("{href}: no package size"))?;
+                    let sha256 =
+                        sha256_of(ty.as_deref(), sum).map_err(|e| format!("{href}: {e}"))?;
+                    if href.is_empty() {
+                        return Err("package without location".into());
+                    }
+                    out.push(RpmPackage { href, size, sha256 });
+                }
+                element.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+#[async_trait]
+impl MirrorProvider for YumProvider {
+    impl_provider_getters!(YumProvider, ProviderType::Yum);
+
+    async fn run(&self, ctx: RunContext) -> Result<(), ProviderError> {
+        if ctx.attempt > 1 {
+            tracing::debug!(
+                "Yum provider {} re-entering on attempt {}",
+                self.config.common.name,
+                ctx.attempt
+            );
+        }
+
+        let cancel = self.run_slot.begin(&ctx.cancel)?;
+        let result = self.run_inner(&ctx, &cancel).await;
+        self.run_slot.end();
+        result
+    }
+
+    async fn terminate(&self) -> Result<(), ProviderError> {
+        if self.run_slot.cancel() {
+            tracing::warn!("Terminating yum provider for {}", self.config.common.name);
+        }
+        Ok(())
+    }
+
+    async fn data_size(&self) -> Option<String> {
+        self.data_size.lock().await.clone()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_published_after_repomd() {
+        assert_eq!(
+            repomd_steps(&REPOMD_EXTRAS),
+            vec![
+                RepomdStep::Move("repodata/repomd.xml.key"),
+                RepomdStep::Move(REPOMD),
+                RepomdStep::Move(REPOMD_ASC),
+            ]
+        );
+        assert_eq!(
+            repomd_steps(&[]),
+            vec![
+                RepomdStep::Remove(REPOMD_ASC),
+                RepomdStep::Remove("repodata/repomd.xml.key"),
+                RepomdStep::Move(REPOMD),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_repomd() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<repomd xmlns="http://linux.duke.edu/metadata/repo" xmlns:rpm="http://linux.duke.edu/metadata/rpm">
+  <revision>1700000000</revision>
+  <data type="primary">
+    <checksum type="sha256">AB12</checksum>
+    <open-checksum type="sha256">ffff</open-checksum>
+    <location href="repodata/ab12-primary.xml.zst"/>
+    <timestamp>1700000000</timestamp>
+    <size>1234</size>
+    <open-size>99999</open-size>
+  </data>
+  <data type="group">
+    <checksum type="sha256">cd34</checksum>
+    <location href="repodata/cd34-comps.xml"/>
+  </data>
+</repomd>"#;
+        let data = parse_repomd(xml).unwrap();
+        assert_eq!(
+            data,
+            vec![
+                RepoData {
+                    kind: "primary".into(),
+                    href: "repodata/ab12-primary.xml.zst".into(),
+                    size: Some(1234),
+                    sha256: "ab12".into(),
+                },
+                RepoData {
+                    kind: "group".into(),
+                    href: "repodata/cd34-comps.xml".into(),
+                    size: None,
+                    sha256: "cd34".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn repomd_with_sha1_is_rejected() {
+        let xml = r#"<repomd><data type="primary"><checksum type="sha">00</checksum>
+            <location href="repodata/primary.xml.gz"/></data></repomd>"#;
+        let err = parse_repomd(xml).unwrap_err();
+        assert!(err.contains("sha256"), "{err}");
+    }
+
+    #[test]
+    fn parses_primary() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata xmlns="http://linux.duke.edu/metadata/common" xmlns:rpm="http://linux.duke.edu/metadata/rpm" packages="2">
+<package type="rpm">
+  <name>bash</name>
+  <arch>x86_64</arch>
+  <version epoch="0" ver="5.2" rel="1"/>
+  <checksum type="sha256" pkgid="YES">aa11</checksum>
+  <summary>The GNU Bourne Again shell</summary>
+  <size package="1800000" installed="8000000" archive="8100000"/>
+  <location href="Packages/b/bash-5.2-1.x86_64.rpm"/>
+  <format><rpm:license>GPLv3+</rpm:license><rpm:header-range start="4504" end="77085"/></format>
+</package>
+<package type="rpm">
+  <name>zsh</name>
+  <checksum type="sha256" pkgid="YES">bb22</checksum>
+  <size package="3000" installed="9000" archive="9100"/>
+  <location href="Packages/z/zsh-5.9-1.x86_64.rpm"/>
+</package>
+</metadata>"#;
+        let pkgs = parse_primary(xml).unwrap();
+        assert_eq!(
+            pkgs,
+            vec![
+                RpmPackage {
+                    href: "Packages/b/bash-5.2-1.x86_64.rpm".into(),
+                    size: 1_800_000,
+                    sha256: "aa11".into(),
+                },
+                RpmPackage {
+                    href: "Packages/z/zsh-5.9-1.x86_64.rpm".into(),
+                    size: 3000,
+                    sha256: "bb22".into(),
+                },
+            ]
+        );
+    }
+}
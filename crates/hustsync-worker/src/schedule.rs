@@ -1,9 +1,53 @@
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use croner::Cron;
+use hustsync_config_parser::MirrorConfig;
 use tokio::time::Instant;
 
 use crate::job::MirrorJob;
 
+/// A mirror's `schedule`: cron run times read in `schedule_timezone`.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    cron: Cron,
+    tz: Tz,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str, tz: Option<&str>) -> Result<Self, String> {
+        let cron = Cron::from_str(expr).map_err(|e| format!("schedule `{expr}`: {e}"))?;
+        let tz = match tz {
+            Some(name) => name
+                .parse()
+                .map_err(|_| format!("unknown time zone `{name}`"))?,
+            None => Tz::UTC,
+        };
+        Ok(Self { cron, tz })
+    }
+
+    /// The schedule configured for `m_cfg`, if any. Validation has already
+    /// vetted it; a value that still fails is logged and ignored, leaving
+    /// the mirror on `interval`.
+    pub fn from_config(name: &str, m_cfg: &MirrorConfig) -> Option<Self> {
+        let expr = m_cfg.schedule.as_deref()?;
+        Self::parse(expr, m_cfg.schedule_timezone.as_deref())
+            .inspect_err(|e| tracing::warn!("{}: {}; using interval", name, e))
+            .ok()
+    }
+
+    /// First run time strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.cron
+            .find_next_occurrence(&after.with_timezone(&self.tz), false)
+            .ok()
+            .map(|next| next.with_timezone(&Utc))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct JobScheduleInfo {
     pub job_name: String,
@@ -141,7 +185,7 @@ impl ScheduleQueue {
 mod tests {
     use super::*;
     use std::sync::Arc;
-    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::{AtomicBool, AtomicU32};
     use tokio::sync::mpsc;
     use tokio::time::Duration;
 
@@ -152,7 +196,11 @@ mod tests {
             tx,
             state: Arc::new(AtomicU32::new(0)),
             disabled: Arc::new(tokio::sync::Notify::new()),
+            allow_mass_delete: Arc::new(AtomicBool::new(false)),
             interval: Duration::from_secs(60),
+            schedule: None,
+            blackout: None,
+            after: Arc::from([]),
         }
     }
 
@@ -216,4 +264,36 @@ mod tests {
         let popped = queue.pop_if_ready().expect("Should pop job1");
         assert_eq!(&*popped.name, "job1");
     }
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_cron_next_run_replaces_interval() {
+        let mut job = create_dummy_job("debian");
+        let last = at("2024-05-01T03:00:00Z");
+        assert_eq!(job.next_run(last), at("2024-05-01T03:01:00Z"));
+
+        job.schedule = Some(Arc::new(CronSchedule::parse("0 2,14 * * *", None).unwrap()));
+        assert_eq!(job.next_run(last), at("2024-05-01T14:00:00Z"));
+        assert_eq!(
+            job.next_run(at("2024-05-01T14:00:00Z")),
+            at("2024-05-02T02:00:00Z"),
+            "a run ending on the hour waits for the next slot"
+        );
+    }
+
+    #[test]
+    fn test_cron_schedule_reads_its_timezone() {
+        // 02:00 in Shanghai (UTC+8) is 18:00 UTC the day before.
+        let schedule = CronSchedule::parse("0 2 * * *", Some("Asia/Shanghai")).unwrap();
+        assert_eq!(
+            schedule.next_after(at("2024-05-01T12:00:00Z")),
+            Some(at("2024-05-01T18:00:00Z"))
+        );
+        assert!(CronSchedule::parse("0 2 * * *", Some("Nowhere/Else")).is_err());
+    }
 }
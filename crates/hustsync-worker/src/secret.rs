@@ -0,0 +1,94 @@
+//! Resolve secret references in mirror config.
+//!
+//! `password` and `secret_env` values may name where a secret lives instead
+//! of holding it (`env:VAR`, `file:/path`, `credential:NAME`; see
+//! [`parse_secret_ref`]), and `password_file` names a file outright. They
+//! are read when the provider is built, so the TOML can live in git. Plain
+//! `env` values are never read as references.
+//!
+//! Resolved values go straight into the provider and are never logged:
+//! errors name the reference (variable, path, credential), not what it
+//! holds.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use hustsync_config_parser::{MirrorConfig, SecretRef, parse_secret_ref};
+use thiserror::Error;
+
+/// Set by systemd for units with `LoadCredential=` and friends.
+const CREDENTIALS_DIRECTORY: &str = "CREDENTIALS_DIRECTORY";
+
+#[derive(Error, Debug)]
+pub enum SecretError {
+    #[error("{field}: {reason}")]
+    Invalid { field: String, reason: String },
+    #[error("{field}: environment variable {var} is not set")]
+    EnvUnset { field: String, var: String },
+    #[error("{field}: $CREDENTIALS_DIRECTORY is not set (credential {name} needs LoadCredential=)")]
+    NoCredentials { field: String, name: String },
+    #[error("{field}: cannot read {}: {source}", path.display())]
+    Read {
+        field: String,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// The value `raw` stands for; `field` labels errors.
+pub fn resolve(field: &str, raw: &str) -> Result<String, SecretError> {
+    let secret_ref = parse_secret_ref(raw).map_err(|reason| SecretError::Invalid {
+        field: field.to_string(),
+        reason,
+    })?;
+    match secret_ref {
+        SecretRef::Literal(value) => Ok(value.to_string()),
+        SecretRef::Env(var) => std::env::var(var).map_err(|_| SecretError::EnvUnset {
+            field: field.to_string(),
+            var: var.to_string(),
+        }),
+        SecretRef::File(path) => read_file(field, Path::new(path)),
+        SecretRef::Credential(name) => {
+            let dir = std::env::var_os(CREDENTIALS_DIRECTORY).ok_or_else(|| {
+                SecretError::NoCredentials {
+                    field: field.to_string(),
+                    name: name.to_string(),
+                }
+            })?;
+            read_file(field, &Path::new(&dir).join(name))
+        }
+    }
+}
+
+/// The mirror's rsync password, from `password_file` or `password`.
+pub fn resolve_password(m_cfg: &MirrorConfig) -> Result<Option<String>, SecretError> {
+    if let Some(path) = m_cfg.password_file.as_deref() {
+        return read_file("password_file", Path::new(path)).map(Some);
+    }
+    m_cfg
+        .password
+        .as_deref()
+        .map(|raw| resolve("password", raw))
+        .transpose()
+}
+
+/// The mirror's `env` as written, plus its `secret_env` resolved.
+pub fn resolve_env(m_cfg: &MirrorConfig) -> Result<HashMap<String, String>, SecretError> {
+    let mut env = m_cfg.env.clone().unwrap_or_default();
+    for (key, raw) in m_cfg.secret_env.iter().flatten() {
+        let value = resolve(&format!("secret_env.{key}"), raw)?;
+        env.insert(key.clone(), value);
+    }
+    Ok(env)
+}
+
+/// File contents without the trailing newline editors and `echo` add.
+fn read_file(field: &str, path: &Path) -> Result<String, SecretError> {
+    let content = std::fs::read_to_string(path).map_err(|source| SecretError::Read {
+        field: field.to_string(),
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let trimmed = content.strip_suffix('\n').unwrap_or(&content);
+    Ok(trimmed.strip_suffix('\r').unwrap_or(trimmed).to_string())
+}
@@ -1,4 +1,5 @@
 use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::post};
+use chrono::{Local, Utc};
 use hustsync_internal::msg::{CmdVerb, WorkerCmd};
 use serde_json::json;
 use std::collections::HashMap;
@@ -77,11 +78,46 @@ async fn handle_cmd(
 
         match cmd.cmd {
             CmdVerb::Start => {
-                if cmd.options.get("force").copied().unwrap_or(false) {
+                let allow_mass_delete = cmd
+                    .options
+                    .get("allow_mass_delete")
+                    .copied()
+                    .unwrap_or(false);
+                let action = if cmd.options.get("force").copied().unwrap_or(false) {
                     crate::job::CtrlAction::ForceStart
+                } else if let Some(until) = job.blackout_until(Local::now()) {
+                    // Requeue as due now; the dispatch loop defers it to the
+                    // window's close and pushes the new schedule.
+                    state
+                        .schedule_queue
+                        .lock()
+                        .await
+                        .add_job(Utc::now(), job.clone());
+                    let mut msg = format!(
+                        "Mirror '{}' is in a blackout window; deferred until {}, \
+                         use --force to start now",
+                        cmd.mirror_id, until
+                    );
+                    // The deferred run is an unattended one; it does not
+                    // get an override meant for a run someone watches.
+                    if allow_mass_delete {
+                        tracing::warn!(
+                            "Start of {} deferred by a blackout; allow_mass_delete not kept",
+                            cmd.mirror_id
+                        );
+                        msg.push_str("; allow_mass_delete was not kept for the deferred run");
+                    }
+                    return (StatusCode::OK, Json(json!({"msg": msg})));
                 } else {
                     crate::job::CtrlAction::Start
+                };
+                // The override belongs to the run this Start begins. A
+                // running job ignores the Start, so there is none to arm.
+                if allow_mass_delete && job.state() != crate::job::STATE_READY {
+                    job.allow_mass_delete
+                        .store(true, std::sync::atomic::Ordering::Release);
                 }
+                action
             }
             CmdVerb::Restart => crate::job::CtrlAction::Restart,
             CmdVerb::Stop => {
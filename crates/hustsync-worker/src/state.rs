@@ -0,0 +1,206 @@
+//! Local copy of the schedule queue and job states.
+//!
+//! With `global.state_file` set, the worker keeps each job's next run and
+//! its paused/disabled state in a small JSON file. At startup the manager's
+//! view wins for the jobs it reports; the file covers the rest, and every
+//! job when the manager cannot be reached, so a restart neither runs every
+//! mirror at once nor forgets what `hustsynctl stop`/`disable` did.
+//!
+//! The file is replaced atomically: written beside the target, synced,
+//! then renamed over it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use hustsync_config_parser::WorkerConfig;
+use hustsync_internal::util::expand_tilde;
+use serde::{Deserialize, Serialize};
+
+use crate::job::{MirrorJob, STATE_DISABLED, STATE_PAUSED};
+use crate::schedule::ScheduleQueue;
+
+/// A state a job keeps until someone starts it again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Held {
+    Paused,
+    Disabled,
+}
+
+impl Held {
+    fn of(state: u32) -> Option<Self> {
+        match state {
+            STATE_PAUSED => Some(Self::Paused),
+            STATE_DISABLED => Some(Self::Disabled),
+            _ => None,
+        }
+    }
+
+    pub fn state(self) -> u32 {
+        match self {
+            Self::Paused => STATE_PAUSED,
+            Self::Disabled => STATE_DISABLED,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedJob {
+    /// When the job is queued to run; unset while it runs, waits for its
+    /// sources, or is held.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub held: Option<Held>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedState {
+    #[serde(default)]
+    pub jobs: BTreeMap<String, SavedJob>,
+}
+
+impl SavedState {
+    /// The state of `jobs` and their places in `queue`.
+    pub fn capture(jobs: &HashMap<String, MirrorJob>, queue: &ScheduleQueue) -> Self {
+        let mut saved: BTreeMap<String, SavedJob> = jobs
+            .iter()
+            .map(|(name, job)| {
+                let entry = SavedJob {
+                    next_run: None,
+                    held: Held::of(job.state()),
+                };
+                (name.clone(), entry)
+            })
+            .collect();
+        for info in queue.get_jobs() {
+            if let Some(entry) = saved.get_mut(&info.job_name) {
+                entry.next_run = Some(info.next_scheduled);
+            }
+        }
+        Self { jobs: saved }
+    }
+}
+
+#[derive(Debug)]
+pub struct StateFile {
+    path: PathBuf,
+    /// Held while writing, so the periodic save and the one at shutdown do
+    /// not share the temporary file.
+    writing: Mutex<()>,
+}
+
+impl StateFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writing: Mutex::new(()),
+        }
+    }
+
+    /// The worker's `global.state_file`, if set.
+    pub fn from_config(cfg: &WorkerConfig) -> Option<Self> {
+        let path = cfg
+            .global
+            .as_ref()
+            .and_then(|g| g.state_file.as_deref())
+            .filter(|p| !p.is_empty())?;
+        Some(Self::new(expand_tilde(path)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The saved state; `None` when there is none yet or it cannot be read.
+    pub fn load(&self) -> Option<SavedState> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!("Cannot read state file {}: {}", self.path.display(), e);
+                return None;
+            }
+        };
+        match serde_json::from_slice(&data) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                tracing::warn!("Ignoring corrupt state file {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Replace the file with `state`. Readers see the old or the new
+    /// contents, never a partial write.
+    pub fn save(&self, state: &SavedState) -> io::Result<()> {
+        let _writing = self.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut file = File::create(&tmp)?;
+        serde_json::to_writer_pretty(&mut file, state)?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SavedState {
+        let next = "2026-10-18T04:00:00Z".parse().unwrap();
+        SavedState {
+            jobs: [
+                (
+                    "arch".to_string(),
+                    SavedJob {
+                        next_run: Some(next),
+                        held: None,
+                    },
+                ),
+                (
+                    "debian".to_string(),
+                    SavedJob {
+                        next_run: None,
+                        held: Some(Held::Paused),
+                    },
+                ),
+            ]
+            .into(),
+        }
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = StateFile::new(tmp.path().join("state/worker.json"));
+        assert_eq!(file.load(), None);
+
+        file.save(&sample()).unwrap();
+        assert_eq!(file.load(), Some(sample()));
+        let text = fs::read_to_string(file.path()).unwrap();
+        assert!(text.contains("\"held\": \"paused\""), "{text}");
+        assert!(!tmp.path().join("state/worker.json.tmp").exists());
+    }
+
+    #[test]
+    fn corrupt_file_loads_as_nothing() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = StateFile::new(tmp.path().join("worker.json"));
+        fs::write(file.path(), "{\"jobs\": ").unwrap();
+        assert_eq!(file.load(), None);
+    }
+}
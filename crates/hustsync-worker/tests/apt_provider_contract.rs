@@ -0,0 +1,391 @@
+//! Contract tests for the `apt` provider.
+//!
+//! A local axum server plays a Debian archive built in memory: pool files,
+//! `Packages.gz` per architecture (the uncompressed `Packages` is listed in
+//! `Release` but not served, as on real mirrors), an unsigned `InRelease`
+//! and `Release` with `Acquire-By-Hash: yes`, and the matching `by-hash`
+//! copies. Every request is recorded.
+//!
+//! Scenarios:
+//! 1. the first run mirrors the selected architecture, fetches indices by
+//!    hash, publishes `dists/` and reports `data_size`
+//! 2. a second run with nothing new fetches no pool files
+//! 3. a pool file whose SHA256 does not match fails the run and leaves the
+//!    previously published metadata in place
+//! 4. a package missing upstream keeps the new metadata unpublished until
+//!    it shows up
+//! 5. a Release entry escaping `dists/` fails the run before anything is
+//!    written for it
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_worker::provider::{MirrorProvider, ProviderError, RunContext, build_provider};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+const SUITE: &str = "stable";
+
+#[derive(Default)]
+struct Archive {
+    /// Path relative to the archive root → body.
+    files: BTreeMap<String, Vec<u8>>,
+    /// Every path requested, in order.
+    hits: Vec<String>,
+}
+
+type Shared = Arc<Mutex<Archive>>;
+
+fn sha256(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(data).unwrap();
+    enc.finish().unwrap()
+}
+
+/// A package: (name, architecture, .deb content).
+type Package = (&'static str, &'static str, &'static str);
+
+impl Archive {
+    /// Rebuild `dists/stable` for `packages`. Pool files are added with
+    /// their real content; `Packages` always carries the real SHA256.
+    fn publish(&mut self, packages: &[Package]) {
+        let mut sums = Vec::new();
+        for arch in ["amd64", "arm64"] {
+            let mut index = String::new();
+            for (name, pkg_arch, body) in packages.iter().filter(|p| p.1 == arch) {
+                let filename = format!("pool/main/{}/{name}/{name}_1.0_{pkg_arch}.deb", &name[..1]);
+                index.push_str(&format!(
+                    "Package: {name}\nArchitecture: {pkg_arch}\nFilename: {filename}\n\
+                     Size: {}\nSHA256: {}\nDescription: test\n\n",
+                    body.len(),
+                    sha256(body.as_bytes())
+                ));
+                self.files.insert(filename, body.as_bytes().to_vec());
+            }
+            let dir = format!("main/binary-{arch}");
+            let plain = index.into_bytes();
+            let gz = gzip(&plain);
+            sums.push((format!("{dir}/Packages"), plain));
+            sums.push((format!("{dir}/Packages.gz"), gz.clone()));
+            self.files
+                .insert(format!("dists/{SUITE}/{dir}/Packages.gz"), gz.clone());
+            self.files.insert(
+                format!("dists/{SUITE}/{dir}/by-hash/SHA256/{}", sha256(&gz)),
+                gz,
+            );
+        }
+
+        let mut release = format!(
+            "Origin: Test\nSuite: {SUITE}\nComponents: main\n\
+             Architectures: amd64 arm64\nAcquire-By-Hash: yes\nSHA256:\n"
+        );
+        for (path, body) in &sums {
+            release.push_str(&format!(" {} {} {path}\n", sha256(body), body.len()));
+        }
+        self.files.insert(
+            format!("dists/{SUITE}/Release"),
+            release.clone().into_bytes(),
+        );
+        self.files
+            .insert(format!("dists/{SUITE}/InRelease"), release.into_bytes());
+    }
+
+    fn hits_under(&self, prefix: &str) -> Vec<String> {
+        self.hits
+            .iter()
+            .filter(|p| p.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+async fn serve(State(archive): State<Shared>, uri: Uri) -> Response {
+    let path = uri.path().trim_start_matches('/').to_string();
+    let mut archive = archive.lock().unwrap();
+    archive.hits.push(path.clone());
+    match archive.files.get(&path) {
+        Some(body) => body.clone().into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn start_archive(archive: Shared) -> String {
+    let app = Router::new().fallback(serve).with_state(archive);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}/")
+}
+
+fn apt_mirror(upstream: &str, dir: &TempDir) -> Box<dyn MirrorProvider> {
+    let mirror = MirrorConfig {
+        name: Some("debian".into()),
+        provider: Some("apt".into()),
+        upstream: Some(upstream.into()),
+        apt_suites: Some(vec![SUITE.into()]),
+        apt_components: Some(vec!["main".into()]),
+        apt_architectures: Some(vec!["amd64".into()]),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+    build_provider("debian", &mirror, &worker).unwrap()
+}
+
+fn ctx() -> RunContext {
+    RunContext {
+        attempt: 1,
+        ..RunContext::default()
+    }
+}
+
+const V1: &[Package] = &[
+    ("hello", "amd64", "hello-amd64-deb"),
+    ("hello", "arm64", "hello-arm64-deb"),
+    ("zlib", "amd64", "zlib-deb"),
+];
+
+fn seeded() -> Shared {
+    let mut archive = Archive::default();
+    archive.publish(V1);
+    Arc::new(Mutex::new(archive))
+}
+
+fn read(root: &Path, rel: &str) -> String {
+    std::fs::read_to_string(root.join(rel)).unwrap()
+}
+
+// ---------------------------------------------------------------------------
+// 1. initial mirror
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn first_run_mirrors_selected_architecture() {
+    let archive = seeded();
+    let url = start_archive(Arc::clone(&archive)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = apt_mirror(&url, &dir);
+
+    provider.run(ctx()).await.unwrap();
+
+    let root = provider.working_dir().to_path_buf();
+    assert_eq!(
+        read(&root, "pool/main/h/hello/hello_1.0_amd64.deb"),
+        "hello-amd64-deb"
+    );
+    assert_eq!(
+        read(&root, "pool/main/z/zlib/zlib_1.0_amd64.deb"),
+        "zlib-deb"
+    );
+    assert!(!root.join("pool/main/h/hello/hello_1.0_arm64.deb").exists());
+    assert!(!root.join("dists/stable/main/binary-arm64").exists());
+    assert!(!root.join(".hustsync-apt-staging").exists());
+    assert!(provider.data_size().await.is_some());
+
+    let upstream = archive.lock().unwrap();
+    assert_eq!(
+        read(&root, "dists/stable/InRelease"),
+        String::from_utf8(upstream.files["dists/stable/InRelease"].clone()).unwrap()
+    );
+    let gz = &upstream.files["dists/stable/main/binary-amd64/Packages.gz"];
+    assert_eq!(
+        &std::fs::read(root.join("dists/stable/main/binary-amd64/Packages.gz")).unwrap(),
+        gz
+    );
+    let by_hash = format!(
+        "dists/stable/main/binary-amd64/by-hash/SHA256/{}",
+        sha256(gz)
+    );
+    assert!(root.join(&by_hash).is_file());
+    // The index itself was fetched through by-hash.
+    assert!(upstream.hits.contains(&by_hash));
+    assert!(
+        !upstream
+            .hits
+            .contains(&"dists/stable/main/binary-amd64/Packages.gz".to_string())
+    );
+    drop(upstream);
+}
+
+// ---------------------------------------------------------------------------
+// 2. nothing new
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn unchanged_archive_fetches_no_pool_files() {
+    let archive = seeded();
+    let url = start_archive(Arc::clone(&archive)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = apt_mirror(&url, &dir);
+
+    provider.run(ctx()).await.unwrap();
+    archive.lock().unwrap().hits.clear();
+    provider.run(ctx()).await.unwrap();
+
+    let (pool_hits, index_hits, gz_sha) = {
+        let archive = archive.lock().unwrap();
+        (
+            archive.hits_under("pool/"),
+            archive.hits_under("dists/stable/main/"),
+            sha256(&archive.files["dists/stable/main/binary-amd64/Packages.gz"]),
+        )
+    };
+    assert!(pool_hits.is_empty(), "{pool_hits:?}");
+    // Only the uncompressed `Packages`, listed but never served, is
+    // asked for again.
+    for hit in index_hits {
+        assert!(
+            !hit.ends_with(".gz") && !hit.ends_with(&gz_sha),
+            "{hit} fetched again"
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 3. checksum mismatch
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn checksum_mismatch_keeps_old_metadata() {
+    let archive = seeded();
+    let url = start_archive(Arc::clone(&archive)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = apt_mirror(&url, &dir);
+
+    provider.run(ctx()).await.unwrap();
+    let root = provider.working_dir().to_path_buf();
+    let old_release = read(&root, "dists/stable/InRelease");
+    let old_size = provider.data_size().await;
+
+    {
+        let mut archive = archive.lock().unwrap();
+        let mut v2 = V1.to_vec();
+        v2.push(("curl", "amd64", "curl-deb"));
+        archive.publish(&v2);
+        // Same length, different bytes: only the SHA256 can tell.
+        archive.files.insert(
+            "pool/main/c/curl/curl_1.0_amd64.deb".into(),
+            b"CURL-DEB".to_vec(),
+        );
+    }
+    let err = provider.run(ctx()).await.unwrap_err();
+
+    assert!(matches!(err, ProviderError::Checksum(_)), "got {err:?}");
+    assert!(!root.join("pool/main/c/curl/curl_1.0_amd64.deb").exists());
+    assert_eq!(read(&root, "dists/stable/InRelease"), old_release);
+    assert_eq!(provider.data_size().await, old_size);
+}
+
+// ---------------------------------------------------------------------------
+// 4. publish only when complete
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn metadata_is_published_only_after_every_package() {
+    let archive = seeded();
+    let url = start_archive(Arc::clone(&archive)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = apt_mirror(&url, &dir);
+
+    provider.run(ctx()).await.unwrap();
+    let root = provider.working_dir().to_path_buf();
+    let old_packages =
+        std::fs::read(root.join("dists/stable/main/binary-amd64/Packages.gz")).unwrap();
+
+    {
+        let mut archive = archive.lock().unwrap();
+        let mut v2 = V1.to_vec();
+        v2.push(("curl", "amd64", "curl-deb"));
+        v2.push(("wget", "amd64", "wget-deb"));
+        archive.publish(&v2);
+        archive.files.remove("pool/main/w/wget/wget_1.0_amd64.deb");
+    }
+    let err = provider.run(ctx()).await.unwrap_err();
+    assert!(
+        matches!(&err, ProviderError::Http(msg) if msg.contains("wget")),
+        "got {err:?}"
+    );
+    // curl arrived, but nothing points at it yet.
+    assert!(root.join("pool/main/c/curl/curl_1.0_amd64.deb").exists());
+    assert_eq!(
+        std::fs::read(root.join("dists/stable/main/binary-amd64/Packages.gz")).unwrap(),
+        old_packages
+    );
+    assert!(!read(&root, "dists/stable/InRelease").contains("curl"));
+
+    let new_release = {
+        let mut archive = archive.lock().unwrap();
+        archive.files.insert(
+            "pool/main/w/wget/wget_1.0_amd64.deb".into(),
+            b"wget-deb".to_vec(),
+        );
+        String::from_utf8(archive.files["dists/stable/InRelease"].clone()).unwrap()
+    };
+    provider.run(ctx()).await.unwrap();
+
+    assert_eq!(read(&root, "dists/stable/InRelease"), new_release);
+    assert_eq!(
+        read(&root, "pool/main/w/wget/wget_1.0_amd64.deb"),
+        "wget-deb"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// 5. unsafe Release paths
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn unsafe_release_path_fails_run() {
+    let archive = seeded();
+    {
+        let mut archive = archive.lock().unwrap();
+        let body = b"escaped".to_vec();
+        let entry = format!(
+            " {} {} main/binary-amd64/../../../../escape\n",
+            sha256(&body),
+            body.len()
+        );
+        for name in ["InRelease", "Release"] {
+            let key = format!("dists/{SUITE}/{name}");
+            let mut release = String::from_utf8(archive.files[&key].clone()).unwrap();
+            release.push_str(&entry);
+            archive.files.insert(key, release.into_bytes());
+        }
+        archive.files.insert("escape".into(), body);
+    }
+    let url = start_archive(Arc::clone(&archive)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = apt_mirror(&url, &dir);
+
+    let err = provider.run(ctx()).await.unwrap_err();
+
+    assert!(
+        matches!(&err, ProviderError::Http(msg) if msg.contains("unsafe path")),
+        "got {err:?}"
+    );
+    let root = provider.working_dir();
+    assert!(!root.join("escape").exists());
+    assert!(!root.parent().unwrap().join("escape").exists());
+    assert!(!root.join("dists").exists());
+}
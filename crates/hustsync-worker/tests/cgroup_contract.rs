@@ -0,0 +1,251 @@
+//! Contract tests for per-mirror cgroup v2 confinement.
+//!
+//! A temp dir with a `cgroup.controllers` file stands in for the cgroupfs
+//! mount (`[cgroup] base_path`). The kernel would move every forked child
+//! into the leader's cgroup on its own; the fake cannot, so scenarios that
+//! need a straggler append its PID to `cgroup.procs` from the job script.
+//!
+//! Scenarios:
+//! 1. `memory_limit` / `cpu_weight` land in `memory.max` / `cpu.weight`, and
+//!    the provider's process writes its own pid to `cgroup.procs` before
+//!    it execs
+//! 2. a straggler that escaped the process group is killed when the run ends
+//! 3. `terminate()` kills everything in the cgroup
+//! 4. a base path that is not a cgroup v2 hierarchy fails the run
+//! 5. containerised mirrors are not placed in a cgroup
+//! 6. a process that cannot join its cgroup is never started
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hustsync_config_parser::{
+    MirrorConfig, WorkerCgroupConfig, WorkerConfig, WorkerDockerConfig, WorkerGlobalConfig,
+};
+use hustsync_worker::provider::{MirrorProvider, ProviderError, RunContext, build_provider};
+use tempfile::TempDir;
+
+fn fake_cgroup_root(dir: &TempDir) -> PathBuf {
+    let root = dir.path().join("cgroupfs");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("cgroup.controllers"), "cpu memory pids\n").unwrap();
+    root
+}
+
+fn worker_config(dir: &TempDir, base_path: &Path) -> WorkerConfig {
+    WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(
+                dir.path()
+                    .join("log/{{.Name}}")
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            mirror_dir: Some(dir.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        cgroup: Some(WorkerCgroupConfig {
+            enable: Some(true),
+            base_path: Some(base_path.to_string_lossy().into_owned()),
+            group: Some("hustsync".into()),
+        }),
+        ..WorkerConfig::default()
+    }
+}
+
+fn command_mirror(name: &str, command: &str, leaf: &Path) -> MirrorConfig {
+    MirrorConfig {
+        name: Some(name.to_string()),
+        provider: Some("command".to_string()),
+        upstream: Some("https://upstream.example/".to_string()),
+        command: Some(command.to_string()),
+        memory_limit: Some("256M".to_string()),
+        cpu_weight: Some(20),
+        env: Some(HashMap::from([(
+            "CG_PROCS".to_string(),
+            leaf.join("cgroup.procs").to_string_lossy().into_owned(),
+        )])),
+        ..MirrorConfig::default()
+    }
+}
+
+/// Whether `pid` is still running (zombies count as dead).
+fn is_alive(pid: i32) -> bool {
+    match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        Ok(stat) => stat
+            .rsplit_once(") ")
+            .map(|(_, rest)| !rest.starts_with('Z'))
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+async fn wait_for_pid_file(path: &Path) -> i32 {
+    for _ in 0..50 {
+        if let Ok(s) = tokio::fs::read_to_string(path).await
+            && let Ok(pid) = s.trim().parse()
+        {
+            return pid;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{} never appeared", path.display());
+}
+
+async fn assert_dies(pid: i32) {
+    for _ in 0..50 {
+        if !is_alive(pid) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("pid {pid} survived the cgroup kill");
+}
+
+#[tokio::test]
+async fn limits_and_process_are_written_to_mirror_cgroup() {
+    let dir = TempDir::new().unwrap();
+    let root = fake_cgroup_root(&dir);
+    let leaf = root.join("hustsync/tools");
+    let pid_file = dir.path().join("leader.pid");
+    let g_cfg = worker_config(&dir, &root);
+    let m_cfg = command_mirror(
+        "tools",
+        &format!("sh -c 'echo $$ > {}'", pid_file.display()),
+        &leaf,
+    );
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(leaf.join("memory.max")).unwrap(),
+        (256u64 << 20).to_string()
+    );
+    assert_eq!(
+        std::fs::read_to_string(leaf.join("cpu.weight")).unwrap(),
+        "20"
+    );
+    let procs = std::fs::read_to_string(leaf.join("cgroup.procs")).unwrap();
+    assert_eq!(
+        procs,
+        std::fs::read_to_string(&pid_file).unwrap(),
+        "cgroup.procs must hold the provider pid"
+    );
+    let subtree = std::fs::read_to_string(root.join("hustsync/cgroup.subtree_control")).unwrap();
+    assert!(
+        subtree.contains("cpu"),
+        "controllers must be delegated to the mirror cgroups, got: {subtree:?}"
+    );
+}
+
+#[tokio::test]
+async fn straggler_is_killed_when_run_ends() {
+    let dir = TempDir::new().unwrap();
+    let root = fake_cgroup_root(&dir);
+    let leaf = root.join("hustsync/tools");
+    let pid_file = dir.path().join("straggler.pid");
+    let g_cfg = worker_config(&dir, &root);
+    let m_cfg = command_mirror(
+        "tools",
+        &format!(
+            "sh -c 'sleep 0.3; setsid sleep 30 & echo $! >> \"$CG_PROCS\"; echo $! > {}'",
+            pid_file.display()
+        ),
+        &leaf,
+    );
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+
+    let straggler = wait_for_pid_file(&pid_file).await;
+    assert_dies(straggler).await;
+}
+
+#[tokio::test]
+async fn terminate_kills_whole_cgroup() {
+    let dir = TempDir::new().unwrap();
+    let root = fake_cgroup_root(&dir);
+    let leaf = root.join("hustsync/slow");
+    let pid_file = dir.path().join("straggler.pid");
+    let g_cfg = worker_config(&dir, &root);
+    let m_cfg = command_mirror(
+        "slow",
+        &format!(
+            "sh -c 'sleep 0.3; setsid sleep 30 & echo $! >> \"$CG_PROCS\"; echo $! > {}; sleep 30'",
+            pid_file.display()
+        ),
+        &leaf,
+    );
+
+    let provider: Arc<dyn MirrorProvider> = build_provider("slow", &m_cfg, &g_cfg).unwrap().into();
+    let runner = Arc::clone(&provider);
+    let run_fut = tokio::spawn(async move { runner.run(RunContext::default()).await });
+
+    let straggler = wait_for_pid_file(&pid_file).await;
+    provider.terminate().await.unwrap();
+    assert_dies(straggler).await;
+
+    let res = tokio::time::timeout(Duration::from_secs(10), run_fut)
+        .await
+        .expect("run must return after terminate")
+        .expect("task join ok");
+    assert!(res.is_err(), "terminated run must not report success");
+}
+
+#[tokio::test]
+async fn non_cgroup2_base_path_fails_run() {
+    let dir = TempDir::new().unwrap();
+    let not_cgroupfs = dir.path().join("plain");
+    std::fs::create_dir_all(&not_cgroupfs).unwrap();
+    let g_cfg = worker_config(&dir, &not_cgroupfs);
+    let m_cfg = command_mirror("tools", "echo never", &not_cgroupfs);
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    let err = provider.run(RunContext::default()).await.unwrap_err();
+    assert!(
+        matches!(&err, ProviderError::Io(e) if e.to_string().contains("cgroup v2")),
+        "got: {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn containerised_mirror_skips_cgroup() {
+    let dir = TempDir::new().unwrap();
+    let root = fake_cgroup_root(&dir);
+    let mut g_cfg = worker_config(&dir, &root);
+    // `true` stands in for the runtime, so the container run is a no-op.
+    g_cfg.docker = Some(WorkerDockerConfig {
+        enable: Some(true),
+        runtime: Some("true".into()),
+        volumes: None,
+        options: None,
+    });
+    let mut m_cfg = command_mirror("tools", "echo hi", &root.join("hustsync/tools"));
+    m_cfg.docker_image = Some("alpine:3".into());
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+
+    assert!(!root.join("hustsync").exists());
+}
+
+#[tokio::test]
+async fn process_that_cannot_join_is_not_started() {
+    let dir = TempDir::new().unwrap();
+    let root = fake_cgroup_root(&dir);
+    let leaf = root.join("hustsync/tools");
+    // A directory in place of `cgroup.procs` makes the child's write fail.
+    std::fs::create_dir_all(leaf.join("cgroup.procs")).unwrap();
+    let ran = dir.path().join("ran");
+    let g_cfg = worker_config(&dir, &root);
+    let m_cfg = command_mirror("tools", &format!("touch {}", ran.display()), &leaf);
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    let err = provider.run(RunContext::default()).await.unwrap_err();
+    assert!(matches!(err, ProviderError::Io(_)), "got: {err:?}");
+    assert!(!ran.exists(), "the provider ran outside its cgroup");
+}
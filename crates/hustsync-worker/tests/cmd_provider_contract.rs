@@ -1,4 +1,5 @@
-// Contract tests for the cmd provider (env, fail_on_match, size, terminate).
+// Contract tests for the cmd provider (env, fail_on_match, size, terminate,
+// fallback upstreams).
 //
 // All six contract points are exercised against real process forks so that
 // the env-injection, fail_on_match, size_pattern, terminate, and timeout
@@ -50,6 +51,29 @@ mod cmd_provider_contract {
         fail_on_match: Option<String>,
         size_pattern: Option<String>,
         env: HashMap<String, String>,
+    ) -> CmdProvider {
+        make_provider_with_fallbacks(
+            dir,
+            name,
+            command,
+            timeout,
+            fail_on_match,
+            size_pattern,
+            env,
+            vec![],
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn make_provider_with_fallbacks(
+        dir: &tempfile::TempDir,
+        name: &str,
+        command: &str,
+        timeout: Duration,
+        fail_on_match: Option<String>,
+        size_pattern: Option<String>,
+        env: HashMap<String, String>,
+        fallback_upstreams: Vec<String>,
     ) -> CmdProvider {
         let log_file = dir.path().join("run.log");
         let cfg = CmdProviderConfig {
@@ -65,10 +89,13 @@ mod cmd_provider_contract {
                 env,
                 is_master: true,
                 success_exit_codes: vec![],
+                cgroup: None,
             },
             command: command.to_string(),
             fail_on_match,
             size_pattern,
+            docker: None,
+            fallback_upstreams,
         };
         CmdProvider::new(cfg).unwrap()
     }
@@ -286,6 +313,9 @@ mod cmd_provider_contract {
             cancel: cancel.clone(),
             attempt: 1,
             env: HashMap::new(),
+            bandwidth: None,
+            progress: None,
+            allow_mass_delete: false,
         };
 
         let p = std::sync::Arc::clone(&provider);
@@ -329,9 +359,69 @@ mod cmd_provider_contract {
             cancel: CancellationToken::new(),
             attempt: 1,
             env: HashMap::new(),
+            bandwidth: None,
+            progress: None,
+            allow_mass_delete: false,
         };
 
         let result = provider.run(ctx).await;
         assert!(result.is_ok(), "expected Ok(()), got {:?}", result);
     }
+
+    // ── 7. fallback upstreams ────────────────────────────────────────────────
+
+    /// A script that exits with a connection-class code is rerun with the
+    /// next upstream in `HUSTSYNC_UPSTREAM_URL`, and the provider reports
+    /// the upstream that succeeded.
+    #[tokio::test]
+    async fn connection_exit_code_reruns_with_next_upstream() {
+        let dir = tempdir().unwrap();
+        let provider = make_provider_with_fallbacks(
+            &dir,
+            "fallback-mirror",
+            r#"sh -c 'echo "try $HUSTSYNC_UPSTREAM_URL"; case "$HUSTSYNC_UPSTREAM_URL" in *upstream.example*) exit 35 ;; esac'"#,
+            Duration::ZERO,
+            None,
+            None,
+            HashMap::new(),
+            vec!["https://backup.example/".to_string()],
+        );
+
+        provider.run(RunContext::default()).await.unwrap();
+        assert_eq!(provider.upstream(), "https://backup.example/");
+
+        let log = tokio::fs::read_to_string(provider.log_file())
+            .await
+            .unwrap();
+        assert!(log.contains("try https://upstream.example/"), "log:\n{log}");
+        assert!(log.contains("try https://backup.example/"), "log:\n{log}");
+    }
+
+    /// Ordinary failures are final: the fallback is never tried.
+    #[tokio::test]
+    async fn other_exit_codes_do_not_fail_over() {
+        let dir = tempdir().unwrap();
+        let provider = make_provider_with_fallbacks(
+            &dir,
+            "no-fallback-mirror",
+            r#"sh -c 'echo "try $HUSTSYNC_UPSTREAM_URL"; exit 1'"#,
+            Duration::ZERO,
+            None,
+            None,
+            HashMap::new(),
+            vec!["https://backup.example/".to_string()],
+        );
+
+        let result = provider.run(RunContext::default()).await;
+        assert!(matches!(
+            result,
+            Err(ProviderError::Execution { code: 1, .. })
+        ));
+        assert_eq!(provider.upstream(), "https://upstream.example/");
+
+        let log = tokio::fs::read_to_string(provider.log_file())
+            .await
+            .unwrap();
+        assert!(!log.contains("backup.example"), "log:\n{log}");
+    }
 }
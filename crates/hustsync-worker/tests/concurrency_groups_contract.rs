@@ -0,0 +1,125 @@
+//! Contract tests for concurrency groups.
+//!
+//! Scenarios:
+//! 1. with `concurrent_per_host = 1`, a second mirror on the same upstream
+//!    host waits for the first to finish, although the worker-wide limit
+//!    has room
+//! 2. `ForceStart` runs past a full group
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_internal::status::SyncStatus;
+use hustsync_worker::concurrency::ConcurrencyGroups;
+use hustsync_worker::job::{CtrlAction, JobActor, JobOptions};
+use hustsync_worker::provider::build_provider;
+use hustsync_worker::{JobMessage, MirrorJob};
+use tempfile::TempDir;
+use tokio::sync::{Semaphore, mpsc};
+
+fn worker(dir: &Path) -> WorkerConfig {
+    WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.join("mirrors").to_string_lossy().into_owned()),
+            concurrent_per_host: Some(1),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    }
+}
+
+fn spawn_job(
+    name: &str,
+    command: &str,
+    worker: &WorkerConfig,
+    groups: &ConcurrencyGroups,
+    semaphore: &Arc<Semaphore>,
+) -> (MirrorJob, mpsc::Receiver<JobMessage>) {
+    let mirror = MirrorConfig {
+        name: Some(name.into()),
+        provider: Some("command".into()),
+        upstream: Some(format!("rsync://rsync.archlinux.org/{name}/")),
+        command: Some(command.into()),
+        ..MirrorConfig::default()
+    };
+    let (manager_tx, manager_rx) = mpsc::channel(64);
+    let (job, actor) = JobActor::new(
+        name.into(),
+        manager_tx,
+        Arc::clone(semaphore),
+        None,
+        build_provider(name, &mirror, worker).unwrap(),
+        vec![],
+        JobOptions {
+            groups: groups.for_mirror(&mirror),
+            ..JobOptions::default()
+        },
+    );
+    tokio::spawn(actor.run());
+    (job, manager_rx)
+}
+
+/// The next report with `status`, or `None` if none arrives in `within`.
+async fn next_with(
+    rx: &mut mpsc::Receiver<JobMessage>,
+    status: SyncStatus,
+    within: Duration,
+) -> Option<JobMessage> {
+    tokio::time::timeout(within, async {
+        loop {
+            let msg = rx.recv().await.expect("manager channel closed");
+            if msg.status == status {
+                return msg;
+            }
+        }
+    })
+    .await
+    .ok()
+}
+
+#[tokio::test]
+async fn same_host_waits_unless_forced() {
+    let tmp = TempDir::new().unwrap();
+    let worker = worker(tmp.path());
+    let groups = ConcurrencyGroups::from_config(&worker);
+    let semaphore = Arc::new(Semaphore::new(10));
+    let long = Duration::from_secs(10);
+
+    let (core, mut core_rx) = spawn_job("core", "sleep 2", &worker, &groups, &semaphore);
+    let (extra, mut extra_rx) = spawn_job("extra", "true", &worker, &groups, &semaphore);
+    let (forced, mut forced_rx) = spawn_job("forced", "true", &worker, &groups, &semaphore);
+
+    core.send_ctrl(CtrlAction::Start).await.unwrap();
+    next_with(&mut core_rx, SyncStatus::Syncing, long)
+        .await
+        .expect("core started");
+
+    extra.send_ctrl(CtrlAction::Start).await.unwrap();
+    assert!(
+        next_with(
+            &mut extra_rx,
+            SyncStatus::PreSyncing,
+            Duration::from_millis(500)
+        )
+        .await
+        .is_none(),
+        "extra must wait for the host's only slot"
+    );
+
+    forced.send_ctrl(CtrlAction::ForceStart).await.unwrap();
+    next_with(&mut forced_rx, SyncStatus::Success, long)
+        .await
+        .expect("forced run ignores the group");
+
+    next_with(&mut core_rx, SyncStatus::Success, long)
+        .await
+        .expect("core finished");
+    next_with(&mut extra_rx, SyncStatus::Success, long)
+        .await
+        .expect("extra ran once core let go");
+}
@@ -18,13 +18,15 @@
 mod contract_cmd {
     use std::collections::HashMap;
     use std::sync::Arc;
-    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
-    use chrono::{Duration as ChronoDuration, Utc};
+    use chrono::{Duration as ChronoDuration, Local, Timelike, Utc};
     use http_body_util::BodyExt as _;
+    use hustsync_config_parser::{BlackoutWindowConfig, MirrorConfig, WorkerConfig};
     use hustsync_internal::msg::{CmdVerb, WorkerCmd};
     use hustsync_worker::MirrorJob;
-    use hustsync_worker::job::{CtrlAction, STATE_DISABLED};
+    use hustsync_worker::blackout::Blackout;
+    use hustsync_worker::job::{CtrlAction, STATE_DISABLED, STATE_READY};
     use hustsync_worker::schedule::ScheduleQueue;
     use hustsync_worker::server::{AppState, make_http_server};
     use serde_json::Value;
@@ -45,7 +47,11 @@ mod contract_cmd {
             tx,
             state: Arc::new(AtomicU32::new(hustsync_worker::job::STATE_NONE)),
             disabled: Arc::new(tokio::sync::Notify::new()),
+            allow_mass_delete: Arc::new(AtomicBool::new(false)),
             interval: tokio::time::Duration::from_secs(3600),
+            schedule: None,
+            blackout: None,
+            after: Arc::from([]),
         };
         (job, rx)
     }
@@ -219,6 +225,114 @@ mod contract_cmd {
         assert_eq!(body["msg"], "Invalid Command");
     }
 
+    /// Start with `allow_mass_delete` arms the job's one-shot override
+    /// before sending Start; a plain Start leaves it alone.
+    #[tokio::test]
+    async fn test_start_with_allow_mass_delete_arms_override() {
+        let (state, mut rx) = state_with_job("archlinux");
+        let allow = Arc::clone(&state.jobs.read().await["archlinux"].allow_mass_delete);
+
+        post_cmd(Arc::clone(&state), worker_cmd("archlinux", CmdVerb::Start)).await;
+        expect_action(&mut rx, CtrlAction::Start).await;
+        assert!(!allow.load(Ordering::Acquire));
+
+        let mut cmd = worker_cmd("archlinux", CmdVerb::Start);
+        cmd["options"] = serde_json::json!({"allow_mass_delete": true});
+        let (status, _) = post_cmd(state, cmd).await;
+
+        assert_eq!(status, 200);
+        expect_action(&mut rx, CtrlAction::Start).await;
+        assert!(allow.load(Ordering::Acquire));
+    }
+
+    /// `allow_mass_delete` is not armed for a Start that starts nothing:
+    /// one deferred by a blackout window, or one sent to a running job.
+    #[tokio::test]
+    async fn test_allow_mass_delete_not_armed_without_a_run() {
+        let mut cmd = worker_cmd("archlinux", CmdVerb::Start);
+        cmd["options"] = serde_json::json!({"allow_mass_delete": true});
+
+        let (job, _rx) = make_mirror_job("archlinux");
+        let job = MirrorJob {
+            blackout: Some(Arc::new(blackout_around_now())),
+            ..job
+        };
+        let allow = Arc::clone(&job.allow_mass_delete);
+        let state = Arc::new(AppState {
+            jobs: Arc::new(RwLock::new(HashMap::from([("archlinux".to_string(), job)]))),
+            schedule_queue: Arc::new(Mutex::new(ScheduleQueue::new())),
+            reload_tx: None,
+        });
+        let (_, body) = post_cmd(state, cmd.clone()).await;
+        assert!(!allow.load(Ordering::Acquire), "deferred start armed it");
+        let msg = body["msg"].as_str().unwrap();
+        assert!(
+            msg.contains("allow_mass_delete was not kept"),
+            "got {msg:?}"
+        );
+
+        let (state, mut rx) = state_with_job("archlinux");
+        let allow = {
+            let jobs = state.jobs.read().await;
+            jobs["archlinux"].set_state(STATE_READY);
+            Arc::clone(&jobs["archlinux"].allow_mass_delete)
+        };
+        post_cmd(state, cmd).await;
+        expect_action(&mut rx, CtrlAction::Start).await;
+        assert!(
+            !allow.load(Ordering::Acquire),
+            "start of a running job armed it"
+        );
+    }
+
+    /// A blackout window covering the next hour either side of now.
+    fn blackout_around_now() -> Blackout {
+        let now = Local::now();
+        let minute = now.hour() * 60 + now.minute();
+        let hhmm = |m: u32| format!("{:02}:{:02}", m / 60 % 24, m % 60);
+        let mirror = MirrorConfig {
+            blackout: Some(vec![BlackoutWindowConfig {
+                start: Some(hhmm(minute + 23 * 60)),
+                end: Some(hhmm(minute + 60)),
+                days: None,
+            }]),
+            ..MirrorConfig::default()
+        };
+        Blackout::from_config("archlinux", &mirror, &WorkerConfig::default()).unwrap()
+    }
+
+    /// Start inside a blackout window is queued for the dispatch loop to
+    /// defer, not sent; `force` still starts at once.
+    #[tokio::test]
+    async fn test_start_in_blackout_is_deferred_unless_forced() {
+        let (job, mut rx) = make_mirror_job("archlinux");
+        let job = MirrorJob {
+            blackout: Some(Arc::new(blackout_around_now())),
+            ..job
+        };
+        let state = Arc::new(AppState {
+            jobs: Arc::new(RwLock::new(HashMap::from([("archlinux".to_string(), job)]))),
+            schedule_queue: Arc::new(Mutex::new(ScheduleQueue::new())),
+            reload_tx: None,
+        });
+
+        let (status, body) =
+            post_cmd(Arc::clone(&state), worker_cmd("archlinux", CmdVerb::Start)).await;
+        assert_eq!(status, 200);
+        let msg = body["msg"].as_str().unwrap();
+        assert!(msg.contains("blackout window"), "got {msg:?}");
+        assert!(rx.try_recv().is_err(), "no action while blacked out");
+        let queued = state.schedule_queue.lock().await.get_jobs();
+        assert_eq!(queued.len(), 1);
+        assert!(queued[0].next_scheduled <= Utc::now());
+
+        let mut cmd = worker_cmd("archlinux", CmdVerb::Start);
+        cmd["options"] = serde_json::json!({"force": true});
+        post_cmd(Arc::clone(&state), cmd).await;
+        expect_action(&mut rx, CtrlAction::ForceStart).await;
+        assert_schedule_flushed(&state, "archlinux").await;
+    }
+
     /// Stop on an active/non-disabled mirror flushes schedule and sends Stop.
     #[tokio::test]
     async fn test_stop_known_mirror_flushes_schedule_and_sends_stop() {
@@ -0,0 +1,234 @@
+//! Contract tests for running provider commands inside a container.
+//!
+//! `tests/fixtures/bin/fake_container_runtime.sh` stands in for the
+//! docker/podman CLI. Each test writes a tiny wrapper script into its
+//! temp dir that points `FAKE_RUNTIME_LOG` at a per-test file and execs
+//! the fixture, then configures that wrapper as `[docker] runtime`. The
+//! runtime log records every `run` / `stop` / `rm` invocation.
+//!
+//! Scenarios:
+//! 1. command mirror with `docker_image` runs through `<runtime> run`
+//! 2. provider env reaches the wrapped command, by key only in argv
+//! 3. cancelling a running job stops the container by name
+//! 4. `docker_image` without `[docker] enable` runs on the host
+//! 5. rsync mirror with `docker_image` is wrapped too
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerDockerConfig, WorkerGlobalConfig};
+use hustsync_worker::provider::{MirrorProvider, ProviderError, RunContext, build_provider};
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+
+fn fake_runtime_fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bin/fake_container_runtime.sh")
+}
+
+/// Write a per-test runtime wrapper into `dir` and return its path.
+fn install_runtime(dir: &TempDir) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let wrapper = dir.path().join("runtime");
+    let script = format!(
+        "#!/bin/sh\nFAKE_RUNTIME_LOG='{}' exec '{}' \"$@\"\n",
+        runtime_log(dir).display(),
+        fake_runtime_fixture().display()
+    );
+    std::fs::write(&wrapper, script).unwrap();
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755)).unwrap();
+    wrapper
+}
+
+fn runtime_log(dir: &TempDir) -> PathBuf {
+    dir.path().join("runtime.log")
+}
+
+fn read_runtime_log(dir: &TempDir) -> Vec<String> {
+    std::fs::read_to_string(runtime_log(dir))
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+fn worker_config(dir: &TempDir, enable: bool) -> WorkerConfig {
+    WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(
+                dir.path()
+                    .join("log/{{.Name}}")
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            mirror_dir: Some(dir.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        docker: Some(WorkerDockerConfig {
+            enable: Some(enable),
+            runtime: Some(install_runtime(dir).to_string_lossy().into_owned()),
+            volumes: Some(vec!["/etc/ssl:/etc/ssl:ro".to_string()]),
+            options: None,
+        }),
+        ..WorkerConfig::default()
+    }
+}
+
+fn command_mirror(name: &str, command: &str) -> MirrorConfig {
+    MirrorConfig {
+        name: Some(name.to_string()),
+        provider: Some("command".to_string()),
+        upstream: Some("https://upstream.example/".to_string()),
+        command: Some(command.to_string()),
+        docker_image: Some("ghcr.io/example/tools:latest".to_string()),
+        docker_options: Some(vec!["--network=host".to_string()]),
+        ..MirrorConfig::default()
+    }
+}
+
+async fn read_log(provider: &dyn MirrorProvider) -> String {
+    tokio::fs::read_to_string(Path::new(provider.log_file()))
+        .await
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn command_mirror_runs_through_container_runtime() {
+    let dir = TempDir::new().unwrap();
+    let g_cfg = worker_config(&dir, true);
+    let m_cfg = command_mirror("tools", "echo inside-container");
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+
+    assert!(
+        read_log(provider.as_ref())
+            .await
+            .contains("inside-container")
+    );
+
+    let calls = read_runtime_log(&dir);
+    assert_eq!(
+        calls[0], "rm -f hustsync-job-tools",
+        "stale container cleanup first"
+    );
+    let run = &calls[1];
+    assert!(run.starts_with("run --rm "), "got: {run}");
+    assert!(run.contains("--name hustsync-job-tools"), "got: {run}");
+    assert!(run.contains("-v /etc/ssl:/etc/ssl:ro"), "got: {run}");
+    assert!(
+        run.ends_with("--network=host ghcr.io/example/tools:latest echo inside-container"),
+        "options, image and command must come last in order, got: {run}"
+    );
+}
+
+#[tokio::test]
+async fn provider_env_is_forwarded_by_key_only() {
+    let dir = TempDir::new().unwrap();
+    let g_cfg = worker_config(&dir, true);
+    let mut m_cfg = command_mirror("tools", "sh -c 'echo token=$UPSTREAM_TOKEN'");
+    m_cfg.env = Some(HashMap::from([(
+        "UPSTREAM_TOKEN".to_string(),
+        "s3cr3t".to_string(),
+    )]));
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+
+    assert!(read_log(provider.as_ref()).await.contains("token=s3cr3t"));
+
+    let run = read_runtime_log(&dir)
+        .into_iter()
+        .find(|l| l.starts_with("run "))
+        .unwrap();
+    assert!(run.contains("-e UPSTREAM_TOKEN"), "got: {run}");
+    assert!(run.contains("-e HUSTSYNC_MIRROR_NAME"), "got: {run}");
+    assert!(
+        !run.contains("s3cr3t"),
+        "env values must stay out of argv: {run}"
+    );
+}
+
+#[tokio::test]
+async fn cancel_stops_container_by_name() {
+    let dir = TempDir::new().unwrap();
+    let g_cfg = worker_config(&dir, true);
+    let m_cfg = command_mirror("slow", "sleep 30");
+
+    let provider = build_provider("slow", &m_cfg, &g_cfg).unwrap();
+    let cancel = CancellationToken::new();
+    let ctx = RunContext {
+        cancel: cancel.clone(),
+        attempt: 1,
+        env: HashMap::new(),
+        bandwidth: None,
+        progress: None,
+        allow_mass_delete: false,
+    };
+
+    let run_fut = tokio::spawn(async move { provider.run(ctx).await });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    cancel.cancel();
+
+    let res = tokio::time::timeout(Duration::from_secs(10), run_fut)
+        .await
+        .expect("run must observe cancellation")
+        .expect("task join ok");
+    assert!(
+        matches!(res, Err(ProviderError::Terminated)),
+        "expected Terminated, got: {res:?}"
+    );
+
+    let calls = read_runtime_log(&dir);
+    assert!(
+        calls.iter().any(|l| l == "stop -t 2 hustsync-job-slow"),
+        "container must be stopped by name, got: {calls:?}"
+    );
+}
+
+#[tokio::test]
+async fn docker_image_is_ignored_when_docker_disabled() {
+    let dir = TempDir::new().unwrap();
+    let g_cfg = worker_config(&dir, false);
+    let m_cfg = command_mirror("tools", "echo on-host");
+
+    let provider = build_provider("tools", &m_cfg, &g_cfg).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+
+    assert!(read_log(provider.as_ref()).await.contains("on-host"));
+    assert!(read_runtime_log(&dir).is_empty());
+}
+
+#[tokio::test]
+async fn rsync_mirror_runs_through_container_runtime() {
+    let dir = TempDir::new().unwrap();
+    let g_cfg = worker_config(&dir, true);
+    // `sh` stands in for rsync so the wrapped argv is observable without a
+    // live rsync daemon.
+    let m_cfg = MirrorConfig {
+        name: Some("debian".to_string()),
+        provider: Some("rsync".to_string()),
+        upstream: Some("rsync://upstream.example/debian/".to_string()),
+        command: Some("sh".to_string()),
+        rsync_override: Some(vec!["-c".to_string(), "echo rsync-ran; exit 0".to_string()]),
+        rsync_override_only: Some(true),
+        docker_image: Some("ghcr.io/example/rsync:latest".to_string()),
+        ..MirrorConfig::default()
+    };
+
+    let provider = build_provider("debian", &m_cfg, &g_cfg).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+
+    assert!(read_log(provider.as_ref()).await.contains("rsync-ran"));
+    let run = read_runtime_log(&dir)
+        .into_iter()
+        .find(|l| l.starts_with("run "))
+        .unwrap();
+    assert!(
+        run.contains("ghcr.io/example/rsync:latest sh -c"),
+        "got: {run}"
+    );
+}
@@ -0,0 +1,46 @@
+#!/usr/bin/env bash
+# Test double for the btrfs CLI used by hooks_btrfs_contract tests.
+#
+# Subvolumes are plain directories; the ones created through this stub
+# are listed, one path per line, in $FAKE_BTRFS_STATE/subvolumes. Every
+# invocation is appended to $FAKE_BTRFS_STATE/calls.log.
+#
+# Supported forms (only those BtrfsSnapshotHook uses):
+#   subvolume show <path>                  → exit 0 iff <path> is listed
+#   subvolume create <path>                → mkdir + list
+#   subvolume snapshot [-r] <src> <dst>    → cp -a <src> <dst> + list
+#   subvolume delete <path>                → rm -rf <path>
+#
+# $FAKE_BTRFS_DELETE_FAIL=1 makes `delete` fail.
+
+state="${FAKE_BTRFS_STATE:?FAKE_BTRFS_STATE must be set}"
+mkdir -p "$state"
+touch "$state/subvolumes"
+echo "$*" >> "$state/calls.log"
+
+[[ "$1" == "subvolume" ]] || exit 2
+verb="$2"
+shift 2
+
+case "$verb" in
+    show)
+        grep -Fxq "$1" "$state/subvolumes"
+        ;;
+    create)
+        mkdir "$1" || exit 1
+        echo "$1" >> "$state/subvolumes"
+        ;;
+    snapshot)
+        [[ "$1" == "-r" ]] && shift
+        grep -Fxq "$1" "$state/subvolumes" || exit 1
+        cp -a "$1" "$2" || exit 1
+        echo "$2" >> "$state/subvolumes"
+        ;;
+    delete)
+        [[ "${FAKE_BTRFS_DELETE_FAIL:-0}" == "1" ]] && exit 1
+        rm -rf "$1"
+        ;;
+    *)
+        exit 2
+        ;;
+esac
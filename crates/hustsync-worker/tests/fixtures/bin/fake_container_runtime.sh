@@ -0,0 +1,32 @@
+#!/usr/bin/env bash
+# Test double for a docker/podman CLI used by docker_wrap_contract tests.
+#
+# Every invocation appends its argv as one line to $FAKE_RUNTIME_LOG (when
+# set), so tests can assert on `run`, `stop` and `rm` calls.
+#
+#   run ... IMAGE CMD...  → skips the runtime options and the image, then
+#                           execs CMD... on the host. Forwarded `-e KEY`
+#                           values are already in our env, as with a real
+#                           runtime client.
+#   stop / rm / other     → logged and exit 0.
+
+if [[ -n "$FAKE_RUNTIME_LOG" ]]; then
+    echo "$*" >> "$FAKE_RUNTIME_LOG"
+fi
+
+if [[ "$1" != "run" ]]; then
+    exit 0
+fi
+shift
+
+while (( $# > 0 )); do
+    case "$1" in
+        -a|--name|-w|-u|-v|-e|--memory) shift 2 ;;
+        -*) shift ;;
+        *) break ;;
+    esac
+done
+
+# $1 is the image; the rest is the wrapped command.
+shift
+exec "$@"
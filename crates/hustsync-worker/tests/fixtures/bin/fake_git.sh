@@ -0,0 +1,50 @@
+#!/usr/bin/env bash
+# Test double for the git CLI used by git_provider_contract tests.
+#
+# Every invocation is appended to $FAKE_GIT_STATE/calls.log as
+# "<pwd>: <args>". Supported forms (only those GitProvider uses):
+#   clone --mirror <url> <dest>   → creates a bare-looking repo at <dest>
+#                                   (HEAD, objects/pack/pack.pack)
+#   remote update --prune         → appends 10 bytes to the pack in $PWD
+#
+# `clone` into a non-empty directory exits 128, as git does.
+# $FAKE_GIT_FAIL_URL makes `clone` of that URL exit 128.
+# $FAKE_GIT_SLEEP sleeps that many seconds first (for terminate tests).
+
+state="${FAKE_GIT_STATE:?FAKE_GIT_STATE must be set}"
+mkdir -p "$state"
+echo "$PWD: $*" >> "$state/calls.log"
+
+if [[ -n "$FAKE_GIT_SLEEP" ]]; then
+    echo $$ > "$state/sleeping.pid"
+    sleep "$FAKE_GIT_SLEEP"
+fi
+
+case "$1" in
+    clone)
+        [[ "$2" == "--mirror" ]] || exit 129
+        url="$3"
+        dest="$4"
+        if [[ "$url" == "${FAKE_GIT_FAIL_URL:-}" ]]; then
+            echo "fatal: repository '$url' not found" >&2
+            exit 128
+        fi
+        if [[ -d "$dest" && -n "$(ls -A "$dest")" ]]; then
+            echo "fatal: destination path '$dest' already exists and is not an empty directory." >&2
+            exit 128
+        fi
+        mkdir -p "$dest/objects/pack" "$dest/refs"
+        echo "ref: refs/heads/main" > "$dest/HEAD"
+        printf '%s' "0123456789" > "$dest/objects/pack/pack.pack"
+        echo "Cloning into bare repository '$dest'..."
+        ;;
+    remote)
+        [[ "$2 $3" == "update --prune" ]] || exit 129
+        [[ -f HEAD ]] || exit 128
+        printf '%s' "0123456789" >> objects/pack/pack.pack
+        echo "Fetching origin"
+        ;;
+    *)
+        exit 129
+        ;;
+esac
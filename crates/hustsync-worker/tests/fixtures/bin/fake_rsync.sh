@@ -10,13 +10,30 @@
 #   - stage 1: $FAKE_STAGE1_EXIT (default 0)
 #   - stage 2: $FAKE_STAGE2_EXIT (default 0)
 #
+# If $FAKE_DOWN_UPSTREAM is set and appears in argv, exit 5 (rsync's
+# "error starting client-server protocol") before anything else.
+#
+# If $FAKE_ARGV_DUMP is set, append one line per invocation holding the
+# argv joined by spaces.
+#
+# If $FAKE_PROGRESS is set, print it to stdout terminated by a carriage
+# return, like an `--info=progress2` update.
+#
+# If $FAKE_STATS is set, print a `--stats` block reporting that many
+# files transferred and bytes received.
+#
+# If $FAKE_DELETES is set, a `--dry-run` invocation prints that many
+# `*deleting` items and reports 100 files.
+#
 # If $FAKE_SLEEP is set, sleep that many seconds before exiting (for
 # terminate-mid-stage tests). The sleep is interruptible by SIGTERM/SIGKILL.
 
 is_stage_two=0
+is_dry_run=0
 for arg in "$@"; do
     case "$arg" in
         --delete) is_stage_two=1 ;;
+        --dry-run) is_dry_run=1 ;;
     esac
 done
 
@@ -35,6 +52,32 @@ if [[ -n "$FAKE_ENV_DUMP" ]]; then
     } >> "$FAKE_ENV_DUMP"
 fi
 
+if [[ -n "$FAKE_ARGV_DUMP" ]]; then
+    echo "$*" >> "$FAKE_ARGV_DUMP"
+fi
+
+if [[ -n "$FAKE_DOWN_UPSTREAM" ]]; then
+    for arg in "$@"; do
+        [[ "$arg" == "$FAKE_DOWN_UPSTREAM" ]] && exit 5
+    done
+fi
+
+if [[ -n "$FAKE_PROGRESS" ]]; then
+    printf '%s\r' "$FAKE_PROGRESS"
+fi
+
+if (( is_dry_run )) && [[ -n "$FAKE_DELETES" ]]; then
+    for (( i = 0; i < FAKE_DELETES; i++ )); do
+        echo "*deleting   pool/old-$i.deb"
+    done
+    echo "Number of files: 100 (reg: 100)"
+fi
+
+if [[ -n "$FAKE_STATS" ]]; then
+    echo "Number of regular files transferred: $FAKE_STATS"
+    echo "Total bytes received: $FAKE_STATS"
+fi
+
 if [[ -n "$FAKE_SLEEP" ]]; then
     sleep "$FAKE_SLEEP" &
     sleep_pid=$!
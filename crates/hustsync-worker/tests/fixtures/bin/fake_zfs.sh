@@ -0,0 +1,52 @@
+#!/usr/bin/env bash
+# Test double for the zfs CLI used by hooks_zfs_contract tests.
+#
+# Datasets live as files under $FAKE_ZFS_STATE: the file path is the
+# dataset name and its content is the mountpoint. Every invocation is
+# appended to $FAKE_ZFS_STATE/calls.log.
+#
+# Supported verbs (only the forms ZfsHook uses):
+#   list -H -o name <ds>                      → exit 0 iff <ds> exists
+#   create -p -o mountpoint=<dir> <ds>        → records <ds>, mkdir <dir>
+#   get -H -o value mounted,mountpoint <ds>   → "yes|no" then the mountpoint
+#
+# $FAKE_ZFS_UNMOUNTED=1 reports every dataset as not mounted.
+# $FAKE_ZFS_CREATE_FAIL=1 makes `create` fail.
+
+state="${FAKE_ZFS_STATE:?FAKE_ZFS_STATE must be set}"
+mkdir -p "$state/datasets"
+echo "$*" >> "$state/calls.log"
+
+verb="$1"
+ds="${!#}"
+ds_file="$state/datasets/$ds"
+
+case "$verb" in
+    list)
+        [[ -f "$ds_file" ]]
+        ;;
+    create)
+        [[ "${FAKE_ZFS_CREATE_FAIL:-0}" == "1" ]] && exit 1
+        mountpoint=""
+        for arg in "$@"; do
+            case "$arg" in
+                mountpoint=*) mountpoint="${arg#mountpoint=}" ;;
+            esac
+        done
+        mkdir -p "$(dirname "$ds_file")"
+        echo "$mountpoint" > "$ds_file"
+        mkdir -p "$mountpoint"
+        ;;
+    get)
+        [[ -f "$ds_file" ]] || exit 1
+        if [[ "${FAKE_ZFS_UNMOUNTED:-0}" == "1" ]]; then
+            echo "no"
+        else
+            echo "yes"
+        fi
+        cat "$ds_file"
+        ;;
+    *)
+        exit 2
+        ;;
+esac
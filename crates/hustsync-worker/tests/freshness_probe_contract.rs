@@ -0,0 +1,245 @@
+//! Contract tests for the freshness probe and the job's skip path.
+//!
+//! A local axum server plays an upstream publishing `lastupdate`; a shell
+//! script stands in for rsync in the `rsync://` cases.
+//!
+//! Scenarios:
+//! 1. `content` markers follow the file body; `mtime` follows
+//!    `Last-Modified` only
+//! 2. an unreadable probe yields no marker
+//! 3. rsync probes: `content` hashes the copied file, `mtime` keeps the
+//!    `--list-only` line
+//! 4. a job skips the provider while the probe is unchanged and syncs again
+//!    once it moves; a forced start syncs regardless
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_internal::status::SyncStatus;
+use hustsync_worker::freshness::{FreshnessMode, FreshnessProbe, SKIPPED_MSG};
+use hustsync_worker::job::{CtrlAction, JobActor, JobOptions, STATE_READY};
+use hustsync_worker::provider::build_provider;
+use hustsync_worker::{JobMessage, MirrorJob};
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+
+/// (body, Last-Modified) of `/lastupdate`.
+type Shared = Arc<Mutex<(String, String)>>;
+
+async fn lastupdate(State(up): State<Shared>) -> impl IntoResponse {
+    let (body, modified) = up.lock().unwrap().clone();
+    ([(header::LAST_MODIFIED, modified)], body)
+}
+
+async fn start_upstream(body: &str) -> (Shared, String) {
+    let up: Shared = Arc::new(Mutex::new((
+        body.to_string(),
+        "Mon, 02 Jan 2023 03:04:05 GMT".to_string(),
+    )));
+    let app = Router::new()
+        .route("/lastupdate", get(lastupdate))
+        .with_state(Arc::clone(&up));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (up, format!("http://{addr}/lastupdate"))
+}
+
+#[tokio::test]
+async fn http_markers_follow_the_chosen_mode() {
+    let (up, url) = start_upstream("1700000000").await;
+    let content = FreshnessProbe::new("arch", &url, FreshnessMode::Content);
+    let mtime = FreshnessProbe::new("arch", &url, FreshnessMode::Mtime);
+
+    let c1 = content.observe().await.expect("content marker");
+    let m1 = mtime.observe().await.expect("mtime marker");
+    assert_eq!(m1, "Mon, 02 Jan 2023 03:04:05 GMT");
+    assert_eq!(content.observe().await.as_deref(), Some(c1.as_str()));
+
+    up.lock().unwrap().0 = "1700003600".to_string();
+    assert_ne!(content.observe().await.unwrap(), c1);
+    assert_eq!(
+        mtime.observe().await.unwrap(),
+        m1,
+        "body alone is not an mtime change"
+    );
+
+    up.lock().unwrap().1 = "Mon, 02 Jan 2023 04:04:05 GMT".to_string();
+    assert_ne!(mtime.observe().await.unwrap(), m1);
+}
+
+#[tokio::test]
+async fn unreadable_probe_yields_no_marker() {
+    let (_up, url) = start_upstream("1").await;
+    let missing = url.replace("lastupdate", "missing");
+    let probe = FreshnessProbe::new("arch", &missing, FreshnessMode::Content);
+    assert!(probe.observe().await.is_none());
+}
+
+/// An executable stand-in for rsync: copies `$dir/upstream` to its last
+/// argument, or lists it under `--list-only`.
+fn fake_rsync(dir: &Path) -> String {
+    let script = dir.join("rsync");
+    let upstream = dir.join("upstream");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\n\
+             for last; do :; done\n\
+             case \"$*\" in\n\
+             *--list-only*) echo \"-rw-r--r--  $(wc -c < {up}) $(cat {up}.mtime) lastupdate\" ;;\n\
+             *) cp {up} \"$last\" ;;\n\
+             esac\n",
+            up = upstream.display()
+        ),
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    std::fs::write(&upstream, "v1").unwrap();
+    std::fs::write(dir.join("upstream.mtime"), "2024/05/01 12:00:00").unwrap();
+    script.to_string_lossy().into_owned()
+}
+
+#[tokio::test]
+async fn rsync_probes_copy_or_list_the_file() {
+    let tmp = TempDir::new().unwrap();
+    let rsync = fake_rsync(tmp.path());
+    let url = "rsync://mirror.test/archlinux/lastupdate";
+    let content =
+        FreshnessProbe::new("arch", url, FreshnessMode::Content).with_rsync_command(&rsync);
+    let mtime = FreshnessProbe::new("arch", url, FreshnessMode::Mtime).with_rsync_command(&rsync);
+
+    let c1 = content.observe().await.expect("content marker");
+    let m1 = mtime.observe().await.expect("mtime marker");
+    assert!(m1.ends_with("2024/05/01 12:00:00 lastupdate"), "got {m1:?}");
+
+    std::fs::write(tmp.path().join("upstream"), "v2").unwrap();
+    assert_ne!(content.observe().await.unwrap(), c1);
+    assert_eq!(mtime.observe().await.unwrap(), m1);
+}
+
+/// Wait for the end of the next run: a `Success` or `Failed` report.
+async fn next_outcome(rx: &mut mpsc::Receiver<JobMessage>) -> JobMessage {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let msg = rx.recv().await.expect("manager channel closed");
+            if matches!(msg.status, SyncStatus::Success | SyncStatus::Failed) {
+                return msg;
+            }
+        }
+    })
+    .await
+    .expect("run did not finish")
+}
+
+/// Start a run once the actor has finished the previous one; a Start that
+/// arrives mid-run is ignored.
+async fn start(job: &MirrorJob) {
+    send_when_idle(job, CtrlAction::Start).await;
+}
+
+async fn send_when_idle(job: &MirrorJob, action: CtrlAction) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while job.state() == STATE_READY {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("previous run did not settle");
+    job.send_ctrl(action).await.unwrap();
+}
+
+fn runs(count: &Path) -> usize {
+    std::fs::read_to_string(count).map_or(0, |s| s.lines().count())
+}
+
+#[tokio::test]
+async fn job_skips_provider_while_upstream_is_unchanged() {
+    let tmp = TempDir::new().unwrap();
+    let (up, url) = start_upstream("1700000000").await;
+    let count = tmp.path().join("runs");
+    let mirror = MirrorConfig {
+        name: Some("arch".into()),
+        provider: Some("command".into()),
+        upstream: Some("https://mirror.test/archlinux/".into()),
+        command: Some(format!("sh -c 'echo run >> {}'", count.display())),
+        freshness_probe: Some(url),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(tmp.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(tmp.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+
+    let (manager_tx, mut manager_rx) = mpsc::channel(64);
+    let (job, actor) = JobActor::new(
+        "arch".into(),
+        manager_tx,
+        Arc::new(tokio::sync::Semaphore::new(1)),
+        None,
+        build_provider("arch", &mirror, &worker).unwrap(),
+        vec![],
+        JobOptions {
+            freshness: FreshnessProbe::from_config("arch", &mirror),
+            ..JobOptions::default()
+        },
+    );
+    tokio::spawn(actor.run());
+
+    start(&job).await;
+    let first = next_outcome(&mut manager_rx).await;
+    assert_eq!(
+        (first.status, first.msg.as_str()),
+        (SyncStatus::Success, "")
+    );
+    assert_eq!(runs(&count), 1);
+
+    start(&job).await;
+    let second = next_outcome(&mut manager_rx).await;
+    assert_eq!(
+        (second.status, second.msg.as_str()),
+        (SyncStatus::Success, SKIPPED_MSG)
+    );
+    assert!(second.skipped && !first.skipped);
+    assert!(second.schedule, "a skipped run is rescheduled like a sync");
+    assert_eq!(runs(&count), 1, "provider not run");
+
+    up.lock().unwrap().0 = "1700003600".to_string();
+    start(&job).await;
+    let third = next_outcome(&mut manager_rx).await;
+    assert_eq!(
+        (third.status, third.msg.as_str()),
+        (SyncStatus::Success, "")
+    );
+    assert_eq!(runs(&count), 2);
+
+    start(&job).await;
+    let skipped = next_outcome(&mut manager_rx).await;
+    assert_eq!(skipped.msg, SKIPPED_MSG);
+    send_when_idle(&job, CtrlAction::ForceStart).await;
+    let forced = next_outcome(&mut manager_rx).await;
+    assert_eq!(
+        (forced.status, forced.msg.as_str()),
+        (SyncStatus::Success, "")
+    );
+    assert_eq!(runs(&count), 3, "a forced start is not skipped");
+}
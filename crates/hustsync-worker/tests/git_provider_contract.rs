@@ -0,0 +1,219 @@
+//! Contract tests for the `git` provider.
+//!
+//! `tests/fixtures/bin/fake_git.sh` stands in for git: `clone --mirror`
+//! lays out a minimal bare repository and `remote update --prune` grows its
+//! pack, so tests can see which operation ran where. Its state dir and
+//! failure knobs are passed through the mirror's `env`.
+//!
+//! Scenarios:
+//! 1. a single `upstream` is cloned into the working dir, then updated
+//! 2. `git_repos` clones each repository into `<name>.git`, and the
+//!    reported `data_size` covers all of them
+//! 3. one failing repository fails the run but the others are still synced
+//! 4. `terminate()` stops the running git process group and the run
+//! 5. a clone is made in `<dest>.partial` and moved into place: what an
+//!    interrupted one left there is removed before cloning again, and
+//!    unrelated files already in the working dir survive
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_worker::provider::{MirrorProvider, ProviderError, RunContext, build_provider};
+use tempfile::TempDir;
+
+fn fake_git_fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bin/fake_git.sh")
+}
+
+fn state_dir(dir: &TempDir) -> PathBuf {
+    dir.path().join("git-state")
+}
+
+fn git_mirror(
+    dir: &TempDir,
+    upstream: &str,
+    repos: Option<&[&str]>,
+    knobs: &[(&str, &str)],
+) -> Box<dyn MirrorProvider> {
+    let mut env: HashMap<String, String> = knobs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    env.insert(
+        "FAKE_GIT_STATE".into(),
+        state_dir(dir).to_string_lossy().into_owned(),
+    );
+    let mirror = MirrorConfig {
+        name: Some("git".into()),
+        provider: Some("git".into()),
+        upstream: Some(upstream.into()),
+        command: Some(fake_git_fixture().to_string_lossy().into_owned()),
+        git_repos: repos.map(|r| r.iter().map(|s| s.to_string()).collect()),
+        env: Some(env),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+    build_provider("git", &mirror, &worker).unwrap()
+}
+
+fn calls(dir: &TempDir) -> Vec<String> {
+    std::fs::read_to_string(state_dir(dir).join("calls.log"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+fn ctx() -> RunContext {
+    RunContext {
+        attempt: 1,
+        ..RunContext::default()
+    }
+}
+
+#[tokio::test]
+async fn single_upstream_is_cloned_then_updated() {
+    let dir = TempDir::new().unwrap();
+    let provider = git_mirror(&dir, "https://git.example/linux.git", None, &[]);
+    let wd = provider.working_dir().to_path_buf();
+
+    provider.run(ctx()).await.unwrap();
+    assert!(wd.join("HEAD").is_file());
+    assert_eq!(provider.data_size().await.as_deref(), Some("31"));
+
+    provider.run(ctx()).await.unwrap();
+    let calls = calls(&dir);
+    assert_eq!(calls.len(), 2, "calls: {calls:?}");
+    assert!(calls[0].ends_with(&format!(
+        "clone --mirror https://git.example/linux.git {}.partial",
+        wd.display()
+    )));
+    assert!(!PathBuf::from(format!("{}.partial", wd.display())).exists());
+    assert_eq!(calls[1], format!("{}: remote update --prune", wd.display()));
+    assert_eq!(provider.data_size().await.as_deref(), Some("41"));
+}
+
+#[tokio::test]
+async fn interrupted_clone_is_retried_without_touching_the_working_dir() {
+    let dir = TempDir::new().unwrap();
+    let provider = git_mirror(&dir, "https://git.example/linux.git", None, &[]);
+    let wd = provider.working_dir().to_path_buf();
+    let partial = PathBuf::from(format!("{}.partial", wd.display()));
+    std::fs::create_dir_all(partial.join("objects/pack")).unwrap();
+    std::fs::write(partial.join("objects/pack/tmp_pack_x"), "partial").unwrap();
+    std::fs::create_dir_all(wd.join("notes")).unwrap();
+    std::fs::write(wd.join("README.mirror"), "kept").unwrap();
+    std::fs::write(wd.join("notes/keep"), "kept").unwrap();
+
+    provider.run(ctx()).await.unwrap();
+    assert!(wd.join("HEAD").is_file());
+    assert!(!wd.join("objects/pack/tmp_pack_x").exists());
+    assert!(!partial.exists());
+    assert_eq!(
+        std::fs::read_to_string(wd.join("README.mirror")).unwrap(),
+        "kept"
+    );
+    assert_eq!(
+        std::fs::read_to_string(wd.join("notes/keep")).unwrap(),
+        "kept"
+    );
+
+    provider.run(ctx()).await.unwrap();
+    assert!(calls(&dir)[1].ends_with("remote update --prune"));
+}
+
+#[tokio::test]
+async fn repo_list_gets_one_bare_mirror_each() {
+    let dir = TempDir::new().unwrap();
+    let provider = git_mirror(
+        &dir,
+        "https://git.example/",
+        Some(&["tools/a", "b.git"]),
+        &[],
+    );
+    let wd = provider.working_dir().to_path_buf();
+
+    provider.run(ctx()).await.unwrap();
+
+    assert!(wd.join("a.git/HEAD").is_file());
+    assert!(wd.join("b.git/HEAD").is_file());
+    let calls = calls(&dir);
+    assert!(
+        calls
+            .iter()
+            .any(|c| c.contains("clone --mirror https://git.example/tools/a "))
+    );
+    assert!(
+        calls
+            .iter()
+            .any(|c| c.contains("clone --mirror https://git.example/b.git "))
+    );
+    assert_eq!(provider.data_size().await.as_deref(), Some("62"));
+}
+
+#[tokio::test]
+async fn failing_repo_fails_run_after_syncing_the_rest() {
+    let dir = TempDir::new().unwrap();
+    let provider = git_mirror(
+        &dir,
+        "https://git.example/",
+        Some(&["gone.git", "ok.git"]),
+        &[("FAKE_GIT_FAIL_URL", "https://git.example/gone.git")],
+    );
+    let wd = provider.working_dir().to_path_buf();
+
+    let err = provider.run(ctx()).await.unwrap_err();
+
+    match err {
+        ProviderError::Execution { code, msg } => {
+            assert_eq!(code, 128);
+            assert!(msg.contains("1 of 2"), "msg: {msg}");
+            assert!(msg.contains("gone.git"), "msg: {msg}");
+        }
+        other => panic!("expected Execution, got {other:?}"),
+    }
+    assert!(wd.join("ok.git/HEAD").is_file());
+    assert!(provider.data_size().await.is_none());
+}
+
+#[tokio::test]
+async fn terminate_stops_git_and_remaining_repos() {
+    let dir = TempDir::new().unwrap();
+    let provider: Arc<dyn MirrorProvider> = Arc::from(git_mirror(
+        &dir,
+        "https://git.example/",
+        Some(&["a.git", "b.git"]),
+        &[("FAKE_GIT_SLEEP", "30")],
+    ));
+
+    let p = Arc::clone(&provider);
+    let handle = tokio::spawn(async move { p.run(ctx()).await });
+    let pid_file = state_dir(&dir).join("sleeping.pid");
+    for _ in 0..50 {
+        if pid_file.exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    provider.terminate().await.unwrap();
+    let res = tokio::time::timeout(Duration::from_secs(10), handle)
+        .await
+        .expect("run must return promptly after terminate")
+        .unwrap();
+
+    assert!(matches!(res, Err(ProviderError::Terminated)), "got {res:?}");
+    assert_eq!(calls(&dir).len(), 1, "b.git must not be started");
+}
@@ -0,0 +1,185 @@
+//! Contract tests for `BtrfsSnapshotHook` — working subvolume setup and
+//! snapshot publishing.
+//!
+//! `tests/fixtures/bin/fake_btrfs.sh` stands in for the btrfs CLI:
+//! subvolumes are plain directories and snapshots are `cp -a` copies, so
+//! the published view can be inspected with ordinary file reads. Each test
+//! writes a wrapper that pins the fake's state dir (plus failure knobs).
+//!
+//! Scenarios:
+//! 1. `pre_job` creates a missing working subvolume
+//! 2. `pre_job` refuses a working dir that is not a subvolume
+//! 3. first `post_success` publishes the working content
+//! 4. later `post_success` swaps in the new view and deletes the old one
+//! 5. an undeletable old snapshot stays in gc and is swept next time
+//! 6. `post_fail` leaves the published snapshot alone
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use hustsync_worker::hooks::{
+    BtrfsSnapshotHook, BtrfsSnapshotPaths, HookCtx, HookErrorKind, JobHook,
+};
+use tempfile::TempDir;
+
+fn fake_btrfs_fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bin/fake_btrfs.sh")
+}
+
+fn state_dir(dir: &TempDir) -> PathBuf {
+    dir.path().join("btrfs-state")
+}
+
+fn install_btrfs(dir: &TempDir, extra_env: &str) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let wrapper = dir.path().join(format!("btrfs{}", extra_env.len()));
+    let script = format!(
+        "#!/bin/sh\nFAKE_BTRFS_STATE='{}' {extra_env} exec '{}' \"$@\"\n",
+        state_dir(dir).display(),
+        fake_btrfs_fixture().display()
+    );
+    std::fs::write(&wrapper, script).unwrap();
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755)).unwrap();
+    wrapper.to_string_lossy().into_owned()
+}
+
+fn paths(dir: &TempDir) -> BtrfsSnapshotPaths {
+    BtrfsSnapshotPaths {
+        working: dir.path().join("srv/debian"),
+        snapshot: dir.path().join("snapshots/debian"),
+        gc: dir.path().join("gc/debian"),
+    }
+}
+
+fn make_hook(dir: &TempDir, extra_env: &str) -> BtrfsSnapshotHook {
+    BtrfsSnapshotHook::new(install_btrfs(dir, extra_env), paths(dir))
+}
+
+fn make_ctx(dir: &TempDir) -> HookCtx {
+    let log_dir = dir.path().join("log");
+    HookCtx {
+        mirror_name: "debian".into(),
+        working_dir: dir.path().join("srv/debian"),
+        upstream_url: "rsync://up.test/debian/".into(),
+        log_file: log_dir.join("latest.log"),
+        log_dir,
+        attempt: 1,
+        env: HashMap::new(),
+    }
+}
+
+fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+fn gc_entries(dir: &TempDir) -> usize {
+    std::fs::read_dir(paths(dir).gc)
+        .map(|d| d.count())
+        .unwrap_or(0)
+}
+
+/// Create the working subvolume through the hook and sync `content` into it.
+async fn sync(hook: &BtrfsSnapshotHook, ctx: &mut HookCtx, content: &str) {
+    hook.pre_job(ctx).await.unwrap();
+    std::fs::write(ctx.working_dir.join("Release"), content).unwrap();
+}
+
+#[tokio::test]
+async fn pre_job_creates_missing_working_subvolume() {
+    let tmp = TempDir::new().unwrap();
+    let hook = make_hook(&tmp, "");
+    let mut ctx = make_ctx(&tmp);
+
+    hook.pre_job(&mut ctx).await.unwrap();
+
+    assert!(ctx.working_dir.is_dir());
+    let calls = read(&state_dir(&tmp).join("calls.log"));
+    assert!(calls.contains(&format!("subvolume create {}", ctx.working_dir.display())));
+}
+
+#[tokio::test]
+async fn pre_job_refuses_plain_working_dir() {
+    let tmp = TempDir::new().unwrap();
+    let hook = make_hook(&tmp, "");
+    let mut ctx = make_ctx(&tmp);
+    std::fs::create_dir_all(&ctx.working_dir).unwrap();
+
+    let err = hook.pre_job(&mut ctx).await.unwrap_err();
+    assert_eq!(err.hook, "btrfs_snapshot");
+    assert!(
+        matches!(&err.kind, HookErrorKind::Config(r) if r.contains("not a btrfs subvolume")),
+        "got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn first_success_publishes_snapshot() {
+    let tmp = TempDir::new().unwrap();
+    let hook = make_hook(&tmp, "");
+    let mut ctx = make_ctx(&tmp);
+    sync(&hook, &mut ctx, "v1").await;
+
+    hook.post_success(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&paths(&tmp).snapshot.join("Release")), "v1");
+    let calls = read(&state_dir(&tmp).join("calls.log"));
+    assert!(calls.contains("subvolume snapshot -r"), "calls: {calls}");
+}
+
+#[tokio::test]
+async fn later_success_replaces_snapshot_and_deletes_old() {
+    let tmp = TempDir::new().unwrap();
+    let hook = make_hook(&tmp, "");
+    let mut ctx = make_ctx(&tmp);
+    sync(&hook, &mut ctx, "v1").await;
+    hook.post_success(&mut ctx).await.unwrap();
+
+    sync(&hook, &mut ctx, "v2").await;
+    hook.post_success(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&paths(&tmp).snapshot.join("Release")), "v2");
+    assert_eq!(gc_entries(&tmp), 0, "old snapshot must be deleted");
+    let leftovers: Vec<_> = std::fs::read_dir(tmp.path().join("snapshots"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(leftovers, ["debian"], "no staging dirs may remain");
+}
+
+#[tokio::test]
+async fn undeletable_snapshot_is_swept_on_next_success() {
+    let tmp = TempDir::new().unwrap();
+    let stuck = make_hook(&tmp, "FAKE_BTRFS_DELETE_FAIL=1");
+    let mut ctx = make_ctx(&tmp);
+    sync(&stuck, &mut ctx, "v1").await;
+    stuck.post_success(&mut ctx).await.unwrap();
+    sync(&stuck, &mut ctx, "v2").await;
+    stuck.post_success(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&paths(&tmp).snapshot.join("Release")), "v2");
+    assert_eq!(gc_entries(&tmp), 1, "old snapshot parked in gc");
+
+    let healthy = make_hook(&tmp, "");
+    sync(&healthy, &mut ctx, "v3").await;
+    healthy.post_success(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&paths(&tmp).snapshot.join("Release")), "v3");
+    assert_eq!(gc_entries(&tmp), 0);
+}
+
+#[tokio::test]
+async fn failed_sync_keeps_published_snapshot() {
+    let tmp = TempDir::new().unwrap();
+    let hook = make_hook(&tmp, "");
+    let mut ctx = make_ctx(&tmp);
+    sync(&hook, &mut ctx, "v1").await;
+    hook.post_success(&mut ctx).await.unwrap();
+
+    sync(&hook, &mut ctx, "half-written").await;
+    hook.post_fail(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&paths(&tmp).snapshot.join("Release")), "v1");
+}
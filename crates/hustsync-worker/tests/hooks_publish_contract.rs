@@ -0,0 +1,239 @@
+//! Contract tests for `PublishHook` — staging, symlink swap and pruning.
+//!
+//! Syncs are simulated by writing into the staging directory the hook
+//! announces in `ctx.env`; the last scenario runs a real `RsyncProvider`
+//! (with `sh` standing in for rsync) to check the redirect reaches argv.
+//!
+//! Scenarios:
+//! 1. `pre_job` refuses a `publish_dir` that is a plain directory
+//! 2. first success publishes the staging tree behind the symlink
+//! 3. later runs are seeded from the live tree and swap the link
+//! 4. generations beyond `keep` are pruned; unrelated entries stay
+//! 5. `post_fail` discards staging and leaves the live tree alone
+//! 6. the rsync provider syncs into staging with `--link-dest`
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use hustsync_worker::hooks::{HookCtx, HookErrorKind, JobHook, PublishHook};
+use hustsync_worker::provider::rsync_provider::{RsyncProvider, RsyncProviderConfig};
+use hustsync_worker::provider::{CommonProviderConfig, MirrorProvider, RunContext};
+use tempfile::TempDir;
+
+fn link(dir: &TempDir) -> PathBuf {
+    dir.path().join("www/debian")
+}
+
+fn make_ctx(dir: &TempDir) -> HookCtx {
+    let log_dir = dir.path().join("log");
+    let working_dir = dir.path().join("srv/debian");
+    std::fs::create_dir_all(&working_dir).unwrap();
+    HookCtx {
+        mirror_name: "debian".into(),
+        working_dir,
+        upstream_url: "rsync://up.test/debian/".into(),
+        log_file: log_dir.join("latest.log"),
+        log_dir,
+        attempt: 1,
+        env: HashMap::new(),
+    }
+}
+
+fn staging(ctx: &HookCtx) -> PathBuf {
+    PathBuf::from(&ctx.env["HUSTSYNC_WORKING_DIR"])
+}
+
+fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+/// Generation directories under the working dir, oldest first.
+fn generations(ctx: &HookCtx) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(&ctx.working_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|n| n.starts_with("20"))
+        .collect();
+    names.sort();
+    names
+}
+
+/// One successful run that writes `content` as the tree's `Release`.
+async fn publish(hook: &PublishHook, ctx: &mut HookCtx, content: &str) {
+    hook.pre_exec(ctx).await.unwrap();
+    std::fs::write(staging(ctx).join("Release"), content).unwrap();
+    hook.post_success(ctx).await.unwrap();
+}
+
+#[tokio::test]
+async fn pre_job_refuses_plain_publish_dir() {
+    let tmp = TempDir::new().unwrap();
+    let hook = PublishHook::new(link(&tmp), 1);
+    let mut ctx = make_ctx(&tmp);
+    hook.pre_job(&mut ctx).await.unwrap();
+
+    std::fs::create_dir_all(link(&tmp)).unwrap();
+    let err = hook.pre_job(&mut ctx).await.unwrap_err();
+    assert_eq!(err.hook, "publish");
+    assert!(
+        matches!(&err.kind, HookErrorKind::Config(r) if r.contains("not a symlink")),
+        "got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn first_success_publishes_staging_tree() {
+    let tmp = TempDir::new().unwrap();
+    let hook = PublishHook::new(link(&tmp), 1);
+    let mut ctx = make_ctx(&tmp);
+
+    hook.pre_exec(&mut ctx).await.unwrap();
+    assert_eq!(staging(&ctx), ctx.working_dir.join(".staging"));
+    assert!(!ctx.env.contains_key("HUSTSYNC_LINK_DEST"));
+    std::fs::write(staging(&ctx).join("Release"), "v1").unwrap();
+    hook.post_success(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&link(&tmp).join("Release")), "v1");
+    assert!(!ctx.working_dir.join(".staging").exists());
+    assert_eq!(generations(&ctx).len(), 1);
+    assert_eq!(
+        ctx.env["HUSTSYNC_WORKING_DIR"],
+        link(&tmp).to_string_lossy(),
+        "later hooks see the published path"
+    );
+}
+
+#[tokio::test]
+async fn later_run_is_seeded_from_live_tree() {
+    let tmp = TempDir::new().unwrap();
+    let hook = PublishHook::new(link(&tmp), 1);
+    let mut ctx = make_ctx(&tmp);
+    publish(&hook, &mut ctx, "v1").await;
+    let live = std::fs::read_link(link(&tmp)).unwrap();
+
+    hook.pre_exec(&mut ctx).await.unwrap();
+    assert_eq!(ctx.env["HUSTSYNC_LINK_DEST"], live.to_string_lossy());
+    std::fs::write(staging(&ctx).join("Release"), "v2").unwrap();
+    assert_eq!(
+        read(&link(&tmp).join("Release")),
+        "v1",
+        "clients keep the old tree while syncing"
+    );
+    hook.post_success(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&link(&tmp).join("Release")), "v2");
+    assert_ne!(std::fs::read_link(link(&tmp)).unwrap(), live);
+    assert_eq!(generations(&ctx).len(), 2, "one previous tree is kept");
+}
+
+#[tokio::test]
+async fn generations_beyond_keep_are_pruned() {
+    let tmp = TempDir::new().unwrap();
+    let hook = PublishHook::new(link(&tmp), 1);
+    let mut ctx = make_ctx(&tmp);
+    std::fs::create_dir_all(ctx.working_dir.join("unrelated")).unwrap();
+
+    for content in ["v1", "v2", "v3"] {
+        publish(&hook, &mut ctx, content).await;
+    }
+
+    let gens = generations(&ctx);
+    assert_eq!(gens.len(), 2, "live plus one kept: {gens:?}");
+    let live = std::fs::read_link(link(&tmp)).unwrap();
+    assert_eq!(read(&live.join("Release")), "v3");
+    assert!(
+        gens.iter()
+            .all(|g| read(&ctx.working_dir.join(g).join("Release")) != "v1")
+    );
+    assert!(ctx.working_dir.join("unrelated").is_dir());
+}
+
+#[tokio::test]
+async fn failed_sync_discards_staging() {
+    let tmp = TempDir::new().unwrap();
+    let hook = PublishHook::new(link(&tmp), 1);
+    let mut ctx = make_ctx(&tmp);
+    publish(&hook, &mut ctx, "v1").await;
+
+    hook.pre_exec(&mut ctx).await.unwrap();
+    std::fs::write(staging(&ctx).join("Release"), "half-written").unwrap();
+    hook.post_fail(&mut ctx).await.unwrap();
+
+    assert_eq!(read(&link(&tmp).join("Release")), "v1");
+    assert!(!ctx.working_dir.join(".staging").exists());
+    assert_eq!(generations(&ctx).len(), 1);
+}
+
+#[tokio::test]
+async fn rsync_provider_syncs_into_staging_with_link_dest() {
+    let tmp = TempDir::new().unwrap();
+    let hook = PublishHook::new(link(&tmp), 1);
+    let mut ctx = make_ctx(&tmp);
+    publish(&hook, &mut ctx, "v1").await;
+    let live = std::fs::read_link(link(&tmp)).unwrap();
+
+    let log_dir = tmp.path().join("log");
+    let provider = RsyncProvider::new(RsyncProviderConfig {
+        common: CommonProviderConfig {
+            name: "debian".to_string(),
+            upstream_url: "rsync://up.test/debian/".to_string(),
+            working_dir: ctx.working_dir.to_string_lossy().into_owned(),
+            log_dir: log_dir.to_string_lossy().into_owned(),
+            log_file: log_dir.join("latest.log").to_string_lossy().into_owned(),
+            interval: Duration::from_secs(3600),
+            retry: 1,
+            timeout: Duration::from_secs(5),
+            env: HashMap::new(),
+            is_master: true,
+            success_exit_codes: vec![],
+            cgroup: None,
+        },
+        command: "sh".to_string(),
+        username: None,
+        password: None,
+        exclude_file: None,
+        rsync_options: vec![],
+        global_options: vec![],
+        rsync_override: Some(vec![
+            "-c".to_string(),
+            r#"echo "$@" > argv; echo "$HUSTSYNC_WORKING_DIR" > env"#.to_string(),
+            "rsync".to_string(),
+        ]),
+        rsync_override_only: true,
+        rsync_no_timeout: false,
+        rsync_timeout: None,
+        rsync_progress: false,
+        use_ipv6: false,
+        use_ipv4: false,
+        docker: None,
+        fallback_upstreams: vec![],
+        max_delete: None,
+        max_delete_percent: None,
+    })
+    .unwrap();
+
+    hook.pre_exec(&mut ctx).await.unwrap();
+    let staged = staging(&ctx);
+    let run = RunContext {
+        env: ctx.env.clone(),
+        ..RunContext::default()
+    };
+    provider.run(run).await.unwrap();
+
+    let argv = read(&staged.join("argv"));
+    assert_eq!(
+        argv.trim(),
+        format!(
+            "--link-dest={} rsync://up.test/debian/ {}",
+            live.display(),
+            staged.display()
+        )
+    );
+    assert_eq!(read(&staged.join("env")).trim(), staged.to_string_lossy());
+
+    hook.post_success(&mut ctx).await.unwrap();
+    assert!(link(&tmp).join("argv").is_file());
+}
@@ -0,0 +1,161 @@
+//! Contract tests for `ZfsHook` — dataset create / mount checks.
+//!
+//! `tests/fixtures/bin/fake_zfs.sh` stands in for the zfs CLI and keeps
+//! its datasets as files under a per-test state dir. Each test writes a
+//! small wrapper that pins `FAKE_ZFS_STATE` (plus any failure knobs) and
+//! hands that wrapper to the hook as its `zfs` command.
+//!
+//! Scenarios:
+//! 1. missing dataset is created, mounted at `working_dir`
+//! 2. existing, mounted dataset is used as-is
+//! 3. unmounted dataset refuses the sync
+//! 4. dataset mounted somewhere else refuses the sync
+//! 5. failed create refuses the sync
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use hustsync_worker::hooks::{HookCtx, HookErrorKind, JobHook, ZfsHook};
+use tempfile::TempDir;
+
+fn fake_zfs_fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bin/fake_zfs.sh")
+}
+
+fn state_dir(dir: &TempDir) -> PathBuf {
+    dir.path().join("zfs-state")
+}
+
+/// Write a wrapper around the fixture with `extra_env` baked in.
+fn install_zfs(dir: &TempDir, extra_env: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let wrapper = dir.path().join("zfs");
+    let script = format!(
+        "#!/bin/sh\nFAKE_ZFS_STATE='{}' {extra_env} exec '{}' \"$@\"\n",
+        state_dir(dir).display(),
+        fake_zfs_fixture().display()
+    );
+    std::fs::write(&wrapper, script).unwrap();
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755)).unwrap();
+    wrapper
+}
+
+/// Pre-register `dataset` in the fake as mounted at `mountpoint`.
+fn seed_dataset(dir: &TempDir, dataset: &str, mountpoint: &Path) {
+    let file = state_dir(dir).join("datasets").join(dataset);
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::write(file, format!("{}\n", mountpoint.display())).unwrap();
+}
+
+fn calls(dir: &TempDir) -> Vec<String> {
+    std::fs::read_to_string(state_dir(dir).join("calls.log"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+fn make_ctx(dir: &TempDir, mirror: &str) -> HookCtx {
+    let log_dir = dir.path().join("log");
+    HookCtx {
+        mirror_name: mirror.into(),
+        working_dir: dir.path().join("srv").join(mirror),
+        upstream_url: "rsync://up.test/m/".into(),
+        log_file: log_dir.join("latest.log"),
+        log_dir,
+        attempt: 1,
+        env: HashMap::new(),
+    }
+}
+
+fn assert_refused(err: hustsync_worker::hooks::HookError, needle: &str) {
+    assert_eq!(err.hook, "zfs");
+    match err.kind {
+        HookErrorKind::Config(reason) => {
+            assert!(reason.contains(needle), "reason: {reason}");
+        }
+        other => panic!("expected Config error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn missing_dataset_is_created_at_working_dir() {
+    let tmp = TempDir::new().unwrap();
+    let hook = ZfsHook::new(install_zfs(&tmp, "").to_string_lossy(), "tank/mirrors");
+    let mut ctx = make_ctx(&tmp, "debian");
+
+    hook.pre_job(&mut ctx).await.unwrap();
+
+    let expected_create = format!(
+        "create -p -o mountpoint={} tank/mirrors/debian",
+        ctx.working_dir.display()
+    );
+    assert!(
+        calls(&tmp).contains(&expected_create),
+        "calls: {:?}",
+        calls(&tmp)
+    );
+    assert!(ctx.working_dir.is_dir());
+}
+
+#[tokio::test]
+async fn existing_mounted_dataset_is_not_recreated() {
+    let tmp = TempDir::new().unwrap();
+    let hook = ZfsHook::new(install_zfs(&tmp, "").to_string_lossy(), "tank/mirrors/");
+    let mut ctx = make_ctx(&tmp, "debian");
+    seed_dataset(&tmp, "tank/mirrors/debian", &ctx.working_dir);
+
+    hook.pre_job(&mut ctx).await.unwrap();
+
+    assert!(
+        !calls(&tmp).iter().any(|c| c.starts_with("create")),
+        "calls: {:?}",
+        calls(&tmp)
+    );
+}
+
+#[tokio::test]
+async fn unmounted_dataset_refuses_sync() {
+    let tmp = TempDir::new().unwrap();
+    let hook = ZfsHook::new(
+        install_zfs(&tmp, "FAKE_ZFS_UNMOUNTED=1").to_string_lossy(),
+        "tank/mirrors",
+    );
+    let mut ctx = make_ctx(&tmp, "debian");
+    seed_dataset(&tmp, "tank/mirrors/debian", &ctx.working_dir);
+
+    let err = hook.pre_job(&mut ctx).await.unwrap_err();
+    assert_refused(err, "not mounted");
+}
+
+#[tokio::test]
+async fn dataset_mounted_elsewhere_refuses_sync() {
+    let tmp = TempDir::new().unwrap();
+    let hook = ZfsHook::new(install_zfs(&tmp, "").to_string_lossy(), "tank/mirrors");
+    let mut ctx = make_ctx(&tmp, "debian");
+    seed_dataset(
+        &tmp,
+        "tank/mirrors/debian",
+        Path::new("/tank/mirrors/debian"),
+    );
+
+    let err = hook.pre_job(&mut ctx).await.unwrap_err();
+    assert_refused(err, "/tank/mirrors/debian");
+}
+
+#[tokio::test]
+async fn failed_create_refuses_sync() {
+    let tmp = TempDir::new().unwrap();
+    let hook = ZfsHook::new(
+        install_zfs(&tmp, "FAKE_ZFS_CREATE_FAIL=1").to_string_lossy(),
+        "tank/mirrors",
+    );
+    let mut ctx = make_ctx(&tmp, "debian");
+
+    let err = hook.pre_job(&mut ctx).await.unwrap_err();
+    assert_refused(err, "could not be created");
+    assert!(!ctx.working_dir.exists());
+}
@@ -0,0 +1,371 @@
+//! Contract tests for the native `http` provider.
+//!
+//! A local axum server plays the upstream: directory URLs return an
+//! nginx-style autoindex page generated from an in-memory file table, and
+//! file URLs honour `If-None-Match` with `304 Not Modified`. Every request
+//! is recorded so tests can tell a download from a conditional hit.
+//!
+//! Scenarios:
+//! 1. the first run mirrors the whole tree, including subdirectories and
+//!    percent-encoded names, and reports `data_size`
+//! 2. an unchanged file is revalidated with its ETag and not re-downloaded
+//! 3. files removed upstream are deleted locally, along with empty dirs
+//! 4. `exclude_patterns` keeps matching paths from being fetched or deleted
+//! 5. cancelling a run mid-transfer returns `Terminated`
+//! 6. an upstream error status fails the run and leaves the last
+//!    successful run's `data_size` in place
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_worker::provider::{MirrorProvider, ProviderError, RunContext, build_provider};
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+
+const LAST_MODIFIED: &str = "Mon, 02 Jan 2023 03:04:05 GMT";
+
+#[derive(Default)]
+struct Upstream {
+    /// Path relative to the root → (body, etag).
+    files: BTreeMap<String, (Vec<u8>, String)>,
+    /// (path, status) for every file request served.
+    hits: Vec<(String, u16)>,
+    /// Paths answered with 500.
+    broken: Vec<String>,
+    /// Path whose response never finishes.
+    stall: Option<String>,
+}
+
+type Shared = Arc<Mutex<Upstream>>;
+
+impl Upstream {
+    fn put(&mut self, path: &str, body: &str) {
+        let etag = format!(
+            "\"{:x}-{}\"",
+            body.len(),
+            body.bytes().map(u32::from).sum::<u32>()
+        );
+        self.files
+            .insert(path.into(), (body.as_bytes().to_vec(), etag));
+    }
+
+    /// Autoindex for `dir` ("" or "sub/"): direct child files and dirs.
+    fn listing(&self, dir: &str) -> Option<String> {
+        let mut children = std::collections::BTreeSet::new();
+        for path in self.files.keys() {
+            let Some(rest) = path.strip_prefix(dir) else {
+                continue;
+            };
+            match rest.split_once('/') {
+                Some((sub, _)) => children.insert(format!("{sub}/")),
+                None => children.insert(rest.to_string()),
+            };
+        }
+        if children.is_empty() && !dir.is_empty() {
+            return None;
+        }
+        let mut html = format!(
+            "<html><head><title>Index of /{dir}</title></head><body>\
+             <h1>Index of /{dir}</h1><hr><pre><a href=\"../\">../</a>\n"
+        );
+        for child in children {
+            let href = child.replace(' ', "%20");
+            html.push_str(&format!(
+                "<a href=\"{href}\">{child}</a>  01-Jan-2023 00:00  -\n"
+            ));
+        }
+        html.push_str("<a href=\"?C=M;O=A\">sort</a></pre><hr></body></html>");
+        Some(html)
+    }
+}
+
+async fn serve(State(up): State<Shared>, uri: Uri, headers: HeaderMap) -> Response {
+    let path = percent_decode(uri.path().trim_start_matches('/'));
+    if path.is_empty() || path.ends_with('/') {
+        let listing = up.lock().unwrap().listing(&path);
+        return match listing {
+            Some(html) => ([(header::CONTENT_TYPE, "text/html")], html).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        };
+    }
+
+    let (found, status, stall) = {
+        let mut up = up.lock().unwrap();
+        if up.broken.contains(&path) {
+            up.hits.push((path, 500));
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        let stall = up.stall.as_deref() == Some(path.as_str());
+        let found = up.files.get(&path).cloned();
+        let status = match &found {
+            None => 404,
+            Some((_, etag))
+                if headers
+                    .get(header::IF_NONE_MATCH)
+                    .is_some_and(|v| v == etag) =>
+            {
+                304
+            }
+            Some(_) => 200,
+        };
+        up.hits.push((path, status));
+        drop(up);
+        (found, status, stall)
+    };
+    if stall {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+    let Some((body, etag)) = found else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if status == 304 {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    Response::builder()
+        .header(header::ETAG, etag)
+        .header(header::LAST_MODIFIED, LAST_MODIFIED)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn percent_decode(s: &str) -> String {
+    s.replace("%20", " ")
+}
+
+async fn start_upstream(up: Shared) -> String {
+    let app = Router::new().fallback(serve).with_state(up);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}/")
+}
+
+fn http_mirror(
+    upstream: &str,
+    dir: &TempDir,
+    exclude: Option<Vec<String>>,
+) -> Box<dyn MirrorProvider> {
+    let mirror = MirrorConfig {
+        name: Some("files".into()),
+        provider: Some("http".into()),
+        upstream: Some(upstream.into()),
+        exclude_patterns: exclude,
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+    build_provider("files", &mirror, &worker).unwrap()
+}
+
+fn ctx() -> RunContext {
+    RunContext {
+        cancel: CancellationToken::new(),
+        attempt: 1,
+        env: Default::default(),
+        bandwidth: None,
+        progress: None,
+        allow_mass_delete: false,
+    }
+}
+
+fn seeded() -> Shared {
+    let mut up = Upstream::default();
+    up.put("README", "hello\n");
+    up.put("pool/a.deb", "aaaa");
+    up.put("pool/main/b c.deb", "bbbbbb");
+    Arc::new(Mutex::new(up))
+}
+
+fn hits_for(up: &Shared, path: &str) -> Vec<u16> {
+    up.lock()
+        .unwrap()
+        .hits
+        .iter()
+        .filter(|(p, _)| p == path)
+        .map(|(_, s)| *s)
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// 1. initial mirror
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn first_run_mirrors_tree_and_reports_size() {
+    let up = seeded();
+    let url = start_upstream(Arc::clone(&up)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = http_mirror(&url, &dir, None);
+
+    provider.run(ctx()).await.unwrap();
+
+    let root = provider.working_dir().to_path_buf();
+    assert_eq!(
+        std::fs::read_to_string(root.join("README")).unwrap(),
+        "hello\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("pool/a.deb")).unwrap(),
+        "aaaa"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("pool/main/b c.deb")).unwrap(),
+        "bbbbbb"
+    );
+    // Listing artefacts (parent link, sort query) are not mirrored.
+    assert_eq!(std::fs::read_dir(&root).unwrap().count(), 2);
+
+    let mtime = std::fs::metadata(root.join("README"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    let expected = chrono::DateTime::parse_from_rfc2822(LAST_MODIFIED).unwrap();
+    assert_eq!(chrono::DateTime::<chrono::Utc>::from(mtime), expected);
+
+    assert_eq!(provider.data_size().await.as_deref(), Some("16"));
+}
+
+// ---------------------------------------------------------------------------
+// 2. conditional revalidation
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn unchanged_files_are_revalidated_not_refetched() {
+    let up = seeded();
+    let url = start_upstream(Arc::clone(&up)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = http_mirror(&url, &dir, None);
+
+    provider.run(ctx()).await.unwrap();
+    up.lock().unwrap().put("pool/a.deb", "changed!");
+    provider.run(ctx()).await.unwrap();
+
+    assert_eq!(hits_for(&up, "README"), vec![200, 304]);
+    assert_eq!(hits_for(&up, "pool/a.deb"), vec![200, 200]);
+    let root = provider.working_dir().to_path_buf();
+    assert_eq!(
+        std::fs::read_to_string(root.join("README")).unwrap(),
+        "hello\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("pool/a.deb")).unwrap(),
+        "changed!"
+    );
+    assert_eq!(provider.data_size().await.as_deref(), Some("20"));
+}
+
+// ---------------------------------------------------------------------------
+// 3. deletions
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn files_removed_upstream_are_deleted() {
+    let up = seeded();
+    let url = start_upstream(Arc::clone(&up)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = http_mirror(&url, &dir, None);
+
+    provider.run(ctx()).await.unwrap();
+    let root = provider.working_dir().to_path_buf();
+    std::fs::write(root.join("local-only"), "x").unwrap();
+    up.lock().unwrap().files.remove("pool/main/b c.deb");
+    provider.run(ctx()).await.unwrap();
+
+    assert!(!root.join("local-only").exists());
+    assert!(!root.join("pool/main").exists(), "emptied dir should go");
+    assert!(root.join("pool/a.deb").exists());
+}
+
+// ---------------------------------------------------------------------------
+// 4. excludes
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn excluded_paths_are_not_fetched_or_deleted() {
+    let up = seeded();
+    let url = start_upstream(Arc::clone(&up)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = http_mirror(
+        &url,
+        &dir,
+        Some(vec![r"^pool/main/".into(), r"^keep".into()]),
+    );
+
+    let root = provider.working_dir().to_path_buf();
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("keep.txt"), "local").unwrap();
+    provider.run(ctx()).await.unwrap();
+
+    assert!(hits_for(&up, "pool/main/b c.deb").is_empty());
+    assert!(!root.join("pool/main").exists());
+    assert_eq!(
+        std::fs::read_to_string(root.join("keep.txt")).unwrap(),
+        "local"
+    );
+    assert!(root.join("pool/a.deb").exists());
+}
+
+// ---------------------------------------------------------------------------
+// 5. cancel
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn cancel_mid_transfer_terminates() {
+    let up = seeded();
+    up.lock().unwrap().stall = Some("README".into());
+    let url = start_upstream(Arc::clone(&up)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = Arc::new(http_mirror(&url, &dir, None));
+
+    let run_ctx = ctx();
+    let cancel = run_ctx.cancel.clone();
+    let p = Arc::clone(&provider);
+    let handle = tokio::spawn(async move { p.run(run_ctx).await });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    cancel.cancel();
+
+    let res = tokio::time::timeout(Duration::from_secs(10), handle)
+        .await
+        .expect("run must return promptly after cancel")
+        .unwrap();
+    assert!(matches!(res, Err(ProviderError::Terminated)), "got {res:?}");
+    assert!(provider.data_size().await.is_none());
+}
+
+// ---------------------------------------------------------------------------
+// 6. upstream error
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn upstream_error_status_fails_run() {
+    let up = seeded();
+    let url = start_upstream(Arc::clone(&up)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = http_mirror(&url, &dir, None);
+    provider.run(ctx()).await.unwrap();
+
+    up.lock().unwrap().broken.push("pool/a.deb".into());
+    let err = provider.run(ctx()).await.unwrap_err();
+    assert!(
+        matches!(&err, ProviderError::Http(msg) if msg.contains("500")),
+        "got {err:?}"
+    );
+    assert_eq!(provider.data_size().await.as_deref(), Some("16"));
+}
@@ -0,0 +1,178 @@
+//! Contract tests for the cross-process mirror lock.
+//!
+//! Scenarios:
+//! 1. a second acquire fails fast and names the holder's pid and host
+//! 2. with `wait`, an acquire succeeds once the holder lets go
+//! 3. a waiting acquire gives up when the job is stopped
+//! 4. a job whose lock is held reports `Failed` without running the
+//!    provider
+//! 5. a job waiting on the lock holds no concurrency slot, and a Restart
+//!    while it waits leaves the actor free to take a later Stop
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_internal::status::SyncStatus;
+use hustsync_worker::job::{CtrlAction, JobActor, JobOptions, STATE_PAUSED};
+use hustsync_worker::lock::{LockError, MirrorLock, lock_path};
+use hustsync_worker::provider::build_provider;
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn second_acquire_names_the_holder() {
+    let tmp = TempDir::new().unwrap();
+    let path = lock_path(tmp.path(), "arch");
+    let _held = MirrorLock::acquire(&path, false, || false).await.unwrap();
+
+    let err = MirrorLock::acquire(&path, false, || false)
+        .await
+        .expect_err("lock is held");
+    assert!(matches!(err, LockError::Held { .. }));
+    let msg = err.to_string();
+    assert!(
+        msg.contains(&format!("locked by pid {} on ", std::process::id())),
+        "got {msg:?}"
+    );
+}
+
+#[tokio::test]
+async fn waiting_acquire_succeeds_after_release() {
+    let tmp = TempDir::new().unwrap();
+    let path = lock_path(tmp.path(), "arch");
+    let held = MirrorLock::acquire(&path, false, || false).await.unwrap();
+
+    let waiter = {
+        let path = path.clone();
+        tokio::spawn(async move { MirrorLock::acquire(&path, true, || false).await })
+    };
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!waiter.is_finished(), "waiter must block while held");
+
+    drop(held);
+    tokio::time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .expect("waiter did not get the lock")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn waiting_acquire_stops_when_cancelled() {
+    let tmp = TempDir::new().unwrap();
+    let path = lock_path(tmp.path(), "arch");
+    let _held = MirrorLock::acquire(&path, false, || false).await.unwrap();
+
+    let err = MirrorLock::acquire(&path, true, || true)
+        .await
+        .expect_err("cancelled");
+    assert!(matches!(err, LockError::Cancelled));
+}
+
+#[tokio::test]
+async fn job_fails_fast_while_another_process_holds_the_lock() {
+    let tmp = TempDir::new().unwrap();
+    let ran = tmp.path().join("ran");
+    let mirror = MirrorConfig {
+        name: Some("arch".into()),
+        provider: Some("command".into()),
+        upstream: Some("https://mirror.test/archlinux/".into()),
+        command: Some(format!("touch {}", ran.display())),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(tmp.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(tmp.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+    let provider = build_provider("arch", &mirror, &worker).unwrap();
+    let _held = MirrorLock::acquire(&lock_path(provider.log_dir(), "arch"), false, || false)
+        .await
+        .unwrap();
+
+    let (manager_tx, mut manager_rx) = mpsc::channel(64);
+    let (job, actor) = JobActor::new(
+        "arch".into(),
+        manager_tx,
+        Arc::new(tokio::sync::Semaphore::new(1)),
+        None,
+        provider,
+        vec![],
+        JobOptions::default(),
+    );
+    tokio::spawn(actor.run());
+    job.send_ctrl(CtrlAction::Start).await.unwrap();
+
+    let failed = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let msg = manager_rx.recv().await.expect("manager channel closed");
+            if msg.status == SyncStatus::Failed {
+                return msg;
+            }
+        }
+    })
+    .await
+    .expect("run did not fail");
+    assert!(failed.msg.contains("locked by pid"), "got {:?}", failed.msg);
+    assert!(!ran.exists(), "provider must not run without the lock");
+}
+
+#[tokio::test]
+async fn job_waiting_on_the_lock_holds_no_slot_and_can_restart() {
+    let tmp = TempDir::new().unwrap();
+    let mirror = MirrorConfig {
+        name: Some("arch".into()),
+        provider: Some("command".into()),
+        upstream: Some("https://mirror.test/archlinux/".into()),
+        command: Some("true".into()),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(tmp.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(tmp.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+    let provider = build_provider("arch", &mirror, &worker).unwrap();
+    let _held = MirrorLock::acquire(&lock_path(provider.log_dir(), "arch"), false, || false)
+        .await
+        .unwrap();
+
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(1));
+    let (manager_tx, _manager_rx) = mpsc::channel(64);
+    let (job, actor) = JobActor::new(
+        "arch".into(),
+        manager_tx,
+        Arc::clone(&semaphore),
+        None,
+        provider,
+        vec![],
+        JobOptions {
+            lock_wait: true,
+            ..JobOptions::default()
+        },
+    );
+    tokio::spawn(actor.run());
+    job.send_ctrl(CtrlAction::Start).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(semaphore.available_permits(), 1, "waiter took a slot");
+
+    job.send_ctrl(CtrlAction::Restart).await.unwrap();
+    job.send_ctrl(CtrlAction::Stop).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while job.state() != STATE_PAUSED {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("actor stuck in Restart");
+    assert_eq!(semaphore.available_permits(), 1);
+}
@@ -0,0 +1,119 @@
+//! Contract tests for the backoff between retry attempts.
+//!
+//! Scenarios:
+//! 1. a failed attempt reports when the next one starts, and the next
+//!    attempt waits until then; the last failure reports no retry
+//! 2. `Stop` cuts a pending backoff short, so the actor takes the next
+//!    `Start` straight away
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use hustsync_config_parser::{MirrorConfig, RetryStrategy, WorkerConfig, WorkerGlobalConfig};
+use hustsync_internal::status::SyncStatus;
+use hustsync_worker::backoff::RetryBackoff;
+use hustsync_worker::job::{CtrlAction, JobActor, JobOptions};
+use hustsync_worker::provider::build_provider;
+use hustsync_worker::{JobMessage, MirrorJob};
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+
+fn failing_job(dir: &Path, backoff_secs: u32) -> (MirrorJob, mpsc::Receiver<JobMessage>) {
+    let mirror = MirrorConfig {
+        name: Some("arch".into()),
+        provider: Some("command".into()),
+        upstream: Some("https://mirror.test/archlinux/".into()),
+        command: Some("false".into()),
+        retry: Some(RetryStrategy {
+            retry: Some(2),
+            timeout: None,
+            interval: None,
+        }),
+        retry_backoff: Some(backoff_secs),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+
+    let (manager_tx, manager_rx) = mpsc::channel(64);
+    let (job, actor) = JobActor::new(
+        "arch".into(),
+        manager_tx,
+        Arc::new(tokio::sync::Semaphore::new(1)),
+        None,
+        build_provider("arch", &mirror, &worker).unwrap(),
+        vec![],
+        JobOptions {
+            backoff: RetryBackoff::from_config(&mirror),
+            ..JobOptions::default()
+        },
+    );
+    tokio::spawn(actor.run());
+    (job, manager_rx)
+}
+
+/// The next report with `status`.
+async fn next_with(rx: &mut mpsc::Receiver<JobMessage>, status: SyncStatus) -> JobMessage {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let msg = rx.recv().await.expect("manager channel closed");
+            if msg.status == status {
+                return msg;
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("no {status:?} report"))
+}
+
+#[tokio::test]
+async fn next_attempt_waits_for_the_reported_retry_time() {
+    let tmp = TempDir::new().unwrap();
+    let (job, mut rx) = failing_job(tmp.path(), 1);
+    job.send_ctrl(CtrlAction::Start).await.unwrap();
+
+    let first = next_with(&mut rx, SyncStatus::Failed).await;
+    let failed_at = Instant::now();
+    let retry_at = first.retry_at.expect("first failure schedules a retry");
+    let ahead = retry_at - Utc::now();
+    assert!(
+        ahead > chrono::TimeDelta::zero() && ahead <= chrono::TimeDelta::seconds(1),
+        "retry {ahead} ahead"
+    );
+    assert!(!first.schedule, "a retried failure must not reschedule");
+
+    next_with(&mut rx, SyncStatus::PreSyncing).await;
+    assert!(
+        failed_at.elapsed() >= Duration::from_millis(900),
+        "second attempt started after {:?}",
+        failed_at.elapsed()
+    );
+
+    let last = next_with(&mut rx, SyncStatus::Failed).await;
+    assert_eq!(last.retry_at, None);
+    assert!(last.schedule);
+}
+
+#[tokio::test]
+async fn stop_interrupts_the_backoff() {
+    let tmp = TempDir::new().unwrap();
+    let (job, mut rx) = failing_job(tmp.path(), 3600);
+    job.send_ctrl(CtrlAction::Start).await.unwrap();
+    let first = next_with(&mut rx, SyncStatus::Failed).await;
+    assert!(first.retry_at.is_some());
+
+    job.send_ctrl(CtrlAction::Stop).await.unwrap();
+    job.send_ctrl(CtrlAction::Start).await.unwrap();
+    // Only reachable once the stopped run has ended.
+    next_with(&mut rx, SyncStatus::PreSyncing).await;
+}
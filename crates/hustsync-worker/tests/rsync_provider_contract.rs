@@ -16,8 +16,14 @@
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
+use hustsync_config_parser::WorkerBandwidthConfig;
+use hustsync_internal::status::SyncStatus;
+use hustsync_worker::JobMessage;
+use hustsync_worker::bandwidth::BandwidthBudget;
+use hustsync_worker::provider::progress::ProgressReporter;
 use hustsync_worker::provider::rsync_provider::{RsyncProvider, RsyncProviderConfig};
 use hustsync_worker::provider::{CommonProviderConfig, MirrorProvider, ProviderError, RunContext};
 use serde::Deserialize;
@@ -86,6 +92,7 @@ fn config_from_fixture(f: &ArgvFixture) -> RsyncProviderConfig {
             env: f.env.clone(),
             is_master: true,
             success_exit_codes: vec![],
+            cgroup: None,
         },
         command: "rsync".to_string(),
         username: f.config.username.clone(),
@@ -97,8 +104,13 @@ fn config_from_fixture(f: &ArgvFixture) -> RsyncProviderConfig {
         rsync_override_only: f.config.rsync_override_only,
         rsync_no_timeout: false,
         rsync_timeout: None,
+        rsync_progress: false,
         use_ipv6: f.config.use_ipv6,
         use_ipv4: f.config.use_ipv4,
+        docker: None,
+        fallback_upstreams: vec![],
+        max_delete: None,
+        max_delete_percent: None,
     }
 }
 
@@ -194,6 +206,7 @@ fn zero_rsync_timeout_falls_back_to_default_not_zero() {
             env: HashMap::new(),
             is_master: true,
             success_exit_codes: vec![],
+            cgroup: None,
         },
         command: "rsync".to_string(),
         username: None,
@@ -205,8 +218,13 @@ fn zero_rsync_timeout_falls_back_to_default_not_zero() {
         rsync_override_only: false,
         rsync_no_timeout: false,
         rsync_timeout: Some(0),
+        rsync_progress: false,
         use_ipv6: false,
         use_ipv4: false,
+        docker: None,
+        fallback_upstreams: vec![],
+        max_delete: None,
+        max_delete_percent: None,
     };
     let provider = RsyncProvider::new(config).unwrap();
     let args = provider.build_args();
@@ -256,6 +274,7 @@ async fn run_injects_canonical_env_and_legacy_aliases() {
             env: HashMap::new(),
             is_master: true,
             success_exit_codes: vec![],
+            cgroup: None,
         },
         command: "sh".to_string(),
         username: None,
@@ -267,8 +286,13 @@ async fn run_injects_canonical_env_and_legacy_aliases() {
         rsync_override_only: true,
         rsync_no_timeout: false,
         rsync_timeout: None,
+        rsync_progress: false,
         use_ipv6: false,
         use_ipv4: false,
+        docker: None,
+        fallback_upstreams: vec![],
+        max_delete: None,
+        max_delete_percent: None,
     };
 
     let provider = RsyncProvider::new(config).unwrap();
@@ -316,6 +340,7 @@ async fn run_returns_timeout_error_when_deadline_exceeded() {
             env: HashMap::new(),
             is_master: true,
             success_exit_codes: vec![],
+            cgroup: None,
         },
         // Use `sh` as the executable with the sleep command as an override so
         // the binary validation in RsyncProvider::new does not reject it.
@@ -330,8 +355,13 @@ async fn run_returns_timeout_error_when_deadline_exceeded() {
         rsync_override_only: true,
         rsync_no_timeout: false,
         rsync_timeout: None,
+        rsync_progress: false,
         use_ipv6: false,
         use_ipv4: false,
+        docker: None,
+        fallback_upstreams: vec![],
+        max_delete: None,
+        max_delete_percent: None,
     };
 
     let provider = RsyncProvider::new(config).unwrap();
@@ -339,6 +369,9 @@ async fn run_returns_timeout_error_when_deadline_exceeded() {
         cancel: CancellationToken::new(),
         attempt: 1,
         env: HashMap::new(),
+        bandwidth: None,
+        progress: None,
+        allow_mass_delete: false,
     };
 
     let result = tokio::time::timeout(Duration::from_secs(5), provider.run(ctx))
@@ -376,6 +409,7 @@ async fn run_returns_terminated_on_cancel() {
             env: HashMap::new(),
             is_master: true,
             success_exit_codes: vec![],
+            cgroup: None,
         },
         command: "sh".to_string(),
         username: None,
@@ -387,8 +421,13 @@ async fn run_returns_terminated_on_cancel() {
         rsync_override_only: true,
         rsync_no_timeout: false,
         rsync_timeout: None,
+        rsync_progress: false,
         use_ipv6: false,
         use_ipv4: false,
+        docker: None,
+        fallback_upstreams: vec![],
+        max_delete: None,
+        max_delete_percent: None,
     };
 
     use std::sync::Arc;
@@ -399,6 +438,9 @@ async fn run_returns_terminated_on_cancel() {
         cancel: token.clone(),
         attempt: 1,
         env: HashMap::new(),
+        bandwidth: None,
+        progress: None,
+        allow_mass_delete: false,
     };
 
     // Spawn run() as a separate task so we can cancel it from this task.
@@ -421,3 +463,364 @@ async fn run_returns_terminated_on_cancel() {
         other => panic!("expected ProviderError::Terminated, got: {other:?}"),
     }
 }
+
+// ---------------------------------------------------------------------------
+// fallback upstreams — the shell stub receives the upstream as `$0` and
+// fails like an unreachable daemon (exit 10) for hosts named `down`.
+// ---------------------------------------------------------------------------
+
+fn failover_config(
+    tmp: &tempfile::TempDir,
+    script: &str,
+    fallbacks: &[&str],
+) -> RsyncProviderConfig {
+    let log_dir = tmp.path().join("log");
+    RsyncProviderConfig {
+        common: CommonProviderConfig {
+            name: "failover-test".to_string(),
+            upstream_url: "rsync://down.example/repo/".to_string(),
+            working_dir: tmp.path().join("work").to_string_lossy().into_owned(),
+            log_dir: log_dir.to_string_lossy().into_owned(),
+            log_file: log_dir.join("run.log").to_string_lossy().into_owned(),
+            interval: Duration::from_secs(3600),
+            retry: 1,
+            timeout: Duration::from_secs(5),
+            env: HashMap::new(),
+            is_master: true,
+            success_exit_codes: vec![],
+            cgroup: None,
+        },
+        command: "sh".to_string(),
+        username: None,
+        password: None,
+        exclude_file: None,
+        rsync_options: vec![],
+        global_options: vec![],
+        rsync_override: Some(vec!["-c".to_string(), script.to_string()]),
+        rsync_override_only: true,
+        rsync_no_timeout: false,
+        rsync_timeout: None,
+        rsync_progress: false,
+        use_ipv6: false,
+        use_ipv4: false,
+        docker: None,
+        fallback_upstreams: fallbacks.iter().map(|s| s.to_string()).collect(),
+        max_delete: None,
+        max_delete_percent: None,
+    }
+}
+
+const FAILOVER_SCRIPT: &str = r#"echo "attempt $0 as $HUSTSYNC_UPSTREAM_URL"
+case "$0" in *down*) exit 10 ;; esac"#;
+
+#[tokio::test]
+async fn connection_failure_moves_to_next_upstream() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let config = failover_config(
+        &tmp,
+        FAILOVER_SCRIPT,
+        &["rsync://down2.example/repo/", "rsync://up.example/repo/"],
+    );
+    let log_file = config.common.log_file.clone();
+
+    let provider = RsyncProvider::new(config).unwrap();
+    provider.run(RunContext::default()).await.unwrap();
+    assert_eq!(provider.upstream(), "rsync://up.example/repo/");
+
+    // Every attempt appends to the same run log.
+    let log = tokio::fs::read_to_string(&log_file).await.unwrap();
+    for upstream in [
+        "rsync://down.example/repo/",
+        "rsync://down2.example/repo/",
+        "rsync://up.example/repo/",
+    ] {
+        assert!(
+            log.contains(&format!("attempt {upstream} as {upstream}")),
+            "missing attempt for {upstream}; log:\n{log}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn exhausted_upstreams_return_last_error() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let config = failover_config(&tmp, FAILOVER_SCRIPT, &["rsync://down2.example/repo/"]);
+
+    let provider = RsyncProvider::new(config).unwrap();
+    let res = provider.run(RunContext::default()).await;
+    assert!(
+        matches!(res, Err(ProviderError::Execution { code: 10, .. })),
+        "got: {res:?}"
+    );
+    assert_eq!(provider.upstream(), "rsync://down2.example/repo/");
+}
+
+#[tokio::test]
+async fn other_failures_stay_on_the_primary() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let config = failover_config(&tmp, "exit 23", &["rsync://up.example/repo/"]);
+
+    let provider = RsyncProvider::new(config).unwrap();
+    let res = provider.run(RunContext::default()).await;
+    assert!(
+        matches!(res, Err(ProviderError::Execution { code: 23, .. })),
+        "got: {res:?}"
+    );
+    assert_eq!(provider.upstream(), "rsync://down.example/repo/");
+}
+
+#[test]
+fn fallback_upstream_must_end_with_slash() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let config = failover_config(&tmp, "true", &["rsync://up.example/repo"]);
+    assert!(matches!(
+        RsyncProvider::new(config),
+        Err(ProviderError::Config(_))
+    ));
+}
+
+// ---------------------------------------------------------------------------
+// worker bandwidth budget — the shell stub echoes the argv it received
+// into the run log (`$0` is the first option after the override).
+// ---------------------------------------------------------------------------
+
+const ARGV_ECHO_SCRIPT: &str = r#"echo "argv: $0 $*""#;
+
+fn budget(limit: &str) -> Arc<BandwidthBudget> {
+    let cfg = WorkerBandwidthConfig {
+        limit: Some(limit.to_string()),
+        tiers: None,
+    };
+    Arc::new(BandwidthBudget::from_config(&cfg).unwrap())
+}
+
+async fn run_with_budget(config: RsyncProviderConfig, budget: &Arc<BandwidthBudget>) -> String {
+    let log_file = config.common.log_file.clone();
+    let provider = RsyncProvider::new(config).unwrap();
+    let ctx = RunContext {
+        bandwidth: Some(Arc::clone(budget)),
+        ..RunContext::default()
+    };
+    provider.run(ctx).await.unwrap();
+    tokio::fs::read_to_string(&log_file).await.unwrap()
+}
+
+#[tokio::test]
+async fn budget_is_split_with_other_running_jobs() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let mut config = failover_config(&tmp, ARGV_ECHO_SCRIPT, &[]);
+    config.rsync_override_only = false;
+    let budget = budget("8M");
+
+    let other_job = budget.join();
+    let log = run_with_budget(config, &budget).await;
+    assert!(log.contains("--bwlimit=4096 "), "log:\n{log}");
+
+    // The run released its share: a lone job now gets the whole budget.
+    drop(other_job);
+    assert_eq!(budget.join().rsync_bwlimit(), Some(8192));
+}
+
+#[tokio::test]
+async fn mirror_bwlimit_overrides_budget_share() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let mut config = failover_config(&tmp, ARGV_ECHO_SCRIPT, &[]);
+    config.rsync_override_only = false;
+    config.rsync_options = vec!["--bwlimit=100".to_string()];
+
+    let log = run_with_budget(config, &budget("8M")).await;
+    let budget_at = log.find("--bwlimit=8192").expect("budget share missing");
+    let mirror_at = log.find("--bwlimit=100 ").expect("mirror limit missing");
+    assert!(budget_at < mirror_at, "mirror option must come last: {log}");
+}
+
+#[tokio::test]
+async fn override_only_ignores_budget() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let config = failover_config(&tmp, ARGV_ECHO_SCRIPT, &[]);
+
+    let log = run_with_budget(config, &budget("8M")).await;
+    assert!(!log.contains("--bwlimit"), "log:\n{log}");
+}
+
+// ---------------------------------------------------------------------------
+// live progress — the stub prints two `--info=progress2` updates and a
+// regular line; only the first update is due within the report interval.
+// ---------------------------------------------------------------------------
+
+const PROGRESS_SCRIPT: &str = r#"printf '  1,024  10%%  1.00MB/s  0:00:01\r  2,048 100%%  2.00MB/s  0:00:00 (xfr#1, to-chk=0/1)\n'
+echo "argv: $0 $*""#;
+
+async fn run_with_progress(rsync_progress: bool) -> (Vec<JobMessage>, String) {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let mut config = failover_config(&tmp, PROGRESS_SCRIPT, &[]);
+    config.rsync_override_only = false;
+    config.rsync_progress = rsync_progress;
+    let log_file = config.common.log_file.clone();
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+    let provider = RsyncProvider::new(config).unwrap();
+    let ctx = RunContext {
+        progress: Some(ProgressReporter::new("failover-test".to_string(), true, tx)),
+        ..RunContext::default()
+    };
+    provider.run(ctx).await.unwrap();
+
+    let mut messages = Vec::new();
+    while let Ok(msg) = rx.try_recv() {
+        messages.push(msg);
+    }
+    let log = tokio::fs::read_to_string(&log_file).await.unwrap();
+    (messages, log)
+}
+
+#[tokio::test]
+async fn progress_lines_are_relayed_not_logged() {
+    let (messages, log) = run_with_progress(true).await;
+
+    assert_eq!(messages.len(), 1, "second update falls inside the interval");
+    let progress = messages[0].progress.as_ref().expect("progress message");
+    assert_eq!(messages[0].name, "failover-test");
+    assert_eq!(messages[0].status, SyncStatus::Syncing);
+    assert_eq!(progress.bytes, 1024);
+    assert_eq!(progress.percent, 10);
+    assert_eq!(progress.rate, "1.00MB/s");
+
+    assert!(log.contains("--info=progress2"), "log:\n{log}");
+    assert!(
+        !log.contains("10%"),
+        "progress must not reach the log:\n{log}"
+    );
+}
+
+#[tokio::test]
+async fn progress_is_off_by_default() {
+    let (messages, log) = run_with_progress(false).await;
+
+    assert!(messages.is_empty());
+    assert!(!log.contains("--info=progress2"), "log:\n{log}");
+    assert!(
+        log.contains("10%"),
+        "stdout goes to the log untouched:\n{log}"
+    );
+}
+
+// ---------------------------------------------------------------------------
+// transfer stats — parsed from the `--stats` block of a successful run only.
+// ---------------------------------------------------------------------------
+
+const STATS_SCRIPT: &str = r#"cat <<'STATS'
+Number of files: 12 (reg: 10, dir: 2)
+Number of deleted files: 3 (reg: 3)
+Number of regular files transferred: 5
+Literal data: 1.50K bytes
+Matched data: 2,048 bytes
+Total bytes sent: 120
+Total bytes received: 3.60K
+STATS
+exit "${FAKE_EXIT:-0}""#;
+
+#[tokio::test]
+async fn stats_block_becomes_transfer_stats() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let provider = RsyncProvider::new(failover_config(&tmp, STATS_SCRIPT, &[])).unwrap();
+    assert!(provider.transfer_stats().await.is_none());
+
+    provider.run(RunContext::default()).await.unwrap();
+    let stats = provider.transfer_stats().await.expect("stats");
+    assert_eq!(stats.files_transferred, 5);
+    assert_eq!(stats.files_deleted, 3);
+    assert_eq!((stats.bytes_sent, stats.bytes_received), (120, 3_600));
+    assert_eq!((stats.literal_data, stats.matched_data), (1_500, 2_048));
+}
+
+#[tokio::test]
+async fn failed_run_reports_no_stats() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let provider = RsyncProvider::new(failover_config(&tmp, STATS_SCRIPT, &[])).unwrap();
+    let ctx = RunContext {
+        env: HashMap::from([("FAKE_EXIT".to_string(), "23".to_string())]),
+        ..RunContext::default()
+    };
+    assert!(provider.run(ctx).await.is_err());
+    assert!(provider.transfer_stats().await.is_none());
+}
+
+// ---------------------------------------------------------------------------
+// mass-deletion guard — the dry run arrives as `sh -c SCRIPT --dry-run ...`,
+// so `$0` tells the plan apart from the transfer. The plan deletes 3 of 10
+// local files.
+// ---------------------------------------------------------------------------
+
+const DELETE_PLAN_SCRIPT: &str = r#"case "$0" in
+--dry-run)
+  printf '*deleting   a\n*deleting   b\n*deleting   c\n'
+  printf 'Number of files: 7 (reg: 7)\nNumber of regular files transferred: 99\n' ;;
+*)
+  echo transfer
+  echo 'Number of regular files transferred: 1' ;;
+esac"#;
+
+fn guarded_config(
+    tmp: &tempfile::TempDir,
+    max_delete: Option<u64>,
+    max_delete_percent: Option<u8>,
+) -> RsyncProviderConfig {
+    RsyncProviderConfig {
+        max_delete,
+        max_delete_percent,
+        ..failover_config(tmp, DELETE_PLAN_SCRIPT, &[])
+    }
+}
+
+#[tokio::test]
+async fn mass_deletion_is_refused_before_transfer() {
+    for (max_delete, max_delete_percent) in [(Some(2), None), (None, Some(20))] {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let config = guarded_config(&tmp, max_delete, max_delete_percent);
+        let log_file = config.common.log_file.clone();
+        let provider = RsyncProvider::new(config).unwrap();
+
+        let res = provider.run(RunContext::default()).await;
+        assert!(
+            matches!(&res, Err(ProviderError::MassDeletion(msg)) if msg.contains("delete 3 files")),
+            "got: {res:?}"
+        );
+        let log = tokio::fs::read_to_string(&log_file).await.unwrap();
+        assert!(!log.contains("transfer\n"), "log:\n{log}");
+    }
+}
+
+#[tokio::test]
+async fn plan_within_limits_goes_ahead() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let config = guarded_config(&tmp, Some(3), Some(30));
+    let log_file = config.common.log_file.clone();
+    let provider = RsyncProvider::new(config).unwrap();
+
+    provider.run(RunContext::default()).await.unwrap();
+    let log = tokio::fs::read_to_string(&log_file).await.unwrap();
+    assert!(
+        log.contains("*deleting") && log.contains("transfer\n"),
+        "log:\n{log}"
+    );
+    let stats = provider.transfer_stats().await.expect("stats");
+    assert_eq!(stats.files_transferred, 1, "the dry run is not counted");
+}
+
+#[tokio::test]
+async fn allow_mass_delete_skips_the_dry_run() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let config = guarded_config(&tmp, Some(0), None);
+    let log_file = config.common.log_file.clone();
+    let provider = RsyncProvider::new(config).unwrap();
+
+    let ctx = RunContext {
+        allow_mass_delete: true,
+        ..RunContext::default()
+    };
+    provider.run(ctx).await.unwrap();
+    let log = tokio::fs::read_to_string(&log_file).await.unwrap();
+    assert!(!log.contains("*deleting"), "log:\n{log}");
+    assert!(log.contains("transfer\n"), "log:\n{log}");
+}
@@ -0,0 +1,360 @@
+//! Contract tests for the `s3` provider.
+//!
+//! A local axum server plays a path-style S3 endpoint for one bucket:
+//! `GET /<bucket>?list-type=2` answers `ListObjectsV2` two keys per page,
+//! `GET /<bucket>/<key>` serves the object with its ETag. Requests are
+//! recorded together with their `Authorization` header.
+//!
+//! Scenarios:
+//! 1. the first run pages through the listing, mirrors the prefix, signs
+//!    its requests and reports `data_size`
+//! 2. unchanged objects are not downloaded again, changed ones are
+//! 3. `delete_removed` decides whether objects gone upstream are deleted
+//! 4. `terminate()` stops a run stuck in a download
+//! 5. an S3 error document fails the run with its error code
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_worker::provider::{MirrorProvider, ProviderError, RunContext, build_provider};
+use tempfile::TempDir;
+
+const BUCKET: &str = "mirror-bucket";
+const PAGE_SIZE: usize = 2;
+
+#[derive(Default)]
+struct Bucket {
+    /// Key → (body, etag).
+    objects: BTreeMap<String, (Vec<u8>, String)>,
+    /// Object keys downloaded, in order.
+    downloads: Vec<String>,
+    /// Authorization header of every request ("" when unsigned).
+    auth: Vec<String>,
+    /// Answer everything with 403 AccessDenied.
+    deny: bool,
+    /// Key whose download never finishes.
+    stall: Option<String>,
+}
+
+type Shared = Arc<Mutex<Bucket>>;
+
+impl Bucket {
+    fn put(&mut self, key: &str, body: &str) {
+        let etag = format!(
+            "\"{:x}-{}\"",
+            body.len(),
+            body.bytes().map(u32::from).sum::<u32>()
+        );
+        self.objects
+            .insert(key.into(), (body.as_bytes().to_vec(), etag));
+    }
+
+    /// One `ListObjectsV2` page; the continuation token is the index of
+    /// the first key on the next page.
+    fn list(&self, prefix: &str, token: Option<&str>) -> String {
+        let start: usize = token.map_or(0, |t| t.parse().unwrap());
+        let keys: Vec<_> = self
+            .objects
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .collect();
+        let page: Vec<_> = keys.iter().skip(start).take(PAGE_SIZE).collect();
+        let next = start + page.len();
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+             <Name>{BUCKET}</Name><Prefix>{prefix}</Prefix><KeyCount>{}</KeyCount>\
+             <IsTruncated>{}</IsTruncated>",
+            page.len(),
+            next < keys.len()
+        );
+        if next < keys.len() {
+            xml.push_str(&format!(
+                "<NextContinuationToken>{next}</NextContinuationToken>"
+            ));
+        }
+        for (key, (body, etag)) in page {
+            xml.push_str(&format!(
+                "<Contents><Key>{}</Key><LastModified>2023-01-02T03:04:05.000Z</LastModified>\
+                 <ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                key.replace('&', "&amp;"),
+                etag.replace('"', "&quot;"),
+                body.len()
+            ));
+        }
+        xml.push_str("</ListBucketResult>");
+        xml
+    }
+}
+
+async fn serve(
+    State(bucket): State<Shared>,
+    uri: Uri,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    let auth = headers
+        .get(header::AUTHORIZATION)
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let path = uri.path().replace("%20", " ");
+    let Some(rest) = path.strip_prefix(&format!("/{BUCKET}")) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let (found, stall) = {
+        let mut b = bucket.lock().unwrap();
+        b.auth.push(auth);
+        if b.deny {
+            return (
+                StatusCode::FORBIDDEN,
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+                 <Error><Code>AccessDenied</Code><Message>Access Denied</Message></Error>",
+            )
+                .into_response();
+        }
+        if rest.is_empty() || rest == "/" {
+            assert_eq!(query.get("list-type").map(String::as_str), Some("2"));
+            let prefix = query.get("prefix").cloned().unwrap_or_default();
+            let xml = b.list(&prefix, query.get("continuation-token").map(String::as_str));
+            return ([(header::CONTENT_TYPE, "application/xml")], xml).into_response();
+        }
+        let key = rest.trim_start_matches('/').to_string();
+        let found = b.objects.get(&key).cloned();
+        let stall = b.stall.as_deref() == Some(key.as_str());
+        b.downloads.push(key);
+        drop(b);
+        (found, stall)
+    };
+    if stall {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+    match found {
+        Some((body, etag)) => ([(header::ETAG, etag)], body).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn start_endpoint(bucket: Shared) -> String {
+    let app = Router::new().fallback(serve).with_state(bucket);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}")
+}
+
+fn s3_mirror(endpoint: &str, dir: &TempDir, delete_removed: bool) -> Box<dyn MirrorProvider> {
+    let env = HashMap::from([
+        ("AWS_ENDPOINT_URL".to_string(), endpoint.to_string()),
+        ("AWS_REGION".to_string(), "eu-west-1".to_string()),
+        ("AWS_ACCESS_KEY_ID".to_string(), "AKIDEXAMPLE".to_string()),
+        (
+            "AWS_SECRET_ACCESS_KEY".to_string(),
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+        ),
+    ]);
+    let mirror = MirrorConfig {
+        name: Some("objects".into()),
+        provider: Some("s3".into()),
+        upstream: Some(format!("s3://{BUCKET}/pub/")),
+        delete_removed: Some(delete_removed),
+        env: Some(env),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+    build_provider("objects", &mirror, &worker).unwrap()
+}
+
+fn ctx() -> RunContext {
+    RunContext {
+        attempt: 1,
+        ..RunContext::default()
+    }
+}
+
+fn seeded() -> Shared {
+    let mut b = Bucket::default();
+    b.put("pub/README", "hello\n");
+    b.put("pub/pool/a.deb", "aaaa");
+    b.put("pub/pool/b c.deb", "bbbbbb");
+    b.put("pub/pool/", "");
+    b.put("private/secret", "nope");
+    Arc::new(Mutex::new(b))
+}
+
+fn downloads(bucket: &Shared) -> Vec<String> {
+    bucket.lock().unwrap().downloads.clone()
+}
+
+// ---------------------------------------------------------------------------
+// 1. initial mirror
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn first_run_pages_through_listing_and_reports_size() {
+    let bucket = seeded();
+    let endpoint = start_endpoint(Arc::clone(&bucket)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = s3_mirror(&endpoint, &dir, false);
+
+    provider.run(ctx()).await.unwrap();
+
+    let root = provider.working_dir().to_path_buf();
+    assert_eq!(
+        std::fs::read_to_string(root.join("README")).unwrap(),
+        "hello\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(root.join("pool/b c.deb")).unwrap(),
+        "bbbbbb"
+    );
+    assert!(!root.join("secret").exists());
+    assert_eq!(downloads(&bucket).len(), 3, "folder marker is not fetched");
+
+    let mtime = std::fs::metadata(root.join("README"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    assert_eq!(
+        chrono::DateTime::<chrono::Utc>::from(mtime).to_rfc3339(),
+        "2023-01-02T03:04:05+00:00"
+    );
+
+    let auth = bucket.lock().unwrap().auth.clone();
+    // Four listed keys at two per page, plus three downloads.
+    assert_eq!(auth.len(), 5);
+    for a in &auth {
+        assert!(
+            a.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/")
+                && a.contains("/eu-west-1/s3/aws4_request"),
+            "auth: {a}"
+        );
+        assert!(!a.contains("EXAMPLEKEY"), "secret leaked into {a}");
+    }
+    assert_eq!(provider.data_size().await.as_deref(), Some("16"));
+}
+
+// ---------------------------------------------------------------------------
+// 2. change detection
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn only_new_or_changed_objects_are_downloaded() {
+    let bucket = seeded();
+    let endpoint = start_endpoint(Arc::clone(&bucket)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = s3_mirror(&endpoint, &dir, false);
+
+    provider.run(ctx()).await.unwrap();
+    bucket.lock().unwrap().downloads.clear();
+    {
+        let mut b = bucket.lock().unwrap();
+        // Same size, different content: only the ETag tells.
+        b.put("pub/pool/a.deb", "AAAA");
+        b.put("pub/new.txt", "new");
+    }
+    provider.run(ctx()).await.unwrap();
+
+    assert_eq!(downloads(&bucket), vec!["pub/new.txt", "pub/pool/a.deb"]);
+    let root = provider.working_dir().to_path_buf();
+    assert_eq!(
+        std::fs::read_to_string(root.join("pool/a.deb")).unwrap(),
+        "AAAA"
+    );
+    assert_eq!(provider.data_size().await.as_deref(), Some("19"));
+
+    // A local file that was tampered with is fetched again.
+    std::fs::write(root.join("README"), "oops").unwrap();
+    bucket.lock().unwrap().downloads.clear();
+    provider.run(ctx()).await.unwrap();
+    assert_eq!(downloads(&bucket), vec!["pub/README"]);
+}
+
+// ---------------------------------------------------------------------------
+// 3. deletions
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn delete_removed_controls_local_deletions() {
+    for delete_removed in [false, true] {
+        let bucket = seeded();
+        let endpoint = start_endpoint(Arc::clone(&bucket)).await;
+        let dir = TempDir::new().unwrap();
+        let provider = s3_mirror(&endpoint, &dir, delete_removed);
+
+        provider.run(ctx()).await.unwrap();
+        bucket.lock().unwrap().objects.remove("pub/pool/b c.deb");
+        bucket.lock().unwrap().objects.remove("pub/pool/a.deb");
+        provider.run(ctx()).await.unwrap();
+
+        let root = provider.working_dir().to_path_buf();
+        assert_eq!(
+            root.join("pool/a.deb").exists(),
+            !delete_removed,
+            "delete_removed = {delete_removed}"
+        );
+        assert_eq!(root.join("pool").exists(), !delete_removed);
+        assert!(root.join("README").exists());
+    }
+}
+
+// ---------------------------------------------------------------------------
+// 4. terminate
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn terminate_stops_stalled_download() {
+    let bucket = seeded();
+    bucket.lock().unwrap().stall = Some("pub/README".into());
+    let endpoint = start_endpoint(Arc::clone(&bucket)).await;
+    let dir = TempDir::new().unwrap();
+    let provider: Arc<dyn MirrorProvider> = Arc::from(s3_mirror(&endpoint, &dir, false));
+
+    let p = Arc::clone(&provider);
+    let handle = tokio::spawn(async move { p.run(ctx()).await });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    provider.terminate().await.unwrap();
+
+    let res = tokio::time::timeout(Duration::from_secs(10), handle)
+        .await
+        .expect("run must return promptly after terminate")
+        .unwrap();
+    assert!(matches!(res, Err(ProviderError::Terminated)), "got {res:?}");
+    assert!(provider.data_size().await.is_none());
+}
+
+// ---------------------------------------------------------------------------
+// 5. S3 errors
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn access_denied_fails_run_with_error_code() {
+    let bucket = seeded();
+    bucket.lock().unwrap().deny = true;
+    let endpoint = start_endpoint(Arc::clone(&bucket)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = s3_mirror(&endpoint, &dir, false);
+
+    let err = provider.run(ctx()).await.unwrap_err();
+    assert!(
+        matches!(&err, ProviderError::Http(msg) if msg.contains("403") && msg.contains("AccessDenied")),
+        "got {err:?}"
+    );
+}
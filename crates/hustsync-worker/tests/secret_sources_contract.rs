@@ -0,0 +1,128 @@
+//! Contract tests for secret references in mirror config.
+//!
+//! Scenarios:
+//! 1. `file:` references in `secret_env` reach the provider's process
+//!    resolved, while plain `env` values pass through as written
+//! 2. `password_file` becomes rsync's `RSYNC_PASSWORD`
+//! 3. an unresolvable reference fails `build_provider`, naming the
+//!    reference and nothing it would have held
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::path::Path;
+
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_worker::provider::{MirrorProvider, RunContext, build_provider};
+use tempfile::TempDir;
+
+fn worker(dir: &Path) -> WorkerConfig {
+    WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    }
+}
+
+async fn run(provider: &dyn MirrorProvider) {
+    std::fs::create_dir_all(provider.log_dir()).unwrap();
+    std::fs::create_dir_all(provider.working_dir()).unwrap();
+    provider
+        .run(RunContext {
+            attempt: 1,
+            ..RunContext::default()
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn env_file_reference_is_resolved_for_the_command() {
+    let tmp = TempDir::new().unwrap();
+    let token = tmp.path().join("token");
+    std::fs::write(&token, "s3cr3t\n").unwrap();
+    let out = tmp.path().join("out");
+    let mirror = MirrorConfig {
+        name: Some("arch".into()),
+        provider: Some("command".into()),
+        upstream: Some("https://mirror.test/archlinux/".into()),
+        command: Some(format!(
+            "sh -c 'printf %s \"$TOKEN $REPO\" > {}'",
+            out.display()
+        )),
+        env: Some([("REPO".to_string(), "file:///srv/mirror".to_string())].into()),
+        secret_env: Some([("TOKEN".to_string(), format!("file:{}", token.display()))].into()),
+        ..MirrorConfig::default()
+    };
+
+    let provider = build_provider("arch", &mirror, &worker(tmp.path())).unwrap();
+    run(provider.as_ref()).await;
+    assert_eq!(
+        std::fs::read_to_string(&out).unwrap(),
+        "s3cr3t file:///srv/mirror"
+    );
+}
+
+#[tokio::test]
+async fn password_file_becomes_rsync_password() {
+    let tmp = TempDir::new().unwrap();
+    let pass = tmp.path().join("arch.pass");
+    std::fs::write(&pass, "hunter2\n").unwrap();
+    let out = tmp.path().join("seen");
+    let rsync = tmp.path().join("rsync");
+    std::fs::write(
+        &rsync,
+        format!(
+            "#!/bin/sh\nprintf %s \"$RSYNC_PASSWORD\" > {}\n",
+            out.display()
+        ),
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&rsync, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let mirror = MirrorConfig {
+        name: Some("arch".into()),
+        provider: Some("rsync".into()),
+        upstream: Some("rsync://mirror.test/archlinux/".into()),
+        command: Some(rsync.to_string_lossy().into_owned()),
+        password_file: Some(pass.to_string_lossy().into_owned()),
+        ..MirrorConfig::default()
+    };
+
+    let provider = build_provider("arch", &mirror, &worker(tmp.path())).unwrap();
+    run(provider.as_ref()).await;
+    assert_eq!(std::fs::read_to_string(&out).unwrap(), "hunter2");
+}
+
+#[tokio::test]
+async fn unresolvable_reference_fails_the_build_without_leaking() {
+    let tmp = TempDir::new().unwrap();
+    let mut mirror = MirrorConfig {
+        name: Some("arch".into()),
+        provider: Some("rsync".into()),
+        upstream: Some("rsync://mirror.test/archlinux/".into()),
+        password: Some("env:HUSTSYNC_TEST_UNSET_PASSWORD".into()),
+        ..MirrorConfig::default()
+    };
+    let err = build_provider("arch", &mirror, &worker(tmp.path()))
+        .err()
+        .expect("unset variable");
+    let msg = err.to_string();
+    assert!(
+        msg.contains("HUSTSYNC_TEST_UNSET_PASSWORD is not set"),
+        "got {msg:?}"
+    );
+
+    mirror.password = None;
+    mirror.password_file = Some(tmp.path().join("missing").to_string_lossy().into_owned());
+    let msg = build_provider("arch", &mirror, &worker(tmp.path()))
+        .err()
+        .expect("missing file")
+        .to_string();
+    assert!(msg.contains("password_file: cannot read"), "got {msg:?}");
+}
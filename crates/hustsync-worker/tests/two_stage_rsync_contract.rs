@@ -13,13 +13,22 @@
 //! 4. stage-1 success + stage-2 failure → run fails
 //! 5. cancel mid-stage-1 via `ctx.cancel` → `ProviderError::Terminated`
 //! 6. unknown `stage1_profile` → rejected at `new()`
+//! 7. configured profiles feed both stages' filters and shadow built-ins
+//! 8. an unreachable upstream hands both stages to the next fallback
+//! 9. both stages pass their share of the worker budget as `--bwlimit`
+//! 10. `rsync_progress` relays each stage's progress updates
+//! 11. a stage-2 dry run over `max_delete` stops the run before stage 1
 
 #![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
+use hustsync_config_parser::{Stage1ProfileConfig, WorkerBandwidthConfig};
+use hustsync_worker::bandwidth::BandwidthBudget;
+use hustsync_worker::provider::progress::ProgressReporter;
 use hustsync_worker::provider::{
     CommonProviderConfig, MirrorProvider, ProviderError, RunContext,
     two_stage_rsync_provider::{TwoStageRsyncProvider, TwoStageRsyncProviderConfig},
@@ -56,17 +65,23 @@ fn make_config(
             env,
             is_master: true,
             success_exit_codes: vec![],
+            cgroup: None,
         },
         command: fake_rsync_path(),
         stage1_profile: "debian".to_string(),
+        stage1_profiles: HashMap::new(),
         username: None,
         password: None,
         exclude_file: None,
         extra_options: vec![],
         rsync_no_timeout: true,
         rsync_timeout: None,
+        rsync_progress: false,
         use_ipv6: false,
         use_ipv4: false,
+        fallback_upstreams: vec![],
+        max_delete: None,
+        max_delete_percent: None,
     }
 }
 
@@ -193,6 +208,9 @@ async fn cancel_mid_stage1_returns_terminated() {
         cancel: cancel.clone(),
         attempt: 1,
         env: HashMap::new(),
+        bandwidth: None,
+        progress: None,
+        allow_mass_delete: false,
     };
 
     let run_fut = tokio::spawn(async move { provider.run(ctx).await });
@@ -227,3 +245,327 @@ fn unknown_stage1_profile_rejected_at_new() {
         Ok(_) => panic!("unknown profile must fail at new()"),
     }
 }
+
+// ---------------------------------------------------------------------------
+// Configured profiles
+// ---------------------------------------------------------------------------
+
+fn strings(v: &[&str]) -> Option<Vec<String>> {
+    Some(v.iter().map(|s| s.to_string()).collect())
+}
+
+/// Run both stages and return the argv line of each, in order.
+async fn run_and_dump_argv(mut cfg: TwoStageRsyncProviderConfig, dir: &TempDir) -> Vec<String> {
+    let dump_path = dir.path().join("argv-dump.txt");
+    cfg.common.env.insert(
+        "FAKE_ARGV_DUMP".to_string(),
+        dump_path.to_string_lossy().into_owned(),
+    );
+    let provider = TwoStageRsyncProvider::new(cfg).unwrap();
+    run(&provider).await.expect("both stages must succeed");
+    std::fs::read_to_string(&dump_path)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn configured_profile_filters_both_stages() {
+    let dir = TempDir::new().unwrap();
+    let mut cfg = make_config("m-ports", &dir, HashMap::new());
+    cfg.stage1_profile = "ubuntu-ports".to_string();
+    cfg.extra_options = vec!["--extra-marker".to_string()];
+    cfg.stage1_profiles.insert(
+        "ubuntu-ports".to_string(),
+        Stage1ProfileConfig {
+            include: strings(&["by-hash/"]),
+            exclude: strings(&["Packages*", "InRelease"]),
+            stage2_include: None,
+            stage2_exclude: strings(&["*-proposed/"]),
+        },
+    );
+
+    let argv = run_and_dump_argv(cfg, &dir).await;
+    assert_eq!(argv.len(), 2, "expected one line per stage: {argv:?}");
+    assert!(
+        argv[0].contains("--include=by-hash/ --exclude=Packages* --exclude=InRelease"),
+        "stage 1 must carry the profile rules, includes first: {}",
+        argv[0]
+    );
+    assert!(
+        !argv[0].contains("--include=*.diff/"),
+        "built-in rules must not leak into a configured profile: {}",
+        argv[0]
+    );
+    assert!(
+        argv[1].contains("--exclude=*-proposed/ --extra-marker"),
+        "stage 2 filters must precede rsync_options: {}",
+        argv[1]
+    );
+}
+
+#[tokio::test]
+async fn builtin_profile_leaves_stage2_unfiltered() {
+    let dir = TempDir::new().unwrap();
+    let cfg = make_config("m-deb", &dir, HashMap::new());
+
+    let argv = run_and_dump_argv(cfg, &dir).await;
+    assert!(argv[0].contains("--include=*.diff/"), "{}", argv[0]);
+    assert!(!argv[1].contains("--exclude="), "{}", argv[1]);
+}
+
+#[tokio::test]
+async fn configured_profile_shadows_builtin_of_same_name() {
+    let dir = TempDir::new().unwrap();
+    let mut cfg = make_config("m-shadow", &dir, HashMap::new());
+    cfg.stage1_profiles.insert(
+        "debian".to_string(),
+        Stage1ProfileConfig {
+            exclude: strings(&["Release*"]),
+            ..Stage1ProfileConfig::default()
+        },
+    );
+
+    let argv = run_and_dump_argv(cfg, &dir).await;
+    assert!(argv[0].contains("--exclude=Release*"), "{}", argv[0]);
+    assert!(!argv[0].contains("--include=*.diff/"), "{}", argv[0]);
+}
+
+#[test]
+fn configured_profile_without_stage1_rules_rejected_at_new() {
+    let dir = TempDir::new().unwrap();
+    let mut cfg = make_config("m-empty", &dir, HashMap::new());
+    cfg.stage1_profile = "empty".to_string();
+    cfg.stage1_profiles.insert(
+        "empty".to_string(),
+        Stage1ProfileConfig {
+            stage2_exclude: strings(&["tmp/"]),
+            ..Stage1ProfileConfig::default()
+        },
+    );
+    assert!(matches!(
+        TwoStageRsyncProvider::new(cfg),
+        Err(ProviderError::Config(_))
+    ));
+}
+
+// ---------------------------------------------------------------------------
+// Fallback upstreams
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn unreachable_upstream_fails_over_for_both_stages() {
+    let dir = TempDir::new().unwrap();
+    let mut env = HashMap::new();
+    env.insert(
+        "FAKE_DOWN_UPSTREAM".to_string(),
+        "rsync://upstream.test/mirror/".to_string(),
+    );
+    let mut cfg = make_config("m-fallback", &dir, env);
+    cfg.fallback_upstreams = vec!["rsync://backup.test/mirror/".to_string()];
+
+    let argv = run_and_dump_argv(cfg, &dir).await;
+    assert_eq!(argv.len(), 3, "stage 1 twice, then stage 2: {argv:?}");
+    assert!(argv[0].contains("rsync://upstream.test/mirror/"));
+    assert!(!argv[0].contains("--delete"));
+    assert!(argv[1].contains("rsync://backup.test/mirror/"));
+    assert!(!argv[1].contains("--delete"));
+    assert!(argv[2].contains("rsync://backup.test/mirror/"));
+    assert!(argv[2].contains("--delete"));
+}
+
+#[tokio::test]
+async fn active_upstream_reports_the_fallback_that_served() {
+    let dir = TempDir::new().unwrap();
+    let mut env = HashMap::new();
+    env.insert(
+        "FAKE_DOWN_UPSTREAM".to_string(),
+        "rsync://upstream.test/mirror/".to_string(),
+    );
+    let mut cfg = make_config("m-active", &dir, env);
+    cfg.fallback_upstreams = vec!["rsync://backup.test/mirror/".to_string()];
+
+    let provider = TwoStageRsyncProvider::new(cfg).unwrap();
+    assert_eq!(provider.upstream(), "rsync://upstream.test/mirror/");
+    run(&provider).await.expect("fallback must serve the sync");
+    assert_eq!(provider.upstream(), "rsync://backup.test/mirror/");
+}
+
+#[tokio::test]
+async fn non_connection_failure_does_not_fail_over() {
+    let dir = TempDir::new().unwrap();
+    let mut env = HashMap::new();
+    env.insert("FAKE_STAGE1_EXIT".to_string(), "23".to_string());
+    let mut cfg = make_config("m-partial", &dir, env);
+    cfg.fallback_upstreams = vec!["rsync://backup.test/mirror/".to_string()];
+
+    let provider = TwoStageRsyncProvider::new(cfg).unwrap();
+    let res = run(&provider).await;
+    assert!(
+        matches!(res, Err(ProviderError::Execution { code: 23, .. })),
+        "got: {res:?}"
+    );
+    assert_eq!(provider.upstream(), "rsync://upstream.test/mirror/");
+}
+
+// ---------------------------------------------------------------------------
+// Worker bandwidth budget
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn both_stages_take_a_share_of_the_worker_budget() {
+    let dir = TempDir::new().unwrap();
+    let dump_path = dir.path().join("argv-dump.txt");
+    let mut env = HashMap::new();
+    env.insert(
+        "FAKE_ARGV_DUMP".to_string(),
+        dump_path.to_string_lossy().into_owned(),
+    );
+    let mut cfg = make_config("m-budget", &dir, env);
+    cfg.extra_options = vec!["--bwlimit=100".to_string()];
+    let budget = Arc::new(
+        BandwidthBudget::from_config(&WorkerBandwidthConfig {
+            limit: Some("6M".to_string()),
+            tiers: None,
+        })
+        .unwrap(),
+    );
+    let _other_job = budget.join();
+
+    let provider = TwoStageRsyncProvider::new(cfg).unwrap();
+    let ctx = RunContext {
+        bandwidth: Some(Arc::clone(&budget)),
+        ..RunContext::default()
+    };
+    provider.run(ctx).await.expect("both stages must succeed");
+
+    let dump = std::fs::read_to_string(&dump_path).unwrap();
+    let argv: Vec<&str> = dump.lines().collect();
+    assert_eq!(argv.len(), 2, "expected one line per stage: {argv:?}");
+    assert!(argv[0].contains("--bwlimit=3072 "), "stage 1: {}", argv[0]);
+    assert!(
+        argv[1].contains("--bwlimit=3072 ")
+            && argv[1].ends_with(&format!(
+                "--bwlimit=100 rsync://upstream.test/mirror/ {}",
+                dir.path().display()
+            )),
+        "extra_options must follow the budget share in stage 2: {}",
+        argv[1]
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Live progress
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn rsync_progress_relays_stage_updates() {
+    let dir = TempDir::new().unwrap();
+    let mut env = HashMap::new();
+    env.insert(
+        "FAKE_PROGRESS".to_string(),
+        "  52,428,800  50%  10.00MB/s  0:00:05".to_string(),
+    );
+    let mut cfg = make_config("m-progress", &dir, env);
+    cfg.rsync_progress = true;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+    let provider = TwoStageRsyncProvider::new(cfg).unwrap();
+    let ctx = RunContext {
+        progress: Some(ProgressReporter::new("m-progress".to_string(), true, tx)),
+        ..RunContext::default()
+    };
+    provider.run(ctx).await.expect("both stages must succeed");
+
+    // Each stage spawns its own rsync, so each reports once.
+    for stage in 1..=2 {
+        let msg = rx
+            .try_recv()
+            .unwrap_or_else(|_| panic!("no update from stage {stage}"));
+        let progress = msg.progress.expect("progress message");
+        assert_eq!(progress.bytes, 52_428_800);
+        assert_eq!(progress.percent, 50);
+    }
+    assert!(rx.try_recv().is_err());
+}
+
+// ---------------------------------------------------------------------------
+// Transfer stats
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn transfer_stats_sum_both_stages_of_this_run() {
+    let dir = TempDir::new().unwrap();
+    let mut env = HashMap::new();
+    env.insert("FAKE_STATS".to_string(), "7".to_string());
+    let cfg = make_config("m-stats", &dir, env);
+    // The log is appended to; a block left by an earlier run must not count.
+    std::fs::write(
+        &cfg.common.log_file,
+        "Number of regular files transferred: 1,000\n",
+    )
+    .unwrap();
+
+    let provider = TwoStageRsyncProvider::new(cfg).unwrap();
+    run(&provider).await.expect("both stages must succeed");
+
+    let stats = provider.transfer_stats().await.expect("stats");
+    assert_eq!(stats.files_transferred, 14);
+    assert_eq!(stats.bytes_received, 14);
+}
+
+// ---------------------------------------------------------------------------
+// Mass-deletion guard
+// ---------------------------------------------------------------------------
+
+/// Run with `FAKE_DELETES=6` under `max_delete = 5`; returns the result
+/// and one argv line per rsync invocation.
+async fn run_guarded(
+    dir: &TempDir,
+    allow_mass_delete: bool,
+) -> (Result<(), ProviderError>, Vec<String>) {
+    let dump_path = dir.path().join("argv-dump.txt");
+    let mut env = HashMap::new();
+    env.insert("FAKE_DELETES".to_string(), "6".to_string());
+    env.insert(
+        "FAKE_ARGV_DUMP".to_string(),
+        dump_path.to_string_lossy().into_owned(),
+    );
+    let mut cfg = make_config("m-guard", dir, env);
+    cfg.max_delete = Some(5);
+
+    let provider = TwoStageRsyncProvider::new(cfg).unwrap();
+    let ctx = RunContext {
+        allow_mass_delete,
+        ..RunContext::default()
+    };
+    let result = provider.run(ctx).await;
+    let argv = std::fs::read_to_string(&dump_path)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    (result, argv)
+}
+
+#[tokio::test]
+async fn mass_deletion_stops_run_before_stage1() {
+    let dir = TempDir::new().unwrap();
+    let (result, argv) = run_guarded(&dir, false).await;
+    assert!(
+        matches!(&result, Err(ProviderError::MassDeletion(msg)) if msg.contains("max_delete = 5")),
+        "got: {result:?}"
+    );
+    assert_eq!(argv.len(), 1, "only the dry run: {argv:?}");
+    assert!(argv[0].contains("--dry-run") && argv[0].contains("--delete"));
+}
+
+#[tokio::test]
+async fn allow_mass_delete_runs_both_stages_without_dry_run() {
+    let dir = TempDir::new().unwrap();
+    let (result, argv) = run_guarded(&dir, true).await;
+    result.expect("override lets the run through");
+    assert_eq!(argv.len(), 2, "{argv:?}");
+    assert!(argv.iter().all(|a| !a.contains("--dry-run")));
+}
@@ -0,0 +1,354 @@
+//! Contract tests for the `yum` provider.
+//!
+//! A local axum server plays an RPM repository built in memory: packages
+//! under `Packages/`, a zstd-compressed `primary` and a gzip-compressed
+//! `filelists` named by their checksum as createrepo does, and
+//! `repomd.xml`. Every request is recorded.
+//!
+//! Scenarios:
+//! 1. the first run mirrors packages and repodata and reports the
+//!    repository size
+//! 2. a second run with nothing new downloads only `repomd.xml`
+//! 3. a package whose SHA256 does not match fails the run and leaves the
+//!    previous repodata in place
+//! 4. metadata from two generations back is deleted, the previous
+//!    generation is kept
+//! 5. cancelling the run context stops a stalled download
+
+#![allow(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use hustsync_config_parser::{MirrorConfig, WorkerConfig, WorkerGlobalConfig};
+use hustsync_internal::util::format_size;
+use hustsync_worker::provider::{MirrorProvider, ProviderError, RunContext, build_provider};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+#[derive(Default)]
+struct Repo {
+    /// Path relative to the repository root → body.
+    files: BTreeMap<String, Vec<u8>>,
+    /// Every path requested, in order.
+    hits: Vec<String>,
+    /// Path whose response never finishes.
+    stall: Option<String>,
+}
+
+type Shared = Arc<Mutex<Repo>>;
+
+fn sha256(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(data).unwrap();
+    enc.finish().unwrap()
+}
+
+impl Repo {
+    /// Add `packages` (name → content) and regenerate the repodata for
+    /// them. Returns the size of the repository as the provider counts it.
+    fn publish(&mut self, packages: &[(&str, &str)]) -> u64 {
+        let mut primary = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <metadata xmlns=\"http://linux.duke.edu/metadata/common\" \
+             xmlns:rpm=\"http://linux.duke.edu/metadata/rpm\">\n",
+        );
+        let mut total = 0;
+        for (name, body) in packages {
+            let href = format!("Packages/{}/{name}-1.0-1.x86_64.rpm", &name[..1]);
+            primary.push_str(&format!(
+                "<package type=\"rpm\"><name>{name}</name><arch>x86_64</arch>\
+                 <checksum type=\"sha256\" pkgid=\"YES\">{}</checksum>\
+                 <size package=\"{}\" installed=\"0\" archive=\"0\"/>\
+                 <location href=\"{href}\"/></package>\n",
+                sha256(body.as_bytes()),
+                body.len()
+            ));
+            self.files.insert(href, body.as_bytes().to_vec());
+            total += body.len() as u64;
+        }
+        primary.push_str("</metadata>\n");
+        let primary = zstd::stream::encode_all(primary.as_bytes(), 0).unwrap();
+        let names: Vec<_> = packages.iter().map(|(name, _)| *name).collect();
+        let filelists = gzip(format!("<filelists>{}</filelists>", names.join(" ")).as_bytes());
+
+        let mut repomd = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <repomd xmlns=\"http://linux.duke.edu/metadata/repo\">\n",
+        );
+        for (kind, name, body) in [
+            ("primary", "primary.xml.zst", primary),
+            ("filelists", "filelists.xml.gz", filelists),
+        ] {
+            let href = format!("repodata/{}-{name}", sha256(&body));
+            repomd.push_str(&format!(
+                "<data type=\"{kind}\"><checksum type=\"sha256\">{}</checksum>\
+                 <location href=\"{href}\"/><size>{}</size></data>\n",
+                sha256(&body),
+                body.len()
+            ));
+            total += body.len() as u64;
+            self.files.insert(href, body);
+        }
+        repomd.push_str("</repomd>\n");
+        total += repomd.len() as u64;
+        self.files
+            .insert("repodata/repomd.xml".into(), repomd.into_bytes());
+        total
+    }
+
+    fn hits_under(&self, prefix: &str) -> Vec<String> {
+        self.hits
+            .iter()
+            .filter(|p| p.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+}
+
+async fn serve(State(repo): State<Shared>, uri: Uri) -> Response {
+    let path = uri.path().trim_start_matches('/').to_string();
+    let (body, stall) = {
+        let mut repo = repo.lock().unwrap();
+        repo.hits.push(path.clone());
+        let stall = repo.stall.as_deref() == Some(path.as_str());
+        (repo.files.get(&path).cloned(), stall)
+    };
+    if stall {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+    match body {
+        Some(body) => body.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn start_repo(repo: Shared) -> String {
+    let app = Router::new().fallback(serve).with_state(repo);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}/")
+}
+
+fn yum_mirror(upstream: &str, dir: &TempDir) -> Box<dyn MirrorProvider> {
+    let mirror = MirrorConfig {
+        name: Some("epel".into()),
+        provider: Some("yum".into()),
+        upstream: Some(upstream.into()),
+        ..MirrorConfig::default()
+    };
+    let worker = WorkerConfig {
+        global: Some(WorkerGlobalConfig {
+            log_dir: Some(dir.path().join("log").to_string_lossy().into_owned()),
+            mirror_dir: Some(dir.path().join("mirrors").to_string_lossy().into_owned()),
+            ..WorkerGlobalConfig::default()
+        }),
+        ..WorkerConfig::default()
+    };
+    build_provider("epel", &mirror, &worker).unwrap()
+}
+
+fn ctx() -> RunContext {
+    RunContext {
+        attempt: 1,
+        ..RunContext::default()
+    }
+}
+
+const V1: &[(&str, &str)] = &[("bash", "bash-rpm"), ("zsh", "zsh-rpm-content")];
+
+fn read(root: &Path, rel: &str) -> String {
+    std::fs::read_to_string(root.join(rel)).unwrap()
+}
+
+fn repodata_files(root: &Path) -> Vec<String> {
+    let mut names: Vec<_> = std::fs::read_dir(root.join("repodata"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+// ---------------------------------------------------------------------------
+// 1. initial mirror
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn first_run_mirrors_packages_and_repodata() {
+    let repo = Arc::new(Mutex::new(Repo::default()));
+    let size = repo.lock().unwrap().publish(V1);
+    let url = start_repo(Arc::clone(&repo)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = yum_mirror(&url, &dir);
+
+    provider.run(ctx()).await.unwrap();
+
+    let root = provider.working_dir().to_path_buf();
+    assert_eq!(read(&root, "Packages/b/bash-1.0-1.x86_64.rpm"), "bash-rpm");
+    assert_eq!(
+        read(&root, "Packages/z/zsh-1.0-1.x86_64.rpm"),
+        "zsh-rpm-content"
+    );
+    let upstream_repomd = repo.lock().unwrap().files["repodata/repomd.xml"].clone();
+    assert_eq!(
+        std::fs::read(root.join("repodata/repomd.xml")).unwrap(),
+        upstream_repomd
+    );
+    assert_eq!(repodata_files(&root).len(), 3);
+    assert!(!root.join(".hustsync-yum-staging").exists());
+    assert_eq!(
+        provider.data_size().await.as_deref(),
+        Some(format_size(size).as_str())
+    );
+}
+
+// ---------------------------------------------------------------------------
+// 2. nothing new
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn unchanged_repository_fetches_only_repomd() {
+    let repo = Arc::new(Mutex::new(Repo::default()));
+    repo.lock().unwrap().publish(V1);
+    let url = start_repo(Arc::clone(&repo)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = yum_mirror(&url, &dir);
+
+    provider.run(ctx()).await.unwrap();
+    repo.lock().unwrap().hits.clear();
+    provider.run(ctx()).await.unwrap();
+
+    let (packages, repodata) = {
+        let repo = repo.lock().unwrap();
+        (repo.hits_under("Packages/"), repo.hits_under("repodata/"))
+    };
+    assert!(packages.is_empty(), "{packages:?}");
+    assert_eq!(
+        repodata,
+        vec![
+            "repodata/repomd.xml",
+            "repodata/repomd.xml.asc",
+            "repodata/repomd.xml.key"
+        ]
+    );
+}
+
+// ---------------------------------------------------------------------------
+// 3. checksum mismatch
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn checksum_mismatch_keeps_previous_repodata() {
+    let repo = Arc::new(Mutex::new(Repo::default()));
+    repo.lock().unwrap().publish(V1);
+    let url = start_repo(Arc::clone(&repo)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = yum_mirror(&url, &dir);
+
+    provider.run(ctx()).await.unwrap();
+    let root = provider.working_dir().to_path_buf();
+    let old_repomd = read(&root, "repodata/repomd.xml");
+    let old_files = repodata_files(&root);
+    let old_size = provider.data_size().await;
+
+    {
+        let mut repo = repo.lock().unwrap();
+        let mut v2 = V1.to_vec();
+        v2.push(("curl", "curl-rpm"));
+        repo.publish(&v2);
+        repo.files.insert(
+            "Packages/c/curl-1.0-1.x86_64.rpm".into(),
+            b"CURL-RPM".to_vec(),
+        );
+    }
+    let err = provider.run(ctx()).await.unwrap_err();
+
+    assert!(matches!(err, ProviderError::Checksum(_)), "got {err:?}");
+    assert!(!root.join("Packages/c/curl-1.0-1.x86_64.rpm").exists());
+    assert_eq!(read(&root, "repodata/repomd.xml"), old_repomd);
+    assert_eq!(repodata_files(&root), old_files);
+    assert_eq!(provider.data_size().await, old_size);
+}
+
+// ---------------------------------------------------------------------------
+// 4. old metadata
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn metadata_is_kept_for_one_generation() {
+    let repo = Arc::new(Mutex::new(Repo::default()));
+    repo.lock().unwrap().publish(V1);
+    let url = start_repo(Arc::clone(&repo)).await;
+    let dir = TempDir::new().unwrap();
+    let provider = yum_mirror(&url, &dir);
+    let root = provider.working_dir().to_path_buf();
+
+    provider.run(ctx()).await.unwrap();
+    let gen1 = repodata_files(&root);
+
+    repo.lock().unwrap().publish(&[("bash", "bash-rpm")]);
+    provider.run(ctx()).await.unwrap();
+    // Generation 1 is still there for clients holding the old repomd.
+    let gen2 = repodata_files(&root);
+    assert!(gen1.iter().all(|f| gen2.contains(f)), "{gen2:?}");
+    assert_eq!(gen2.len(), 5);
+
+    repo.lock().unwrap().publish(&[("zsh", "zsh-rpm-content")]);
+    provider.run(ctx()).await.unwrap();
+    let gen3 = repodata_files(&root);
+    assert_eq!(gen3.len(), 5, "{gen3:?}");
+    assert!(
+        gen1.iter()
+            .filter(|f| *f != "repomd.xml")
+            .all(|f| !gen3.contains(f)),
+        "{gen3:?}"
+    );
+    // Packages dropped upstream stay.
+    assert!(root.join("Packages/b/bash-1.0-1.x86_64.rpm").exists());
+}
+
+// ---------------------------------------------------------------------------
+// 5. cancel
+// ---------------------------------------------------------------------------
+
+#[tokio::test]
+async fn cancelling_run_context_stops_download() {
+    let repo = Arc::new(Mutex::new(Repo::default()));
+    {
+        let mut repo = repo.lock().unwrap();
+        repo.publish(V1);
+        repo.stall = Some("Packages/b/bash-1.0-1.x86_64.rpm".into());
+    }
+    let url = start_repo(Arc::clone(&repo)).await;
+    let dir = TempDir::new().unwrap();
+    let provider: Arc<dyn MirrorProvider> = Arc::from(yum_mirror(&url, &dir));
+
+    let run_ctx = ctx();
+    let cancel = run_ctx.cancel.clone();
+    let p = Arc::clone(&provider);
+    let handle = tokio::spawn(async move { p.run(run_ctx).await });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    cancel.cancel();
+
+    let res = tokio::time::timeout(Duration::from_secs(10), handle)
+        .await
+        .expect("run must return promptly after cancel")
+        .unwrap();
+    assert!(matches!(res, Err(ProviderError::Terminated)), "got {res:?}");
+    assert!(!provider.working_dir().join("repodata/repomd.xml").exists());
+}
@@ -143,9 +143,12 @@ enum Commands {
         /// Send the command to worker
         #[arg(short, long)]
         worker: Option<String>,
-        /// Override the concurrent limit
+        /// Override the concurrent limit and any blackout window
         #[arg(short, long)]
         force: bool,
+        /// Let this run through the mirror's mass-deletion guard once
+        #[arg(long)]
+        allow_mass_delete: bool,
         mirror: String,
         args: Option<String>,
     },
@@ -292,6 +295,7 @@ async fn main() {
         } => set_size(&base_url, &client, &worker, &mirror, &size).await,
         Commands::Start {
             force,
+            allow_mass_delete,
             worker,
             mirror,
             args,
@@ -300,6 +304,9 @@ async fn main() {
             if force {
                 opts.insert("force".to_string(), true);
             }
+            if allow_mass_delete {
+                opts.insert("allow_mass_delete".to_string(), true);
+            }
             send_cmd(
                 &base_url,
                 &client,